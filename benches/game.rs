@@ -0,0 +1,68 @@
+//! Benchmarks of the hot paths of the simulation: generating child states,
+//! playing out whole games, and searching for a single AI decision.
+//! Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use monopoly_math::{seed_rng, Agent, Budget, Game, GameConfig, Logger, MctsAgent, RandomAgent};
+
+/// The seed that the dice and agents' random choices are drawn from in every benchmark.
+const SEED: u64 = 21;
+/// The number of players in every benchmarked game.
+const PLAYERS: usize = 4;
+/// The turn limit of played-out games, which keeps every iteration the same length.
+const MAX_TURNS: usize = 100;
+/// The number of MCTS iterations spent on the benchmarked decision.
+const MCTS_ITERATIONS: u32 = 50;
+
+fn gen_children(c: &mut Criterion) {
+    let game = Game::new(PLAYERS);
+
+    c.bench_function("gen_roll_children", |b| {
+        b.iter(|| black_box(&game).bench_roll_children())
+    });
+    c.bench_function("gen_cc_children", |b| {
+        b.iter(|| black_box(&game).bench_cc_children())
+    });
+}
+
+fn rollout(c: &mut Criterion) {
+    let config = GameConfig {
+        max_turns: Some(MAX_TURNS),
+        ..GameConfig::default()
+    };
+
+    c.bench_function("random_rollout", |b| {
+        b.iter(|| {
+            seed_rng(SEED);
+            let agents: Vec<Box<dyn Agent>> =
+                (0..PLAYERS).map(|_| Box::new(RandomAgent) as _).collect();
+            Game::with_config(PLAYERS, config)
+                .with_logger(Logger::silent())
+                .resume(agents)
+        })
+    });
+}
+
+fn mcts_decision(c: &mut Criterion) {
+    // Get to the first decision of the game
+    seed_rng(SEED);
+    let mut game = Game::new(PLAYERS).with_logger(Logger::silent());
+    game.legal_moves().expect("failed to start the game");
+
+    c.bench_function("mcts_decision", |b| {
+        b.iter_batched(
+            || {
+                seed_rng(SEED);
+                let agent = MctsAgent::new(0, 0., 0)
+                    .with_budget(Budget::Iterations(MCTS_ITERATIONS))
+                    .with_logger(Logger::silent());
+                (agent, game.clone())
+            },
+            |(mut agent, mut game)| agent.make_choice(&mut game),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, gen_children, rollout, mcts_decision);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    compile_grpc_service();
+}
+
+/// Generate the server for the service in `proto/monopoly.proto`. The service is
+/// described here instead of being compiled from the `.proto` file, so that building
+/// it doesn't need `protoc`. The messages are defined in `src/grpc.rs`.
+#[cfg(feature = "grpc")]
+fn compile_grpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let methods = [
+        ("start_game", "StartGame", "StartGameRequest", "GameState"),
+        ("step", "Step", "StepRequest", "GameState"),
+        ("analyze", "Analyze", "AnalyzeRequest", "AnalyzeResponse"),
+        (
+            "run_tournament",
+            "RunTournament",
+            "TournamentRequest",
+            "TournamentResponse",
+        ),
+    ];
+
+    let service = methods
+        .iter()
+        .fold(
+            Service::builder().name("Monopoly").package("monopoly"),
+            |service, (name, route_name, input, output)| {
+                service.method(
+                    Method::builder()
+                        .name(name)
+                        .route_name(route_name)
+                        .input_type(format!("crate::grpc::{}", input))
+                        .output_type(format!("crate::grpc::{}", output))
+                        .codec_path("tonic::codec::ProstCodec")
+                        .build(),
+                )
+            },
+        )
+        .build();
+
+    Builder::new().build_client(false).compile(&[service]);
+}
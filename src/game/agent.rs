@@ -1,18 +1,147 @@
-use super::globals::*;
 use super::Game;
 use rand::Rng;
-use std::iter::zip;
-use std::time::{Duration, Instant};
+use serde::Serialize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::cmp::Reverse;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+// `std::time::Instant` panics on the web, so use the browser's clock instead
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
-use super::state_diff::BranchType;
+use super::endgame::EndgameSolver;
+use super::globals::with_rng;
+#[cfg(not(target_arch = "wasm32"))]
+use super::logger::OutputFormat;
+use super::logger::{LogEvent, LogLevel, Logger};
+use super::metrics::Metrics;
+use super::opening_book::OpeningBook;
+use super::opponent_model::OpponentModel;
+use super::shared_cache::SharedCache;
+use super::state_diff::{BranchType, DiffMessage};
+use super::state_view::{MoveSummary, Observation, StateView};
+use super::value_function::{
+    BalanceTimesPropertyWorth, NetWorthDifferential, ValueFunction, WinLoss,
+};
+
+/// A user-supplied model (e.g. a neural network) that estimates, for the observed state,
+/// prior probabilities for each of its children (in the same order as `Game::legal_moves()`)
+/// and the value of the state for the acting player.
+pub type Evaluator = dyn Fn(&Observation) -> (Vec<f64>, f64) + Send + Sync;
+
+/// How moves are chosen during MCTS rollouts.
+#[derive(Debug, Clone, Copy)]
+pub enum RolloutPolicy {
+    /// Play uniformly at random.
+    Random,
+    /// Play randomly with probability `epsilon`, and otherwise play the move preferred
+    /// by a cheap heuristic, which buys affordable properties and avoids going into debt.
+    EpsilonGreedy { epsilon: f64 },
+}
+
+impl RolloutPolicy {
+    /// Return the index of the child of the choice node at `handle` to play.
+    pub(super) fn choose(&self, game: &Game, handle: usize) -> usize {
+        with_rng(|rng| self.choose_with(rng, game, handle))
+    }
+
+    /// Return the index of the child of the choice node at `handle` to play, using `rng`.
+    fn choose_with(&self, rng: &mut impl Rng, game: &Game, handle: usize) -> usize {
+        let children = &game.nodes[handle].children;
+
+        let epsilon = match *self {
+            RolloutPolicy::Random => 1.,
+            RolloutPolicy::EpsilonGreedy { epsilon } => epsilon,
+        };
+
+        if rng.gen::<f64>() < epsilon {
+            return rng.gen_range(0..children.len());
+        }
+
+        let pindex = game.get_acting_pindex(handle);
+        let scores: Vec<i32> = children
+            .iter()
+            .map(|&child| {
+                let in_debt = game.diff_players(child)[pindex].balance < 0;
+                let preference = match game.nodes[child].message {
+                    DiffMessage::BuyProp { .. } => 2,
+                    DiffMessage::NoLocation | DiffMessage::NoTrade | DiffMessage::DeclineTrade => 1,
+                    _ => 0,
+                };
+
+                preference - if in_debt { 10 } else { 0 }
+            })
+            .collect();
+
+        // Break ties between the best moves randomly
+        let best_score = *scores.iter().max().unwrap();
+        let best: Vec<usize> = (0..scores.len())
+            .filter(|&i| scores[i] == best_score)
+            .collect();
+
+        best[rng.gen_range(0..best.len())]
+    }
+}
+
+/// The maximum number of moves played in a rollout before the game state is evaluated.
+pub(super) const MAX_ROLLOUT_MOVES: u32 = 1000;
+
+/// The settings that a search is performed with.
+#[derive(Clone, Copy)]
+struct SearchParams<'a> {
+    /// The index of the player that the search is for.
+    pindex: usize,
+    /// Value of `C` constant in UCB1 formula.
+    temperature: f64,
+    /// How moves are chosen during rollouts.
+    rollout_policy: RolloutPolicy,
+    /// How the game state at the end of a rollout is scored.
+    value_function: &'a dyn ValueFunction,
+    /// The model used to guide the search with PUCT instead of UCB1 and rollouts, if any.
+    evaluator: Option<&'a Evaluator>,
+    /// How the opponents of `pindex` choose their moves, if they aren't searched.
+    opponent_model: Option<&'a OpponentModel>,
+    /// The cache that new choice nodes are started off with the values of, if any.
+    shared_cache: Option<&'a SharedCache>,
+    /// Where problems with the search are logged.
+    logger: Logger,
+}
+
+impl SearchParams<'_> {
+    /// Return the index of the child of the choice node at `handle` that the opponent
+    /// model expects, or `None` if there's no opponent model or it's `pindex`'s move.
+    fn opponent_choice(&self, game: &Game, handle: usize) -> Option<usize> {
+        self.opponent_model
+            .filter(|_| game.get_acting_pindex(handle) != self.pindex)
+            .map(|model| model.choose(game, handle))
+    }
+}
+
+/// The number of MCTS iterations performed so far by every search, which
+/// tells how recently each `MCTreeNode` was visited.
+static VISIT_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// The fraction of `Game::with_node_limit()` that the game tree is shrunk to when it grows
+/// past the limit, so that eviction doesn't have to happen again straight away.
+const EVICTION_TARGET: f64 = 0.75;
 
 /// An MTCS tree is essentially a mirror copy of the game tree,
 /// except with property + auction states combined into one node.
 pub struct MCTreeNode {
     total_value: f64,
     num_visits: u32,
+    /// The evaluator's prior probability of choosing this node, or NaN if it hasn't been set.
+    prior: f64,
     branch_type: BranchType,
-    children: Vec<Box<MCTreeNode>>,
+    children: Vec<MCTreeNode>,
+    /// The value of `VISIT_CLOCK` when this node was last traversed.
+    last_visit: u64,
 }
 
 impl MCTreeNode {
@@ -21,28 +150,16 @@ impl MCTreeNode {
         MCTreeNode {
             total_value: 0.,
             num_visits: 0,
+            prior: f64::NAN,
             branch_type,
             children: vec![],
+            last_visit: 0,
         }
     }
 
     /// Return `self.total_value / self.num_visits`.
     fn get_average_value(&self) -> f64 {
-        self.total_value as f64 / self.num_visits as f64
-    }
-
-    /// Return the index of the child with the greatest average value.
-    fn get_best_child_index(&self) -> usize {
-        self.children
-            .iter()
-            .enumerate()
-            .max_by(|(_, a), (_, b)| {
-                a.get_average_value()
-                    .partial_cmp(&b.get_average_value())
-                    .unwrap()
-            })
-            .map(|(i, _)| i)
-            .unwrap()
+        self.total_value / self.num_visits as f64
     }
 
     /// Generate as many direct child nodes as needed to mirror `state`'s
@@ -65,7 +182,27 @@ impl MCTreeNode {
 
         for i in 0..count {
             let bt = game.nodes[game.nodes[handle].children[i]].branch_type;
-            self.children.push(Box::new(MCTreeNode::new(bt)));
+            self.children.push(MCTreeNode::new(bt));
+        }
+    }
+
+    /// Start this node's unvisited choices off with the values that the shared cache has for
+    /// their states, if there's a shared cache, as if they had already been visited.
+    fn seed_children(&mut self, game: &Game, handle: usize, params: &SearchParams) {
+        let Some(cache) = params.shared_cache else {
+            return;
+        };
+
+        for (child, &child_handle) in self.children.iter_mut().zip(&game.nodes[handle].children) {
+            if !matches!(child.branch_type, BranchType::Choice) || child.num_visits > 0 {
+                continue;
+            }
+
+            if let Some(estimate) = cache.estimate(game.nodes[child_handle].hash, params.pindex) {
+                let seed = estimate.as_seed();
+                child.total_value = seed.total_value;
+                child.num_visits = seed.visits;
+            }
         }
     }
 
@@ -73,61 +210,92 @@ impl MCTreeNode {
     /// Replace this node with the node at the end of the traversal.
     fn sync_with_walk(&mut self, game: &mut Game, latest_unseen_move: usize) {
         for &step in &game.move_history[latest_unseen_move..] {
-            if self.children.len() == 0 {
+            // The move may not be in this tree (e.g. if a player resigned)
+            let Some(child) = self.children.get_mut(step) else {
                 let ending_node = &game.nodes[game.root_handle];
                 *self = MCTreeNode::new(ending_node.branch_type);
                 break;
-            }
+            };
 
-            *self = std::mem::replace(
-                self.children[step].as_mut(),
-                MCTreeNode::new(BranchType::Choice),
-            );
+            *self = std::mem::replace(child, MCTreeNode::new(BranchType::Choice));
         }
     }
 
     /// Traverse the MCTS tree and create child nodes as needed. Return rollout result.
-    fn traverse(&mut self, game: &mut Game, handle: usize, pindex: usize, temperature: f64) -> f64 {
+    fn traverse(
+        &mut self,
+        game: &mut Game,
+        handle: usize,
+        params: &SearchParams,
+        depth: u32,
+        stats: &mut SearchStats,
+    ) -> f64 {
+        stats.max_depth = stats.max_depth.max(depth);
+        self.last_visit = VISIT_CLOCK.load(Ordering::Relaxed);
         let value_multiplier = match self.branch_type {
             BranchType::Chance(p) => p,
             _ => 1.,
         };
 
+        // Another agent's search may have evicted the game tree below this node, so
+        // generate its children again (in the same order) if this node mirrors them
+        if !self.children.is_empty() {
+            game.gen_children_save(handle);
+        }
+
+        // Let the evaluator guide the search if there is one
+        if let Some(evaluator) = params.evaluator {
+            return self.traverse_puct(game, handle, params, evaluator, depth, stats);
+        }
+
         // If `self` is not a leaf node, calculate the UCB1 values of its child nodes
-        if self.children.len() > 0 {
+        if !self.children.is_empty() {
             // The UCB1 formula is `V_i + C * sqrt( ln(N) / n_i )`
 
             // mean_value = V_i
-            let mean_value = self.total_value as f64 / self.num_visits as f64;
-
-            // All the UCB1 values of `self`'s children
-            let ucb1_values: Vec<f64> = self
-                .children
-                .iter()
-                .map(|s| {
-                    if self.num_visits == 0 || s.num_visits == 0 {
-                        f64::INFINITY
-                    } else {
-                        mean_value
-                            + temperature
-                                * ((self.num_visits as f64).ln() / s.num_visits as f64).sqrt()
-                    }
-                })
-                .collect();
+            let mean_value = self.total_value / self.num_visits as f64;
 
             // The index of the child to traverse next
-            let child_index = ucb1_values
-                .iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                .map(|(i, _)| i)
-                .unwrap();
+            let child_index = match self.children[0].branch_type {
+                // Sample chance outcomes according to their probabilities rather than
+                // trying every one of them, so that chance nodes with many children
+                // (e.g. auctions) don't stop the search from looking deeper
+                BranchType::Chance(_) => game.get_any_chance_child(handle),
+                // Play the move that the opponent model expects, if it's an opponent's
+                _ => match params.opponent_choice(game, handle) {
+                    Some(i) => i,
+                    None => {
+                        // All the UCB1 values of `self`'s children
+                        let ucb1_values: Vec<f64> = self
+                            .children
+                            .iter()
+                            .map(|s| {
+                                if self.num_visits == 0 || s.num_visits == 0 {
+                                    f64::INFINITY
+                                } else {
+                                    mean_value
+                                        + params.temperature
+                                            * ((self.num_visits as f64).ln() / s.num_visits as f64)
+                                                .sqrt()
+                                }
+                            })
+                            .collect();
+
+                        ucb1_values
+                            .iter()
+                            .enumerate()
+                            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                            .map(|(i, _)| i)
+                            .unwrap()
+                    }
+                },
+            };
 
             let next_handle = game.nodes[handle].children[child_index];
 
             // Value of the rollout to propagate
             let propagated_value =
-                self.children[child_index].traverse(game, next_handle, pindex, temperature);
+                self.children[child_index].traverse(game, next_handle, params, depth + 1, stats);
 
             // Update n and t
             self.num_visits += 1;
@@ -138,7 +306,8 @@ impl MCTreeNode {
 
         // Perform a rollout if the node has never been visited before
         if self.num_visits == 0 {
-            let rollout_outcome = MCTreeNode::rollout(game, handle, pindex);
+            stats.rollouts += 1;
+            let rollout_outcome = MCTreeNode::rollout(game, handle, params);
 
             // Update n and t
             self.num_visits += 1;
@@ -149,177 +318,1356 @@ impl MCTreeNode {
 
         // We can't generate any more child states if we're at a terminal game state
         if game.is_terminal(handle) {
-            return MCTreeNode::rollout(game, handle, pindex) * value_multiplier;
+            stats.rollouts += 1;
+            return MCTreeNode::rollout(game, handle, params) * value_multiplier;
         }
 
-        // Expand the tree and rollout from the first child if
-        // the node is a leaf node that hasn't been visited yet
+        // Expand the tree and rollout from the first child (or a sampled
+        // chance outcome) if the node is a leaf node that hasn't been visited yet
         game.gen_children_save(handle);
 
         // Sync the MCTS tree with the game-state tree
         self.sync_children_count(game, handle);
+        self.seed_children(game, handle, params);
+        stats.nodes_expanded += self.children.len() as u32;
+        stats.rollouts += 1;
+
+        let child_index = match self.children[0].branch_type {
+            BranchType::Chance(_) => game.get_any_chance_child(handle),
+            _ => 0,
+        };
 
-        MCTreeNode::rollout(game, game.nodes[handle].children[0], pindex) * value_multiplier
+        MCTreeNode::rollout(game, game.nodes[handle].children[child_index], params)
+            * value_multiplier
     }
 
-    fn rollout(game: &mut Game, mut handle: usize, pindex: usize) -> f64 {
-        let mut rng = rand::thread_rng();
+    /// Traverse the tree like `traverse()`, but select choices with the PUCT formula
+    /// and evaluate new leaf nodes with `evaluator` instead of rolling out from them.
+    fn traverse_puct(
+        &mut self,
+        game: &mut Game,
+        handle: usize,
+        params: &SearchParams,
+        evaluator: &Evaluator,
+        depth: u32,
+        stats: &mut SearchStats,
+    ) -> f64 {
+        let value_multiplier = match self.branch_type {
+            BranchType::Chance(p) => p,
+            _ => 1.,
+        };
 
-        // Play the game randomly until game-over
-        while !game.is_terminal(handle) {
+        let value = if game.is_terminal(handle) {
+            params.value_function.evaluate(
+                game.diff_players(handle),
+                game.diff_owned_properties(handle),
+                &game.board,
+                params.pindex,
+            )
+        } else if self.children.is_empty() {
+            // Expand the leaf node and let the evaluator estimate its value
             game.gen_children_save(handle);
-            let first_child_i = game.nodes[handle].children[0];
+            self.sync_children_count(game, handle);
+            stats.nodes_expanded += self.children.len() as u32;
+            stats.rollouts += 1;
+            self.evaluate(game, handle, params, evaluator)
+        } else {
+            let child_index = match self.children[0].branch_type {
+                BranchType::Chance(_) => game.get_any_chance_child(handle),
+                _ => match params.opponent_choice(game, handle) {
+                    Some(i) => i,
+                    None => {
+                        // The root's children are created without being evaluated
+                        if self.children[0].prior.is_nan() {
+                            self.evaluate(game, handle, params, evaluator);
+                        }
+
+                        // The PUCT formula is `Q_i + C * P_i * sqrt(N) / (1 + n_i)`
+                        let sqrt_visits = (self.num_visits as f64).sqrt();
+                        let puct_values: Vec<f64> = self
+                            .children
+                            .iter()
+                            .map(|s| {
+                                let mean_value = if s.num_visits == 0 {
+                                    0.
+                                } else {
+                                    s.get_average_value()
+                                };
+                                mean_value
+                                    + params.temperature * s.prior * sqrt_visits
+                                        / (1 + s.num_visits) as f64
+                            })
+                            .collect();
+
+                        puct_values
+                            .iter()
+                            .enumerate()
+                            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                            .map(|(i, _)| i)
+                            .unwrap()
+                    }
+                },
+            };
+
+            let next_handle = game.nodes[handle].children[child_index];
+            self.children[child_index].traverse(game, next_handle, params, depth + 1, stats)
+        };
+
+        self.num_visits += 1;
+        self.total_value += value * value_multiplier;
+
+        value
+    }
+
+    /// Ask `evaluator` about the state at `handle`, store its priors in this node's
+    /// children (if they're choices), and return its value for the searching player.
+    fn evaluate(
+        &mut self,
+        game: &Game,
+        handle: usize,
+        params: &SearchParams,
+        evaluator: &Evaluator,
+    ) -> f64 {
+        let observation = Observation::new(StateView::new(game, handle), game.root_turn);
+        let (priors, value) = evaluator(&observation);
+
+        if matches!(self.children.first(), Some(c) if matches!(c.branch_type, BranchType::Choice)) {
+            if priors.len() != self.children.len() {
+                panic!(
+                    "evaluator returned {} priors for {} moves",
+                    priors.len(),
+                    self.children.len()
+                );
+            }
+
+            for (child, prior) in self.children.iter_mut().zip(priors) {
+                child.prior = prior;
+            }
+        }
+
+        // The value is for the acting player, so treat the game as zero-sum
+        // when it's one of the searching player's opponents
+        if observation.acting_pindex == params.pindex {
+            value
+        } else {
+            -value
+        }
+    }
+
+    /// Search from the root of `game` until `budget` (which started at `start_time`) runs
+    /// out and every child of this node has been visited. Return statistics about the search.
+    fn search(
+        &mut self,
+        game: &mut Game,
+        start_time: Instant,
+        budget: Budget,
+        params: &SearchParams,
+    ) -> SearchStats {
+        let mut stats = SearchStats {
+            reused_visits: self.num_visits,
+            ..SearchStats::default()
+        };
+        let mut iterations = 0;
 
-            match game.nodes[first_child_i].branch_type {
-                BranchType::Chance(_) => {
-                    let child_index = game.get_any_chance_child(handle);
-                    handle = game.nodes[handle].children[child_index];
+        // Continue searching until the budget runs out
+        while !budget.is_spent(start_time, iterations)
+            || self.children.iter().any(|n| n.get_average_value().is_nan())
+        {
+            if let Budget::Time(max_time) = budget {
+                if start_time.elapsed() > max_time {
+                    params.logger.log(
+                        LogLevel::Search,
+                        format_args!("MCTS exceeding time limit ({:?})", start_time.elapsed()),
+                    );
                 }
-                BranchType::Choice => {
-                    let children = &game.nodes[handle].children;
-                    handle = children[rng.gen_range(0..children.len())];
+            }
+
+            self.traverse(game, game.root_handle, params, 0, &mut stats);
+            VISIT_CLOCK.fetch_add(1, Ordering::Relaxed);
+            iterations += 1;
+
+            // The decision won't be used anyway
+            if game.is_cancelled() {
+                break;
+            }
+
+            if let Some(limit) = game.node_limit {
+                if game.node_count() > limit {
+                    let target = (limit as f64 * EVICTION_TARGET) as usize;
+                    stats.evicted_nodes += self.evict(game, target);
                 }
-                BranchType::Undefined => unreachable!(),
             }
         }
 
-        // Tabulate everyone's balances
-        let player_balances = game.diff_players(handle).iter().map(|p| p.balance as f64);
+        stats
+    }
+
+    /// Free the game tree below the least recently visited nodes of this tree (which mirrors
+    /// the game's root) until the game tree has at most `target` nodes, and forget those
+    /// nodes' children in this tree too. The nodes themselves keep their statistics, so
+    /// they're expanded again when they're next visited. Return the number of game tree
+    /// nodes that were freed.
+    fn evict(&mut self, game: &mut Game, target: usize) -> usize {
+        // Every node below this one with any game tree below it, from the least recently
+        // visited to the most, with descendants before their ancestors
+        let mut candidates = vec![];
+        for (i, child) in self.children.iter().enumerate() {
+            let handle = game.nodes[game.root_handle].children[i];
+            child.evictable(game, handle, &mut vec![i], &mut candidates);
+        }
+        candidates.sort_by_key(|(last_visit, path)| (*last_visit, Reverse(path.len())));
+
+        let mut freed = 0;
+        for (_, path) in candidates {
+            if game.node_count() <= target {
+                break;
+            }
+
+            freed += self.evict_path(game, game.root_handle, &path);
+        }
+
+        freed
+    }
+
+    /// Evict the node reached from this one (which mirrors the state at `handle`) through
+    /// the children at `path`, and return the number of game tree nodes that were freed.
+    /// Nothing is evicted if one of the nodes along the path was already evicted.
+    fn evict_path(&mut self, game: &mut Game, handle: usize, path: &[usize]) -> usize {
+        let Some((&i, rest)) = path.split_first() else {
+            self.children.clear();
+            return game.free_descendants(handle);
+        };
 
-        // Tabulate everyone's property worths
-        let props = game.diff_owned_properties(handle);
-        let mut total_prop_worths = vec![0.; game.get_player_count()];
-        for (pos, prop) in props {
-            total_prop_worths[prop.owner] += PROPERTIES[pos].price as f64;
+        match (self.children.get_mut(i), game.nodes[handle].children.get(i)) {
+            (Some(child), Some(&child_handle)) => child.evict_path(game, child_handle, rest),
+            _ => 0,
+        }
+    }
+
+    /// Add this node (which mirrors the state at `handle`, and is reached from the root
+    /// through the children at `path`) and its descendants to `candidates` if there's
+    /// any game tree below them, along with when they were last visited.
+    fn evictable(
+        &self,
+        game: &Game,
+        handle: usize,
+        path: &mut Vec<usize>,
+        candidates: &mut Vec<(u64, Vec<usize>)>,
+    ) {
+        if game.nodes[handle].children.is_empty() {
+            return;
+        }
+        candidates.push((self.last_visit, path.clone()));
+
+        for (i, child) in self.children.iter().enumerate() {
+            path.push(i);
+            child.evictable(game, game.nodes[handle].children[i], path, candidates);
+            path.pop();
+        }
+    }
+
+    /// Return the messages of the states along the line of play that the search expects
+    /// from the state at `handle` (which this node mirrors), by following the most visited
+    /// child at every step for at most `depth` moves.
+    fn principal_variation(
+        &self,
+        game: &Game,
+        mut handle: usize,
+        depth: usize,
+    ) -> Vec<DiffMessage> {
+        let mut line = vec![];
+        let mut node = self;
+
+        while line.len() < depth {
+            let best = node
+                .children
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| child.num_visits > 0)
+                .max_by_key(|(_, child)| child.num_visits);
+            let Some((i, child)) = best else {
+                break;
+            };
+
+            // The search may have gone further than the game tree (e.g. if it searched a
+            // determinization of the game rather than the game itself)
+            let Some(&child_handle) = game.nodes[handle].children.get(i) else {
+                break;
+            };
+            handle = child_handle;
+            line.push(game.nodes[handle].message.clone());
+            node = child;
+        }
+
+        line
+    }
+
+    fn rollout(game: &mut Game, handle: usize, params: &SearchParams) -> f64 {
+        let handle = play_out(game, handle, params.rollout_policy);
+        params.value_function.evaluate(
+            game.diff_players(handle),
+            game.diff_owned_properties(handle),
+            &game.board,
+            params.pindex,
+        )
+    }
+}
+
+/// Play the game from the state at `handle` with `rollout_policy` until game-over, or until
+/// the rollout has gone on for so long that the game has probably stalled, and return the
+/// handle of the state that the rollout ended at.
+pub(super) fn play_out(game: &mut Game, mut handle: usize, rollout_policy: RolloutPolicy) -> usize {
+    let mut moves = 0;
+    while !game.is_terminal(handle) && moves < MAX_ROLLOUT_MOVES {
+        moves += 1;
+        game.gen_children_save(handle);
+        let first_child_i = game.nodes[handle].children[0];
+
+        let child_index = match game.nodes[first_child_i].branch_type {
+            BranchType::Chance(_) => game.get_any_chance_child(handle),
+            BranchType::Choice => rollout_policy.choose(game, handle),
+            BranchType::Undefined => unreachable!(),
+        };
+        handle = game.nodes[handle].children[child_index];
+    }
+
+    handle
+}
+
+/// What an agent decides to do when it's asked to make a choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Move to the child of the game's root node at this index.
+    Move(usize),
+    /// Resign from the game (see `Game::resign()`).
+    Resign,
+}
+
+/// An agent playing the game, or the "brains" of a player. Implement
+/// this to plug a custom policy into `Game::play()` or a tournament.
+pub trait Agent: Send {
+    /// Choose a child of the game's root node to move to. Return the index of that child.
+    fn make_choice(&mut self, game: &mut Game) -> usize;
+
+    /// Decide whether to make a move (chosen with `make_choice()`) or to resign.
+    /// Agents never resign unless they override this.
+    fn decide(&mut self, game: &mut Game) -> Decision {
+        Decision::Move(self.make_choice(game))
+    }
+}
+
+/// Statistics about the search an MCTS agent performed for its last decision.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchStats {
+    /// The number of visits the root node already had from previous searches,
+    /// which is 0 if the tree wasn't reused.
+    pub reused_visits: u32,
+    /// The number of new MCTS nodes created during the search.
+    pub nodes_expanded: u32,
+    /// The number of rollouts performed during the search.
+    pub rollouts: u32,
+    /// The deepest level of the tree that the search reached, with the root at 0.
+    pub max_depth: u32,
+    /// The number of visits and the average value of each of the root's children.
+    pub children: Vec<(u32, f64)>,
+    /// The ID of the game that was searched (see `Game::id()`).
+    pub game_id: u64,
+    /// The number of game tree nodes stored after the search.
+    pub game_nodes: usize,
+    /// The estimated memory used by the game tree after the search, in bytes.
+    pub game_memory: usize,
+    /// The number of game tree nodes freed during the search to stay within
+    /// `Game::with_node_limit()`.
+    pub evicted_nodes: usize,
+    /// Whether the move was taken from the opening book instead of being searched.
+    pub from_book: bool,
+    /// Whether the move was chosen by the endgame solver instead of being searched,
+    /// in which case `children` holds the exact win probability of each child.
+    pub solved: bool,
+}
+
+impl std::fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.from_book {
+            return writeln!(f, "played from the opening book");
+        }
+
+        if self.solved {
+            writeln!(f, "solved exactly")?;
+            for (i, (_, value)) in self.children.iter().enumerate() {
+                writeln!(f, "  [{}] win probability {:.3}", i, value)?;
+            }
+            return Ok(());
+        }
+
+        writeln!(
+            f,
+            "{} rollouts, {} nodes expanded, max depth {}, {} visits reused",
+            self.rollouts, self.nodes_expanded, self.max_depth, self.reused_visits
+        )?;
+        writeln!(
+            f,
+            "game tree: {} nodes, {} KiB, {} nodes evicted",
+            self.game_nodes,
+            self.game_memory / 1024,
+            self.evicted_nodes
+        )?;
+
+        for (i, (visits, value)) in self.children.iter().enumerate() {
+            writeln!(f, "  [{}] {} visits, value {:.1}", i, visits, value)?;
         }
 
-        let scores: Vec<f64> = zip(player_balances, total_prop_worths)
-            .map(|(balance, prop_worth)| balance * prop_worth)
+        Ok(())
+    }
+}
+
+/// How many moves of the expected line of play `MctsAgent::analyze()` returns for each move.
+const ANALYSIS_DEPTH: usize = 8;
+
+/// How an MCTS search rated one of the moves available at a position.
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveAnalysis {
+    /// The move that was rated.
+    pub summary: MoveSummary,
+    /// The number of times the search visited the move.
+    pub visits: u32,
+    /// The average value of the move for the player making it.
+    pub value: f64,
+    /// The line of play that the search expects, starting with the move itself
+    /// and following the most visited move (or chance outcome) from there on.
+    pub principal_variation: Vec<DiffMessage>,
+}
+
+impl std::fmt::Display for MoveAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line: Vec<String> = self
+            .principal_variation
+            .iter()
+            .map(|message| message.to_string())
             .collect();
-        let mean_score: f64 = scores.iter().sum::<f64>() / scores.len() as f64;
 
-        // The value of the game state is calculated as a player's distance from the mean balance
-        scores[pindex] - mean_score
+        write!(
+            f,
+            "[{}] {} visits, value {:.1}: {}",
+            self.summary.index,
+            self.visits,
+            self.value,
+            line.join(" -> ")
+        )
     }
 }
 
-/// An agent playing the game, or the "brains" of a player.
-pub enum Agent {
-    /// An MCTS AI agent.
-    Ai {
-        /// Amount of time that the AI is given to "think", in milliseconds.
-        time_limit: u64,
-        /// Value of `C` constant in UCB1 formula.
-        temperature: f64,
-        /// Index of this agent in `Game.agents`.
-        index: usize,
-        /// Index of the last move that this agent played, from `Game.move_history`.
-        latest_unseen_move: usize,
-        /// The Monte-Carlo search tree associated with this AI.
-        mcts_tree: MCTreeNode,
-    },
-    /// A physical human player.
-    Human,
-    /// An agent that plays randomly
-    Random,
+/// How much searching an MCTS agent does for each decision.
+#[derive(Debug, Clone, Copy)]
+pub enum Budget {
+    /// Search until this much time has passed.
+    Time(Duration),
+    /// Search for this many iterations per search tree, which is
+    /// reproducible across machines of different speeds.
+    Iterations(u32),
+}
+
+impl Budget {
+    /// Return whether the budget has run out for a search that started
+    /// at `start_time` and has performed `iterations` iterations.
+    pub(super) fn is_spent(&self, start_time: Instant, iterations: u32) -> bool {
+        match *self {
+            Budget::Time(max_time) => start_time.elapsed() >= max_time,
+            Budget::Iterations(max_iterations) => iterations >= max_iterations,
+        }
+    }
 }
 
-impl Agent {
-    /*********        PUBLIC INTERFACES        *********/
+/// An MCTS AI agent.
+pub struct MctsAgent {
+    /// How much the AI is allowed to "think" for each decision.
+    budget: Budget,
+    /// Value of `C` constant in UCB1 formula.
+    temperature: f64,
+    /// How moves are chosen during rollouts.
+    rollout_policy: RolloutPolicy,
+    /// How the game state at the end of a rollout is scored.
+    value_function: Box<dyn ValueFunction>,
+    /// The model that guides the search with PUCT instead of UCB1 and rollouts, if any.
+    evaluator: Option<Box<Evaluator>>,
+    /// Index of this agent in `Game.agents`.
+    index: usize,
+    /// Index of the last move that this agent played, from `Game.move_history`.
+    latest_unseen_move: usize,
+    /// The Monte-Carlo search trees associated with this AI, one for each search thread.
+    /// Each tree is searched independently and their root statistics are merged.
+    mcts_trees: Vec<MCTreeNode>,
+    /// Statistics about the search for the last decision.
+    search_stats: SearchStats,
+    /// Where `search_stats` is logged after every decision.
+    logger: Logger,
+    /// The book of early-game moves that this agent consults and adds its searches to.
+    opening_book: Option<Arc<Mutex<OpeningBook>>>,
+    /// The solver that replaces the search for positions small enough to solve exactly.
+    endgame_solver: Option<EndgameSolver>,
+    /// Whether each search tree searches a copy of the game in which the chance cards that
+    /// have been drawn are sampled, rather than the game itself. See `with_determinization()`.
+    determinize: bool,
+    /// How the search expects opponents to choose their moves, if they aren't searched.
+    opponent_model: Option<OpponentModel>,
+    /// The transposition table that this agent shares with others, if any.
+    shared_cache: Option<Arc<SharedCache>>,
+    /// The number of moves in `Game.transcript` that the opponent model has learned from.
+    observed_moves: usize,
+    /// The estimated chance of winning below which this agent resigns, if it ever does.
+    resign_threshold: Option<f64>,
+    /// Where the work done by every search is added up, if anywhere.
+    metrics: Option<Arc<Metrics>>,
+}
 
-    /// Return a new AI agent.
-    pub fn new_ai(time_limit: u64, temperature: f64, index: usize) -> Agent {
-        Agent::Ai {
-            time_limit,
+impl MctsAgent {
+    /// Return a new AI agent that thinks for `time_limit` milliseconds per decision.
+    pub fn new(time_limit: u64, temperature: f64, index: usize) -> MctsAgent {
+        MctsAgent {
+            budget: Budget::Time(Duration::from_millis(time_limit)),
             temperature,
+            rollout_policy: RolloutPolicy::Random,
+            value_function: Box::new(BalanceTimesPropertyWorth),
+            evaluator: None,
             index,
             latest_unseen_move: 0,
-            mcts_tree: MCTreeNode::new(BranchType::Choice),
+            mcts_trees: vec![MCTreeNode::new(BranchType::Choice)],
+            search_stats: SearchStats::default(),
+            logger: Logger::default(),
+            opening_book: None,
+            endgame_solver: None,
+            determinize: false,
+            opponent_model: None,
+            shared_cache: None,
+            observed_moves: 0,
+            resign_threshold: None,
+            metrics: None,
         }
     }
 
-    /// Return a new human agent.
-    pub fn new_human() -> Agent {
-        Agent::Human
+    /// Search with `budget` for each decision instead of a time limit.
+    pub fn with_budget(mut self, budget: Budget) -> MctsAgent {
+        self.budget = budget;
+        self
+    }
+
+    /// Choose moves during rollouts with `rollout_policy` instead of randomly.
+    pub fn with_rollout_policy(mut self, rollout_policy: RolloutPolicy) -> MctsAgent {
+        self.rollout_policy = rollout_policy;
+        self
+    }
+
+    /// Score the end of rollouts with `value_function` instead of
+    /// `BalanceTimesPropertyWorth`.
+    pub fn with_value_function(mut self, value_function: Box<dyn ValueFunction>) -> MctsAgent {
+        self.value_function = value_function;
+        self
+    }
+
+    /// Guide the search with `evaluator` (e.g. a neural network), AlphaZero-style: choices
+    /// are selected with the PUCT formula using the evaluator's priors, and new leaf nodes
+    /// are scored by the evaluator instead of by rollouts. Game-over states are scored with
+    /// `WinLoss` to be on the same scale as the evaluator's values, unless
+    /// `with_value_function()` is called afterwards.
+    pub fn with_evaluator(
+        mut self,
+        evaluator: impl Fn(&Observation) -> (Vec<f64>, f64) + Send + Sync + 'static,
+    ) -> MctsAgent {
+        self.evaluator = Some(Box::new(evaluator));
+        self.value_function = Box::new(WinLoss);
+        self
+    }
+
+    /// Search with `threads` independent trees in parallel (root parallelisation).
+    pub fn with_threads(mut self, threads: usize) -> MctsAgent {
+        // There are no threads on the web
+        #[cfg(target_arch = "wasm32")]
+        let threads = threads.min(1);
+
+        self.mcts_trees = (0..threads.max(1))
+            .map(|_| MCTreeNode::new(BranchType::Choice))
+            .collect();
+        self
+    }
+
+    /// Log with `logger`, which logs the search statistics after
+    /// every decision if it's set to `LogLevel::Search`.
+    pub fn with_logger(mut self, logger: Logger) -> MctsAgent {
+        self.logger = logger;
+        self
+    }
+
+    /// Play the moves recommended by `book` for states that have been searched often
+    /// enough, and add the results of every other search within its depth to it. The
+    /// book can be shared between agents, so that they build it up together.
+    pub fn with_opening_book(mut self, book: Arc<Mutex<OpeningBook>>) -> MctsAgent {
+        self.opening_book = Some(book);
+        self
+    }
+
+    /// Play the exact best move instead of searching when the game tree below the
+    /// current state has at most `node_limit` nodes, which is the case near the end
+    /// of games where few properties and little money remain.
+    pub fn with_endgame_solver(mut self, node_limit: usize) -> MctsAgent {
+        self.endgame_solver = Some(EndgameSolver::new(node_limit));
+        self
+    }
+
+    /// Don't let the search know which chance cards have been drawn, like a human player who
+    /// hasn't kept track of them. Each search tree searches its own determinization of the
+    /// game, in which the drawn cards are sampled at random (see `ChanceDeck::determinized()`),
+    /// and their results are merged as usual, so more threads means more determinizations.
+    /// The trees are built from scratch for every decision, since a determinization's game
+    /// tree doesn't carry over to the next one.
+    pub fn with_determinization(mut self) -> MctsAgent {
+        self.determinize = true;
+        self
+    }
+
+    /// Expect opponents to choose their moves as `opponent_model` does, instead of searching
+    /// their moves like this agent's own. The search then plays out what the opponents would
+    /// actually do rather than what's best for them. An `OpponentModel::Frequencies` model
+    /// keeps learning from the opponents' moves over the course of the game.
+    pub fn with_opponent_model(mut self, opponent_model: OpponentModel) -> MctsAgent {
+        self.opponent_model = Some(opponent_model);
+        self
+    }
+
+    /// Share `cache` with other agents, across games and threads: its opening book is used
+    /// like `with_opening_book()`, new choice nodes are started off with the values it has
+    /// for their states, and the value of every move searched for a decision is added to it.
+    pub fn with_shared_cache(mut self, cache: Arc<SharedCache>) -> MctsAgent {
+        self.opening_book = Some(cache.opening_book().clone());
+        self.shared_cache = Some(cache);
+        self
+    }
+
+    /// Add the work done by every search to `metrics`, which can be shared with other agents.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> MctsAgent {
+        self.metrics = Some(metrics);
+        self
     }
 
-    /// Return an agent that plays randomly.
-    pub fn new_random() -> Agent {
-        Agent::Random
+    /// Resign instead of searching once this agent's estimated chance of winning (see
+    /// `Game::win_probabilities()`) drops below `threshold`, e.g. 0.05.
+    pub fn with_resignation(mut self, threshold: f64) -> MctsAgent {
+        self.resign_threshold = Some(threshold);
+        self
     }
 
-    /// Choose a child of `from_node` to move to. Return the index of that child.
-    pub fn make_choice(&mut self, game: &mut Game) -> usize {
-        match self {
-            Agent::Ai { .. } => self.ai_choice(game),
-            Agent::Human => self.human_choice(game),
-            Agent::Random => self.random_choice(game),
+    /// Return the line of play that this agent's last search expects from the current
+    /// state of `game`, starting with the move it chose and following the most visited
+    /// move (or chance outcome) from there on, for at most `depth` moves. This explains
+    /// why the agent chose its move, so it has to be called after `make_choice()` and
+    /// before the move is made. The line is empty if the move came from the opening book.
+    pub fn principal_variation(&self, game: &Game, depth: usize) -> Vec<DiffMessage> {
+        match self.best_child() {
+            Some(i) if depth > 0 => self.line_after(game, i, depth),
+            _ => vec![],
         }
     }
 
-    /*********        PLAYER LOGIC        *********/
+    /// Return the index of the root's child with the greatest average value
+    /// in the last search, or `None` if the last move wasn't searched.
+    fn best_child(&self) -> Option<usize> {
+        self.search_stats
+            .children
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(i, _)| i)
+    }
+
+    /// Return the line of play that the last search expects after the root's `i`th child,
+    /// starting with the child itself, for at most `depth` (at least 1) moves. The line is
+    /// followed in the tree that visited the child the most.
+    fn line_after(&self, game: &Game, i: usize, depth: usize) -> Vec<DiffMessage> {
+        let tree = self
+            .mcts_trees
+            .iter()
+            .max_by_key(|tree| tree.children[i].num_visits)
+            .unwrap();
+        let handle = game.nodes[game.root_handle].children[i];
+
+        let mut line = vec![game.nodes[handle].message.clone()];
+        line.extend(tree.children[i].principal_variation(game, handle, depth - 1));
+        line
+    }
+
+    /// Search the current position of `game`, whose acting player must be this agent's,
+    /// and return how the search rated each of the moves in `moves` (which should be
+    /// `game.legal_moves()`), along with the line of play it expects after each of them.
+    pub fn analyze(&mut self, game: &mut Game, moves: Vec<MoveSummary>) -> Vec<MoveAnalysis> {
+        self.make_choice(game);
+
+        moves
+            .into_iter()
+            .zip(&self.search_stats.children)
+            .enumerate()
+            .map(|(i, (summary, &(visits, value)))| {
+                let principal_variation = self.line_after(game, i, ANALYSIS_DEPTH);
+                MoveAnalysis {
+                    summary,
+                    visits,
+                    value,
+                    principal_variation,
+                }
+            })
+            .collect()
+    }
 
-    fn ai_choice(&mut self, game: &mut Game) -> usize {
+    /// Return statistics about the search this agent performed for its last decision.
+    pub fn search_stats(&self) -> &SearchStats {
+        &self.search_stats
+    }
+}
+
+impl Agent for MctsAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
         let start_time = Instant::now();
 
-        // Extract relevant fields from agent
-        let (max_time, temperature, agent_index, latest_unseen_move, mcts_node) = match self {
-            Agent::Ai {
-                time_limit,
-                temperature,
-                index,
-                latest_unseen_move,
-                mcts_tree,
-            } => (
-                Duration::from_millis(*time_limit),
-                *temperature,
-                *index,
-                latest_unseen_move,
-                mcts_tree,
-            ),
-            _ => unreachable!(),
-        };
+        // Ensure the root node has all of its direct children
+        game.gen_children_save(game.root_handle);
 
-        // Update mcts_node to reflect the current game state
-        mcts_node.sync_with_walk(game, *latest_unseen_move);
+        // Update every search tree to reflect the current game state
+        for tree in &mut self.mcts_trees {
+            if self.determinize {
+                // The tree of the last decision was built for another determinization
+                *tree = MCTreeNode::new(BranchType::Choice);
+            } else {
+                tree.sync_with_walk(game, self.latest_unseen_move);
+            }
+            tree.sync_children_count(game, game.root_handle);
+        }
         // Set the lastest unseen move to the move after this one
-        *latest_unseen_move = game.move_history.len();
+        self.latest_unseen_move = game.move_history.len();
+
+        // Learn from the moves that were made since this agent's last move
+        let entries = &game.transcript.entries;
+        if let Some(model) = &mut self.opponent_model {
+            model.observe(&entries[self.observed_moves.min(entries.len())..]);
+        }
+        self.observed_moves = entries.len() + 1;
+
+        // Skip the search if the book already knows the best move
+        let children = game.nodes[game.root_handle].children.len();
+        if let Some(book) = &self.opening_book {
+            let book_move = book
+                .lock()
+                .unwrap()
+                .lookup(&game.move_history, children)
+                .map(|entry| entry.best_move());
+
+            if let Some(choice) = book_move {
+                self.search_stats = SearchStats {
+                    from_book: true,
+                    game_id: game.id(),
+                    game_nodes: game.node_count(),
+                    game_memory: game.memory_usage(),
+                    ..SearchStats::default()
+                };
+                self.logger.event(
+                    LogLevel::Search,
+                    LogEvent::Search {
+                        pindex: self.index,
+                        stats: &self.search_stats,
+                    },
+                );
+                return choice;
+            }
+        }
+
+        // Skip the search if the rest of the game is small enough to solve exactly
+        if let Some(solution) = self
+            .endgame_solver
+            .and_then(|solver| solver.solve(game, self.index))
+        {
+            self.search_stats = SearchStats {
+                solved: true,
+                children: solution.values.iter().map(|&value| (0, value)).collect(),
+                game_id: game.id(),
+                game_nodes: game.node_count(),
+                game_memory: game.memory_usage(),
+                ..SearchStats::default()
+            };
+            self.logger.event(
+                LogLevel::Search,
+                LogEvent::Search {
+                    pindex: self.index,
+                    stats: &self.search_stats,
+                },
+            );
+            return solution.best_move;
+        }
+
+        let budget = self.budget;
+        let determinize = self.determinize;
+        let params = SearchParams {
+            pindex: self.index,
+            temperature: self.temperature,
+            rollout_policy: self.rollout_policy,
+            value_function: self.value_function.as_ref(),
+            evaluator: self.evaluator.as_deref(),
+            opponent_model: self.opponent_model.as_ref(),
+            shared_cache: self.shared_cache.as_deref(),
+            logger: self.logger,
+        };
+
+        // Start the root's new children off with what the cache knows about them
+        for tree in &mut self.mcts_trees {
+            tree.seed_children(game, game.root_handle, &params);
+        }
+        let before_search = merged_root_children(&self.mcts_trees, children);
+
+        let (first_tree, other_trees) = self.mcts_trees.split_first_mut().unwrap();
+
+        let all_stats = search_trees(
+            first_tree,
+            other_trees,
+            game,
+            start_time,
+            budget,
+            &params,
+            determinize,
+        );
+
+        let merged = merged_root_children(&self.mcts_trees, children);
+
+        // Add only what this search found out to the cache, not what it started off with
+        if let Some(cache) = &self.shared_cache {
+            let child_handles = &game.nodes[game.root_handle].children;
+            for ((&handle, after), before) in child_handles.iter().zip(&merged).zip(before_search) {
+                cache.record(
+                    game.nodes[handle].hash,
+                    self.index,
+                    after.1 - before.1,
+                    after.0 - before.0,
+                );
+            }
+        }
+
+        self.search_stats = SearchStats {
+            reused_visits: all_stats.iter().map(|s| s.reused_visits).sum(),
+            nodes_expanded: all_stats.iter().map(|s| s.nodes_expanded).sum(),
+            rollouts: all_stats.iter().map(|s| s.rollouts).sum(),
+            max_depth: all_stats.iter().map(|s| s.max_depth).max().unwrap(),
+            children: merged
+                .iter()
+                .map(|&(visits, total_value)| (visits, total_value / visits as f64))
+                .collect(),
+            game_id: game.id(),
+            game_nodes: game.node_count(),
+            game_memory: game.memory_usage(),
+            evicted_nodes: all_stats.iter().map(|s| s.evicted_nodes).sum(),
+            from_book: false,
+            solved: false,
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_search(&self.search_stats);
+        }
+
+        if let Some(book) = &self.opening_book {
+            let visits: Vec<u32> = merged.iter().map(|&(visits, _)| visits).collect();
+            book.lock().unwrap().record(&game.move_history, &visits);
+        }
+
+        self.logger.event(
+            LogLevel::Search,
+            LogEvent::Search {
+                pindex: self.index,
+                stats: &self.search_stats,
+            },
+        );
+
+        // Choose the child with the greatest average value
+        self.best_child().unwrap()
+    }
+
+    fn decide(&mut self, game: &mut Game) -> Decision {
+        match self.resign_threshold {
+            Some(threshold) if game.win_probabilities()[self.index] < threshold => Decision::Resign,
+            _ => Decision::Move(self.make_choice(game)),
+        }
+    }
+}
+
+/// Return the total visits and value of each of the root's `children`, across all of `trees`.
+fn merged_root_children(trees: &[MCTreeNode], children: usize) -> Vec<(u32, f64)> {
+    let mut merged = vec![(0, 0.); children];
+    for tree in trees {
+        for (m, child) in merged.iter_mut().zip(&tree.children) {
+            m.0 += child.num_visits;
+            m.1 += child.total_value;
+        }
+    }
+
+    merged
+}
+
+/// Search each tree independently, with the first tree on this thread and the rest on their
+/// own threads with their own copy of the game. If `determinize`, every tree searches its own
+/// determinization of the game instead (see `MctsAgent::with_determinization()`).
+#[cfg(not(target_arch = "wasm32"))]
+fn search_trees(
+    first_tree: &mut MCTreeNode,
+    other_trees: &mut [MCTreeNode],
+    game: &mut Game,
+    start_time: Instant,
+    budget: Budget,
+    params: &SearchParams,
+    determinize: bool,
+) -> Vec<SearchStats> {
+    thread::scope(|s| {
+        let handles: Vec<_> = other_trees
+            .iter_mut()
+            .map(|tree| {
+                let mut game = if determinize {
+                    game.determinized()
+                } else {
+                    game.clone()
+                };
+                s.spawn(move || tree.search(&mut game, start_time, budget, params))
+            })
+            .collect();
+
+        let first_stats = if determinize {
+            first_tree.search(&mut game.determinized(), start_time, budget, params)
+        } else {
+            first_tree.search(game, start_time, budget, params)
+        };
+        let mut all_stats = vec![first_stats];
+        all_stats.extend(handles.into_iter().map(|h| h.join().unwrap()));
+        all_stats
+    })
+}
+
+/// Search the first tree. There are no threads on the web, so `MctsAgent::with_threads()`
+/// never creates any other trees.
+#[cfg(target_arch = "wasm32")]
+fn search_trees(
+    first_tree: &mut MCTreeNode,
+    _other_trees: &mut [MCTreeNode],
+    game: &mut Game,
+    start_time: Instant,
+    budget: Budget,
+    params: &SearchParams,
+    determinize: bool,
+) -> Vec<SearchStats> {
+    if determinize {
+        return vec![first_tree.search(&mut game.determinized(), start_time, budget, params)];
+    }
+
+    vec![first_tree.search(game, start_time, budget, params)]
+}
+
+/// The width of a tile on the text board shown to hot-seat players, in characters.
+#[cfg(not(target_arch = "wasm32"))]
+const TEXT_TILE_WIDTH: usize = 9;
+
+/// A physical human player, who picks moves through the terminal.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct HumanAgent {
+    /// Whether the terminal is shared with other human players, who take turns at it.
+    hot_seat: bool,
+    /// The number of moves in the game's transcript after this player's last move.
+    moves_seen: Option<usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HumanAgent {
+    /// Return a human player who has the terminal to themselves.
+    pub fn new() -> HumanAgent {
+        HumanAgent::default()
+    }
+
+    /// Return a human player who shares the terminal with other human players (hot-seat).
+    /// Whenever the player's turn comes after someone else's, the screen is cleared and
+    /// they're asked to take the keyboard, and the board is shown before every move.
+    pub fn hot_seat() -> HumanAgent {
+        HumanAgent {
+            hot_seat: true,
+            moves_seen: None,
+        }
+    }
+
+    /// Clear the screen (or separate it with a line if `plain`), and
+    /// wait for player `pindex` to confirm that they have the keyboard.
+    fn hand_over(pindex: usize, plain: bool) {
+        if plain {
+            println!("\n{}", "=".repeat(TEXT_TILE_WIDTH * 10));
+        } else {
+            print!("\x1b[2J\x1b[H");
+        }
+
+        print!(
+            "Player {}'s turn, press enter when you have the keyboard: ",
+            pindex
+        );
+        io::stdout().flush().unwrap();
+        if io::stdin().read_line(&mut String::new()).unwrap() == 0 {
+            panic!("stdin closed while waiting for a human move");
+        }
+    }
+
+    /// Show the current player, `pindex`, the moves available to them (without
+    /// highlighting if `plain`), and return the index of the one they choose.
+    fn ask_for_move(&self, game: &mut Game, pindex: usize, plain: bool) -> usize {
+        let children = &game.nodes[game.root_handle].children;
+        let player = &game.diff_players(game.root_handle)[pindex];
+        if plain {
+            println!("\nPlayer {}'s move: {:#}", pindex, player);
+        } else {
+            println!("\nPlayer {}'s move: {}", pindex, player);
+        }
+        for (i, &child) in children.iter().enumerate() {
+            println!("  [{}] {}", i, game.nodes[child].message);
+
+            for diff in &game.nodes[child].diffs {
+                if plain {
+                    println!("        {:#}", diff);
+                } else {
+                    println!("        {}", diff);
+                }
+            }
+        }
+
+        // Keep asking until we get a valid index
+        loop {
+            print!(
+                "Choose a move (0-{}), 'save <path>' or 'transcript <path>': ",
+                children.len() - 1
+            );
+            io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input).unwrap() == 0 {
+                panic!("stdin closed while waiting for a human move");
+            }
+
+            // Allow the game to be saved mid-way with `save <path>`
+            if let Some(path) = input.trim().strip_prefix("save ") {
+                match game.save(path) {
+                    Ok(()) => println!("Game saved to '{}'", path),
+                    Err(e) => println!("Failed to save game: {}", e),
+                }
+                continue;
+            }
+
+            // Allow the moves so far to be saved as a replay file with `transcript <path>`
+            if let Some(path) = input.trim().strip_prefix("transcript ") {
+                match game.transcript().save(path) {
+                    Ok(()) => println!("Transcript saved to '{}'", path),
+                    Err(e) => println!("Failed to save transcript: {}", e),
+                }
+                continue;
+            }
 
-        // Ensure `mcts_node` has all of its direct children
+            match input.trim().parse::<usize>() {
+                Ok(i) if i < children.len() => return i,
+                _ => println!("'{}' is not a valid move", input.trim()),
+            }
+        }
+    }
+}
+
+/// Return the board at `state` as text, one side of the board per row of tiles. Each tile
+/// shows its position and kind, the owner and rent level of a property, and the indexes
+/// of the players standing on it, followed by a line for each player.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn render_board(state: StateView) -> String {
+    let board = state.board();
+    let side = board.size.div_ceil(4);
+    let mut text = String::new();
+
+    for start in (0..board.size).step_by(side as usize) {
+        let mut lines = [String::new(), String::new(), String::new()];
+
+        for pos in start..(start + side).min(board.size) {
+            let ownership = match state.owned_properties().get(&pos) {
+                Some(prop) => format!("p{} L{}", prop.owner, prop.rent_level),
+                None if board.is_property(pos) => format!("${}", board.property(pos).price),
+                None => String::new(),
+            };
+            let tokens: String = state
+                .players()
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !p.bankrupt && p.position == pos)
+                .map(|(i, _)| i.to_string())
+                .collect();
+
+            let cells = [
+                format!("{:02} {}", pos, board.tile_label(pos)),
+                ownership,
+                tokens,
+            ];
+            for (line, cell) in lines.iter_mut().zip(cells) {
+                *line += &format!("{:<width$}", cell, width = TEXT_TILE_WIDTH);
+            }
+        }
+
+        for line in lines {
+            text += line.trim_end();
+            text.push('\n');
+        }
+    }
+
+    for (i, player) in state.players().iter().enumerate() {
+        let marker = if i == state.acting_pindex() { ">" } else { " " };
+        text += &format!("{} p{} {:#}\n", marker, i, player);
+    }
+
+    text
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Agent for HumanAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
         game.gen_children_save(game.root_handle);
-        mcts_node.sync_children_count(game, game.root_handle);
 
-        // Continue searching until time is up
-        while start_time.elapsed() < max_time
-            || mcts_node
-                .children
+        // Don't highlight anything if the game isn't being logged with ANSI escape codes
+        let plain = game.logger().format() != OutputFormat::Ansi;
+        let pindex = game.get_acting_pindex(game.root_handle);
+
+        if self.hot_seat {
+            // Hand the keyboard over if anyone else has chosen a move since this player's last one
+            let entries = &game.transcript().entries;
+            let others_moved = match self.moves_seen.and_then(|seen| entries.get(seen..)) {
+                Some(moves) => moves
+                    .iter()
+                    .any(|m| matches!(m.branch_type, BranchType::Choice)),
+                None => true,
+            };
+            if others_moved {
+                HumanAgent::hand_over(pindex, plain);
+            }
+
+            print!("\n{}", render_board(game.current_state()));
+        }
+
+        let choice = self.ask_for_move(game, pindex, plain);
+        self.moves_seen = Some(game.transcript().entries.len() + 1);
+        choice
+    }
+}
+
+/// An agent that plays randomly.
+pub struct RandomAgent;
+
+impl Agent for RandomAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
+        game.gen_children_save(game.root_handle);
+        let child_count = game.nodes[game.root_handle].children.len();
+        with_rng(|rng| rng.gen_range(0..child_count))
+    }
+}
+
+/// An agent that searches a fixed number of moves ahead with expectimax, assuming
+/// that its opponents play against it and that chance outcomes follow their probabilities.
+pub struct ExpectimaxAgent {
+    /// The number of moves (including chance events) to search ahead.
+    depth: u32,
+    /// Index of this agent in `Game.agents`.
+    index: usize,
+}
+
+impl ExpectimaxAgent {
+    /// Return a new expectimax agent that searches `depth` moves ahead.
+    pub fn new(depth: u32, index: usize) -> ExpectimaxAgent {
+        ExpectimaxAgent {
+            depth: depth.max(1),
+            index,
+        }
+    }
+
+    /// Return the expectimax value of the state at `handle`, searching `depth` moves ahead.
+    fn search(&self, game: &mut Game, handle: usize, depth: u32) -> f64 {
+        if depth == 0 || game.is_terminal(handle) {
+            return NetWorthDifferential.evaluate(
+                game.diff_players(handle),
+                game.diff_owned_properties(handle),
+                &game.board,
+                self.index,
+            );
+        }
+
+        game.gen_children_save(handle);
+        let children = game.nodes[handle].children.clone();
+        let acting_pindex = game.get_acting_pindex(handle);
+
+        match game.nodes[children[0]].branch_type {
+            // Chance nodes are worth the expected value of their outcomes
+            BranchType::Chance(_) => children
                 .iter()
-                .any(|n| n.get_average_value().is_nan())
-        {
-            if start_time.elapsed() > max_time {
-                println!("MCTS exceeding time limit ({:?})", start_time.elapsed());
+                .map(|&child| match game.nodes[child].branch_type {
+                    BranchType::Chance(p) => p * self.search(game, child, depth - 1),
+                    _ => unreachable!(),
+                })
+                .sum(),
+            // We pick our best move, and our opponents pick our worst
+            BranchType::Choice => {
+                let values = children
+                    .iter()
+                    .map(|&child| self.search(game, child, depth - 1));
+
+                if acting_pindex == self.index {
+                    values.fold(f64::NEG_INFINITY, f64::max)
+                } else {
+                    values.fold(f64::INFINITY, f64::min)
+                }
             }
+            BranchType::Undefined => unreachable!(),
+        }
+    }
+}
+
+impl Agent for ExpectimaxAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
+        game.gen_children_save(game.root_handle);
+        let children = game.nodes[game.root_handle].children.clone();
+
+        let values: Vec<f64> = children
+            .iter()
+            .map(|&child| self.search(game, child, self.depth - 1))
+            .collect();
+
+        values
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(i, _)| i)
+            .unwrap()
+    }
+}
+
+/// An agent that follows simple Monopoly rules of thumb, as a stronger baseline than
+/// `RandomAgent`. It buys whatever it can afford, values completed color sets highly,
+/// only teleports to properties that progress its color sets, and only accepts trades
+/// that leave it better off.
+pub struct HeuristicAgent;
 
-            mcts_node.traverse(game, game.root_handle, agent_index, temperature);
+impl HeuristicAgent {
+    /// The amount of money to keep in reserve when teleporting to a property to buy it.
+    const CASH_RESERVE: i32 = 150;
+
+    /// Return how much `pindex` is worth at the state at `handle`: their cash, plus
+    /// the price and current rent of their properties, plus a bonus for every color
+    /// set that they own completely.
+    fn net_worth(game: &Game, handle: usize, pindex: usize) -> f64 {
+        let props = game.diff_owned_properties(handle);
+        let mut worth = game.diff_players(handle)[pindex].balance as f64;
+
+        for (pos, prop) in props.iter().filter(|(_, p)| p.owner == pindex) {
+            let property = game.board.property(*pos);
+            worth += (property.price + property.rent(prop.rent_level)) as f64;
         }
 
-        let p = mcts_node
-            .children
+        // Completing a color set is worth half as much again as its properties
+        for positions in game.board.props_by_color().values() {
+            if positions
+                .iter()
+                .all(|pos| props.get(pos).map(|p| p.owner) == Some(pindex))
+            {
+                let set_price: i32 = positions
+                    .iter()
+                    .map(|&p| game.board.property(p).price)
+                    .sum();
+                worth += set_price as f64 / 2.;
+            }
+        }
+
+        worth
+    }
+
+    /// Return whether teleporting to the property at `pos` is worth it for `pindex`, which
+    /// is the case if it's unowned, affordable, and in a color set they already have a stake in.
+    fn should_teleport_to(game: &Game, handle: usize, pindex: usize, pos: u8) -> bool {
+        let props = game.diff_owned_properties(handle);
+        let property = game.board.property(pos);
+        let balance = game.diff_players(handle)[pindex].balance;
+
+        let affordable =
+            balance - game.config.location_fee - property.price >= HeuristicAgent::CASH_RESERVE;
+        let progresses_set = game.board.props_by_color()[&property.color]
             .iter()
-            .map(|n| n.get_average_value())
-            .collect::<Vec<f64>>();
-        println!("{:?}", p);
+            .any(|p| props.get(p).map(|p| p.owner) == Some(pindex));
 
-        mcts_node.get_best_child_index()
+        !props.contains_key(&pos) && affordable && progresses_set
     }
 
-    fn human_choice(&self, _game: &mut Game) -> usize {
-        0
+    /// Return how good the state at `handle` is for `pindex`.
+    fn score(game: &Game, handle: usize, pindex: usize) -> f64 {
+        let parent = game.nodes[handle].parent;
+
+        match game.nodes[handle].message {
+            // Always buy a property if it's affordable
+            DiffMessage::BuyProp { .. } => f64::INFINITY,
+            // Only teleport to properties that we want to buy, preferring
+            // the ones that will collect the most rent
+            DiffMessage::Location(pos) => {
+                if HeuristicAgent::should_teleport_to(game, parent, pindex, pos) {
+                    1. + game.valuation().rent_yield(&game.board, pos, 1)
+                } else {
+                    f64::NEG_INFINITY
+                }
+            }
+            DiffMessage::NoLocation => 0.,
+            // Bid for properties up to their price, if it leaves enough cash in reserve
+            DiffMessage::Bid { pos, amount, .. } => {
+                let balance = game.diff_players(parent)[pindex].balance;
+                if amount <= game.board.property(pos).price
+                    && balance - amount >= HeuristicAgent::CASH_RESERVE
+                {
+                    f64::INFINITY
+                } else {
+                    f64::NEG_INFINITY
+                }
+            }
+            DiffMessage::PassBid(_) => 0.,
+            // Build whenever it leaves enough cash in reserve
+            DiffMessage::Build(_) => {
+                if game.diff_players(handle)[pindex].balance >= HeuristicAgent::CASH_RESERVE {
+                    f64::INFINITY
+                } else {
+                    f64::NEG_INFINITY
+                }
+            }
+            DiffMessage::NoBuild => 0.,
+            // Otherwise, maximise our worth relative to our strongest opponent
+            _ => {
+                let strongest_opponent = (0..game.player_count())
+                    .filter(|&i| i != pindex && !game.diff_players(handle)[i].bankrupt)
+                    .map(|i| HeuristicAgent::net_worth(game, handle, i))
+                    .fold(0., f64::max);
+
+                HeuristicAgent::net_worth(game, handle, pindex) - strongest_opponent
+            }
+        }
     }
 
-    fn random_choice(&self, game: &mut Game) -> usize {
-        let mut rng = rand::thread_rng();
+    /// Return the index of the child of the choice node at `handle` (whose children have
+    /// been generated) that this agent would play.
+    pub(super) fn choose(game: &Game, handle: usize) -> usize {
+        let pindex = game.get_acting_pindex(handle);
+
+        // Pick the first of the best-scoring moves, so that ties go to
+        // the "do nothing" move (e.g. not proposing a trade)
+        let mut best = (0, f64::NEG_INFINITY);
+        for (i, &child) in game.nodes[handle].children.iter().enumerate() {
+            let score = HeuristicAgent::score(game, child, pindex);
+            if score > best.1 {
+                best = (i, score);
+            }
+        }
+
+        best.0
+    }
+}
+
+impl Agent for HeuristicAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
         game.gen_children_save(game.root_handle);
-        rng.gen_range(0..game.nodes[game.root_handle].children.len())
+        HeuristicAgent::choose(game, game.root_handle)
     }
 }
@@ -0,0 +1,201 @@
+use super::state_diff::{FieldDiff, StateDiff};
+use serde::{Deserialize, Serialize};
+use std::mem::size_of;
+use std::ops::{Index, IndexMut};
+
+/// The number of high bits of a handle that hold the generation of its slot.
+const GENERATION_BITS: u32 = usize::BITS / 4;
+/// The number of low bits of a handle that hold the index of its slot.
+const INDEX_BITS: u32 = usize::BITS - GENERATION_BITS;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+
+/// Storage for the nodes of the game tree. Nodes are addressed by their handle,
+/// and the slots of freed nodes are kept on a free list so that they can be
+/// reused by newer nodes.
+///
+/// A handle holds the index of its slot in its low bits and the generation of
+/// the slot in its high bits. The generation is bumped whenever the slot is
+/// freed, so a handle that outlives its node (e.g. after `free_subtree()`)
+/// is caught when it's used instead of silently reaching whichever node has
+/// reused its slot. Generations wrap around after 2^16 reuses of a slot
+/// (2^8 on 32-bit targets), so this is a safety net rather than a guarantee.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NodeArena {
+    /// Every slot in the arena, which is `None` if its node has been freed.
+    slots: Vec<Option<StateDiff>>,
+    /// The generation of every slot. Slots past the end of this have a generation of 0.
+    #[serde(default)]
+    generations: Vec<usize>,
+    /// Indexes of the slots that have been freed, which can be reused by newer nodes.
+    free: Vec<usize>,
+}
+
+impl NodeArena {
+    /// Return a new arena containing only `root`, which has a handle of 0.
+    pub fn with_root(root: StateDiff) -> Self {
+        NodeArena {
+            slots: vec![Some(root)],
+            generations: vec![],
+            free: vec![],
+        }
+    }
+
+    /// Return the handle of the slot at `index`, in its current generation.
+    fn handle(&self, index: usize) -> usize {
+        self.generation(index) << INDEX_BITS | index
+    }
+
+    /// Return the current generation of the slot at `index`.
+    fn generation(&self, index: usize) -> usize {
+        self.generations.get(index).copied().unwrap_or(0)
+    }
+
+    /// Return the index of the slot that `handle` refers to, or `None`
+    /// if its node has been freed.
+    fn slot_index(&self, handle: usize) -> Option<usize> {
+        let index = handle & INDEX_MASK;
+        let is_live = matches!(self.slots.get(index), Some(Some(_)))
+            && self.generation(index) == handle >> INDEX_BITS;
+
+        is_live.then_some(index)
+    }
+
+    /// Empty the slot at `index` and move it on to its next generation,
+    /// so that its old handles are no longer valid.
+    fn release(&mut self, index: usize) -> Option<StateDiff> {
+        let node = self.slots[index].take()?;
+        if self.generations.len() <= index {
+            self.generations.resize(self.slots.len(), 0);
+        }
+        self.generations[index] = (self.generations[index] + 1) & ((1 << GENERATION_BITS) - 1);
+        self.free.push(index);
+
+        Some(node)
+    }
+
+    /// Store `node` in the arena and return its handle.
+    pub fn insert(&mut self, node: StateDiff) -> usize {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Some(node);
+                index
+            }
+            None => {
+                self.slots.push(Some(node));
+                self.slots.len() - 1
+            }
+        };
+
+        self.handle(index)
+    }
+
+    /// Free the node at `handle`, without touching its descendants.
+    pub fn free(&mut self, handle: usize) {
+        if let Some(index) = self.slot_index(handle) {
+            self.release(index);
+        }
+    }
+
+    /// Free the node at `handle` and all of its descendants.
+    pub fn free_subtree(&mut self, handle: usize) {
+        // Walk the subtree with an explicit stack so that deep trees can't overflow the call stack
+        let mut stack = vec![handle];
+
+        while let Some(h) = stack.pop() {
+            if let Some(node) = self.slot_index(h).and_then(|index| self.release(index)) {
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    /// Return the number of nodes currently stored in the arena.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Return the number of slots in the arena, including freed ones.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Return an estimate of the number of bytes used by the arena.
+    /// This counts the nodes and any of their diffs and children that don't fit
+    /// inline, but not the heap allocations of individual diffs (e.g. vectors of players).
+    pub fn memory_usage(&self) -> usize {
+        let nodes: usize = self
+            .slots
+            .iter()
+            .flatten()
+            .map(|n| {
+                let children = if n.children.spilled() {
+                    n.children.capacity() * size_of::<usize>()
+                } else {
+                    0
+                };
+                let diffs = if n.diffs.spilled() {
+                    n.diffs.capacity() * size_of::<FieldDiff>()
+                } else {
+                    0
+                };
+
+                children + diffs
+            })
+            .sum();
+
+        self.slots.capacity() * size_of::<Option<StateDiff>>()
+            + (self.generations.capacity() + self.free.capacity()) * size_of::<usize>()
+            + nodes
+    }
+
+    /// Move every node to the front of the arena and release the memory of the
+    /// freed slots. This changes the handles of the nodes, so the new handle of
+    /// `root` is returned. Every slot starts again from generation 0.
+    pub fn compact(&mut self, root: usize) -> usize {
+        // The new handle of every node that is still stored
+        let mut new_handles = vec![usize::MAX; self.slots.len()];
+        let mut next_handle = 0;
+        for (h, slot) in self.slots.iter().enumerate() {
+            if slot.is_some() {
+                new_handles[h] = next_handle;
+                next_handle += 1;
+            }
+        }
+
+        let slots = std::mem::take(&mut self.slots);
+        self.slots = slots
+            .into_iter()
+            .flatten()
+            .map(|mut node| {
+                node.parent = new_handles[node.parent & INDEX_MASK];
+                for child in &mut node.children {
+                    *child = new_handles[*child & INDEX_MASK];
+                }
+                Some(node)
+            })
+            .collect();
+
+        self.generations = vec![];
+        self.free = vec![];
+        new_handles[root & INDEX_MASK]
+    }
+}
+
+impl Index<usize> for NodeArena {
+    type Output = StateDiff;
+
+    fn index(&self, handle: usize) -> &StateDiff {
+        let index = self
+            .slot_index(handle)
+            .expect("tried to access a freed game tree node");
+        self.slots[index].as_ref().unwrap()
+    }
+}
+
+impl IndexMut<usize> for NodeArena {
+    fn index_mut(&mut self, handle: usize) -> &mut StateDiff {
+        let index = self
+            .slot_index(handle)
+            .expect("tried to access a freed game tree node");
+        self.slots[index].as_mut().unwrap()
+    }
+}
@@ -0,0 +1,104 @@
+use super::globals::{Player, Property};
+
+/// The smallest amount that can be bid in an auction, which bids are also rounded to.
+const MIN_BID: i32 = 20;
+
+/// Models how auctions play out, so that they can be treated as chance moves instead
+/// of being played out with real bids. The model decides who wins an auction and how
+/// much they pay, which shapes how much the AI expects to gain from auctioning.
+pub trait AuctionModel: Send + Sync {
+    /// Return the chance of each player winning the auction, as `(player_index, chance)`.
+    /// By default, every player with at least the minimum bid can win, with a chance
+    /// proportional to their balance.
+    fn winner_chances(&self, players: &[Player]) -> Vec<(usize, f64)> {
+        let possible_winners = players
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.balance >= MIN_BID);
+        let total_balance = possible_winners
+            .clone()
+            .map(|(_, p)| p.balance)
+            .sum::<i32>() as f64;
+
+        possible_winners
+            .map(|(i, p)| (i, p.balance as f64 / total_balance))
+            .collect()
+    }
+
+    /// Return the chance of each winning bid for `property`, as `(bid, chance)`, given
+    /// the `balance` of the auction winner. `balance` is always at least the minimum bid.
+    fn winning_bid_chances(&self, balance: i32, property: &Property) -> Vec<(i32, f64)>;
+
+    /// Return a name that tells the model apart from other models,
+    /// which is the name of its type by default.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// Return `amount` rounded down to a multiple of the minimum bid,
+/// and kept between the minimum bid and `balance`.
+fn affordable_bid(amount: f64, balance: i32) -> i32 {
+    ((amount as i32).min(balance) / MIN_BID).max(1) * MIN_BID
+}
+
+/// Return `bids` with the chances of equal consecutive bids added together.
+fn merge_equal_bids(bids: impl Iterator<Item = (i32, f64)>) -> Vec<(i32, f64)> {
+    bids.fold(vec![], |mut acc: Vec<(i32, f64)>, (bid, chance)| {
+        match acc.last_mut() {
+            Some(last) if last.0 == bid => last.1 += chance,
+            _ => acc.push((bid, chance)),
+        }
+        acc
+    })
+}
+
+/// Winning bids follow a bell curve over the winner's balance, centred on half of it.
+pub struct BellCurveBids;
+
+impl AuctionModel for BellCurveBids {
+    fn winning_bid_chances(&self, balance: i32, _property: &Property) -> Vec<(i32, f64)> {
+        let balance_at_pos = |pos: f64| {
+            ((balance - MIN_BID) as f64 * pos / MIN_BID as f64).round() as i32 * MIN_BID + MIN_BID
+        };
+
+        merge_equal_bids(
+            [
+                (1. / 6., 0.0675),
+                (2. / 6., 0.2410),
+                (3. / 6., 0.3830),
+                (4. / 6., 0.2410),
+                (5. / 6., 0.0675),
+            ]
+            .iter()
+            .map(|&(pos, chance)| (balance_at_pos(pos), chance)),
+        )
+    }
+}
+
+/// Winning bids are equally likely to be anywhere between the minimum bid and the winner's balance.
+pub struct UniformBids;
+
+impl AuctionModel for UniformBids {
+    fn winning_bid_chances(&self, balance: i32, _property: &Property) -> Vec<(i32, f64)> {
+        merge_equal_bids(
+            (1..=5).map(|i| (affordable_bid(balance as f64 * i as f64 / 5., balance), 0.2)),
+        )
+    }
+}
+
+/// Winning bids are at or above the price of the property, as long as the winner can afford them.
+pub struct AggressiveBids;
+
+impl AuctionModel for AggressiveBids {
+    fn winning_bid_chances(&self, balance: i32, property: &Property) -> Vec<(i32, f64)> {
+        merge_equal_bids([(1., 0.25), (1.25, 0.5), (1.5, 0.25)].iter().map(
+            |&(multiple, chance)| {
+                (
+                    affordable_bid(property.price as f64 * multiple, balance),
+                    chance,
+                )
+            },
+        ))
+    }
+}
@@ -0,0 +1,533 @@
+use super::chance_deck::ChanceDeck;
+use super::classic::{self, ClassicCard};
+use super::globals::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The most turns that the chain of `Board::landing_frequencies()` is stepped for,
+/// which is far more than it takes to settle down on any reasonably sized board.
+const MAX_ITERATIONS: usize = 1000;
+
+/// The change in the distribution of a player around the board below which the
+/// chain of `Board::landing_frequencies()` is considered to have settled down.
+const TOLERANCE: f64 = 1e-12;
+
+/// The most cards that are followed in a row after a roll (e.g. a card that moves the
+/// player onto another card tile), so that cards can't move players around forever.
+const MAX_CARD_CHAIN: usize = 4;
+
+/// Where a card sends the player who drew it.
+enum CardMove {
+    /// To the tile at this position, which they act on as if they'd rolled to it.
+    To(u8),
+    /// Straight to jail.
+    Jail,
+}
+
+/// The layout of the tiles around the game board. The default board is
+/// the 36-tile board from Monopoly: Ultimate Banking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    /// The number of tiles around the board. 'Go' is always at 0.
+    pub size: u8,
+    /// The position of 'Jail' on the game board.
+    pub jail_position: u8,
+    /// The position of 'Free parking' on the game board.
+    pub free_parking_position: u8,
+    /// The position of the 'Go to jail' tile on the game board.
+    pub go_to_jail_position: u8,
+    /// Positions of the chance card tiles on the game board.
+    pub cc_positions: HashSet<u8>,
+    /// Positions of the location tiles on the game board.
+    pub loc_positions: HashSet<u8>,
+    /// Positions of the Community Chest tiles on the game board, which only classic Monopoly has.
+    #[serde(default)]
+    pub community_chest_positions: HashSet<u8>,
+    /// The tax tiles on the game board, in the form `HashMap<tax_position, tax_amount>`.
+    #[serde(default)]
+    pub tax_positions: HashMap<u8, i32>,
+    /// All the properties on the game board, in the form `HashMap<property_position, property>`.
+    pub properties: HashMap<u8, Property>,
+}
+
+impl Default for Board {
+    #[rustfmt::skip]
+    fn default() -> Self {
+        Board {
+            size: 36,
+            jail_position: 9,
+            free_parking_position: 18,
+            go_to_jail_position: 27,
+            cc_positions: HashSet::from([2, 4, 11, 20, 29, 32]),
+            loc_positions: HashSet::from([7, 16, 25, 34]),
+            community_chest_positions: HashSet::new(),
+            tax_positions: HashMap::new(),
+            properties: HashMap::from([
+                (1, Property::new(Color::Brown, 60, [70, 130, 220, 370, 750])),
+                (3, Property::new(Color::Brown, 60, [70, 130, 220, 370, 750])),
+                (5, Property::new(Color::LightBlue, 100, [80, 140, 240, 410, 800])),
+                (6, Property::new(Color::LightBlue, 100, [80, 140, 240, 410, 800])),
+                (8, Property::new(Color::LightBlue, 120, [100, 160, 260, 440, 860])),
+                (10, Property::new(Color::Pink, 140, [110, 180, 290, 460, 900])),
+                (12, Property::new(Color::Pink, 140, [110, 180, 290, 460, 900])),
+                (13, Property::new(Color::Pink, 160, [130, 200, 310, 490, 980])),
+                (14, Property::new(Color::Orange, 180, [140, 210, 330, 520, 1000])),
+                (15, Property::new(Color::Orange, 180, [140, 210, 330, 520, 1000])),
+                (17, Property::new(Color::Orange, 200, [160, 230, 350, 550, 1100])),
+                (19, Property::new(Color::Red, 220, [170, 250, 380, 580, 1160])),
+                (21, Property::new(Color::Red, 220, [170, 250, 380, 580, 1160])),
+                (22, Property::new(Color::Red, 240, [190, 270, 400, 610, 1200])),
+                (23, Property::new(Color::Yellow, 260, [200, 280, 420, 640, 1300])),
+                (24, Property::new(Color::Yellow, 260, [200, 280, 420, 640, 1300])),
+                (26, Property::new(Color::Yellow, 280, [220, 300, 440, 670, 1340])),
+                (28, Property::new(Color::Green, 300, [230, 320, 460, 700, 1400])),
+                (30, Property::new(Color::Green, 300, [230, 320, 460, 700, 1400])),
+                (31, Property::new(Color::Green, 320, [250, 340, 480, 730, 1440])),
+                (33, Property::new(Color::Blue, 350, [270, 360, 510, 740, 1500])),
+                (35, Property::new(Color::Blue, 400, [300, 400, 560, 810, 1600])),
+            ]),
+        }
+    }
+}
+
+impl Board {
+    /// The 40-tile board from classic Monopoly (US edition). Chance tiles are
+    /// stored in `cc_positions`, and the rents of a utility are multipliers of the dice roll.
+    #[rustfmt::skip]
+    pub fn classic() -> Board {
+        let railroad = || Property::classic(Color::Railroad, 200, [25, 50, 100, 200, 200], 0, 0);
+        let utility = || Property::classic(Color::Utility, 150, [4, 10, 10, 10, 10], 0, 0);
+
+        Board {
+            size: 40,
+            jail_position: 10,
+            free_parking_position: 20,
+            go_to_jail_position: 30,
+            cc_positions: HashSet::from([7, 22, 36]),
+            loc_positions: HashSet::new(),
+            community_chest_positions: HashSet::from([2, 17, 33]),
+            tax_positions: HashMap::from([(4, 200), (38, 100)]),
+            properties: HashMap::from([
+                (1, Property::classic(Color::Brown, 60, [2, 10, 30, 90, 160], 250, 50)),
+                (3, Property::classic(Color::Brown, 60, [4, 20, 60, 180, 320], 450, 50)),
+                (5, railroad()),
+                (6, Property::classic(Color::LightBlue, 100, [6, 30, 90, 270, 400], 550, 50)),
+                (8, Property::classic(Color::LightBlue, 100, [6, 30, 90, 270, 400], 550, 50)),
+                (9, Property::classic(Color::LightBlue, 120, [8, 40, 100, 300, 450], 600, 50)),
+                (11, Property::classic(Color::Pink, 140, [10, 50, 150, 450, 625], 750, 100)),
+                (12, utility()),
+                (13, Property::classic(Color::Pink, 140, [10, 50, 150, 450, 625], 750, 100)),
+                (14, Property::classic(Color::Pink, 160, [12, 60, 180, 500, 700], 900, 100)),
+                (15, railroad()),
+                (16, Property::classic(Color::Orange, 180, [14, 70, 200, 550, 750], 950, 100)),
+                (18, Property::classic(Color::Orange, 180, [14, 70, 200, 550, 750], 950, 100)),
+                (19, Property::classic(Color::Orange, 200, [16, 80, 220, 600, 800], 1000, 100)),
+                (21, Property::classic(Color::Red, 220, [18, 90, 250, 700, 875], 1050, 150)),
+                (23, Property::classic(Color::Red, 220, [18, 90, 250, 700, 875], 1050, 150)),
+                (24, Property::classic(Color::Red, 240, [20, 100, 300, 750, 925], 1100, 150)),
+                (25, railroad()),
+                (26, Property::classic(Color::Yellow, 260, [22, 110, 330, 800, 975], 1150, 150)),
+                (27, Property::classic(Color::Yellow, 260, [22, 110, 330, 800, 975], 1150, 150)),
+                (28, utility()),
+                (29, Property::classic(Color::Yellow, 280, [24, 120, 360, 850, 1025], 1200, 150)),
+                (31, Property::classic(Color::Green, 300, [26, 130, 390, 900, 1100], 1275, 200)),
+                (32, Property::classic(Color::Green, 300, [26, 130, 390, 900, 1100], 1275, 200)),
+                (34, Property::classic(Color::Green, 320, [28, 150, 450, 1000, 1200], 1400, 200)),
+                (35, railroad()),
+                (37, Property::classic(Color::Blue, 350, [35, 175, 500, 1100, 1300], 1500, 200)),
+                (39, Property::classic(Color::Blue, 400, [50, 200, 600, 1400, 1700], 2000, 200)),
+            ]),
+        }
+    }
+
+    /*********        LOADING        *********/
+
+    /// Load a board from a TOML file (if the path ends in `.toml`) or a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Board> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let board: Board = if path.extension() == Some("toml".as_ref()) {
+            toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        } else {
+            serde_json::from_str(&contents)?
+        };
+
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// Check that every special tile is actually on the board, and that no two overlap.
+    fn validate(&self) -> io::Result<()> {
+        let mut seen = HashSet::from([0]);
+        let special = [
+            self.jail_position,
+            self.free_parking_position,
+            self.go_to_jail_position,
+        ];
+        let tiles = special
+            .iter()
+            .chain(&self.cc_positions)
+            .chain(&self.loc_positions)
+            .chain(&self.community_chest_positions)
+            .chain(self.tax_positions.keys())
+            .chain(self.properties.keys());
+
+        for &pos in tiles {
+            if pos >= self.size || !seen.insert(pos) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("tile {} is off the board or used more than once", pos),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /*********        LOOKUPS        *********/
+
+    /// Return the property at `pos`. Panics if there isn't one.
+    pub fn property(&self, pos: u8) -> &Property {
+        &self.properties[&pos]
+    }
+
+    /// Return whether there is a property at `pos`.
+    pub fn is_property(&self, pos: u8) -> bool {
+        self.properties.contains_key(&pos)
+    }
+
+    /// Return a short name for the tile at `pos`, such as "Go", "CC" for a chance
+    /// tile, or the abbreviated color set of a property (e.g. "LB" for light blue).
+    pub fn tile_label(&self, pos: u8) -> &'static str {
+        if let Some(property) = self.properties.get(&pos) {
+            return match property.color {
+                Color::Brown => "Br",
+                Color::LightBlue => "LB",
+                Color::Pink => "Pk",
+                Color::Orange => "Or",
+                Color::Red => "Rd",
+                Color::Yellow => "Ye",
+                Color::Green => "Gr",
+                Color::Blue => "Bl",
+                Color::Railroad => "RR",
+                Color::Utility => "Ut",
+            };
+        }
+
+        if pos == 0 {
+            "Go"
+        } else if pos == self.jail_position {
+            "Jl"
+        } else if pos == self.free_parking_position {
+            "FP"
+        } else if pos == self.go_to_jail_position {
+            "GJ"
+        } else if self.cc_positions.contains(&pos) {
+            "CC"
+        } else if self.loc_positions.contains(&pos) {
+            "Loc"
+        } else if self.community_chest_positions.contains(&pos) {
+            "Com"
+        } else if self.tax_positions.contains_key(&pos) {
+            "Tax"
+        } else {
+            ""
+        }
+    }
+
+    /// Return the positions of all the properties on the board.
+    pub fn prop_positions(&self) -> impl Iterator<Item = u8> + '_ {
+        self.properties.keys().copied()
+    }
+
+    /// Return the positions of the properties on the board, sorted by their color set.
+    pub fn props_by_color(&self) -> HashMap<Color, HashSet<u8>> {
+        let mut by_color: HashMap<Color, HashSet<u8>> = HashMap::new();
+
+        for (&pos, prop) in &self.properties {
+            by_color.entry(prop.color).or_default().insert(pos);
+        }
+
+        by_color
+    }
+
+    /// Return the positions of the properties on the board, sorted by the side of the board they're on.
+    pub fn props_by_side(&self) -> [HashSet<u8>; 4] {
+        let mut by_side: [HashSet<u8>; 4] = Default::default();
+
+        for pos in self.prop_positions() {
+            by_side[self.side(pos)].insert(pos);
+        }
+
+        by_side
+    }
+
+    /// Return the side of the board (from 0 to 3) that the tile at `pos` is on.
+    fn side(&self, pos: u8) -> usize {
+        (pos as usize * 4 / self.size as usize).min(3)
+    }
+
+    /// Return whether the properties at `a` and `b` are interchangeable, which is the case
+    /// if they're in the same color set and on the same side of the board, with the same
+    /// price and rents. Such properties only differ in where they are on that side.
+    ///
+    /// ```
+    /// use monopoly_math::Board;
+    ///
+    /// let board = Board::default();
+    /// assert!(board.interchangeable(1, 3));
+    /// // Same color set, but a different price
+    /// assert!(!board.interchangeable(5, 8));
+    /// ```
+    pub fn interchangeable(&self, a: u8, b: u8) -> bool {
+        let (prop_a, prop_b) = (self.property(a), self.property(b));
+
+        prop_a.color == prop_b.color
+            && prop_a.price == prop_b.price
+            && prop_a.rents == prop_b.rents
+            && prop_a.hotel_rent == prop_b.hotel_rent
+            && prop_a.house_cost == prop_b.house_cost
+            && self.side(a) == self.side(b)
+    }
+
+    /// Return the first property of the `color` set going clockwise from `pos`, if there is one.
+    pub fn nearest(&self, pos: u8, color: Color) -> Option<u8> {
+        (1..self.size)
+            .map(|d| (pos + d) % self.size)
+            .find(|p| self.properties.get(p).map(|p| p.color) == Some(color))
+    }
+
+    /// Return the closest properties towards the left and right of the property at `pos`,
+    /// in the form `[anti_clockwise_neighbour_pos, clockwise_neighbour_pos]`.
+    pub fn property_neighbours(&self, pos: u8) -> [u8; 2] {
+        let mut positions: Vec<u8> = self.prop_positions().collect();
+        positions.sort_unstable();
+
+        let i = positions.iter().position(|&p| p == pos).unwrap();
+        let len = positions.len();
+
+        [positions[(i + len - 1) % len], positions[(i + 1) % len]]
+    }
+
+    /*********        LANDING FREQUENCIES        *********/
+
+    /// Return the probability that a player lands on each tile of the board (by position),
+    /// out of every tile they land on over a long game played with `config`. This is the
+    /// steady state of the Markov chain of `Board::landing_frequencies()`.
+    ///
+    /// ```
+    /// use monopoly_math::{Board, GameConfig};
+    ///
+    /// let board = Board::default();
+    /// let distribution = board.landing_distribution(&GameConfig::default());
+    /// assert!((distribution.iter().sum::<f64>() - 1.).abs() < 1e-9);
+    ///
+    /// // Players leaving jail make the tiles a roll of 7 past it busier than those just before it
+    /// let jail = board.jail_position as usize;
+    /// assert!(distribution[jail + 7] > distribution[jail - 2]);
+    /// ```
+    pub fn landing_distribution(&self, config: &GameConfig) -> Vec<f64> {
+        let frequencies = self.landing_frequencies(config);
+        let total: f64 = frequencies.iter().sum();
+
+        frequencies.iter().map(|f| f / total).collect()
+    }
+
+    /// Return the expected number of times that a player lands on each tile (by position) per
+    /// turn, over a long game played with `config`. The player's whereabouts are a Markov chain
+    /// over the tiles and the doubles they've rolled this turn, or the tries they have left
+    /// in jail, which is stepped a roll at a time until it settles down. Landing on a tile
+    /// counts whether it's rolled to or reached with a card, but not being sent to jail.
+    ///
+    /// Only the current player's own moves are accounted for, and choices are assumed
+    /// to be made at random where they have to be made at all: location tiles are never
+    /// used, and 'Go to any property' goes to every property equally often. The chance
+    /// cards are drawn as often as they're in the deck.
+    pub(super) fn landing_frequencies(&self, config: &GameConfig) -> Vec<f64> {
+        let size = self.size as usize;
+        let jail_tries = config.jail_tries.max(1) as usize;
+        // Where a player on the tile at `pos` who's rolled `doubles` doubles this turn, or
+        // who's in jail with `tries` tries left, is in the chain
+        let free_state = |pos: usize, doubles: usize| pos * 3 + doubles;
+        let jail_state = |tries: usize| size * 3 + tries - 1;
+
+        // For landing on each tile, the tiles landed on along the way (with cards that move
+        // the player), and the tiles where the player can end their move
+        let resolved: Vec<_> = (0..self.size)
+            .map(|pos| {
+                let mut landings = vec![0.; size];
+                let mut outcomes = vec![];
+                self.follow_landing(pos, 1., config, 0, &mut landings, &mut outcomes);
+
+                let landings: Vec<(usize, f64)> = landings
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(_, l)| l > 0.)
+                    .collect();
+                (landings, outcomes)
+            })
+            .collect();
+
+        let mut distribution = vec![0.; size * 3 + jail_tries];
+        distribution[free_state(0, 0)] = 1.;
+        let mut landings = vec![0.; size];
+
+        for _ in 0..MAX_ITERATIONS {
+            let mut next = vec![0.; distribution.len()];
+            landings = vec![0.; size];
+
+            for (state, &p) in distribution.iter().enumerate().filter(|(_, &p)| p > 0.) {
+                let (pos, doubles, tries) = if state < size * 3 {
+                    (state / 3, state % 3, None)
+                } else {
+                    (self.jail_position as usize, 0, Some(state - size * 3 + 1))
+                };
+
+                for roll in SIGNIFICANT_ROLLS.iter() {
+                    let chance = p * roll.probability;
+
+                    // The doubles that the player will have rolled this turn
+                    // if they get to roll again after this roll
+                    let doubles = match tries {
+                        // Players stay in jail unless they roll doubles or it's their last try
+                        Some(tries) if tries > 1 && !roll.is_double => {
+                            next[jail_state(tries - 1)] += chance;
+                            continue;
+                        }
+                        // Getting out of jail with doubles doesn't earn another roll
+                        Some(_) => 0,
+                        // Rolling doubles a third time sends the player to jail
+                        None if roll.is_double && doubles == 2 => {
+                            next[jail_state(jail_tries)] += chance;
+                            continue;
+                        }
+                        None if roll.is_double => doubles + 1,
+                        None => 0,
+                    };
+
+                    let (landed, outcomes) = &resolved[(pos + roll.sum as usize) % size];
+                    for &(tile, l) in landed {
+                        landings[tile] += chance * l;
+                    }
+                    for &(rest, p) in outcomes {
+                        match rest {
+                            Some(pos) => next[free_state(pos as usize, doubles)] += chance * p,
+                            None => next[jail_state(jail_tries)] += chance * p,
+                        }
+                    }
+                }
+            }
+
+            let change: f64 = distribution
+                .iter()
+                .zip(&next)
+                .map(|(a, b)| (a - b).abs())
+                .sum();
+            distribution = next;
+
+            if change < TOLERANCE {
+                break;
+            }
+        }
+
+        // Every turn starts with a roll by a player in jail or who hasn't rolled doubles yet
+        let turn_starts: f64 = (0..size)
+            .map(|pos| distribution[free_state(pos, 0)])
+            .chain((1..=jail_tries).map(|tries| distribution[jail_state(tries)]))
+            .sum();
+
+        landings.iter().map(|l| l / turn_starts).collect()
+    }
+
+    /// Add `chance` to the landings on the tile at `pos`, and follow any card drawn there
+    /// (`depth` cards having been followed already). Every tile where the player ends their
+    /// move is added to `outcomes` with the chance of ending up there, or `None` for jail.
+    fn follow_landing(
+        &self,
+        pos: u8,
+        chance: f64,
+        config: &GameConfig,
+        depth: usize,
+        landings: &mut [f64],
+        outcomes: &mut Vec<(Option<u8>, f64)>,
+    ) {
+        landings[pos as usize] += chance;
+
+        if pos == self.go_to_jail_position {
+            outcomes.push((None, chance));
+            return;
+        }
+
+        let moves = if depth < MAX_CARD_CHAIN {
+            self.card_moves(pos, config)
+        } else {
+            vec![]
+        };
+
+        // The chance that the player draws a card that doesn't move them
+        let mut stays = chance;
+        for (card_move, p) in moves {
+            stays -= chance * p;
+            match card_move {
+                CardMove::To(to) => {
+                    self.follow_landing(to, chance * p, config, depth + 1, landings, outcomes)
+                }
+                CardMove::Jail => outcomes.push((None, chance * p)),
+            }
+        }
+
+        outcomes.push((Some(pos), stays));
+    }
+
+    /// Return where the cards that can be drawn on the tile at `pos` send the player who
+    /// drew them, along with the probability of drawing each one. Cards that don't move
+    /// the player are left out.
+    fn card_moves(&self, pos: u8, config: &GameConfig) -> Vec<(CardMove, f64)> {
+        let chance = self.cc_positions.contains(&pos);
+        let community_chest = self.community_chest_positions.contains(&pos);
+        if !chance && !community_chest {
+            return vec![];
+        }
+
+        match config.rules {
+            RuleSet::UltimateBanking => {
+                let mut moves = vec![];
+                for (card, p) in ChanceDeck::new().draw_probabilities() {
+                    match card {
+                        ChanceCard::AllToParking => {
+                            moves.push((CardMove::To(self.free_parking_position), p))
+                        }
+                        ChanceCard::GoToAnyProperty => {
+                            let p = p / self.properties.len() as f64;
+                            moves.extend(self.prop_positions().map(|to| (CardMove::To(to), p)));
+                        }
+                        _ => {}
+                    }
+                }
+
+                moves
+            }
+            RuleSet::Classic => classic::deck_probabilities(community_chest)
+                .into_iter()
+                .filter_map(|(card, p)| {
+                    let card_move = match card {
+                        ClassicCard::AdvanceTo(to) => CardMove::To(to),
+                        ClassicCard::AdvanceToNearest(color) => {
+                            CardMove::To(self.nearest(pos, color)?)
+                        }
+                        ClassicCard::GoBack(distance) => {
+                            CardMove::To((pos + self.size - distance) % self.size)
+                        }
+                        ClassicCard::GoToJail => CardMove::Jail,
+                        _ => return None,
+                    };
+                    Some((card_move, p))
+                })
+                .collect(),
+        }
+    }
+}
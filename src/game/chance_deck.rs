@@ -0,0 +1,197 @@
+use super::globals::{ChanceCard, TOTAL_CHANCE_CARDS};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::iter;
+
+/// Every chance card, along with how many copies of it there are in the deck.
+const DECK: [(ChanceCard, u8); 14] = [
+    (ChanceCard::RentTo1, 3),
+    (ChanceCard::RentTo5, 1),
+    (ChanceCard::SetRentInc, 3),
+    (ChanceCard::SetRentDec, 1),
+    (ChanceCard::SideRentInc, 1),
+    (ChanceCard::SideRentDec, 1),
+    (ChanceCard::RentSpike, 2),
+    (ChanceCard::Bonus, 2),
+    (ChanceCard::SwapProperty, 2),
+    (ChanceCard::OpponentToJail, 1),
+    (ChanceCard::GoToAnyProperty, 1),
+    (ChanceCard::PropertyTax, 1),
+    (ChanceCard::Level1Rent, 1),
+    (ChanceCard::AllToParking, 1),
+];
+
+/// The deck of chance cards, as far as the players know it. Cards are drawn from the top
+/// and put back at the bottom, so until every card has been drawn once, the next card could
+/// be any of the ones that haven't been seen yet. After that, the deck cycles through the
+/// cards in the order they were first drawn, so the next card is known for certain, unless
+/// the deck is reshuffled (see `GameConfig::reshuffle_chance_deck`).
+///
+/// ```
+/// use monopoly_math::{ChanceCard, ChanceDeck, TOTAL_CHANCE_CARDS};
+///
+/// let mut deck = ChanceDeck::new();
+/// assert_eq!(deck.next_card(), None);
+/// assert_eq!(deck.probability(ChanceCard::RentTo1), 3. / 21.);
+///
+/// // Draw every card once, in some order
+/// let mut order = vec![];
+/// while !deck.is_known() {
+///     let (card, _) = deck.draw_probabilities()[0];
+///     order.push(card);
+///     deck = deck.drawn(card, false);
+/// }
+///
+/// // Now the deck is known, and cycles through the cards in the same order
+/// assert_eq!(order.len(), TOTAL_CHANCE_CARDS);
+/// assert!(deck.is_known());
+/// for &card in order.iter().chain(&order) {
+///     assert_eq!(deck.next_card(), Some(card));
+///     assert_eq!(deck.draw_probabilities(), vec![(card, 1.)]);
+///     deck = deck.drawn(card, false);
+/// }
+///
+/// // Unless it's reshuffled as soon as every card has been drawn
+/// let almost_known = ChanceDeck::with_seen(order[..TOTAL_CHANCE_CARDS - 1].to_vec());
+/// let last_card = order[TOTAL_CHANCE_CARDS - 1];
+/// assert_eq!(almost_known.draw_probabilities(), vec![(last_card, 1.)]);
+/// assert_eq!(almost_known.drawn(last_card, true), ChanceDeck::new());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChanceDeck {
+    /// The chance cards that have been drawn, ordered from least recent to most recent.
+    seen: Vec<ChanceCard>,
+    /// The index of the next card in `seen`, once every card has been drawn.
+    top: usize,
+}
+
+impl ChanceDeck {
+    /// Return a deck that no cards have been drawn from yet.
+    pub fn new() -> Self {
+        ChanceDeck::default()
+    }
+
+    /// Return a deck that `seen` have been drawn from, from least recent to most recent.
+    /// Panics if a card was drawn more times than it's in the deck.
+    pub fn with_seen(seen: Vec<ChanceCard>) -> Self {
+        for &(card, count) in &DECK {
+            if seen.iter().filter(|&&c| c == card).count() > count as usize {
+                panic!("{:?} was drawn more times than it's in the deck", card);
+            }
+        }
+
+        ChanceDeck { seen, top: 0 }
+    }
+
+    /// Return the cards that have been drawn, ordered from least recent to most recent.
+    pub fn seen(&self) -> &[ChanceCard] {
+        &self.seen
+    }
+
+    /// Return the index in `seen()` of the next card, once every card has been drawn.
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Return whether every card has been drawn, so that the order of the deck is known.
+    pub fn is_known(&self) -> bool {
+        self.seen.len() == TOTAL_CHANCE_CARDS
+    }
+
+    /// Return the card that will definitely be drawn next, if the order of the deck is known.
+    pub fn next_card(&self) -> Option<ChanceCard> {
+        self.is_known().then(|| self.seen[self.top])
+    }
+
+    /// Return how many copies of each card haven't been drawn yet.
+    pub fn unseen_counts(&self) -> HashMap<ChanceCard, u8> {
+        let mut counts = HashMap::from(DECK);
+        for card in &self.seen {
+            *counts.get_mut(card).unwrap() -= 1;
+        }
+
+        counts
+    }
+
+    /// Return the probability that `card` is the next card drawn.
+    pub fn probability(&self, card: ChanceCard) -> f64 {
+        self.draw_probabilities()
+            .into_iter()
+            .find(|&(c, _)| c == card)
+            .map_or(0., |(_, p)| p)
+    }
+
+    /// Return every card that could be drawn next, along with the probability of drawing it.
+    pub fn draw_probabilities(&self) -> Vec<(ChanceCard, f64)> {
+        self.draw_probabilities_except(None)
+    }
+
+    /// Return every card that could be drawn next, along with the probability of drawing it,
+    /// given that it isn't `excluded` (e.g. because that card was already dealt with).
+    pub fn draw_probabilities_except(
+        &self,
+        excluded: Option<ChanceCard>,
+    ) -> Vec<(ChanceCard, f64)> {
+        if let Some(card) = self.next_card() {
+            return if Some(card) == excluded {
+                vec![]
+            } else {
+                vec![(card, 1.)]
+            };
+        }
+
+        let counts = self.unseen_counts();
+        let candidates: Vec<(ChanceCard, u8)> = DECK
+            .iter()
+            .map(|&(card, _)| (card, counts[&card]))
+            .filter(|&(card, count)| count > 0 && Some(card) != excluded)
+            .collect();
+        let total: u8 = candidates.iter().map(|&(_, count)| count).sum();
+
+        candidates
+            .into_iter()
+            .map(|(card, count)| (card, count as f64 / total as f64))
+            .collect()
+    }
+
+    /// Return a deck with as many cards drawn as this one, but with the cards that were drawn
+    /// (and so the order of the deck, once every card has been drawn) sampled at random. This
+    /// is the deck as a player who hasn't kept track of the cards could imagine it.
+    pub fn determinized(&self, rng: &mut impl Rng) -> ChanceDeck {
+        let mut cards: Vec<ChanceCard> = DECK
+            .iter()
+            .flat_map(|&(card, count)| iter::repeat_n(card, count as usize))
+            .collect();
+        cards.shuffle(rng);
+        cards.truncate(self.seen.len());
+
+        ChanceDeck {
+            seen: cards,
+            top: self.top,
+        }
+    }
+
+    /// Return the deck after `card` is drawn from it. If `reshuffle`, the deck is reshuffled
+    /// as soon as every card has been drawn, so that its order is never known.
+    pub fn drawn(&self, card: ChanceCard, reshuffle: bool) -> ChanceDeck {
+        let deck = if self.is_known() {
+            // The card goes back at the bottom of the deck
+            ChanceDeck {
+                seen: self.seen.clone(),
+                top: (self.top + 1) % TOTAL_CHANCE_CARDS,
+            }
+        } else {
+            let mut seen = self.seen.clone();
+            seen.push(card);
+            ChanceDeck { seen, top: 0 }
+        };
+
+        if reshuffle && deck.is_known() {
+            ChanceDeck::new()
+        } else {
+            deck
+        }
+    }
+}
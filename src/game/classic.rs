@@ -0,0 +1,336 @@
+use super::globals::*;
+use super::state_diff::{BranchType, DiffMessage, MoveType, PropertyOwnership, StateDiff};
+use super::Game;
+use serde::{Deserialize, Serialize};
+
+/// The rent level of a property with a hotel. Rent level 1 has no houses,
+/// and rent levels 2 to 5 have 1 to 4 houses.
+pub const HOTEL_LEVEL: usize = 6;
+
+/// The average sum of two dice, which stands in for the dice roll
+/// that a utility's rent is multiplied by.
+const AVERAGE_ROLL: i32 = 7;
+
+/// A card from the Chance or Community Chest deck of classic Monopoly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClassicCard {
+    /// Advance to the tile at this position, collecting $200 if you pass 'Go'.
+    AdvanceTo(u8),
+    /// Advance to the nearest property of this color set.
+    AdvanceToNearest(Color),
+    /// Go back this many tiles.
+    GoBack(u8),
+    /// Go directly to jail.
+    GoToJail,
+    /// Collect this amount from the bank.
+    Collect(i32),
+    /// Pay this amount to the bank.
+    Pay(i32),
+    /// Collect this amount from every other player.
+    CollectFromEach(i32),
+    /// Pay this amount to every other player.
+    PayEach(i32),
+    /// Pay for repairs on every house and hotel you own.
+    Repairs { house: i32, hotel: i32 },
+}
+
+/// The Chance deck, without 'Get Out of Jail Free'.
+const CHANCE_DECK: [ClassicCard; 15] = [
+    ClassicCard::AdvanceTo(39),
+    ClassicCard::AdvanceTo(0),
+    ClassicCard::AdvanceTo(24),
+    ClassicCard::AdvanceTo(11),
+    ClassicCard::AdvanceTo(5),
+    ClassicCard::AdvanceToNearest(Color::Railroad),
+    ClassicCard::AdvanceToNearest(Color::Railroad),
+    ClassicCard::AdvanceToNearest(Color::Utility),
+    ClassicCard::Collect(50),
+    ClassicCard::GoBack(3),
+    ClassicCard::GoToJail,
+    ClassicCard::Repairs {
+        house: 25,
+        hotel: 100,
+    },
+    ClassicCard::Pay(15),
+    ClassicCard::PayEach(50),
+    ClassicCard::Collect(150),
+];
+
+/// The Community Chest deck, without 'Get Out of Jail Free'.
+const COMMUNITY_CHEST_DECK: [ClassicCard; 15] = [
+    ClassicCard::AdvanceTo(0),
+    ClassicCard::Collect(200),
+    ClassicCard::Pay(50),
+    ClassicCard::Collect(50),
+    ClassicCard::GoToJail,
+    ClassicCard::Collect(100),
+    ClassicCard::Collect(20),
+    ClassicCard::CollectFromEach(10),
+    ClassicCard::Collect(100),
+    ClassicCard::Pay(100),
+    ClassicCard::Pay(50),
+    ClassicCard::Collect(25),
+    ClassicCard::Repairs {
+        house: 40,
+        hotel: 115,
+    },
+    ClassicCard::Collect(10),
+    ClassicCard::Collect(100),
+];
+
+/// Return every card in the Chance deck (if `community_chest` is false) or the Community
+/// Chest deck, along with the probability of drawing it, in the order of the deck.
+pub(super) fn deck_probabilities(community_chest: bool) -> Vec<(ClassicCard, f64)> {
+    let deck: &[ClassicCard] = if community_chest {
+        &COMMUNITY_CHEST_DECK
+    } else {
+        &CHANCE_DECK
+    };
+
+    // Count the copies of each card, keeping the order of the deck
+    let mut counts: Vec<(ClassicCard, usize)> = vec![];
+    for &card in deck {
+        match counts.iter_mut().find(|(c, _)| *c == card) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((card, 1)),
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(card, count)| (card, count as f64 / deck.len() as f64))
+        .collect()
+}
+
+impl Game {
+    /*********        CLASSIC RULES        *********/
+
+    /// Return the rent owed for landing on the property at `pos` under the classic rules.
+    /// Railroad rent depends on how many railroads the owner has, utility rent is a
+    /// multiple of an average dice roll, and an unimproved property's rent is doubled
+    /// when its owner has the whole color set.
+    pub(super) fn classic_rent(&self, handle: usize, pos: u8, prop: &PropertyOwnership) -> i32 {
+        let property = self.board.property(pos);
+        let owned_in_set = self
+            .diff_owned_properties(handle)
+            .iter()
+            .filter(|(&p, o)| {
+                o.owner == prop.owner && self.board.property(p).color == property.color
+            })
+            .count();
+
+        match property.color {
+            Color::Railroad => property.rents[owned_in_set.min(5) - 1],
+            Color::Utility => property.rents[owned_in_set.min(5) - 1] * AVERAGE_ROLL,
+            color if prop.rent_level == 1 && owned_in_set == self.set_size(color) => {
+                property.rents[0] * 2
+            }
+            _ => property.rent(prop.rent_level),
+        }
+    }
+
+    /// Return how much the bank pays for the property at `pos`, which is the resale
+    /// percentage of its price. Under the classic rules, the houses and hotel on it
+    /// are also sold back for half their cost.
+    pub(super) fn sale_value(&self, pos: u8, rent_level: usize) -> i32 {
+        let property = self.board.property(pos);
+        let price = property.price * self.config.resale_percent / 100;
+
+        match self.config.rules {
+            RuleSet::UltimateBanking => price,
+            RuleSet::Classic => price + property.house_cost * (rent_level as i32 - 1) / 2,
+        }
+    }
+
+    /// Return the number of properties in the color set `color`.
+    fn set_size(&self, color: Color) -> usize {
+        self.board
+            .properties
+            .values()
+            .filter(|p| p.color == color)
+            .count()
+    }
+
+    /// Return child states that can be reached by drawing a Chance card
+    /// (if `community_chest` is false) or a Community Chest card.
+    /// Cards are drawn with replacement, so every card is always equally likely.
+    pub(super) fn gen_classic_card_children(
+        &self,
+        handle: usize,
+        community_chest: bool,
+    ) -> Vec<StateDiff> {
+        deck_probabilities(community_chest)
+            .into_iter()
+            .map(|(card, probability)| self.gen_classic_card_child(handle, card, probability))
+            .collect()
+    }
+
+    /// Return the state after the current player draws `card`.
+    fn gen_classic_card_child(
+        &self,
+        handle: usize,
+        card: ClassicCard,
+        probability: f64,
+    ) -> StateDiff {
+        let i = self.diff_current_pindex(handle);
+        let mut players = self.diff_players(handle).clone();
+        let mut state = StateDiff::new_with_parent(handle);
+        state.branch_type = BranchType::Chance(probability);
+        state.message = DiffMessage::ClassicCard(card);
+        state.next_move = MoveType::Roll;
+
+        let size = self.board.size;
+        let position = players[i].position;
+
+        match card {
+            ClassicCard::AdvanceTo(pos) => {
+                players[i].move_by(
+                    (pos + size - position) % size,
+                    &self.board,
+                    self.config.go_salary,
+                );
+                state.next_move = MoveType::when_landed_on(pos, &self.board);
+            }
+            ClassicCard::AdvanceToNearest(color) => {
+                if let Some(pos) = self.board.nearest(position, color) {
+                    players[i].move_by(
+                        (pos + size - position) % size,
+                        &self.board,
+                        self.config.go_salary,
+                    );
+                    state.next_move = MoveType::Property;
+                }
+            }
+            ClassicCard::GoBack(distance) => {
+                players[i].position = (position + size - distance) % size;
+                state.next_move = MoveType::when_landed_on(players[i].position, &self.board);
+            }
+            ClassicCard::GoToJail => {
+                players[i].send_to_jail(&self.board);
+
+                let mut jail_rounds = self.diff_jail_rounds(handle).clone();
+                jail_rounds[i] = self.config.jail_tries;
+                state.set_jail_rounds(jail_rounds);
+            }
+            ClassicCard::Collect(amount) => players[i].balance += amount,
+            ClassicCard::Pay(amount) => {
+                players[i].balance -= amount;
+                self.add_to_jackpot(handle, &mut state, amount);
+            }
+            ClassicCard::CollectFromEach(amount) | ClassicCard::PayEach(amount) => {
+                // Positive if the current player collects, negative if they pay
+                let amount = match card {
+                    ClassicCard::CollectFromEach(_) => amount,
+                    _ => -amount,
+                };
+
+                for j in 0..players.len() {
+                    if j != i && !players[j].bankrupt {
+                        players[j].balance -= amount;
+                        players[i].balance += amount;
+                    }
+                }
+            }
+            ClassicCard::Repairs { house, hotel } => {
+                let cost: i32 = self
+                    .diff_owned_properties(handle)
+                    .values()
+                    .filter(|prop| prop.owner == i)
+                    .map(|prop| match prop.rent_level {
+                        HOTEL_LEVEL => hotel,
+                        level => house * (level as i32 - 1),
+                    })
+                    .sum();
+                players[i].balance -= cost;
+                self.add_to_jackpot(handle, &mut state, cost);
+            }
+        }
+
+        state.set_players(players);
+        self.hand_over_turn(handle, &mut state);
+        state
+    }
+
+    /// Return the state after the current player pays the tax of the tile they're on.
+    pub(super) fn gen_tax_children(&self, handle: usize) -> Vec<StateDiff> {
+        let i = self.diff_current_pindex(handle);
+        let mut players = self.diff_players(handle).clone();
+        let tax = self.board.tax_positions[&players[i].position];
+        players[i].balance -= tax;
+
+        let mut state = StateDiff::new_with_parent(handle);
+        state.branch_type = BranchType::Chance(1.);
+        state.message = DiffMessage::PayTax(tax);
+        state.set_players(players);
+        self.add_to_jackpot(handle, &mut state, tax);
+        self.end_move(handle, &mut state);
+
+        vec![state]
+    }
+
+    /// Return child states that can be reached by the current player building a house
+    /// (or a hotel) on one of their properties, or finishing building. Houses can only
+    /// be built on complete color sets, and evenly across the set.
+    pub(super) fn gen_build_children(&self, handle: usize) -> Vec<StateDiff> {
+        let i = self.diff_current_pindex(handle);
+        let balance = self.diff_players(handle)[i].balance;
+        let props = self.diff_owned_properties(handle);
+        let mut children = vec![];
+
+        for (color, positions) in self.board.props_by_color() {
+            if matches!(color, Color::Railroad | Color::Utility)
+                || !positions
+                    .iter()
+                    .all(|pos| props.get(pos).map(|p| p.owner) == Some(i))
+            {
+                continue;
+            }
+
+            let min_level = positions.iter().map(|pos| props[pos].rent_level).min();
+
+            for &pos in &positions {
+                let house_cost = self.board.property(pos).house_cost;
+                let level = props[&pos].rent_level;
+
+                if Some(level) != min_level || level >= HOTEL_LEVEL || balance < house_cost {
+                    continue;
+                }
+
+                let mut players = self.diff_players(handle).clone();
+                players[i].balance -= house_cost;
+                let mut new_props = props.clone();
+                new_props.get_mut(&pos).unwrap().rent_level += 1;
+
+                let mut state = StateDiff::new_with_parent(handle);
+                state.branch_type = BranchType::Choice;
+                state.message = DiffMessage::Build(pos);
+                state.next_move = MoveType::Build;
+                state.set_players(players);
+                state.set_owned_properties(new_props);
+                children.push(state);
+            }
+        }
+
+        children.sort_by_key(|child| match child.message {
+            DiffMessage::Build(pos) => pos,
+            _ => unreachable!(),
+        });
+
+        // Finishing building comes first
+        let mut no_build = StateDiff::new_with_parent(handle);
+        no_build.message = DiffMessage::NoBuild;
+        no_build.branch_type = if children.is_empty() {
+            BranchType::Chance(1.)
+        } else {
+            BranchType::Choice
+        };
+        no_build.next_move = if self.config.trading {
+            MoveType::Trade
+        } else {
+            MoveType::Roll
+        };
+        children.insert(0, no_build);
+
+        children
+    }
+}
@@ -0,0 +1,64 @@
+use super::{GameError, ResolvedState, Transcript};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Everything needed to reproduce a game that ran into a bug: the error it ended with,
+/// every move made up to that point, the seed that its random number generator started
+/// with, and the state that it was in. Games played with `Game::with_crash_dir()` save one
+/// of these when they end with an error that's down to a bug, rather than to how the game
+/// was set up, so that rare bugs found during long runs can be looked into afterwards.
+/// The game can be reconstructed with `Game::replay(&report.transcript)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// The error that the game ended with.
+    pub error: GameError,
+    /// The seed that the game's random number generator started with, if it was seeded.
+    pub seed: Option<u64>,
+    /// Every move made in the game before it ended.
+    pub transcript: Transcript,
+    /// The state that the game was in when it ended, or `None` if the state
+    /// was too broken to resolve.
+    pub state: Option<ResolvedState>,
+}
+
+impl CrashReport {
+    /// Save the report as JSON to a new file in `dir` (which is created if it doesn't
+    /// exist), and return the path of the file.
+    pub fn save_to_dir(&self, dir: impl AsRef<Path>) -> io::Result<PathBuf> {
+        fs::create_dir_all(&dir)?;
+
+        // Games that crash at the same time (on different threads or in different
+        // processes) still get their own files
+        static SAVED: AtomicUsize = AtomicUsize::new(0);
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let count = SAVED.fetch_add(1, Ordering::Relaxed);
+        let path = dir.as_ref().join(format!(
+            "crash-{}-{}-{}.json",
+            seconds,
+            std::process::id(),
+            count
+        ));
+
+        self.save(&path)?;
+        Ok(path)
+    }
+
+    /// Save the report to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Load a report that was previously saved with `CrashReport::save()`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
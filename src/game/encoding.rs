@@ -0,0 +1,107 @@
+use super::globals::Player;
+use super::state_diff::MoveType;
+use super::state_view::ResolvedState;
+
+/// The byte that stands for a missing player index (e.g. an auction with no bids yet).
+const NO_PLAYER: u8 = u8::MAX;
+
+impl ResolvedState {
+    /// Return a compact binary encoding of this state. Two states of games with the same
+    /// number of players have the same encoding if and only if they're equal, so it can be
+    /// used as a key for looking up states (e.g. in a transposition table), to find
+    /// duplicate states in self-play data, or to compare states cheaply.
+    ///
+    /// The encoding is, in order:
+    /// - every player's position, balance (4 bytes, little-endian), and a byte with
+    ///   whether they're in jail (bit 0), bankrupt (bit 1) and the doubles they've rolled
+    ///   (the rest), followed by the rounds they have left in jail
+    /// - the current player, the rounds of level 1 rent left, and the jackpot (4 bytes)
+    /// - the number of chance cards drawn, followed by each of them and the top card
+    /// - the number of owned properties, followed by the position of each of them (from
+    ///   lowest to highest) and a byte with its owner (shifted left by 3) and rent level
+    /// - the type of the next move, followed by any of its fields
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            self.players.len() * 8
+                + self.chance_deck.seen().len()
+                + self.owned_properties.len() * 2
+                + 24,
+        );
+
+        for (player, &jail_rounds) in self.players.iter().zip(&self.jail_rounds) {
+            encode_player(&mut bytes, player);
+            bytes.push(jail_rounds);
+        }
+
+        bytes.push(self.current_pindex as u8);
+        bytes.push(self.level_1_rent);
+        bytes.extend(self.jackpot.to_le_bytes());
+
+        let seen_ccs = self.chance_deck.seen();
+        bytes.push(seen_ccs.len() as u8);
+        bytes.extend(seen_ccs.iter().map(|&card| card as u8));
+        bytes.push(self.chance_deck.top() as u8);
+
+        // Sort the properties so that the encoding doesn't depend on the hash map's order
+        let mut props: Vec<_> = self.owned_properties.iter().collect();
+        props.sort_unstable_by_key(|(&pos, _)| pos);
+        bytes.push(props.len() as u8);
+        for (&pos, prop) in props {
+            bytes.push(pos);
+            bytes.push((prop.owner as u8) << 3 | prop.rent_level as u8);
+        }
+
+        encode_move_type(&mut bytes, &self.next_move);
+        bytes
+    }
+}
+
+/// Append the position, balance and status of `player` to `bytes`.
+fn encode_player(bytes: &mut Vec<u8>, player: &Player) {
+    bytes.push(player.position);
+    bytes.extend(player.balance.to_le_bytes());
+    bytes.push(player.in_jail as u8 | (player.bankrupt as u8) << 1 | player.doubles_rolled << 2);
+}
+
+/// Append a tag for the type of `next_move` to `bytes`, followed by any of its fields.
+pub(super) fn encode_move_type(bytes: &mut Vec<u8>, next_move: &MoveType) {
+    let player = |pindex: Option<usize>| pindex.map_or(NO_PLAYER, |p| p as u8);
+
+    match next_move {
+        MoveType::Undefined => bytes.push(0),
+        MoveType::Roll => bytes.push(1),
+        MoveType::Property => bytes.push(2),
+        MoveType::SellProperty { creditor, then } => {
+            bytes.push(3);
+            bytes.push(player(*creditor));
+            encode_move_type(bytes, then);
+        }
+        MoveType::Auction => bytes.push(4),
+        MoveType::Location => bytes.push(5),
+        MoveType::ChanceCard => bytes.push(6),
+        MoveType::ChoicefulCC(card) => {
+            bytes.push(7);
+            bytes.push(*card as u8);
+        }
+        MoveType::Trade => bytes.push(8),
+        MoveType::TradeOffer(offer) => {
+            bytes.push(9);
+            bytes.push(offer.proposer as u8);
+            bytes.push(offer.receiver as u8);
+            bytes.push(offer.give);
+            bytes.push(offer.take);
+            bytes.extend(offer.cash.to_le_bytes());
+        }
+        MoveType::Bid(auction) => {
+            bytes.push(10);
+            bytes.push(auction.pos);
+            bytes.extend(auction.high_bid.to_le_bytes());
+            bytes.push(player(auction.high_bidder));
+            bytes.push(auction.bidder as u8);
+            bytes.extend(auction.dropped.to_le_bytes());
+        }
+        MoveType::CommunityChest => bytes.push(11),
+        MoveType::Tax => bytes.push(12),
+        MoveType::Build => bytes.push(13),
+    }
+}
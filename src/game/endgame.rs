@@ -0,0 +1,117 @@
+use super::state_diff::BranchType;
+use super::Game;
+
+/// The exact values of a position's moves, found by expanding its game tree to the end.
+#[derive(Debug, Clone)]
+pub struct EndgameSolution {
+    /// The index of the root's child with the greatest value.
+    pub best_move: usize,
+    /// The probability that the solving player wins after each of the root's children.
+    pub values: Vec<f64>,
+    /// The number of game tree nodes that were visited to solve the position.
+    pub nodes: usize,
+}
+
+/// Solves endgame positions exactly, by expanding their game trees until every branch
+/// ends with a single player left standing. This is only feasible when few properties
+/// and little money remain, so the solver gives up on any position whose tree has more
+/// than `node_limit` nodes.
+///
+/// Like `ExpectimaxAgent`, the solver assumes that the other players play against the
+/// solving player, and that chance outcomes follow their probabilities. Turn limits
+/// aren't taken into account, since the game tree doesn't track turns below the root.
+#[derive(Debug, Clone, Copy)]
+pub struct EndgameSolver {
+    /// The most game tree nodes that the solver will visit before giving up.
+    node_limit: usize,
+}
+
+impl EndgameSolver {
+    /// Return a solver that gives up on positions with more than `node_limit` nodes.
+    pub fn new(node_limit: usize) -> EndgameSolver {
+        EndgameSolver { node_limit }
+    }
+
+    /// Return the exact values of the root's children for player `pindex`,
+    /// or `None` if the game tree is too big to be solved.
+    pub fn solve(&self, game: &mut Game, pindex: usize) -> Option<EndgameSolution> {
+        game.gen_children_save(game.root_handle);
+        let children = game.nodes[game.root_handle].children.clone();
+
+        let mut nodes = 1;
+        let values = children
+            .iter()
+            .map(|&child| self.value(game, child, pindex, &mut nodes))
+            .collect::<Option<Vec<f64>>>()?;
+
+        let best_move = values
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(i, _)| i)
+            .unwrap();
+
+        Some(EndgameSolution {
+            best_move,
+            values,
+            nodes,
+        })
+    }
+
+    /// Return the probability that `pindex` wins from the state at `handle`, or `None`
+    /// if the total number of visited nodes (counted in `nodes`) exceeds the limit.
+    fn value(
+        &self,
+        game: &mut Game,
+        handle: usize,
+        pindex: usize,
+        nodes: &mut usize,
+    ) -> Option<f64> {
+        *nodes += 1;
+        if *nodes > self.node_limit {
+            return None;
+        }
+
+        if game.is_terminal(handle) {
+            return Some(if game.get_winner(handle) == Ok(pindex) {
+                1.
+            } else {
+                0.
+            });
+        }
+
+        game.gen_children_save(handle);
+        let children = game.nodes[handle].children.clone();
+        let acting_pindex = game.get_acting_pindex(handle);
+
+        match game.nodes[children[0]].branch_type {
+            // Chance nodes are worth the expected value of their outcomes
+            BranchType::Chance(_) => {
+                let mut expected = 0.;
+                for &child in &children {
+                    match game.nodes[child].branch_type {
+                        BranchType::Chance(p) => {
+                            expected += p * self.value(game, child, pindex, nodes)?
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                Some(expected)
+            }
+            // We pick our best move, and our opponents pick our worst
+            BranchType::Choice => {
+                let mut values = Vec::with_capacity(children.len());
+                for &child in &children {
+                    values.push(self.value(game, child, pindex, nodes)?);
+                }
+
+                if acting_pindex == pindex {
+                    Some(values.into_iter().fold(f64::NEG_INFINITY, f64::max))
+                } else {
+                    Some(values.into_iter().fold(f64::INFINITY, f64::min))
+                }
+            }
+            BranchType::Undefined => unreachable!(),
+        }
+    }
+}
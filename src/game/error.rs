@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Something that went wrong while playing a game, which stops it from going on.
+///
+/// ```
+/// use monopoly_math::{Game, GameError};
+///
+/// let mut game = Game::new(2);
+/// let moves = game.legal_moves().unwrap().len();
+/// assert_eq!(
+///     game.apply_move(moves),
+///     Err(GameError::IllegalMove { index: moves, moves })
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameError {
+    /// The game was given a different number of agents than it has players.
+    AgentCount { players: usize, agents: usize },
+    /// A move was chosen that isn't one of the moves available.
+    IllegalMove { index: usize, moves: usize },
+    /// A player was referred to who isn't in the game.
+    NoSuchPlayer { pindex: usize, players: usize },
+    /// A player's agent panicked while it was deciding on a move.
+    AgentPanicked { pindex: usize },
+    /// The move at `index` of a transcript couldn't be replayed.
+    InvalidTranscript { index: usize },
+    /// The engine got into a state it should never be in (i.e. there's a bug),
+    /// with the message that it panicked with.
+    Internal(String),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::AgentCount { players, agents } => write!(
+                f,
+                "{} agents were given for a game of {} players",
+                agents, players
+            ),
+            GameError::IllegalMove { index, moves } => {
+                write!(f, "move {} is not legal ({} moves available)", index, moves)
+            }
+            GameError::NoSuchPlayer { pindex, players } => write!(
+                f,
+                "there is no player {} in a game of {} players",
+                pindex, players
+            ),
+            GameError::AgentPanicked { pindex } => {
+                write!(f, "player {}'s agent panicked while deciding", pindex)
+            }
+            GameError::InvalidTranscript { index } => {
+                write!(f, "move {} of the transcript can't be replayed", index)
+            }
+            GameError::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// Call `f`, turning any panic into a `GameError::Internal`. The engine returns errors for
+/// the bugs that it checks for, so this is only a last resort for the ones it doesn't
+/// (e.g. an index out of bounds), which keeps them from taking down the thread (e.g. a
+/// tournament's worker) along with every other game on it. Nothing is caught when panics
+/// abort, as they do on the web.
+pub(super) fn catch_internal<T>(f: impl FnOnce() -> T) -> Result<T, GameError> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| GameError::Internal(panic_message(payload.as_ref()).to_string()))
+}
+
+/// Return the message that a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
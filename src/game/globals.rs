@@ -1,8 +1,15 @@
+use super::board::Board;
 use lazy_static::lazy_static;
-use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Debug, Copy, Clone)]
 /// A possible outcome of rolling the dice.
@@ -15,7 +22,7 @@ pub struct DiceRoll {
     pub is_double: bool,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 /// The color sets of properties.
 pub enum Color {
     Brown,
@@ -26,9 +33,15 @@ pub enum Color {
     Yellow,
     Green,
     Blue,
+    /// The railroads of classic Monopoly, whose rent is `rents[n - 1]`
+    /// when their owner has `n` railroads.
+    Railroad,
+    /// The utilities of classic Monopoly, whose rent is `rents[n - 1]` times
+    /// the dice roll when their owner has `n` utilities.
+    Utility,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 /// Chance cards that require the player to make a choice.
 ///
 /// Note that any chance card that affects a property requires the
@@ -75,40 +88,16 @@ pub enum ChanceCard {
 }
 
 impl ChanceCard {
-    pub fn unseen_counts(seen_cards: &[ChanceCard]) -> HashMap<ChanceCard, u8> {
-        let mut counts = HashMap::from([
-            (ChanceCard::RentTo1, 3),
-            (ChanceCard::RentTo5, 1),
-            (ChanceCard::SetRentInc, 3),
-            (ChanceCard::SetRentDec, 1),
-            (ChanceCard::SideRentInc, 1),
-            (ChanceCard::SideRentDec, 1),
-            (ChanceCard::RentSpike, 2),
-            (ChanceCard::Bonus, 2),
-            (ChanceCard::SwapProperty, 2),
-            (ChanceCard::OpponentToJail, 1),
-            (ChanceCard::GoToAnyProperty, 1),
-            (ChanceCard::PropertyTax, 1),
-            (ChanceCard::Level1Rent, 1),
-            (ChanceCard::AllToParking, 1),
-        ]);
-
-        for card in seen_cards {
-            *counts.get_mut(card).unwrap() -= 1;
-        }
-
-        counts
-    }
-
     pub fn is_choiceless(&self) -> bool {
-        match self {
-            ChanceCard::PropertyTax | ChanceCard::Level1Rent | ChanceCard::AllToParking => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            ChanceCard::PropertyTax | ChanceCard::Level1Rent | ChanceCard::AllToParking
+        )
     }
 }
 
 /// A property tile on the board.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Property {
     /// The color set that the property belongs to.
     pub color: Color,
@@ -118,6 +107,12 @@ pub struct Property {
     /// `rents[0]` would be the rent amount for rent level 1,
     /// and `rents[4]` would be that of rent level 5.
     pub rents: [i32; 5],
+    /// The rent with a hotel, which is rent level 6 in classic Monopoly.
+    #[serde(default)]
+    pub hotel_rent: i32,
+    /// The cost of building a house or hotel on the property in classic Monopoly.
+    #[serde(default)]
+    pub house_cost: i32,
 }
 
 impl Property {
@@ -127,11 +122,39 @@ impl Property {
             color,
             price,
             rents,
+            hotel_rent: 0,
+            house_cost: 0,
+        }
+    }
+
+    /// Creates a new property of classic Monopoly, where `rents` are the rents
+    /// with no houses up to 4 houses.
+    pub fn classic(
+        color: Color,
+        price: i32,
+        rents: [i32; 5],
+        hotel_rent: i32,
+        house_cost: i32,
+    ) -> Property {
+        Property {
+            color,
+            price,
+            rents,
+            hotel_rent,
+            house_cost,
+        }
+    }
+
+    /// Return the rent at `rent_level`, which is the hotel rent at rent level 6.
+    pub fn rent(&self, rent_level: usize) -> i32 {
+        match rent_level {
+            1..=5 => self.rents[rent_level - 1],
+            _ => self.hotel_rent,
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 /// A player playing the game.
 pub struct Player {
     /// Whether the player is currently in jail.
@@ -143,31 +166,40 @@ pub struct Player {
     pub balance: i32,
     /// The number of consecutive doubles the player has rolled.
     pub doubles_rolled: u8,
+    /// Whether the player has gone bankrupt and been eliminated from the game.
+    pub bankrupt: bool,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player::new(GameConfig::default().starting_balance)
+    }
 }
 
 impl Player {
-    /// Return a new player.
-    pub fn new() -> Player {
+    /// Return a new player with `balance` in cash.
+    pub fn new(balance: i32) -> Player {
         Player {
             in_jail: false,
             position: 0,
-            balance: 1500,
+            balance,
             doubles_rolled: 0,
+            bankrupt: false,
         }
     }
 
     /// Move the player on the board.
-    pub fn move_by(&mut self, distance: u8) {
-        let new_pos = (self.position + distance) % 36;
+    pub fn move_by(&mut self, distance: u8, board: &Board, go_salary: i32) {
+        let new_pos = (self.position + distance) % board.size;
 
         // Set the player's `in_jail` flag to false if appropriate
         if self.in_jail && distance != 0 {
             self.in_jail = false;
         }
 
-        // Give the player $200 if they pass 'Go'
+        // Pay the player their salary if they pass 'Go'
         if new_pos < self.position {
-            self.balance += 200;
+            self.balance += go_salary;
         }
 
         // Update the position
@@ -175,9 +207,9 @@ impl Player {
     }
 
     /// Send the player to jail.
-    pub fn send_to_jail(&mut self) {
+    pub fn send_to_jail(&mut self, board: &Board) {
         // Set the player's position to jail
-        self.position = JAIL_POSITION;
+        self.position = board.jail_position;
         self.in_jail = true;
 
         // Reset the doubles counter
@@ -185,8 +217,27 @@ impl Player {
     }
 }
 
+/// Players are highlighted with ANSI escape codes, unless they're
+/// formatted with the alternate flag (`{:#}`) for plain text.
 impl std::fmt::Display for Player {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            if self.bankrupt {
+                return write!(f, "bankrupt");
+            }
+
+            let jailed = if self.in_jail { " (jail)" } else { "" };
+            return write!(
+                f,
+                "[{:02}{}] {}dbls ${}",
+                self.position, jailed, self.doubles_rolled, self.balance
+            );
+        }
+
+        if self.bankrupt {
+            return write!(f, "\x1b[31mbankrupt\x1b[0m");
+        }
+
         let pos_color = if self.in_jail { "\x1b[31m" } else { "\x1b[36m" };
 
         write!(
@@ -197,7 +248,184 @@ impl std::fmt::Display for Player {
     }
 }
 
-#[derive(Debug)]
+/// The edition of Monopoly that a game is played by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleSet {
+    /// Monopoly: Ultimate Banking, where landing on a property raises its rent level.
+    #[default]
+    UltimateBanking,
+    /// Classic Monopoly, with houses and hotels, railroads, utilities,
+    /// taxes, and the Chance and Community Chest decks.
+    Classic,
+}
+
+impl RuleSet {
+    /// Return the board that the edition is played on.
+    pub fn board(&self) -> Board {
+        match self {
+            RuleSet::UltimateBanking => Board::default(),
+            RuleSet::Classic => Board::classic(),
+        }
+    }
+}
+
+/// Optional rules that can be toggled for a game, and the amounts of money
+/// that the rules deal in. Missing fields are deserialized to their defaults.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Whether players can propose property trades to each other at the start of their turn.
+    pub trading: bool,
+    /// Whether auctions are played out with every player bidding in turn,
+    /// instead of being resolved by a model of how much players would bid.
+    pub real_auctions: bool,
+    /// The edition of Monopoly that the game is played by.
+    pub rules: RuleSet,
+    /// Whether taxes and penalties are put on 'Free parking' instead of going to the
+    /// bank, to be collected by the next player to land there (a popular house rule).
+    pub free_parking_jackpot: bool,
+    /// The cash that every player starts the game with.
+    pub starting_balance: i32,
+    /// The cash that a player collects every time they pass 'Go'.
+    pub go_salary: i32,
+    /// The fine for failing to roll doubles on the last try to get out of jail.
+    pub jail_penalty: i32,
+    /// The number of turns that a player in jail gets to try to roll doubles. On the
+    /// last one, they leave jail whatever they roll, and pay the fine if they fail.
+    pub jail_tries: u8,
+    /// The fee for teleporting to a property from a location tile.
+    pub location_fee: i32,
+    /// Whether location tiles only offer to teleport to properties that aren't owned by
+    /// an opponent. Landing on an opponent's property only costs rent, so leaving those
+    /// out shrinks the choices without taking away any that are worth making.
+    pub restrict_location_targets: bool,
+    /// The percentage of a property's price that the bank pays for it when
+    /// a player in debt sells it (e.g. 50 to sell properties at half price).
+    pub resale_percent: i32,
+    /// Whether players in debt are only offered the sales that no other sale beats on
+    /// both the number of properties sold and the money left over after paying the debt,
+    /// instead of every sale of the fewest properties that covers the debt. Sales of more
+    /// than `PARETO_EXTRA_SALES` properties over the fewest aren't offered.
+    pub pareto_sales: bool,
+    /// The number of turns after which the game ends, with the richest player
+    /// winning, or `None` to play until only one player is left.
+    pub max_turns: Option<usize>,
+    /// The number of times that the game can be in the same state before it ends, with the
+    /// richest player winning, or `None` to let games cycle (e.g. players bouncing between
+    /// each other's properties with offsetting rents) until some other rule ends them.
+    pub max_repetitions: Option<usize>,
+    /// Whether the chance cards are reshuffled as soon as every one of them has been drawn,
+    /// instead of the deck cycling through them in the order they were first drawn.
+    pub reshuffle_chance_deck: bool,
+    /// Whether a player teleporting to a property is only offered one of each group of
+    /// interchangeable properties (see `Board::interchangeable()`) whose color set nobody
+    /// owns yet. The rest would lead to states that are alike in every way but the
+    /// player's exact position, so this shrinks the choices in the opening at the cost
+    /// of the game tree no longer being exact, which is why it's off unless asked for.
+    pub symmetry_reduction: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            trading: false,
+            real_auctions: false,
+            rules: RuleSet::default(),
+            free_parking_jackpot: false,
+            starting_balance: 1500,
+            go_salary: 200,
+            jail_penalty: 100,
+            jail_tries: JAIL_TRIES,
+            location_fee: 100,
+            restrict_location_targets: false,
+            resale_percent: 100,
+            pareto_sales: false,
+            max_turns: None,
+            max_repetitions: None,
+            reshuffle_chance_deck: false,
+            symmetry_reduction: false,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Return the default config for classic Monopoly, whose jail fine is $50.
+    pub fn classic() -> Self {
+        GameConfig {
+            rules: RuleSet::Classic,
+            jail_penalty: 50,
+            ..GameConfig::default()
+        }
+    }
+}
+
+/// Where the money in a transfer comes from, or goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Account {
+    /// The player at this index.
+    Player(usize),
+    /// The bank, which pays salaries and collects purchases, taxes, etc.
+    Bank,
+    /// The money waiting on 'Free parking' (with the free parking jackpot rule).
+    Jackpot,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Account::Player(i) => write!(f, "player {}", i),
+            Account::Bank => write!(f, "bank"),
+            Account::Jackpot => write!(f, "jackpot"),
+        }
+    }
+}
+
+/// A transfer of money from one account to another, as recorded in the ledger of a game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+    /// The round that the transfer happened in.
+    pub round: usize,
+    /// Where the money came from.
+    pub payer: Account,
+    /// Where the money went to.
+    pub payee: Account,
+    /// The amount of money transferred, which is always positive.
+    pub amount: i32,
+    /// The kind of move that caused the transfer (e.g. "LandOppProp"), from `DiffMessage::name()`.
+    pub reason: String,
+}
+
+/// How an auction of a property turned out, whether it was resolved by the game's
+/// `AuctionModel` or played out with real bids.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuctionOutcome {
+    /// The round that the auction happened in.
+    pub round: usize,
+    /// The position of the property that was auctioned.
+    pub pos: u8,
+    /// The player who landed on the property and put it up for auction.
+    pub landing_pindex: usize,
+    /// The player who won the auction, or `None` if nobody bid for the property.
+    pub winner: Option<usize>,
+    /// The winning bid, which is 0 if nobody bid.
+    pub winning_bid: i32,
+    /// The list price of the property.
+    pub price: i32,
+}
+
+impl AuctionOutcome {
+    /// Return the winning bid as a fraction of the list price.
+    pub fn bid_ratio(&self) -> f64 {
+        self.winning_bid as f64 / self.price as f64
+    }
+
+    /// Return whether the player who put the property up for auction ended up owning it.
+    pub fn won_by_landing_player(&self) -> bool {
+        self.winner == Some(self.landing_pindex)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameplayStats {
     /// The net property worths of each player over time.
     property_worth: Vec<Vec<i32>>,
@@ -209,6 +437,52 @@ pub struct GameplayStats {
     location_tile_usage: Vec<(u32, u32)>,
     /// The number of rounds that each player was in jail for.
     sentenced_rounds: Vec<u32>,
+    /// The players who went bankrupt, in the order they were eliminated.
+    /// Each entry looks like `(round, player)`.
+    bankruptcies: Vec<(usize, usize)>,
+    /// The time taken and game tree nodes created by each decision made by an agent.
+    /// Each entry looks like `(round, player, milliseconds, nodes)`.
+    #[serde(default)]
+    decisions: Vec<(usize, usize, f64, usize)>,
+    /// The number of game tree nodes still stored when the game ended.
+    #[serde(default)]
+    final_tree_size: usize,
+    /// Every time a player came to own a property (by buying it, winning an auction, etc).
+    /// Each entry looks like `(round, player, position)`.
+    #[serde(default)]
+    acquisitions: Vec<(usize, usize, u8)>,
+    /// Every rent payment. Each entry looks like `(round, position, payer, owner, rent)`.
+    #[serde(default)]
+    rent_payments: Vec<(usize, u8, usize, usize, i32)>,
+    /// The estimated win probability of each player after every move.
+    /// Each entry looks like `(round, probabilities)`.
+    #[serde(default)]
+    win_probabilities: Vec<(usize, Vec<f64>)>,
+    /// Every Free Parking jackpot collected. Each entry looks like `(round, player, amount)`.
+    #[serde(default)]
+    jackpots: Vec<(usize, usize, i32)>,
+    /// The number of moves available at every state that the game passed through.
+    /// Each entry looks like `(round, move type, children)`.
+    #[serde(default)]
+    branching: Vec<(usize, String, usize)>,
+    /// Every time the game came back to a state it had been in before.
+    /// Each entry looks like `(round, times the state has been seen)`.
+    #[serde(default)]
+    repetitions: Vec<(usize, usize)>,
+    /// Every transfer of money between the players, the bank and the jackpot.
+    #[serde(default)]
+    ledger: Vec<Transfer>,
+    /// The balance of each player at the end of every turn.
+    /// Each entry looks like `(round, balances)`.
+    #[serde(default)]
+    balances: Vec<(usize, Vec<i32>)>,
+    /// The net worth (cash plus property prices) of each player at the end of every turn.
+    /// Each entry looks like `(round, net worths)`.
+    #[serde(default)]
+    net_worths: Vec<(usize, Vec<i32>)>,
+    /// How every auction turned out.
+    #[serde(default)]
+    auctions: Vec<AuctionOutcome>,
 }
 
 impl GameplayStats {
@@ -219,6 +493,19 @@ impl GameplayStats {
             property_worth: vec![],
             location_tile_usage: vec![(0, 0); player_count],
             auction_rate: vec![],
+            bankruptcies: vec![],
+            decisions: vec![],
+            final_tree_size: 0,
+            acquisitions: vec![],
+            rent_payments: vec![],
+            win_probabilities: vec![],
+            jackpots: vec![],
+            branching: vec![],
+            repetitions: vec![],
+            ledger: vec![],
+            balances: vec![],
+            net_worths: vec![],
+            auctions: vec![],
         }
     }
 
@@ -235,30 +522,233 @@ impl GameplayStats {
         self.property_worth.push(worths);
     }
 
-    pub fn inc_sentenced_rounds(&mut self, pindex: usize) {
-        self.sentenced_rounds[pindex] += JAIL_TRIES as u32;
+    pub fn inc_sentenced_rounds(&mut self, pindex: usize, rounds: u8) {
+        self.sentenced_rounds[pindex] += rounds as u32;
     }
 
-    pub fn save_to_csv(&self, loser: usize) {
-        let uid: String = rand::thread_rng().gen::<u32>().to_string();
-        println!("{:?}", fs::create_dir_all(format!("./data/{}", uid)));
+    pub fn update_bankruptcies(&mut self, pindex: usize, round: usize) {
+        self.bankruptcies.push((round, pindex));
+    }
+
+    pub fn update_decisions(&mut self, pindex: usize, round: usize, time: Duration, nodes: usize) {
+        self.decisions
+            .push((round, pindex, time.as_secs_f64() * 1000., nodes));
+    }
+
+    pub fn update_final_tree_size(&mut self, nodes: usize) {
+        self.final_tree_size = nodes;
+    }
+
+    pub fn update_acquisitions(&mut self, pindex: usize, round: usize, pos: u8) {
+        self.acquisitions.push((round, pindex, pos));
+    }
+
+    pub fn update_rent_payments(
+        &mut self,
+        round: usize,
+        pos: u8,
+        payer: usize,
+        owner: usize,
+        rent: i32,
+    ) {
+        self.rent_payments.push((round, pos, payer, owner, rent));
+    }
+
+    pub fn update_win_probabilities(&mut self, round: usize, probabilities: Vec<f64>) {
+        self.win_probabilities.push((round, probabilities));
+    }
+
+    pub fn update_jackpots(&mut self, round: usize, pindex: usize, amount: i32) {
+        self.jackpots.push((round, pindex, amount));
+    }
+
+    pub fn update_branching(&mut self, round: usize, move_type: &str, children: usize) {
+        self.branching
+            .push((round, move_type.to_string(), children));
+    }
+
+    pub fn update_ledger(&mut self, transfers: impl IntoIterator<Item = Transfer>) {
+        self.ledger.extend(transfers);
+    }
+
+    pub fn update_repetitions(&mut self, round: usize, times_seen: usize) {
+        self.repetitions.push((round, times_seen));
+    }
+
+    pub fn update_wealth(&mut self, round: usize, balances: Vec<i32>, net_worths: Vec<i32>) {
+        self.balances.push((round, balances));
+        self.net_worths.push((round, net_worths));
+    }
+
+    pub fn update_auctions(&mut self, outcome: AuctionOutcome) {
+        self.auctions.push(outcome);
+    }
+
+    /// Return how every auction in the game turned out, in order.
+    pub fn auctions(&self) -> &[AuctionOutcome] {
+        &self.auctions
+    }
+
+    /// Return the balance of each player at the end of every turn,
+    /// in the form `(round, balances)`.
+    pub fn balances(&self) -> &[(usize, Vec<i32>)] {
+        &self.balances
+    }
+
+    /// Return the net worth (cash plus property prices) of each player at the
+    /// end of every turn, in the form `(round, net worths)`.
+    pub fn net_worths(&self) -> &[(usize, Vec<i32>)] {
+        &self.net_worths
+    }
+
+    /// Return the number of moves available at every state that the game passed
+    /// through, in the form `(round, move type, children)`.
+    pub fn branching(&self) -> &[(usize, String, usize)] {
+        &self.branching
+    }
+
+    /// Return the time taken and game tree nodes created by each decision made
+    /// by an agent, in the form `(round, player, milliseconds, nodes)`.
+    pub fn decisions(&self) -> &[(usize, usize, f64, usize)] {
+        &self.decisions
+    }
+
+    /// Return every transfer of money made in the game, in order.
+    pub fn ledger(&self) -> &[Transfer] {
+        &self.ledger
+    }
+
+    /// Return the total money that each player received and paid out
+    /// over the game, in the form `(income, expenses)`.
+    pub fn cash_flow(&self) -> Vec<(i32, i32)> {
+        let mut flows = vec![(0, 0); self.get_player_count()];
+        for transfer in &self.ledger {
+            if let Account::Player(i) = transfer.payee {
+                flows[i].0 += transfer.amount;
+            }
+            if let Account::Player(i) = transfer.payer {
+                flows[i].1 += transfer.amount;
+            }
+        }
+
+        flows
+    }
+
+    /// Return every time the game came back to a state it had been
+    /// in before, in the form `(round, times the state has been seen)`.
+    pub fn repetitions(&self) -> &[(usize, usize)] {
+        &self.repetitions
+    }
+
+    /// Return the total Free Parking jackpot money that each player collected.
+    pub fn jackpot_winnings(&self) -> Vec<i32> {
+        let mut winnings = vec![0; self.get_player_count()];
+        for &(_, pindex, amount) in &self.jackpots {
+            winnings[pindex] += amount;
+        }
+
+        winnings
+    }
+
+    /// Return the estimated win probability of each player after every move,
+    /// in the form `(round, probabilities)`.
+    pub fn win_probabilities(&self) -> &[(usize, Vec<f64>)] {
+        &self.win_probabilities
+    }
+
+    /// Return every time a player came to own a property (by buying it, winning an
+    /// auction, etc), in the form `(round, player, position)`.
+    pub fn acquisitions(&self) -> &[(usize, usize, u8)] {
+        &self.acquisitions
+    }
+
+    /// Return the positions of the properties that each player came to own, in order.
+    pub fn acquired_properties(&self) -> Vec<Vec<u8>> {
+        let mut acquired = vec![vec![]; self.get_player_count()];
+        for &(_, pindex, pos) in &self.acquisitions {
+            acquired[pindex].push(pos);
+        }
+
+        acquired
+    }
+
+    /// Return the total rent collected on each property, in the form `HashMap<position, rent>`.
+    pub fn rent_by_property(&self) -> HashMap<u8, i32> {
+        let mut rents = HashMap::new();
+        for &(_, pos, _, _, rent) in &self.rent_payments {
+            *rents.entry(pos).or_insert(0) += rent;
+        }
+
+        rents
+    }
+
+    /// Return the total rent collected on the properties of each color set on `board`.
+    pub fn rent_by_color(&self, board: &Board) -> HashMap<Color, i32> {
+        let mut rents = HashMap::new();
+        for (pos, rent) in self.rent_by_property() {
+            *rents.entry(board.property(pos).color).or_insert(0) += rent;
+        }
+
+        rents
+    }
+
+    /// Return the indexes of the players who went bankrupt, in the order they were eliminated.
+    pub fn bankrupt_players(&self) -> Vec<usize> {
+        self.bankruptcies
+            .iter()
+            .map(|&(_, pindex)| pindex)
+            .collect()
+    }
+
+    /// Write each of the collected statistics to its own CSV file in `dir`.
+    pub fn to_csv(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("sentences.csv"), self.csv_sentenced_rounds())?;
+        fs::write(dir.join("auctions.csv"), self.csv_auction_rate())?;
+        fs::write(dir.join("prop_worth.csv"), self.csv_prop_worth())?;
+        fs::write(dir.join("location.csv"), self.csv_location())?;
+        fs::write(dir.join("decisions.csv"), self.csv_decisions())?;
+        fs::write(dir.join("acquisitions.csv"), self.csv_acquisitions())?;
+        fs::write(dir.join("rent.csv"), self.csv_rent_payments())?;
+        fs::write(dir.join("win_prob.csv"), self.csv_win_probabilities())?;
+        fs::write(dir.join("jackpots.csv"), self.csv_jackpots())?;
+        fs::write(dir.join("branching.csv"), self.csv_branching())?;
+        fs::write(dir.join("repetitions.csv"), self.csv_repetitions())?;
+        fs::write(dir.join("ledger.csv"), self.csv_ledger())?;
         fs::write(
-            format!("./data/{}/sentences.csv", uid),
-            self.csv_sentenced_rounds(),
-        );
+            dir.join("auction_outcomes.csv"),
+            self.csv_auction_outcomes(),
+        )?;
         fs::write(
-            format!("./data/{}/auctions.csv", uid),
-            self.csv_auction_rate(),
-        );
+            dir.join("balances.csv"),
+            Self::csv_per_player_series(&self.balances),
+        )?;
         fs::write(
-            format!("./data/{}/prop_worth.csv", uid),
-            self.csv_prop_worth(),
-        );
-        fs::write(format!("./data/{}/location.csv", uid), self.csv_location());
+            dir.join("net_worth.csv"),
+            Self::csv_per_player_series(&self.net_worths),
+        )?;
         fs::write(
-            format!("./data/{}/loser.csv", uid),
-            format!("loser\n{}", loser.to_string()),
-        );
+            dir.join("tree_size.csv"),
+            format!("final tree size\n{}", self.final_tree_size),
+        )?;
+        fs::write(dir.join("bankruptcies.csv"), self.csv_bankruptcies())
+    }
+
+    /// Return all the collected statistics as a JSON string.
+    pub fn to_json(&self) -> String {
+        // Serializing plain vectors of numbers can't fail
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Save the statistics (as both CSV and JSON) to a new directory in `parent`.
+    pub fn save_to_csv(&self, parent: impl AsRef<Path>, winner: usize) -> io::Result<()> {
+        let uid: String = rand::thread_rng().gen::<u32>().to_string();
+        let dir = parent.as_ref().join(uid);
+
+        self.to_csv(&dir)?;
+        fs::write(dir.join("stats.json"), self.to_json())?;
+        fs::write(dir.join("winner.csv"), format!("winner\n{}", winner))
     }
 
     /****     HELPER FUNCTIONS     ****/
@@ -267,12 +757,8 @@ impl GameplayStats {
         self.sentenced_rounds.len()
     }
 
-    fn get_round_count(&self) -> usize {
-        self.property_worth.len()
-    }
-
     fn csv_sentenced_rounds(&self) -> String {
-        let headers = (0..self.sentenced_rounds.len())
+        let headers = (0..self.get_player_count())
             .map(|i| format!("player {}", i))
             .collect::<Vec<String>>()
             .join(",");
@@ -290,7 +776,7 @@ impl GameplayStats {
     fn csv_prop_worth(&self) -> String {
         let mut csv = "move number,".to_owned();
         csv.push_str(
-            &(0..self.sentenced_rounds.len())
+            &(0..self.get_player_count())
                 .map(|i| format!("player {}", i))
                 .collect::<Vec<String>>()
                 .join(","),
@@ -311,7 +797,7 @@ impl GameplayStats {
     }
 
     fn csv_location(&self) -> String {
-        let headers = (0..self.sentenced_rounds.len())
+        let headers = (0..self.get_player_count())
             .map(|i| format!("player {}", i))
             .collect::<Vec<String>>()
             .join(",");
@@ -333,6 +819,162 @@ impl GameplayStats {
         [headers, row].join("\n")
     }
 
+    fn csv_bankruptcies(&self) -> String {
+        let mut csv = "move number,player number".to_owned();
+
+        for row in &self.bankruptcies {
+            csv.push_str(&format!("\n{},{}", row.0, row.1));
+        }
+
+        csv
+    }
+
+    fn csv_jackpots(&self) -> String {
+        let mut csv = "move number,player number,amount".to_owned();
+
+        for row in &self.jackpots {
+            csv.push_str(&format!("\n{},{},{}", row.0, row.1, row.2));
+        }
+
+        csv
+    }
+
+    fn csv_ledger(&self) -> String {
+        let mut csv = "move number,payer,payee,amount,reason".to_owned();
+
+        for t in &self.ledger {
+            csv.push_str(&format!(
+                "\n{},{},{},{},{}",
+                t.round, t.payer, t.payee, t.amount, t.reason
+            ));
+        }
+
+        csv
+    }
+
+    fn csv_auction_outcomes(&self) -> String {
+        let mut csv =
+            "move number,position,landing player,winner,winning bid,price,bid ratio,landing player won"
+                .to_owned();
+
+        for a in &self.auctions {
+            csv.push_str(&format!(
+                "\n{},{},{},{},{},{},{},{}",
+                a.round,
+                a.pos,
+                a.landing_pindex,
+                a.winner.map_or(String::new(), |w| w.to_string()),
+                a.winning_bid,
+                a.price,
+                a.bid_ratio(),
+                a.won_by_landing_player()
+            ));
+        }
+
+        csv
+    }
+
+    /// Return a time series with a value for each player in every round as CSV.
+    fn csv_per_player_series(series: &[(usize, Vec<i32>)]) -> String {
+        let players = series.first().map_or(0, |(_, row)| row.len());
+        let mut csv = "round,".to_owned();
+        csv.push_str(
+            &(0..players)
+                .map(|i| format!("player {}", i))
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+
+        for (round, row) in series {
+            csv.push_str(&format!(
+                "\n{},{}",
+                round,
+                row.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ));
+        }
+
+        csv
+    }
+
+    fn csv_repetitions(&self) -> String {
+        let mut csv = "move number,times seen".to_owned();
+
+        for row in &self.repetitions {
+            csv.push_str(&format!("\n{},{}", row.0, row.1));
+        }
+
+        csv
+    }
+
+    fn csv_branching(&self) -> String {
+        let mut csv = "move number,move type,children".to_owned();
+
+        for row in &self.branching {
+            csv.push_str(&format!("\n{},{},{}", row.0, row.1, row.2));
+        }
+
+        csv
+    }
+
+    fn csv_decisions(&self) -> String {
+        let mut csv = "move number,player number,time (ms),nodes created".to_owned();
+
+        for row in &self.decisions {
+            csv.push_str(&format!("\n{},{},{},{}", row.0, row.1, row.2, row.3));
+        }
+
+        csv
+    }
+
+    fn csv_acquisitions(&self) -> String {
+        let mut csv = "move number,player number,position".to_owned();
+
+        for row in &self.acquisitions {
+            csv.push_str(&format!("\n{},{},{}", row.0, row.1, row.2));
+        }
+
+        csv
+    }
+
+    fn csv_rent_payments(&self) -> String {
+        let mut csv = "move number,position,payer,owner,rent".to_owned();
+
+        for row in &self.rent_payments {
+            csv.push_str(&format!(
+                "\n{},{},{},{},{}",
+                row.0, row.1, row.2, row.3, row.4
+            ));
+        }
+
+        csv
+    }
+
+    fn csv_win_probabilities(&self) -> String {
+        let mut csv = "move number,".to_owned();
+        csv.push_str(
+            &(0..self.get_player_count())
+                .map(|i| format!("player {}", i))
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+
+        for (round, row) in &self.win_probabilities {
+            csv.push_str(&format!(
+                "\n{},{}",
+                round,
+                row.iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ));
+        }
+
+        csv
+    }
+
     fn csv_auction_rate(&self) -> String {
         let mut csv = "move number,player number,auctioned".to_owned();
 
@@ -344,130 +986,24 @@ impl GameplayStats {
     }
 }
 
-#[derive(Copy, Clone)]
-pub enum DiffID {
-    Level1Rent = 1,
-    SeenCcsHead,
-    SeenCcs,
-    OwnedProperties,
-    CurrentPlayer,
-    Players,
-    JailRounds,
-}
-
-impl DiffID {
-    pub fn all() -> [DiffID; 7] {
-        [
-            DiffID::Level1Rent,
-            DiffID::SeenCcsHead,
-            DiffID::SeenCcs,
-            DiffID::OwnedProperties,
-            DiffID::CurrentPlayer,
-            DiffID::Players,
-            DiffID::JailRounds,
-        ]
-    }
-}
-
-/// The position of 'Jail' on the game board.
-pub const JAIL_POSITION: u8 = 9;
-/// The position of 'Free parking' on the game board.
-pub const FREE_PARKING_POSITION: u8 = 18;
-/// The position of the 'Go to jail' tile on the game board.
-pub const GO_TO_JAIL_POSITION: u8 = 27;
 /// The total number of chance cards there are.
 pub const TOTAL_CHANCE_CARDS: usize = 21;
-/// Number of tries you can use to get out of jail before you have to pay.
+/// The amount that the 'Property tax' chance card charges for every property the player owns.
+pub const PROPERTY_TAX: i32 = 50;
+/// The default number of tries you get to roll doubles in jail, the last of which you pay on if you fail.
 pub const JAIL_TRIES: u8 = 3;
+/// The default number of states that a field lookup can walk through before
+/// reaching a state that stores every field. See `Game::with_materialize_interval()`.
+pub const MATERIALIZE_INTERVAL: usize = 32;
+/// The amounts of cash that can be added to a property trade. A positive
+/// amount is paid by the proposer, and a negative amount by the receiver.
+pub const TRADE_CASH_OFFERS: [i32; 3] = [-100, 0, 100];
+/// How many more properties than the fewest that cover a debt a player can be offered to
+/// sell with `GameConfig::pareto_sales`, which keeps the search for such sales from
+/// blowing up for players who own many properties.
+pub const PARETO_EXTRA_SALES: usize = 2;
 
 lazy_static! {
-    /// Positions of the chance card tiles on the game board.
-    pub static ref CC_POSITIONS: HashSet<u8> = HashSet::from([2, 4, 11, 20, 29, 32]);
-
-    /// Positions of the location tiles on the game board.
-    pub static ref LOC_POSITIONS: HashSet<u8> = HashSet::from([7, 16, 25, 34]);
-
-    /// Positions of the property tiles on the game board.
-    pub static ref PROP_POSITIONS: HashSet<u8> = HashSet::from([
-        1, 3, 5, 6, 8, 10, 12, 13, 14, 15, 17, 19, 21, 22, 23, 24, 26, 28, 30, 31, 33, 35,
-    ]);
-
-    /// Positions of the corners of the game board.
-    pub static ref CORNER_POSITIONS: HashSet<u8> = HashSet::from([0, 9, 18, 27]);
-
-    /// All the properties on the game board, in the form `HashMap<property_position, property>`.
-    pub static ref PROPERTIES: HashMap<u8, Property> = HashMap::from([
-        (1, Property::new(Color::Brown, 60, [70, 130, 220, 370, 750])),
-        (3, Property::new(Color::Brown, 60, [70, 130, 220, 370, 750])),
-        (5, Property::new(Color::LightBlue, 100, [80, 140, 240, 410, 800])),
-        (6, Property::new(Color::LightBlue, 100, [80, 140, 240, 410, 800])),
-        (8, Property::new(Color::LightBlue, 120, [100, 160, 260, 440, 860])),
-        (10, Property::new(Color::Pink, 140, [110, 180, 290, 460, 900])),
-        (12, Property::new(Color::Pink, 140, [110, 180, 290, 460, 900])),
-        (13, Property::new(Color::Pink, 160, [130, 200, 310, 490, 980])),
-        (14, Property::new(Color::Orange, 180, [140, 210, 330, 520, 1000])),
-        (15, Property::new(Color::Orange, 180, [140, 210, 330, 520, 1000])),
-        (17, Property::new(Color::Orange, 200, [160, 230, 350, 550, 1100])),
-        (19, Property::new(Color::Red, 220, [170, 250, 380, 580, 1160])),
-        (21, Property::new(Color::Red, 220, [170, 250, 380, 580, 1160])),
-        (22, Property::new(Color::Red, 240, [190, 270, 400, 610, 1200])),
-        (23, Property::new(Color::Yellow, 260, [200, 280, 420, 640, 1300])),
-        (24, Property::new(Color::Yellow, 260, [200, 280, 420, 640, 1300])),
-        (26, Property::new(Color::Yellow, 280, [220, 300, 440, 670, 1340])),
-        (28, Property::new(Color::Green, 300, [230, 320, 460, 700, 1400])),
-        (30, Property::new(Color::Green, 300, [230, 320, 460, 700, 1400])),
-        (31, Property::new(Color::Green, 320, [250, 340, 480, 730, 1440])),
-        (33, Property::new(Color::Blue, 350, [270, 360, 510, 740, 1500])),
-        (35, Property::new(Color::Blue, 400, [300, 400, 560, 810, 1600])),
-    ]);
-
-    /// Positions of the properties on the game board, sorted by their color set.
-    pub static ref PROPS_BY_COLOR: HashMap<Color,HashSet<u8>> = HashMap::from([
-        (Color::Brown, HashSet::from([1, 3])),
-        (Color::LightBlue, HashSet::from([5, 6, 8])),
-        (Color::Pink, HashSet::from([10, 12, 13])),
-        (Color::Orange, HashSet::from([14, 15, 17])),
-        (Color::Red, HashSet::from([19, 21, 22])),
-        (Color::Yellow, HashSet::from([23, 24, 26])),
-        (Color::Green, HashSet::from([28, 30, 31])),
-        (Color::Blue, HashSet::from([33, 35])),
-    ]);
-
-    /// Positions of the properties on the game board, sorted by the side of the board they're on.
-    pub static ref PROPS_BY_SIDE: [HashSet<u8>; 4] = [
-        HashSet::from([1, 3, 5, 6, 8]),
-        HashSet::from([10, 12, 13, 14, 15, 17]),
-        HashSet::from([19, 21, 22, 23, 24, 26]),
-        HashSet::from([28, 30, 31, 33, 35])
-    ];
-
-    /// Neighbours of properties in the form
-    /// `HashMap<prop_pos, [anti_clockwise_neighbour_pos, clockwise_neighbour_pos]>`.
-    pub static ref PROPERTY_NEIGHBOURS: HashMap<u8, [u8; 2]> = HashMap::from([
-        (1, [35, 3]),
-        (3, [1, 5]),
-        (5, [3, 6]),
-        (6, [5, 8]),
-        (8, [6, 10]),
-        (10, [8, 12]),
-        (12, [10, 13]),
-        (13, [12, 14]),
-        (14, [13, 15]),
-        (15, [14, 17]),
-        (17, [15, 19]),
-        (19, [17, 21]),
-        (21, [19, 22]),
-        (22, [21, 23]),
-        (23, [22, 24]),
-        (24, [23, 26]),
-        (26, [24, 28]),
-        (28, [26, 30]),
-        (30, [28, 31]),
-        (31, [30, 33]),
-        (33, [31, 35]),
-        (35, [33, 1])
-    ]);
-
     /// A vector of all possible dice rolls.
     pub static ref SIGNIFICANT_ROLLS: Vec<DiceRoll> = {
         let mut sig_rolls = vec![];
@@ -512,6 +1048,23 @@ lazy_static! {
         .sum::<f64>();
 }
 
+thread_local! {
+    /// The random number generator that rolls the dice and makes
+    /// agents' random choices on this thread.
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Seed the random number generator of the current thread, so that the dice
+/// rolls and agents' random choices made on it are the same every time.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Call `f` with the random number generator of the current thread.
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
 /// From the set of {x ∈ Z | 0 ≤ x ≤ n }, return all the possible k-long combinations.
 /// Adapted from this stackoverflow answer (https://stackoverflow.com/a/8332722) written in Delphi.
 pub fn get_combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
@@ -0,0 +1,284 @@
+use super::agent::{play_out, Agent, Budget, RolloutPolicy, SearchStats};
+use super::logger::{LogEvent, LogLevel, Logger};
+use super::metrics::Metrics;
+use super::state_diff::BranchType;
+use super::value_function::{Rank, ValueFunction};
+use super::Game;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+// `std::time::Instant` panics on the web, so use the browser's clock instead
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// A node of an ISMCTS tree, which stands for an information set: the states that the
+/// searching player can't tell apart because they only differ in the order of the chance
+/// deck. Its children are keyed by `Game::observed_hash()`, so that the same node is reached
+/// whichever determinization of the game the search is playing through.
+#[derive(Default)]
+struct InfoSetNode {
+    children: HashMap<u64, InfoSetNode>,
+    /// The number of times this node was visited.
+    visits: u32,
+    /// The number of times this node could have been selected, which is the number of times
+    /// its parent was visited in a determinization that has it as a child.
+    availability: u32,
+    /// The total value of the node for each player.
+    total_values: Vec<f64>,
+}
+
+impl InfoSetNode {
+    /// Return the average value of this node for player `pindex`.
+    fn mean_value(&self, pindex: usize) -> f64 {
+        self.total_values[pindex] / self.visits as f64
+    }
+
+    /// Add a visit with `values` (one for each player) to this node's statistics.
+    fn update(&mut self, values: &[f64]) {
+        if self.total_values.is_empty() {
+            self.total_values = vec![0.; values.len()];
+        }
+
+        for (total, value) in self.total_values.iter_mut().zip(values) {
+            *total += value;
+        }
+        self.visits += 1;
+    }
+
+    /// Play one iteration of the search from the state at `handle` (which this node stands
+    /// for) in `game`, a determinization, and return the value it found for each player.
+    fn iterate(
+        &mut self,
+        game: &mut Game,
+        handle: usize,
+        agent: &IsmctsAgent,
+        depth: u32,
+        stats: &mut SearchStats,
+    ) -> Vec<f64> {
+        stats.max_depth = stats.max_depth.max(depth);
+
+        // Roll out from nodes that haven't been visited yet, and score game-over states
+        let values = if game.is_terminal(handle) || (self.visits == 0 && depth > 0) {
+            stats.rollouts += 1;
+            let end = play_out(game, handle, agent.rollout_policy);
+            agent.evaluate(game, end)
+        } else {
+            game.gen_children_save(handle);
+            let children = game.nodes[handle].children.clone();
+
+            let child_index = match game.nodes[children[0]].branch_type {
+                // Chance outcomes are sampled in this determinization
+                BranchType::Chance(_) => game.get_any_chance_child(handle),
+                _ => self.select(game, handle, agent.temperature, stats),
+            };
+
+            let child_handle = children[child_index];
+            let child = self
+                .children
+                .entry(game.observed_hash(child_handle))
+                .or_default();
+            child.iterate(game, child_handle, agent, depth + 1, stats)
+        };
+
+        self.update(&values);
+        values
+    }
+
+    /// Return the index of the child of the choice state at `handle` to visit next, which
+    /// is the one with the greatest UCB1 value for the acting player among the children
+    /// that are available in this determinization.
+    fn select(
+        &mut self,
+        game: &Game,
+        handle: usize,
+        temperature: f64,
+        stats: &mut SearchStats,
+    ) -> usize {
+        let pindex = game.get_acting_pindex(handle);
+        let mut best = (0, f64::NEG_INFINITY);
+
+        for (i, &child_handle) in game.nodes[handle].children.iter().enumerate() {
+            let child = self
+                .children
+                .entry(game.observed_hash(child_handle))
+                .or_insert_with(|| {
+                    stats.nodes_expanded += 1;
+                    InfoSetNode::default()
+                });
+            child.availability += 1;
+
+            let ucb1 = if child.visits == 0 {
+                f64::INFINITY
+            } else {
+                child.mean_value(pindex)
+                    + temperature * ((child.availability as f64).ln() / child.visits as f64).sqrt()
+            };
+
+            if ucb1 > best.1 {
+                best = (i, ucb1);
+            }
+        }
+
+        best.0
+    }
+}
+
+/// An Information Set MCTS (ISMCTS) agent, which doesn't know which chance cards have been
+/// drawn. Unlike `MctsAgent::with_determinization()`, which searches a separate tree for
+/// each determinization, it builds a single tree over information sets and samples a new
+/// determinization of the game (see `ChanceDeck::determinized()`) for every iteration.
+/// Every player's value is kept in the tree, so each choice is made for the player making it.
+pub struct IsmctsAgent {
+    /// How much the AI is allowed to "think" for each decision.
+    budget: Budget,
+    /// Value of `C` constant in UCB1 formula.
+    temperature: f64,
+    /// How moves are chosen during rollouts.
+    rollout_policy: RolloutPolicy,
+    /// How the game state at the end of a rollout is scored for each player.
+    value_function: Box<dyn ValueFunction>,
+    /// Index of this agent in `Game.agents`.
+    index: usize,
+    /// Statistics about the search for the last decision.
+    search_stats: SearchStats,
+    /// Where `search_stats` is logged after every decision.
+    logger: Logger,
+    /// Where the work done by every search is added up, if anywhere.
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl IsmctsAgent {
+    /// Return a new ISMCTS agent that thinks for `time_limit` milliseconds per decision.
+    /// States are scored with `Rank`, which is on a scale of -1 to 1, so `temperature`
+    /// should be about 1.
+    pub fn new(time_limit: u64, temperature: f64, index: usize) -> IsmctsAgent {
+        IsmctsAgent {
+            budget: Budget::Time(Duration::from_millis(time_limit)),
+            temperature,
+            rollout_policy: RolloutPolicy::Random,
+            value_function: Box::new(Rank),
+            index,
+            search_stats: SearchStats::default(),
+            logger: Logger::default(),
+            metrics: None,
+        }
+    }
+
+    /// Search with `budget` for each decision instead of a time limit.
+    pub fn with_budget(mut self, budget: Budget) -> IsmctsAgent {
+        self.budget = budget;
+        self
+    }
+
+    /// Choose moves during rollouts with `rollout_policy` instead of randomly.
+    pub fn with_rollout_policy(mut self, rollout_policy: RolloutPolicy) -> IsmctsAgent {
+        self.rollout_policy = rollout_policy;
+        self
+    }
+
+    /// Score the end of rollouts with `value_function` instead of `Rank`.
+    pub fn with_value_function(mut self, value_function: Box<dyn ValueFunction>) -> IsmctsAgent {
+        self.value_function = value_function;
+        self
+    }
+
+    /// Log with `logger`, which logs the search statistics after
+    /// every decision if it's set to `LogLevel::Search`.
+    pub fn with_logger(mut self, logger: Logger) -> IsmctsAgent {
+        self.logger = logger;
+        self
+    }
+
+    /// Add the work done by every search to `metrics`, which can be shared with other agents.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> IsmctsAgent {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Return statistics about the search this agent performed for its last decision.
+    pub fn search_stats(&self) -> &SearchStats {
+        &self.search_stats
+    }
+
+    /// Return the value of the state at `handle` for every player.
+    fn evaluate(&self, game: &Game, handle: usize) -> Vec<f64> {
+        let players = game.diff_players(handle);
+        let props = game.diff_owned_properties(handle);
+
+        (0..players.len())
+            .map(|pindex| {
+                self.value_function
+                    .evaluate(players, props, &game.board, pindex)
+            })
+            .collect()
+    }
+}
+
+impl Agent for IsmctsAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
+        let start_time = Instant::now();
+        game.gen_children_save(game.root_handle);
+
+        let mut root = InfoSetNode::default();
+        let mut stats = SearchStats::default();
+        let mut iterations = 0;
+        let children = game.nodes[game.root_handle].children.len() as u32;
+
+        // Every child is visited once before any of them is visited again, so keep
+        // searching at least until they've all been visited
+        while !self.budget.is_spent(start_time, iterations) || iterations < children {
+            let mut determinization = game.determinized();
+            let handle = determinization.root_handle;
+            root.iterate(&mut determinization, handle, self, 0, &mut stats);
+            iterations += 1;
+
+            // The decision won't be used anyway
+            if game.is_cancelled() {
+                break;
+            }
+        }
+
+        // The root's children are the same in every determinization,
+        // so they can be matched up with the game's by their hashes
+        let children: Vec<(u32, f64)> = game.nodes[game.root_handle]
+            .children
+            .iter()
+            .map(
+                |&child| match root.children.get(&game.observed_hash(child)) {
+                    Some(node) if node.visits > 0 => (node.visits, node.mean_value(self.index)),
+                    _ => (0, f64::NAN),
+                },
+            )
+            .collect();
+
+        self.search_stats = SearchStats {
+            children,
+            game_id: game.id(),
+            game_nodes: game.node_count(),
+            game_memory: game.memory_usage(),
+            ..stats
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_search(&self.search_stats);
+        }
+        self.logger.event(
+            LogLevel::Search,
+            LogEvent::Search {
+                pindex: self.index,
+                stats: &self.search_stats,
+            },
+        );
+
+        // Choose the most visited child, which is more robust than the greatest average
+        // value, since children that are rarely available can have few, lucky visits
+        self.search_stats
+            .children
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (visits, _))| *visits)
+            .map(|(i, _)| i)
+            .unwrap()
+    }
+}
@@ -0,0 +1,190 @@
+use super::agent::SearchStats;
+use super::state_diff::DiffMessage;
+use super::GameResult;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+/// How much is logged, from least to most verbose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    /// Log nothing.
+    Silent,
+    /// Log the outcome of each game and any errors.
+    #[default]
+    Summary,
+    /// Also log every move made in a game.
+    Move,
+    /// Also log the statistics of every search an AI performs.
+    Search,
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "silent" => Ok(LogLevel::Silent),
+            "summary" => Ok(LogLevel::Summary),
+            "move" => Ok(LogLevel::Move),
+            "search" => Ok(LogLevel::Search),
+            _ => Err(format!("'{}' is not a log level", s)),
+        }
+    }
+}
+
+/// How log messages and the game state are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable text, highlighted with ANSI escape codes.
+    #[default]
+    Ansi,
+    /// Human-readable text without any escape codes, for files and terminals without VT support.
+    Plain,
+    /// One JSON object per line, for other programs to read.
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ansi" => Ok(OutputFormat::Ansi),
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("'{}' is not an output format", s)),
+        }
+    }
+}
+
+/// Something that happened during a game, which is logged as a line
+/// of text or as a JSON object tagged with its `event` name.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEvent<'a> {
+    /// A move was made.
+    Move {
+        /// The number of moves made before this one.
+        index: usize,
+        /// The player who made the move.
+        acting_pindex: usize,
+        message: &'a DiffMessage,
+        /// The probability of the move if it was a chance move.
+        probability: Option<f64>,
+    },
+    /// An MCTS agent finished searching for its next move.
+    Search {
+        pindex: usize,
+        stats: &'a SearchStats,
+    },
+    /// A player's agent took longer than `timeout` to decide, so a random move was played.
+    DecisionTimeout { pindex: usize, timeout: Duration },
+    /// The game ended.
+    GameOver { result: &'a GameResult },
+    /// Any other message, such as an error.
+    Message { text: String },
+}
+
+impl fmt::Display for LogEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogEvent::Move {
+                index,
+                message,
+                probability: Some(p),
+                ..
+            } => write!(f, "{:>5}: {} (p = {:.3})", index, message, p),
+            LogEvent::Move { index, message, .. } => write!(f, "{:>5}: {}", index, message),
+            // The search stats end with a newline, which `Logger` already adds
+            LogEvent::Search { stats, .. } => write!(f, "{}", stats.to_string().trim_end()),
+            LogEvent::DecisionTimeout { pindex, timeout } => write!(
+                f,
+                "Player {} took longer than {:?} to decide, so a random move was played",
+                pindex, timeout
+            ),
+            LogEvent::GameOver { result } => {
+                write!(
+                    f,
+                    "Player {} won after {} turns",
+                    result.winner, result.turns
+                )?;
+                if result.turn_limit_reached {
+                    write!(f, " (turn limit reached, won on net worth)")?;
+                } else if result.repetition_limit_reached {
+                    write!(f, " (state repeated too often, won on net worth)")?;
+                }
+                Ok(())
+            }
+            LogEvent::Message { text } => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Writes log messages to the terminal, depending on how verbose it's set to be.
+/// There's no terminal on the web, so nothing is ever logged there.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger {
+    level: LogLevel,
+    format: OutputFormat,
+}
+
+impl Logger {
+    /// Return a logger that logs messages up to `level`.
+    pub fn new(level: LogLevel) -> Self {
+        Logger {
+            level,
+            format: OutputFormat::default(),
+        }
+    }
+
+    /// Write messages in `format` instead of ANSI-highlighted text.
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Return the format that messages are written in.
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Return a logger that never logs anything.
+    pub fn silent() -> Self {
+        Logger::new(LogLevel::Silent)
+    }
+
+    /// Return whether messages at `level` are logged.
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level != LogLevel::Silent && level <= self.level
+    }
+
+    /// Log `message` if messages at `level` are logged.
+    pub fn log(&self, level: LogLevel, message: fmt::Arguments) {
+        if self.enabled(level) {
+            self.event(
+                level,
+                LogEvent::Message {
+                    text: message.to_string(),
+                },
+            );
+        }
+    }
+
+    /// Log `event` if messages at `level` are logged.
+    pub fn event(&self, level: LogLevel, event: LogEvent) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.enabled(level) {
+            match self.format {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string(&event).expect("failed to serialize log event")
+                ),
+                _ => println!("{}", event),
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        let _ = (level, event);
+    }
+}
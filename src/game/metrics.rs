@@ -0,0 +1,120 @@
+use super::agent::SearchStats;
+use super::GameResult;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Running totals of the work done by every game and search that shares them, across
+/// threads, so that long simulations can be monitored while they run (e.g. through
+/// `prometheus::serve()`). Share one with `MctsAgent::with_metrics()` and
+/// `Tournament::with_metrics()`. The size of the game tree is summed over every
+/// game in progress, which `Tournament` takes each game out of once it ends.
+///
+/// ```
+/// use monopoly_math::{Metrics, SearchStats};
+///
+/// let metrics = Metrics::new();
+/// metrics.record_search(&SearchStats {
+///     rollouts: 100,
+///     nodes_expanded: 40,
+///     game_id: 0,
+///     game_nodes: 5000,
+///     ..SearchStats::default()
+/// });
+/// metrics.record_search(&SearchStats {
+///     game_id: 1,
+///     game_nodes: 3000,
+///     ..SearchStats::default()
+/// });
+///
+/// let snapshot = metrics.snapshot();
+/// assert_eq!(snapshot.searches, 2);
+/// assert_eq!(snapshot.rollouts, 100);
+/// assert_eq!(snapshot.game_nodes, 8000);
+///
+/// metrics.end_game(1);
+/// assert_eq!(metrics.snapshot().game_nodes, 5000);
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    games: AtomicU64,
+    turns: AtomicU64,
+    searches: AtomicU64,
+    rollouts: AtomicU64,
+    nodes_expanded: AtomicU64,
+    /// The size of the game tree of every game in progress after its latest search,
+    /// in the form `HashMap<game_id, (nodes, bytes)>`.
+    game_trees: Mutex<HashMap<u64, (u64, u64)>>,
+}
+
+/// The totals of `Metrics` at one point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The number of games that have finished.
+    pub games: u64,
+    /// The number of turns played over every finished game.
+    pub turns: u64,
+    /// The number of decisions that were searched.
+    pub searches: u64,
+    /// The number of rollouts performed by every search.
+    pub rollouts: u64,
+    /// The number of MCTS nodes created by every search.
+    pub nodes_expanded: u64,
+    /// The number of game tree nodes stored by every game in progress,
+    /// as of the latest search of each.
+    pub game_nodes: u64,
+    /// The estimated memory used by the game trees of every game in progress,
+    /// as of the latest search of each, in bytes.
+    pub game_memory: u64,
+}
+
+impl Metrics {
+    /// Return metrics with every total at 0.
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Add the work done by a search for a decision.
+    pub fn record_search(&self, stats: &SearchStats) {
+        self.searches.fetch_add(1, Ordering::Relaxed);
+        self.rollouts
+            .fetch_add(stats.rollouts as u64, Ordering::Relaxed);
+        self.nodes_expanded
+            .fetch_add(stats.nodes_expanded as u64, Ordering::Relaxed);
+        self.game_trees.lock().unwrap().insert(
+            stats.game_id,
+            (stats.game_nodes as u64, stats.game_memory as u64),
+        );
+    }
+
+    /// Stop counting the game tree of the game with ID `game_id`, which has finished.
+    pub fn end_game(&self, game_id: u64) {
+        self.game_trees.lock().unwrap().remove(&game_id);
+    }
+
+    /// Add a game that has finished.
+    pub fn record_game(&self, result: &GameResult) {
+        self.games.fetch_add(1, Ordering::Relaxed);
+        self.turns.fetch_add(result.turns as u64, Ordering::Relaxed);
+    }
+
+    /// Return the current totals.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let (game_nodes, game_memory) = self
+            .game_trees
+            .lock()
+            .unwrap()
+            .values()
+            .fold((0, 0), |(nodes, bytes), &(n, b)| (nodes + n, bytes + b));
+
+        MetricsSnapshot {
+            games: self.games.load(Ordering::Relaxed),
+            turns: self.turns.load(Ordering::Relaxed),
+            searches: self.searches.load(Ordering::Relaxed),
+            rollouts: self.rollouts.load(Ordering::Relaxed),
+            nodes_expanded: self.nodes_expanded.load(Ordering::Relaxed),
+            game_nodes,
+            game_memory,
+        }
+    }
+}
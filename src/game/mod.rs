@@ -1,30 +1,257 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
 use std::iter::zip;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+mod arena;
+use arena::NodeArena;
+
+mod auction_model;
+pub use auction_model::{AggressiveBids, AuctionModel, BellCurveBids, UniformBids};
+
+mod board;
+pub use board::Board;
+
+mod chance_deck;
+pub use chance_deck::ChanceDeck;
+
+mod classic;
+pub use classic::ClassicCard;
 
 mod globals;
 use globals::*;
+pub use globals::{
+    seed_rng, Account, AuctionOutcome, ChanceCard, Color, GameConfig, GameplayStats, Player,
+    Property, RuleSet, Transfer, JAIL_TRIES, PROPERTY_TAX, TOTAL_CHANCE_CARDS,
+};
 
 mod agent;
-pub use agent::Agent;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use agent::render_board;
+#[cfg(not(target_arch = "wasm32"))]
+pub use agent::HumanAgent;
+pub use agent::{
+    Agent, Budget, Decision, Evaluator, ExpectimaxAgent, HeuristicAgent, MctsAgent, MoveAnalysis,
+    RandomAgent, RolloutPolicy, SearchStats,
+};
+
+mod encoding;
+
+mod endgame;
+pub use endgame::{EndgameSolution, EndgameSolver};
+
+mod ismcts;
+pub use ismcts::IsmctsAgent;
+
+mod observer;
+pub use observer::GameObserver;
+use observer::{GameEvent, Observers};
+
+mod opening_book;
+pub use opening_book::{BookEntry, OpeningBook};
+
+mod crash;
+pub use crash::CrashReport;
+
+mod error;
+use error::catch_internal;
+pub use error::GameError;
+
+mod metrics;
+pub use metrics::{Metrics, MetricsSnapshot};
+
+mod opponent_model;
+pub use opponent_model::{MoveFrequencies, OpponentModel};
+
+mod rollout;
+pub use rollout::{BatchRollout, BatchValueFunction, RolloutOutcome};
+
+mod scenario;
+pub use scenario::ScenarioBuilder;
+
+mod seat;
+use seat::Seat;
+
+mod shared_cache;
+pub use shared_cache::{SharedCache, ValueEstimate};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod subprocess;
+#[cfg(not(target_arch = "wasm32"))]
+pub use subprocess::{AgentReply, EngineRequest, SubprocessAgent};
+
+mod transcript;
+pub use transcript::{Transcript, TranscriptEntry};
+
+#[cfg(test)]
+mod tests;
+
+mod valuation;
+pub use valuation::Valuation;
+
+mod zobrist;
+
+mod value_function;
+pub use value_function::{
+    BalanceTimesPropertyWorth, FactorBreakdown, FactoredEvaluation, NetWorthDifferential, Rank,
+    ValueFunction, WinLoss, FACTOR_NAMES, NUM_FACTORS,
+};
+
+mod logger;
+pub use logger::{LogEvent, LogLevel, Logger, OutputFormat};
 
 mod state_diff;
-use state_diff::{BranchType, DiffMessage, FieldDiff, MoveType, PropertyOwnership, StateDiff};
+pub use state_diff::{
+    AuctionProgress, BranchType, DiffMessage, FieldDiff, MoveType, PropertyOwnership, TradeOffer,
+};
+use state_diff::{DiffID, StateDiff};
+
+mod state_view;
+pub use state_view::{MoveSummary, Observation, ResolvedState, StateView};
+
+mod turn_flow;
+use turn_flow::TurnFlow;
+
+mod validation;
+pub use validation::{ValidationError, ValidationErrorKind};
+
+/// The outcome of a finished game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameResult {
+    /// The index of the last player standing, or the richest player if the turn limit was reached.
+    pub winner: usize,
+    /// The indexes of the players who went bankrupt, in the order they were eliminated.
+    /// If the turn limit was reached, these are followed by the other players who didn't
+    /// win, from the lowest net worth to the highest.
+    pub losers: Vec<usize>,
+    /// The number of turns that were played before the game ended.
+    pub turns: usize,
+    /// Whether the game was ended by the turn limit instead of by bankruptcies.
+    #[serde(default)]
+    pub turn_limit_reached: bool,
+    /// Whether the game ended because it was in the same state too many times, in which
+    /// case the winner is the richest player rather than the last player standing.
+    #[serde(default)]
+    pub repetition_limit_reached: bool,
+    /// Whether any player was made to resign because they had clearly lost
+    /// (see `Game::with_adjudication()`).
+    #[serde(default)]
+    pub adjudicated: bool,
+    /// The balance of every player when the game ended.
+    #[serde(default)]
+    pub final_balances: Vec<i32>,
+    /// The statistics collected over the course of the game.
+    pub stats: GameplayStats,
+    /// The problems found with the game tree, if the game was played with validation.
+    #[serde(default)]
+    pub validation_errors: Vec<ValidationError>,
+}
 
 /// A simulation of Monopoly.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     root_turn: usize,
     /// The moves taken by players in terms of the indexes of the children.
     move_history: Vec<usize>,
     /// The current game state, as well as all its decendants.
-    nodes: Vec<StateDiff>,
-    /// Indexes of states that have been marked for deletion.
-    /// These states can be safely replaced by newer states.
-    dirty_handles: Vec<usize>,
+    nodes: NodeArena,
     /// The index of the state the game is currently at.
     root_handle: usize,
     /// The data collected during the simulation.
     gameplay_stats: GameplayStats,
+    /// The optional rules that this game is played with.
+    config: GameConfig,
+    /// The layout of the board that this game is played on.
+    board: Board,
+    /// A record of every move made in this game so far.
+    transcript: Transcript,
+    /// The values of the fields that every move overwrote, in the same
+    /// order as `move_history`, so that moves can be undone.
+    #[serde(default)]
+    undo_log: Vec<Vec<FieldDiff>>,
+    /// The Zobrist hash of every state that the game has been in since the start,
+    /// in the same order as `move_history`.
+    #[serde(default)]
+    root_hashes: Vec<u64>,
+    /// The number of times that the game has been in each state, keyed by its Zobrist hash.
+    #[serde(default)]
+    state_counts: HashMap<u64, usize>,
+    /// Where the moves and outcome of this game are logged.
+    #[serde(skip)]
+    logger: Logger,
+    /// How auctions are resolved when they aren't played out with real bids.
+    #[serde(skip, default = "default_auction_model")]
+    auction_model: Arc<dyn AuctionModel>,
+    /// Whether the children of every state are checked as they're generated.
+    #[serde(skip)]
+    validating: bool,
+    /// The problems found by validation so far.
+    #[serde(skip)]
+    validation_errors: Vec<ValidationError>,
+    /// How many states can separate a state from the nearest one that stores every field.
+    #[serde(skip, default = "default_materialize_interval")]
+    materialize_interval: usize,
+    /// The most nodes the game tree should hold before MCTS searches evict the parts of
+    /// it that they visited least recently, or `None` if it can grow without bound.
+    #[serde(skip)]
+    node_limit: Option<usize>,
+    /// The observers that are notified of every move made in this game.
+    #[serde(skip)]
+    observers: Observers,
+    /// The longest that each player's agent can take to make a decision, keyed by player
+    /// index. Players without a timeout can take as long as they like.
+    #[serde(skip)]
+    decision_timeouts: HashMap<usize, Duration>,
+    /// The estimated chance of winning below which players are made to resign,
+    /// if they ever are. See `with_adjudication()`.
+    #[serde(skip)]
+    adjudication_threshold: Option<f64>,
+    /// Whether any player has been made to resign by adjudication.
+    #[serde(default)]
+    adjudicated: bool,
+    /// The valuation of the board, which is only worked out once it's needed.
+    #[serde(skip)]
+    valuation: OnceLock<Valuation>,
+    /// The seed that the random number generator was started with, if it was.
+    #[serde(default)]
+    seed: Option<u64>,
+    /// Where a `CrashReport` is saved if the game runs into a bug, if anywhere.
+    #[serde(skip)]
+    crash_dir: Option<PathBuf>,
+    /// Set once the agent deciding on this copy of a game has run out of time.
+    #[serde(skip)]
+    cancelled: Option<Arc<AtomicBool>>,
+    /// The number that identifies this game (see `Game::id()`).
+    #[serde(skip, default = "next_game_id")]
+    id: u64,
+}
+
+/// The ID of the next game to be made, so that every game of the process gets its own.
+static NEXT_GAME_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Return an ID that no other game of the process has.
+fn next_game_id() -> u64 {
+    NEXT_GAME_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Return the auction model that games use unless they're given another one.
+fn default_auction_model() -> Arc<dyn AuctionModel> {
+    Arc::new(BellCurveBids)
+}
+
+/// Return the materialize interval that games use unless they're given another one.
+fn default_materialize_interval() -> usize {
+    MATERIALIZE_INTERVAL
 }
 
 impl Game {
@@ -32,75 +259,1002 @@ impl Game {
 
     /// Return a new game.
     pub fn new(player_count: usize) -> Self {
+        Game::with_config(player_count, GameConfig::default())
+    }
+
+    /// Return a new game played with the specified optional rules.
+    pub fn with_config(player_count: usize, config: GameConfig) -> Self {
+        Game::new_with_board(player_count, config.rules.board(), config)
+    }
+
+    /// Return a new game played on a custom board with the specified optional rules.
+    pub fn new_with_board(player_count: usize, board: Board, config: GameConfig) -> Self {
         Self {
             root_turn: 0,
             move_history: vec![],
-            nodes: vec![StateDiff::new_root(player_count)],
-            dirty_handles: vec![],
+            nodes: NodeArena::with_root(StateDiff::new_root(player_count, config.starting_balance)),
             root_handle: 0,
             gameplay_stats: GameplayStats::new(player_count),
+            transcript: Transcript::new(player_count, config, board.clone()),
+            undo_log: vec![],
+            root_hashes: vec![],
+            state_counts: HashMap::new(),
+            config,
+            board,
+            logger: Logger::default(),
+            auction_model: default_auction_model(),
+            validating: false,
+            validation_errors: vec![],
+            materialize_interval: MATERIALIZE_INTERVAL,
+            node_limit: None,
+            observers: Observers::default(),
+            decision_timeouts: HashMap::new(),
+            adjudication_threshold: None,
+            adjudicated: false,
+            valuation: OnceLock::new(),
+            seed: None,
+            crash_dir: None,
+            cancelled: None,
+            id: next_game_id(),
         }
     }
 
-    /// Play the game until it ends, and save the gameplay statistics to a CSV file.
-    pub fn play(mut agents: Vec<Agent>) {
-        let mut game = Game::new(agents.len());
+    /// Resolve auctions with `auction_model` instead of the default `BellCurveBids`.
+    /// This has no effect on games played with real auctions.
+    pub fn with_auction_model(mut self, auction_model: Arc<dyn AuctionModel>) -> Self {
+        self.auction_model = auction_model;
+        self
+    }
 
-        while !game.is_terminal(game.root_handle) {
-            // Generate the root node's direct children
-            game.gen_children_save(game.root_handle);
+    /// Seed the random number generator of the current thread (see `seed_rng()`), which the
+    /// game should be played on, and remember the seed in case the game crashes.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        seed_rng(seed);
+        self.seed = Some(seed);
+        self
+    }
 
-            let first_child = game.nodes[game.root_handle].children[0];
-            let next_branch_type = game.nodes[first_child].branch_type;
-            let curr_pindex = game.diff_current_pindex(game.root_handle);
+    /// Save a `CrashReport` to a new file in `dir` if the game runs into a bug (i.e. it
+    /// ends with `GameError::Internal` or `GameError::AgentPanicked`) while it's played.
+    pub fn with_crash_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.crash_dir = Some(dir.into());
+        self
+    }
+
+    /// Log the game with `logger` instead of the default summary-level logger.
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = logger;
+        self
+    }
 
-            // Randomly select a child if it's a chance node, or get
-            // the current player to choose one if it's the choice node.
-            let next_node = match next_branch_type {
-                BranchType::Chance(_) => game.get_any_chance_child(game.root_handle),
-                BranchType::Choice => agents[curr_pindex].make_choice(&mut game),
-                BranchType::Undefined => panic!("undefined branch type while playing game"),
+    /// Store every field of a state once it's `interval` states away from the last state
+    /// that did, instead of only the fields that changed. This bounds how many states
+    /// have to be walked through to look up a field (which matters for deep searches),
+    /// at the cost of memory. A smaller interval means faster lookups and more memory.
+    pub fn with_materialize_interval(mut self, interval: usize) -> Self {
+        self.materialize_interval = interval.max(1);
+        self
+    }
+
+    /// Keep the game tree to about `limit` nodes during MCTS searches. Once it grows past
+    /// that, the searches free the parts of the tree below the states they visited least
+    /// recently, which are generated again if they're needed later. This bounds the memory
+    /// used by long searches, at the cost of some of the search being thrown away.
+    pub fn with_node_limit(mut self, limit: usize) -> Self {
+        self.node_limit = Some(limit);
+        self
+    }
+
+    /// Check the children of every state as they're generated (e.g. that their chances add
+    /// up to 1), recording any problems in `Game::validation_errors()`. This slows the game
+    /// down, so it's meant for debugging the rules.
+    pub fn with_validation(mut self) -> Self {
+        self.validating = true;
+        self
+    }
+
+    /// Only give player `pindex`'s agent `timeout` to make each decision, so that a slow
+    /// or stuck agent can't stall the game. The agent decides on its own thread with a copy
+    /// of the game (so it can't rely on the game tree it generated being kept), and if it
+    /// runs out of time, a random move is played for it instead. It also plays randomly
+    /// for as long as it's still busy with the decision it ran out of time for, which
+    /// it's asked to give up on (see `Game::is_cancelled()`).
+    pub fn with_decision_timeout(mut self, pindex: usize, timeout: Duration) -> Self {
+        self.decision_timeouts.insert(pindex, timeout);
+        self
+    }
+
+    /// Make players resign (see `Game::resign()`) as soon as their estimated chance of
+    /// winning (see `Game::win_probabilities()`) drops below `threshold`, since there's
+    /// little point in playing out a game that's clearly lost. This ends most games long
+    /// before the last bankruptcy, which makes playing many of them (e.g. to generate
+    /// training data) much faster.
+    pub fn with_adjudication(mut self, threshold: f64) -> Self {
+        self.adjudication_threshold = Some(threshold);
+        self
+    }
+
+    /// Return the problems found so far by a game played with validation.
+    pub fn validation_errors(&self) -> &[ValidationError] {
+        &self.validation_errors
+    }
+
+    /// Check the children of the current state, whether or not the game is played with validation.
+    pub fn validate(&mut self) -> Result<(), Vec<ValidationError>> {
+        if self.is_terminal(self.root_handle) {
+            return Ok(());
+        }
+
+        self.gen_children_save(self.root_handle);
+        let errors = self.validate_children(self.root_handle);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Notify `observer` of every move made in this game from now on.
+    pub fn subscribe(&mut self, observer: Box<dyn GameObserver>) {
+        self.observers.0.push(observer);
+    }
+
+    /// Return whether the agent deciding on this game has run out of time (see
+    /// `Game::with_decision_timeout()`), in which case its decision won't be used, so it
+    /// should stop searching and return as soon as it can. The searches of this crate's
+    /// agents check this, but an agent that doesn't holds on to its thread until it's done.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+            .as_ref()
+            .is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Return the number that identifies this game among the games of the process.
+    /// Copies of the game made for agents to search (e.g. with `Clone`) keep its ID.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Return the logger that this game is logged with.
+    pub fn logger(&self) -> Logger {
+        self.logger
+    }
+
+    /// Play the game until it ends, and return its outcome.
+    pub fn play(agents: Vec<Box<dyn Agent>>) -> Result<GameResult, GameError> {
+        Game::new(agents.len()).resume(agents)
+    }
+
+    /// Return the statistics collected over the course of the game so far.
+    pub fn gameplay_stats(&self) -> &GameplayStats {
+        &self.gameplay_stats
+    }
+
+    /// Return a cheap estimate of each player's chance of winning from the current
+    /// state, which is their share of the total net worth (cash plus property prices).
+    pub fn win_probabilities(&self) -> Vec<f64> {
+        self.get_win_probabilities(self.root_handle)
+    }
+
+    /// Make player `pindex` resign. They're eliminated from the game like a player who went
+    /// bankrupt to the bank, so their properties go back to the bank. If they were in the
+    /// middle of their turn, or of a decision in someone else's (e.g. a bid or an answer to
+    /// a trade offer), that turn ends too. Players who are already out can't resign.
+    pub fn resign(&mut self, pindex: usize) -> Result<(), GameError> {
+        let players = self.player_count();
+        if pindex >= players {
+            return Err(GameError::NoSuchPlayer { pindex, players });
+        }
+
+        self.resign_player(pindex);
+        Ok(())
+    }
+
+    /// Make player `pindex` resign, like `Game::resign()`, given that they're in the game.
+    fn resign_player(&mut self, pindex: usize) {
+        if self.is_terminal(self.root_handle)
+            || self.diff_players(self.root_handle)[pindex].bankrupt
+        {
+            return;
+        }
+
+        let mut resignation =
+            self.gen_bankruptcy_child(self.root_handle, pindex, None, &MoveType::Roll);
+        resignation.branch_type = BranchType::Choice;
+        resignation.message = DiffMessage::Resign(pindex);
+        self.end_move(self.root_handle, &mut resignation);
+        self.append_state(resignation);
+
+        let index = self.nodes[self.root_handle].children.len() - 1;
+        self.advance_and_compact(index);
+    }
+
+    /// Return how often each tile of the board is landed on, and how much rent
+    /// its properties can be expected to collect, under the rules of this game.
+    pub fn valuation(&self) -> &Valuation {
+        self.valuation
+            .get_or_init(|| Valuation::new(&self.board, &self.config))
+    }
+
+    /// Return the number of players in the game, including bankrupt ones.
+    pub fn player_count(&self) -> usize {
+        self.diff_players(self.root_handle).len()
+    }
+
+    /// Return the index of the player whose turn it currently is.
+    pub fn current_pindex(&self) -> usize {
+        self.diff_current_pindex(self.root_handle)
+    }
+
+    /// Return the number of turns that have been played so far.
+    pub fn turn(&self) -> usize {
+        self.root_turn
+    }
+
+    /// Return every player in the game as they currently are, including bankrupt ones.
+    pub fn players(&self) -> &[Player] {
+        self.diff_players(self.root_handle)
+    }
+
+    /// Return the properties that are currently owned, in the form
+    /// `HashMap<property_position, ownership>`.
+    pub fn owned_properties(&self) -> &HashMap<u8, PropertyOwnership> {
+        self.diff_owned_properties(self.root_handle)
+    }
+
+    /// Return the optional rules that this game is played with.
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Return the board that this game is played on.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Return the number of game tree nodes currently stored.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Return an estimate of the number of bytes used by the game tree.
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.memory_usage()
+    }
+
+    /// Return the part of the game tree that has been generated so far, down to `max_depth`
+    /// levels below the current state, with one state per line. Each line shows the move
+    /// that led to the state, its probability if it was down to chance, and the players.
+    /// This is mainly useful for debugging how states are generated.
+    pub fn format_tree(&self, max_depth: usize) -> String {
+        let mut lines = vec![];
+        // Walk the tree depth-first with an explicit stack, like `NodeArena::free_subtree()`
+        let mut stack = vec![(self.root_handle, 0)];
+
+        while let Some((handle, depth)) = stack.pop() {
+            let state = &self.nodes[handle];
+            let probability = match state.branch_type {
+                BranchType::Chance(p) => format!(" (p = {:.3})", p),
+                _ => String::new(),
             };
+            let players: Vec<String> = self
+                .diff_players(handle)
+                .iter()
+                .map(|p| format!("{:#}", p))
+                .collect();
+
+            lines.push(format!(
+                "{}{}{}: {}",
+                "  ".repeat(depth),
+                state.message,
+                probability,
+                players.join(", ")
+            ));
+
+            if depth < max_depth {
+                stack.extend(state.children.iter().rev().map(|&c| (c, depth + 1)));
+            }
+        }
 
-            // Set this chosen child node as the new root node
-            game.advance_root_node(next_node);
+        lines.join("\n")
+    }
+
+    /// Return a view of the current game state.
+    pub fn current_state(&self) -> StateView<'_> {
+        StateView::new(self, self.root_handle)
+    }
+
+    /// Return views of the states that can be reached from the current state,
+    /// in the order of the indexes that `Agent::make_choice()` chooses from.
+    pub fn child_states(&mut self) -> Vec<StateView<'_>> {
+        self.gen_children_save(self.root_handle);
+
+        let game: &Self = self;
+        game.nodes[game.root_handle]
+            .children
+            .iter()
+            .map(|&child| StateView::new(game, child))
+            .collect()
+    }
+
+    /// Release the memory held by game tree nodes that have been freed.
+    /// This renumbers the nodes, but doesn't affect agents' search trees
+    /// since they refer to nodes by their position among their siblings.
+    pub fn compact_nodes(&mut self) {
+        self.root_handle = self.nodes.compact(self.root_handle);
+    }
+
+    /// Continue playing the game from its current state until it ends. This is
+    /// mainly useful for a game that was restored with `Game::load()`.
+    /// The gameplay statistics are returned in the result, and can be saved with
+    /// `GameplayStats::save_to_csv()`. An error is returned instead if there isn't an
+    /// agent for every player, an agent chooses an illegal move or panics, or the
+    /// engine runs into a bug, any of which end the game early.
+    pub fn resume(mut self, agents: Vec<Box<dyn Agent>>) -> Result<GameResult, GameError> {
+        self.play_on(agents)
+    }
+
+    /// Continue playing the game like `Game::resume()`, but keep the game afterwards,
+    /// so that it can still be looked at once it's over (e.g. its transcript).
+    pub fn play_on(&mut self, agents: Vec<Box<dyn Agent>>) -> Result<GameResult, GameError> {
+        let players = self.player_count();
+        if agents.len() != players {
+            return Err(GameError::AgentCount {
+                players,
+                agents: agents.len(),
+            });
         }
 
-        // Save the gameplay statistics to a CSV file
-        game.gameplay_stats
-            .save_to_csv(game.get_loser(game.root_handle));
+        let mut seats: Vec<Seat> = agents.into_iter().map(Seat::new).collect();
+        let result = catch_internal(|| self.play_out(&mut seats)).and_then(|result| result);
+        if let Err(error @ (GameError::Internal(_) | GameError::AgentPanicked { .. })) = &result {
+            self.save_crash_report(error);
+        }
+
+        result
     }
 
-    /*********        HELPERS        *********/
+    /// Save a `CrashReport` of the game ending with `error` to the crash directory, if there
+    /// is one, logging where it was saved. The report is the only trace of a crash during a
+    /// long run, so failing to save it is logged too, rather than replacing the error.
+    fn save_crash_report(&self, error: &GameError) {
+        let Some(dir) = &self.crash_dir else {
+            return;
+        };
 
-    /// Push the new state node to `self.state_nodes` and return its handle.
-    fn append_state(&mut self, state: StateDiff) -> usize {
-        let i;
-        let parent = state.parent;
+        let report = CrashReport {
+            error: error.clone(),
+            seed: self.seed,
+            transcript: self.transcript.clone(),
+            // The state may be too broken to resolve without panicking again
+            state: catch_internal(|| self.resolved_state()).ok(),
+        };
+        let text = match report.save_to_dir(dir) {
+            Ok(path) => format!("Saved a crash report to {}", path.display()),
+            Err(e) => format!("Failed to save a crash report to {}: {}", dir.display(), e),
+        };
+        self.logger
+            .event(LogLevel::Summary, LogEvent::Message { text });
+    }
+
+    /// Play the game until it ends with the agents in `seats`, and return its outcome.
+    fn play_out(&mut self, seats: &mut [Seat]) -> Result<GameResult, GameError> {
+        loop {
+            self.resolve_chance_moves()?;
+            if self.is_terminal(self.root_handle) {
+                break;
+            }
+
+            // Get the acting player to choose the next move, keeping track of
+            // how long it took and how much of the game tree it generated
+            let acting_pindex = self.get_acting_pindex(self.root_handle);
+            let start_time = Instant::now();
+            let start_nodes = self.node_count();
+            let timeout = self.decision_timeouts.get(&acting_pindex).copied();
+            let decision = match seats[acting_pindex].decide(self, acting_pindex, timeout)? {
+                Some(decision) => decision,
+                None => Decision::Move(self.time_out(acting_pindex, timeout.unwrap())),
+            };
+            self.gameplay_stats.update_decisions(
+                acting_pindex,
+                self.root_turn,
+                start_time.elapsed(),
+                self.node_count().saturating_sub(start_nodes),
+            );
+
+            match decision {
+                Decision::Move(next_node) => {
+                    self.check_move(next_node)?;
+                    self.advance_and_compact(next_node);
+                }
+                Decision::Resign => self.resign_player(acting_pindex),
+            }
+        }
+
+        self.gameplay_stats
+            .update_final_tree_size(self.node_count());
+
+        let result = self
+            .result()?
+            .ok_or_else(|| GameError::Internal("game ended without a result".to_string()))?;
+        self.logger
+            .event(LogLevel::Summary, LogEvent::GameOver { result: &result });
+
+        Ok(result)
+    }
+
+    /// Return an error unless `index` is one of the moves available at the current state.
+    fn check_move(&self, index: usize) -> Result<(), GameError> {
+        let moves = self.nodes[self.root_handle].children.len();
+        if index < moves {
+            Ok(())
+        } else {
+            Err(GameError::IllegalMove { index, moves })
+        }
+    }
+
+    /// Return the moves that the acting player can choose from at the current
+    /// state, or an empty vector if the game is over. Any chance moves (e.g. dice
+    /// rolls) leading up to the next choice are made first, which returns an error
+    /// if the engine runs into a bug.
+    pub fn legal_moves(&mut self) -> Result<Vec<MoveSummary>, GameError> {
+        self.resolve_chance_moves()?;
+        self.gen_children_save(self.root_handle);
+        Ok(self.get_move_summaries(self.root_handle))
+    }
+
+    /// Search the position at `handle` (e.g. `StateView::handle()` of the current state or
+    /// one of its children) with an MCTS agent for `budget`, like a chess engine's analysis
+    /// mode, and return how the search rated each of the moves from it for the acting player.
+    /// Any chance moves leading up to the next choice are made first (on a copy of the game
+    /// unless `handle` is the current state, whose moves are then `Game::legal_moves()`),
+    /// and nothing is returned if the game is over at that point.
+    pub fn analyze(
+        &mut self,
+        handle: usize,
+        budget: Budget,
+    ) -> Result<Vec<MoveAnalysis>, GameError> {
+        if handle != self.root_handle {
+            let mut game = self.rooted_at(handle);
+            return game.analyze(game.root_handle, budget);
+        }
+
+        let moves = self.legal_moves()?;
+        if moves.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let pindex = self.get_acting_pindex(self.root_handle);
+        Ok(MctsAgent::new(0, 2., pindex)
+            .with_budget(budget)
+            .analyze(self, moves))
+    }
+
+    /// Return each of the moves in `Game::legal_moves()`, along with how much each factor
+    /// of `evaluation` contributes to the value of the state after it for the acting player.
+    /// This explains how the moves compare without searching, unlike `Game::analyze()`.
+    pub fn explain_moves(
+        &mut self,
+        evaluation: &FactoredEvaluation,
+    ) -> Result<Vec<(MoveSummary, FactorBreakdown)>, GameError> {
+        let moves = self.legal_moves()?;
+        if moves.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let pindex = self.get_acting_pindex(self.root_handle);
+        let children = &self.nodes[self.root_handle].children;
+        Ok(moves
+            .into_iter()
+            .zip(children)
+            .map(|(summary, &child)| {
+                let breakdown = evaluation.breakdown(
+                    self.diff_players(child),
+                    self.diff_owned_properties(child),
+                    &self.board,
+                    pindex,
+                );
+                (summary, breakdown)
+            })
+            .collect())
+    }
+
+    /// Make the move at `index` in `Game::legal_moves()`, followed by any chance
+    /// moves leading up to the next choice. Nothing is changed if `index` isn't a legal move.
+    pub fn apply_move(&mut self, index: usize) -> Result<(), GameError> {
+        self.resolve_chance_moves()?;
+        self.gen_children_save(self.root_handle);
+        self.check_move(index)?;
+
+        self.advance_and_compact(index);
+        self.resolve_chance_moves()
+    }
 
-        match self.dirty_handles.pop() {
-            Some(handle) => {
-                i = handle;
-                self.nodes[i] = state;
+    /// Move to the state at `index` in `Game::child_states()`, whether it's reached by a
+    /// choice or by chance (e.g. a particular dice roll), without making any chance moves
+    /// after it. Unlike `Game::apply_move()`, this steps through the game one state at a
+    /// time, e.g. to follow how a game got into a state it shouldn't be in.
+    pub fn step(&mut self, index: usize) -> Result<(), GameError> {
+        self.gen_children_save(self.root_handle);
+        self.check_move(index)?;
+        self.advance_and_compact(index);
+        Ok(())
+    }
+
+    /// Return a snapshot of the current game state.
+    pub fn current_observation(&self) -> Observation {
+        Observation::new(self.current_state(), self.root_turn)
+    }
+
+    /// Return every field of the current game state.
+    pub fn resolved_state(&self) -> ResolvedState {
+        self.resolve_state(self.root_handle)
+    }
+
+    /// Return the outcome of the game, or `None` if it isn't over yet. An error is returned
+    /// if the game ended in a state without a winner, which is down to a bug.
+    pub fn result(&self) -> Result<Option<GameResult>, GameError> {
+        if !self.is_terminal(self.root_handle) {
+            return Ok(None);
+        }
+
+        let winner = self.get_winner(self.root_handle)?;
+        let mut losers = self.gameplay_stats.bankrupt_players();
+        let players = self.diff_players(self.root_handle);
+        let net_worths = self.get_net_worths(self.root_handle);
+        let mut survivors: Vec<usize> = (0..players.len())
+            .filter(|&i| i != winner && !players[i].bankrupt)
+            .collect();
+        survivors.sort_by_key(|&i| net_worths[i]);
+        losers.extend(survivors);
+
+        Ok(Some(GameResult {
+            winner,
+            losers,
+            turns: self.root_turn,
+            turn_limit_reached: self.turn_limit_reached(),
+            repetition_limit_reached: self.repetition_limit_reached(),
+            adjudicated: self.adjudicated,
+            final_balances: players.iter().map(|p| p.balance).collect(),
+            stats: self.gameplay_stats.clone(),
+            validation_errors: self.validation_errors.clone(),
+        }))
+    }
+
+    /// Save the game (including its move history and gameplay statistics) to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Load a game that was previously saved with `Game::save()`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut game: Game = serde_json::from_reader(reader)?;
+        game.rehash();
+        Ok(game)
+    }
+
+    /// Rewind the game to the state it was in `n` moves ago (counting chance moves
+    /// such as dice rolls), and return the number of moves that were undone. This is
+    /// fewer than `n` if the game has had fewer moves, or if it was loaded from a save
+    /// that doesn't record how to undo them. The gameplay statistics aren't rewound,
+    /// and agents that have already played in the game should be replaced, since they
+    /// keep track of the moves they've seen.
+    pub fn undo(&mut self, n: usize) -> usize {
+        // Moves can only be undone as far back as every record of them goes
+        let n = n
+            .min(self.undo_log.len())
+            .min(self.root_hashes.len())
+            .min(self.transcript.entries.len());
+        if n == 0 {
+            return 0;
+        }
+
+        // Start from every field of the current state, and revert the fields that
+        // each undone move overwrote, from the latest move to the earliest
+        let mut state = StateDiff::new_with_parent(0);
+        for d in DiffID::all() {
+            state.set_diff(self.diff_field(self.root_handle, d).clone());
+        }
+
+        for _ in 0..n {
+            for diff in self.undo_log.pop().unwrap() {
+                state.set_diff(diff);
+            }
+
+            self.move_history.pop();
+            let hash = self.root_hashes.pop().unwrap();
+            if let Some(count) = self.state_counts.get_mut(&hash) {
+                *count -= 1;
+            }
+
+            let entry = self.transcript.entries.pop().unwrap();
+            if matches!(entry.next_move, MoveType::Roll) {
+                self.root_turn = self.root_turn.saturating_sub(1);
+            }
+        }
+
+        // The rest of the earlier state comes from the move that led to it,
+        // or from the start of the game if there was no such move
+        match self.transcript.entries.last() {
+            Some(entry) => {
+                state.branch_type = entry.branch_type;
+                state.message = entry.message.clone();
+                state.next_move = entry.next_move.clone();
             }
-            None => {
-                self.nodes.push(state);
-                i = self.nodes.len() - 1;
+            None => state.next_move = MoveType::Roll,
+        }
+
+        // Replace the whole game tree with the earlier state
+        state.hash = state.full_hash();
+        self.nodes.free_subtree(self.root_handle);
+        let handle = self.nodes.insert(state);
+        self.nodes[handle].parent = handle;
+        self.root_handle = handle;
+
+        if self.nodes.len() * 4 < self.nodes.slot_count() {
+            self.compact_nodes();
+        }
+
+        n
+    }
+
+    /// Return a record of every move made in this game so far.
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    /// Reconstruct a game by replaying every move in `transcript`. An error is returned
+    /// if a move can't be replayed (e.g. because the transcript was edited by hand).
+    pub fn replay(transcript: &Transcript) -> Result<Self, GameError> {
+        Game::replay_with(transcript, |_| {})
+    }
+
+    /// Reconstruct a game by replaying every move in `transcript`, calling `on_move` with
+    /// the game after each move (e.g. to look at every state that the game went through).
+    pub fn replay_with(
+        transcript: &Transcript,
+        mut on_move: impl FnMut(&Game),
+    ) -> Result<Self, GameError> {
+        let mut game = Game::new_with_board(
+            transcript.player_count,
+            transcript.board.clone(),
+            transcript.config,
+        );
+
+        for (index, entry) in transcript.entries.iter().enumerate() {
+            // Rebuild each recorded state from its diffs rather than regenerating the root's
+            // children, since those aren't always generated in the same order (or with the
+            // same diffs) as when the game was played
+            let mut state = StateDiff::new_with_parent(game.root_handle);
+            for diff in &entry.diffs {
+                state.set_diff(diff.clone());
             }
+            if !game.replayable(&state, entry) {
+                return Err(GameError::InvalidTranscript { index });
+            }
+            state.branch_type = entry.branch_type;
+            state.next_move = entry.next_move.clone();
+            state.message = entry.message.clone();
+            game.append_state(state);
+
+            game.advance_root_node(0);
+            game.transcript.entries.last_mut().unwrap().child_index = entry.child_index;
+            on_move(&game);
         }
 
+        Ok(game)
+    }
+
+    /// Return whether `state`, rebuilt from the diffs of `entry` as a child of the root, is
+    /// one that the game could have moved to (as far as it can be told without generating
+    /// the root's children).
+    fn replayable(&self, state: &StateDiff, entry: &TranscriptEntry) -> bool {
+        let players = match state.get_diff(DiffID::Players) {
+            Some(FieldDiff::Players(players)) => players,
+            _ => self.diff_players(self.root_handle),
+        };
+        let current_pindex = match state.get_diff(DiffID::CurrentPlayer) {
+            Some(&FieldDiff::CurrentPlayer(pindex)) => pindex,
+            _ => self.diff_current_pindex(self.root_handle),
+        };
+        let jail_rounds = match state.get_diff(DiffID::JailRounds) {
+            Some(FieldDiff::JailRounds(jail_rounds)) => jail_rounds,
+            _ => self.diff_jail_rounds(self.root_handle),
+        };
+
+        // Every field is recorded at most once, and in the order that it's stored in
+        state.present_diffs == entry.present_diffs
+            && state.diffs.len() == entry.diffs.len()
+            && !matches!(entry.branch_type, BranchType::Undefined)
+            && !matches!(entry.next_move, MoveType::Undefined)
+            && players.len() == self.diff_players(self.root_handle).len()
+            && jail_rounds.len() == players.len()
+            && current_pindex < players.len()
+            && players.iter().all(|p| p.position < self.board.size)
+    }
+
+    /// Generate (without saving) the states that can be reached by rolling the dice
+    /// from the current state, and return how many there are. Used by `benches/`.
+    #[doc(hidden)]
+    pub fn bench_roll_children(&self) -> usize {
+        self.gen_roll_children(self.root_handle).len()
+    }
+
+    /// Generate (without saving) the states that can be reached by drawing a chance
+    /// card from the current state, and return how many there are. Used by `benches/`.
+    #[doc(hidden)]
+    pub fn bench_cc_children(&self) -> usize {
+        self.gen_cc_children(self.root_handle).len()
+    }
+
+    /*********        HELPERS        *********/
+
+    /// Store the new state node in `self.nodes` and return its handle.
+    fn append_state(&mut self, mut state: StateDiff) -> usize {
+        let parent = state.parent;
+        state.chain_length = self.nodes[parent].chain_length + 1;
+        state.hash = self.child_hash(parent, &state);
+        let i = self.nodes.insert(state);
+
         // Update parent state's children vector
         self.nodes[parent].children.push(i);
 
+        if self.nodes[i].chain_length >= self.materialize_interval {
+            self.materialize(i);
+        }
+
         i
     }
 
+    /// Return the Zobrist hash of `state`, a child of the state at `parent`, by
+    /// swapping the keys of the fields that it changes into the parent's hash.
+    fn child_hash(&self, parent: usize, state: &StateDiff) -> u64 {
+        let parent_state = &self.nodes[parent];
+        let mut hash = parent_state.hash
+            ^ zobrist::move_hash(&parent_state.next_move)
+            ^ zobrist::move_hash(&state.next_move);
+
+        for diff in &state.diffs {
+            let old = self.diff_field(parent, diff.id());
+            hash ^= zobrist::field_hash(old) ^ zobrist::field_hash(diff);
+        }
+
+        hash
+    }
+
+    /// Return the Zobrist hash of the state at `handle` without its chance deck, which is
+    /// the same for states that only differ in their deck (e.g. the same state in different
+    /// determinizations of a game, which a search that doesn't know the deck can't tell apart).
+    fn observed_hash(&self, handle: usize) -> u64 {
+        self.nodes[handle].hash ^ zobrist::deck_hash(self.diff_chance_deck(handle))
+    }
+
+    /// Recompute the Zobrist hash of every state in the game tree, e.g. after the
+    /// game is loaded (since the hashes aren't saved) or after the root is edited.
+    fn rehash(&mut self) {
+        self.nodes[self.root_handle].hash = self.nodes[self.root_handle].full_hash();
+
+        let mut stack = vec![self.root_handle];
+        while let Some(handle) = stack.pop() {
+            for child in self.nodes[handle].children.clone() {
+                let hash = self.child_hash(handle, &self.nodes[child]);
+                self.nodes[child].hash = hash;
+                stack.push(child);
+            }
+        }
+    }
+
+    /// Make the state at `handle` store every field, rather than only the ones that changed.
+    fn materialize(&mut self, handle: usize) {
+        for d in DiffID::all() {
+            if !self.nodes[handle].diff_exists(d) {
+                let diff = self.diff_field(handle, d).clone();
+                self.nodes[handle].set_diff(diff);
+            }
+        }
+
+        self.nodes[handle].chain_length = 0;
+    }
+
+    /// Return a copy of this game to search, without the game tree below the current state,
+    /// in which the chance cards that have been drawn are sampled at random (see
+    /// `ChanceDeck::determinized()`), so that the search can't rely on knowing them.
+    fn determinized(&self) -> Game {
+        let mut game = self.rooted_at(self.root_handle);
+        let deck = self.diff_chance_deck(self.root_handle);
+        let root = &mut game.nodes[game.root_handle];
+
+        root.set_chance_deck(with_rng(|rng| deck.determinized(rng)));
+        if let (MoveType::ChanceCard, Some(card)) = (&root.next_move, deck.next_card()) {
+            // The player can see the card they're drawing, so it stays the same
+            if self.config.rules == RuleSet::UltimateBanking {
+                root.next_move = MoveType::ChoicefulCC(card);
+            }
+        }
+        root.hash = root.full_hash();
+
+        game
+    }
+
+    /// Return a copy of this game to search whose current state is the state at `handle`,
+    /// without the rest of the game tree.
+    fn rooted_at(&self, handle: usize) -> Game {
+        let old_root = &self.nodes[handle];
+        let mut root = StateDiff::new_with_parent(0);
+        for d in DiffID::all() {
+            root.set_diff(self.diff_field(handle, d).clone());
+        }
+        root.branch_type = old_root.branch_type;
+        root.next_move = old_root.next_move.clone();
+        root.message = old_root.message.clone();
+        root.hash = root.full_hash();
+
+        let player_count = self.player_count();
+        Game {
+            root_turn: self.root_turn,
+            move_history: self.move_history.clone(),
+            nodes: NodeArena::with_root(root),
+            root_handle: 0,
+            gameplay_stats: GameplayStats::new(player_count),
+            transcript: Transcript::new(player_count, self.config, self.board.clone()),
+            undo_log: vec![],
+            root_hashes: vec![],
+            state_counts: HashMap::new(),
+            config: self.config,
+            board: self.board.clone(),
+            logger: self.logger,
+            auction_model: self.auction_model.clone(),
+            validating: false,
+            validation_errors: vec![],
+            materialize_interval: self.materialize_interval,
+            node_limit: self.node_limit,
+            observers: Observers::default(),
+            decision_timeouts: HashMap::new(),
+            adjudication_threshold: None,
+            adjudicated: false,
+            valuation: self.valuation.clone(),
+            seed: self.seed,
+            crash_dir: None,
+            cancelled: self.cancelled.clone(),
+            id: self.id,
+        }
+    }
+
     /// Generate and append children.
     fn gen_children_save(&mut self, handle: usize) {
-        if self.nodes[handle].children.len() == 0 && !self.is_terminal(handle) {
+        if self.nodes[handle].children.is_empty() && !self.is_terminal(handle) {
             for child in self.gen_children(handle) {
                 self.append_state(child);
             }
+
+            if self.validating {
+                for error in self.validate_children(handle) {
+                    self.logger
+                        .log(LogLevel::Summary, format_args!("{}", error));
+                    self.validation_errors.push(error);
+                }
+            }
+        }
+    }
+
+    /// Free every descendant of the state at `handle`, so that its children are generated
+    /// again when they're next needed. Return the number of nodes that were freed.
+    fn free_descendants(&mut self, handle: usize) -> usize {
+        let node_count = self.nodes.len();
+        for child in std::mem::take(&mut self.nodes[handle].children) {
+            self.nodes.free_subtree(child);
+        }
+
+        node_count - self.nodes.len()
+    }
+
+    /// Report that player `pindex`'s agent didn't make its decision within `timeout`,
+    /// and return the index of a random child of the root to play for it instead.
+    fn time_out(&mut self, pindex: usize, timeout: Duration) -> usize {
+        self.logger.event(
+            LogLevel::Summary,
+            LogEvent::DecisionTimeout { pindex, timeout },
+        );
+
+        let mut observers = std::mem::take(&mut self.observers);
+        observers.notify(self, &[GameEvent::DecisionTimeout(pindex, timeout)]);
+        self.observers = observers;
+
+        let children = self.nodes[self.root_handle].children.len();
+        with_rng(|rng| rng.gen_range(0..children))
+    }
+
+    /// Make every player whose estimated chance of winning is below the adjudication
+    /// threshold resign, if there is one. Return whether anyone resigned.
+    fn adjudicate(&mut self) -> bool {
+        let Some(threshold) = self.adjudication_threshold else {
+            return false;
+        };
+
+        let players = self.diff_players(self.root_handle);
+        let lost: Vec<usize> = zip(players, self.win_probabilities())
+            .enumerate()
+            .filter(|(_, (player, p))| !player.bankrupt && *p < threshold)
+            .map(|(i, _)| i)
+            .collect();
+
+        for &pindex in &lost {
+            self.resign_player(pindex);
+        }
+
+        self.adjudicated |= !lost.is_empty();
+        !lost.is_empty()
+    }
+
+    /// Randomly make chance moves from the root state until a choice has to be made.
+    fn resolve_chance_moves(&mut self) -> Result<(), GameError> {
+        while !self.is_terminal(self.root_handle) {
+            self.gen_children_save(self.root_handle);
+
+            let Some(&first_child) = self.nodes[self.root_handle].children.first() else {
+                return Err(GameError::Internal(
+                    "non-terminal state without any moves".to_string(),
+                ));
+            };
+            match self.nodes[first_child].branch_type {
+                BranchType::Chance(_) => {
+                    let next_node = self.try_any_chance_child(self.root_handle)?;
+                    self.advance_and_compact(next_node);
+                }
+                // Players who have clearly lost resign before anyone has to choose
+                BranchType::Choice => {
+                    if !self.adjudicate() {
+                        return Ok(());
+                    }
+                }
+                BranchType::Undefined => {
+                    return Err(GameError::Internal(
+                        "undefined branch type while playing game".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Advance the root node, and give back memory once most of the game tree has been freed.
+    fn advance_and_compact(&mut self, child_index: usize) {
+        self.advance_root_node(child_index);
+
+        if self.nodes.len() * 4 < self.nodes.slot_count() {
+            self.compact_nodes();
+        }
+    }
+
+    /// Return how the auction at the root ended, in the form `(position, winner, winning bid)`,
+    /// if moving to `new_handle` ends one. There's no winner if nobody bid for the property.
+    fn ended_auction(&self, new_handle: usize) -> Option<(u8, Option<usize>, i32)> {
+        let new_state = &self.nodes[new_handle];
+        match &self.nodes[self.root_handle].next_move {
+            // Auctions that aren't played out are won by the player that the model picked, and
+            // are always of the property that the current player is on
+            MoveType::Auction => {
+                let pindex = self.diff_current_pindex(self.root_handle);
+                let pos = self.diff_players(self.root_handle)[pindex].position;
+                match new_state.message {
+                    DiffMessage::AfterAuction(winner, bid) => Some((pos, Some(winner), bid)),
+                    _ => Some((pos, None, 0)),
+                }
+            }
+            // Real auctions end once nobody is left to bid
+            MoveType::Bid(auction) if !matches!(new_state.next_move, MoveType::Bid(_)) => {
+                match new_state.message {
+                    DiffMessage::Bid { bidder, amount, .. } => {
+                        Some((auction.pos, Some(bidder), amount))
+                    }
+                    _ => Some((auction.pos, auction.high_bidder, auction.high_bid)),
+                }
+            }
+            _ => None,
         }
     }
 
@@ -112,19 +1266,57 @@ impl Game {
             .children
             .swap_remove(child_index);
 
+        let events = if self.observers.0.is_empty() {
+            vec![]
+        } else {
+            self.move_events(new_handle)
+        };
+
+        self.transcript.entries.push(TranscriptEntry {
+            child_index,
+            branch_type: self.nodes[new_handle].branch_type,
+            message: self.nodes[new_handle].message.clone(),
+            present_diffs: self.nodes[new_handle].present_diffs,
+            diffs: self.nodes[new_handle].diffs.to_vec(),
+            next_move: self.nodes[new_handle].next_move.clone(),
+        });
+
         let curr_pindex = self.diff_current_pindex(self.root_handle);
 
+        self.logger.event(
+            LogLevel::Move,
+            LogEvent::Move {
+                index: self.move_history.len(),
+                acting_pindex: self.get_acting_pindex(self.root_handle),
+                message: &self.nodes[new_handle].message,
+                probability: match self.nodes[new_handle].branch_type {
+                    BranchType::Chance(p) => Some(p),
+                    _ => None,
+                },
+            },
+        );
+
+        // Branching factor stats, counting the child that was just removed
+        self.gameplay_stats.update_branching(
+            self.root_turn,
+            self.nodes[self.root_handle].next_move.name(),
+            self.nodes[self.root_handle].children.len() + 1,
+        );
+
         // Update the gameplay stats
         match self.nodes[self.root_handle].next_move {
             // Log whether the property was auctioned
             MoveType::Property => {
                 let child_msg = &self.nodes[new_handle].message;
                 // child_msg could be something other than these
-                if matches!(child_msg, DiffMessage::BuyProp | DiffMessage::AuctionProp) {
+                if matches!(
+                    child_msg,
+                    DiffMessage::BuyProp { .. } | DiffMessage::AuctionProp(_)
+                ) {
                     self.gameplay_stats.update_auction_rate(
                         curr_pindex,
                         self.root_turn,
-                        matches!(child_msg, DiffMessage::AuctionProp),
+                        matches!(child_msg, DiffMessage::AuctionProp(_)),
                     );
                 }
             }
@@ -139,6 +1331,44 @@ impl Game {
             _ => (),
         }
 
+        // Rent stats
+        if let DiffMessage::LandOppProp { pos, owner, rent } = self.nodes[new_handle].message {
+            self.gameplay_stats
+                .update_rent_payments(self.root_turn, pos, curr_pindex, owner, rent);
+        }
+
+        // Auction stats, once an auction is over
+        if let Some((pos, winner, winning_bid)) = self.ended_auction(new_handle) {
+            self.gameplay_stats.update_auctions(AuctionOutcome {
+                round: self.root_turn,
+                pos,
+                landing_pindex: curr_pindex,
+                winner,
+                winning_bid,
+                price: self.board.property(pos).price,
+            });
+        }
+
+        // Record every transfer of money that the move made
+        let transfers = self.get_transfers(self.root_handle, new_handle);
+        self.gameplay_stats.update_ledger(transfers);
+
+        // Property acquisition stats
+        if self.nodes[new_handle].diff_exists(DiffID::OwnedProperties) {
+            let old_props = self.diff_owned_properties(self.root_handle);
+            let acquisitions: Vec<(usize, u8)> = self
+                .diff_owned_properties(new_handle)
+                .iter()
+                .filter(|(pos, new)| old_props.get(pos).map(|old| old.owner) != Some(new.owner))
+                .map(|(&pos, new)| (new.owner, pos))
+                .collect();
+
+            for (owner, pos) in acquisitions {
+                self.gameplay_stats
+                    .update_acquisitions(owner, self.root_turn, pos);
+            }
+        }
+
         // Property worth stats
         if matches!(self.nodes[new_handle].next_move, MoveType::Roll) {
             let props = self.diff_owned_properties(new_handle);
@@ -146,12 +1376,27 @@ impl Game {
             let mut worths = vec![0; player_count];
 
             for (pos, prop) in props {
-                worths[prop.owner] += PROPERTIES[pos].price;
+                worths[prop.owner] += self.board.property(*pos).price;
             }
 
             self.gameplay_stats.update_prop_worths(worths);
         }
 
+        // Balance and net worth stats, at the end of every turn (including the last)
+        if matches!(self.nodes[new_handle].next_move, MoveType::Roll)
+            || self.is_terminal(new_handle)
+        {
+            let players = self.diff_players(new_handle);
+            let balances: Vec<i32> = players.iter().map(|p| p.balance).collect();
+            let mut net_worths = balances.clone();
+            for (pos, prop) in self.diff_owned_properties(new_handle) {
+                net_worths[prop.owner] += self.board.property(*pos).price;
+            }
+
+            self.gameplay_stats
+                .update_wealth(self.root_turn, balances, net_worths);
+        }
+
         // Jail stats
         if self.nodes[new_handle].diff_exists(DiffID::JailRounds) {
             let update_flags: Vec<(usize, bool)> = zip(
@@ -165,51 +1410,210 @@ impl Game {
             .collect();
 
             for (i, update) in update_flags {
-                // The player got into jail in this round or is still serving jail
+                // The player got into jail in this round
                 if update {
-                    self.gameplay_stats.inc_sentenced_rounds(i);
+                    self.gameplay_stats
+                        .inc_sentenced_rounds(i, self.config.jail_tries);
                 }
             }
         }
 
-        // Mark the old handle and all of the new handle's siblings as 'dirty'
-        self.dirty_handles.push(self.root_handle);
-        for h in self.nodes[self.root_handle].children.clone() {
-            self.mark_dirty(h);
+        // Bankruptcy stats
+        let newly_bankrupt: Vec<usize> = zip(
+            self.diff_players(self.root_handle),
+            self.diff_players(new_handle),
+        )
+        .enumerate()
+        .filter(|(_, (old, new))| new.bankrupt && !old.bankrupt)
+        .map(|(i, _)| i)
+        .collect();
+
+        for i in newly_bankrupt {
+            self.gameplay_stats.update_bankruptcies(i, self.root_turn);
         }
 
+        // Jackpot stats, where the jackpot is only ever emptied by being collected
+        let old_jackpot = self.diff_jackpot(self.root_handle);
+        if old_jackpot > 0 && self.diff_jackpot(new_handle) == 0 {
+            self.gameplay_stats
+                .update_jackpots(self.root_turn, curr_pindex, old_jackpot);
+        }
+
+        // Win probability stats
+        let probabilities = self.get_win_probabilities(new_handle);
+        self.gameplay_stats
+            .update_win_probabilities(self.root_turn, probabilities);
+
         // Update the root turn
         if matches!(self.nodes[new_handle].next_move, MoveType::Roll) {
             self.root_turn += 1;
         }
 
+        // Remember the values that the move overwrote, so that it can be undone
+        let overwritten = DiffID::all()
+            .iter()
+            .filter(|&&d| self.nodes[new_handle].diff_exists(d))
+            .map(|&d| self.diff_field(self.root_handle, d).clone())
+            .collect();
+        self.undo_log.push(overwritten);
+
         // Ensure the new root node has every diff
-        for d in DiffID::all() {
-            if !self.nodes[new_handle].diff_exists(d) {
-                let diff = self.diff_field(new_handle, d).clone();
-                self.nodes[new_handle].set_diff(d, diff);
-            }
+        self.materialize(new_handle);
+
+        // Free the old root and all of the new root's siblings
+        for h in self.nodes[self.root_handle].children.clone() {
+            self.nodes.free_subtree(h);
         }
+        self.nodes.free(self.root_handle);
 
         // Update the game's move history
         self.move_history.push(child_index);
 
+        // Count how many times the game has been in the new state
+        let hash = self.nodes[new_handle].hash;
+        let times_seen = self.state_counts.entry(hash).or_insert(0);
+        *times_seen += 1;
+        if *times_seen > 1 {
+            let times_seen = *times_seen;
+            self.gameplay_stats
+                .update_repetitions(self.root_turn, times_seen);
+        }
+        self.root_hashes.push(hash);
+
         // Set itself as its parent to ensure that there are
         // no more references to deleted nodes (just in case)
         self.nodes[new_handle].parent = new_handle;
 
         // Update the root handle
         self.root_handle = new_handle;
+
+        if !events.is_empty() {
+            let mut observers = std::mem::take(&mut self.observers);
+            observers.notify(self, &events);
+            self.observers = observers;
+        }
     }
 
-    /// Mark a state and all of its descendants as 'dirty'.
-    fn mark_dirty(&mut self, handle: usize) {
-        self.dirty_handles.push(handle);
+    /// Return the transfers of money made by moving from the state at `handle`
+    /// to its child at `child`. Players who lost money are matched up with the players
+    /// (or the jackpot) who gained it, and the bank makes up any difference.
+    fn get_transfers(&self, handle: usize, child: usize) -> Vec<Transfer> {
+        let reason = self.nodes[child].message.name();
+        let old_players = self.diff_players(handle);
+        let new_players = self.diff_players(child);
 
-        // Mark all the descendants as 'dirty'
-        for h in self.nodes[handle].children.clone() {
-            self.mark_dirty(h);
+        let mut changes: Vec<(Account, i32)> = zip(old_players, new_players)
+            .enumerate()
+            .map(|(i, (old, new))| (Account::Player(i), new.balance - old.balance))
+            .collect();
+        changes.push((
+            Account::Jackpot,
+            self.diff_jackpot(child) - self.diff_jackpot(handle),
+        ));
+
+        let mut payers: Vec<(Account, i32)> = changes
+            .iter()
+            .filter(|(_, change)| *change < 0)
+            .map(|&(account, change)| (account, -change))
+            .collect();
+        let mut payees: Vec<(Account, i32)> = changes
+            .into_iter()
+            .filter(|(_, change)| *change > 0)
+            .collect();
+
+        // Whoever is left over after matching players up pays or is paid by the bank
+        let paid: i32 = payers.iter().map(|(_, amount)| amount).sum();
+        let received: i32 = payees.iter().map(|(_, amount)| amount).sum();
+        if paid > received {
+            payees.push((Account::Bank, paid - received));
+        } else if received > paid {
+            payers.push((Account::Bank, received - paid));
+        }
+
+        let mut transfers = vec![];
+        let mut payees = payees.into_iter().peekable();
+        for (payer, mut owed) in payers {
+            while owed > 0 {
+                let Some((payee, due)) = payees.peek_mut() else {
+                    break;
+                };
+
+                let amount = owed.min(*due);
+                transfers.push(Transfer {
+                    round: self.root_turn,
+                    payer,
+                    payee: *payee,
+                    amount,
+                    reason: reason.to_string(),
+                });
+
+                owed -= amount;
+                *due -= amount;
+                if *due == 0 {
+                    payees.next();
+                }
+            }
+        }
+
+        transfers
+    }
+
+    /// Return the events that observers are notified of when
+    /// the root state advances to its child at `new_handle`.
+    fn move_events(&self, new_handle: usize) -> Vec<GameEvent> {
+        let state = &self.nodes[new_handle];
+        let curr_pindex = self.diff_current_pindex(self.root_handle);
+        let old_players = self.diff_players(self.root_handle);
+        let new_players = self.diff_players(new_handle);
+
+        let probability = match state.branch_type {
+            BranchType::Chance(p) => Some(p),
+            _ => None,
+        };
+        let mut events = vec![GameEvent::Move(state.message.clone(), probability)];
+
+        match state.message {
+            DiffMessage::LandOppProp { pos, owner, rent } => events.push(GameEvent::RentPaid {
+                payer: curr_pindex,
+                owner,
+                pos,
+                rent,
+            }),
+            DiffMessage::ChanceCard(card) => events.push(GameEvent::ChanceCard(curr_pindex, card)),
+            DiffMessage::ClassicCard(card) => {
+                events.push(GameEvent::ClassicCard(curr_pindex, card))
+            }
+            _ => (),
+        }
+
+        // Properties that went from the bank to a player were bought, at the cost of
+        // whatever the buyer's balance went down by
+        if state.diff_exists(DiffID::OwnedProperties) {
+            let old_props = self.diff_owned_properties(self.root_handle);
+            let mut bought: Vec<(u8, usize)> = self
+                .diff_owned_properties(new_handle)
+                .iter()
+                .filter(|(pos, _)| !old_props.contains_key(pos))
+                .map(|(&pos, prop)| (pos, prop.owner))
+                .collect();
+            bought.sort_unstable();
+
+            for (pos, pindex) in bought {
+                let price = (old_players[pindex].balance - new_players[pindex].balance).max(0);
+                events.push(GameEvent::PropertyBought { pindex, pos, price });
+            }
+        }
+
+        for (i, (old, new)) in zip(old_players, new_players).enumerate() {
+            if new.in_jail && !old.in_jail {
+                events.push(GameEvent::PlayerJailed(i));
+            }
+            if new.bankrupt && !old.bankrupt {
+                events.push(GameEvent::Bankruptcy(i));
+            }
         }
+
+        events
     }
 
     /// Return the player whose turn it currently is at the specified state.
@@ -217,50 +1621,130 @@ impl Game {
         &self.diff_players(handle)[self.diff_current_pindex(handle)]
     }
 
-    /// Return the index of the player whose turn it will be next.
-    fn get_next_pindex(&self, handle: usize) -> usize {
-        (self.diff_current_pindex(handle) + 1) % self.diff_players(handle).len()
+    /// Return the index of the player who has to settle their debt
+    /// at the specified state, if anyone is in debt.
+    fn get_debtor(&self, handle: usize) -> Option<usize> {
+        self.diff_players(handle)
+            .iter()
+            .position(|p| p.balance < 0 && !p.bankrupt)
+    }
+
+    /// Return the index of the player who makes the next move at the specified state.
+    /// This is usually the current player, except when someone else has to settle a debt.
+    fn get_acting_pindex(&self, handle: usize) -> usize {
+        match self.nodes[handle].next_move {
+            MoveType::SellProperty { .. } => self
+                .get_debtor(handle)
+                .unwrap_or_else(|| self.diff_current_pindex(handle)),
+            MoveType::TradeOffer(offer) => offer.receiver,
+            MoveType::Bid(auction) => auction.bidder,
+            _ => self.diff_current_pindex(handle),
+        }
     }
 
-    /// Return the next value of `top_cc`.
-    fn get_next_top_cc(&self, handle: usize) -> usize {
-        (self.diff_top_cc(handle) + 1) % TOTAL_CHANCE_CARDS
+    /// Return a summary of every child of the specified state, generating
+    /// them (without storing them) if they haven't been generated yet.
+    fn get_move_summaries(&self, handle: usize) -> Vec<MoveSummary> {
+        if self.is_terminal(handle) {
+            return vec![];
+        }
+
+        let acting_pindex = self.get_acting_pindex(handle);
+        let summarise = |index, child: &StateDiff| MoveSummary {
+            index,
+            acting_pindex,
+            message: child.message.clone(),
+            probability: match child.branch_type {
+                BranchType::Chance(p) => Some(p),
+                _ => None,
+            },
+        };
+
+        if self.nodes[handle].children.is_empty() {
+            self.gen_children(handle)
+                .iter()
+                .enumerate()
+                .map(|(i, child)| summarise(i, child))
+                .collect()
+        } else {
+            self.nodes[handle]
+                .children
+                .iter()
+                .enumerate()
+                .map(|(i, &child)| summarise(i, &self.nodes[child]))
+                .collect()
+        }
+    }
+
+    /// Return a cheap estimate of each player's chance of winning from the specified
+    /// state, which is their share of the total net worth (cash plus property prices).
+    fn get_win_probabilities(&self, handle: usize) -> Vec<f64> {
+        let players = self.diff_players(handle);
+        let net_worths: Vec<f64> =
+            value_function::net_worths(players, self.diff_owned_properties(handle), &self.board)
+                .iter()
+                .zip(players)
+                .map(|(&w, p)| if p.bankrupt { 0. } else { w.max(0.) })
+                .collect();
+        let total: f64 = net_worths.iter().sum();
+
+        if total == 0. {
+            return vec![1. / players.len() as f64; players.len()];
+        }
+
+        net_worths.iter().map(|w| w / total).collect()
     }
 
     /// Return the probabilities of all the child nodes of `handle`.
     /// This will return an empty vector if the `handle` node doesn't
-    /// have any children. Panics if a child is not a chance node.
-    fn get_children_chances(&self, handle: usize) -> Vec<f64> {
+    /// have any children, or an error if a child is not a chance node.
+    fn get_children_chances(&self, handle: usize) -> Result<Vec<f64>, GameError> {
         let mut chances = vec![];
 
         for &child_handle in &self.nodes[handle].children {
             match self.nodes[child_handle].branch_type {
                 BranchType::Chance(p) => chances.push(p),
-                _ => panic!("Choice node found in get_children_chances()"),
+                _ => {
+                    return Err(GameError::Internal(
+                        "choice node found among chance nodes".to_string(),
+                    ))
+                }
             }
         }
 
-        chances
+        Ok(chances)
     }
 
-    /// Return the index of a randomly selected child chance node.
+    /// Return the index of a randomly selected child chance node, or an error if
+    /// `handle` doesn't have any children or they aren't all chance nodes.
     /// Note that this returns the node's index in `handle`'s `children`
     /// vector, not a handle that can used in `game.nodes[handle]`.
-    fn get_any_chance_child(&self, handle: usize) -> usize {
-        let chances = self.get_children_chances(handle);
-        let mut rng = rand::thread_rng();
-        let mut pos: f64 = rng.gen();
+    fn try_any_chance_child(&self, handle: usize) -> Result<usize, GameError> {
+        let chances = self.get_children_chances(handle)?;
+        if chances.is_empty() {
+            return Err(GameError::Internal(
+                "chance node without any children".to_string(),
+            ));
+        }
 
+        let mut pos: f64 = with_rng(|rng| rng.gen());
         for (i, &c) in chances.iter().enumerate() {
             if pos <= c {
-                return i;
+                return Ok(i);
             }
 
             pos -= c;
         }
 
         // Just in case of floating-point arithmetic inacuraccies
-        chances.len() - 1
+        Ok(chances.len() - 1)
+    }
+
+    /// Return `Game::try_any_chance_child()`, panicking on an error. This is for the
+    /// searches, where a panic is caught as the searching agent's (see `Seat::decide()`).
+    fn get_any_chance_child(&self, handle: usize) -> usize {
+        self.try_any_chance_child(handle)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     fn get_current_props(&self, handle: usize) -> HashSet<u8> {
@@ -277,125 +1761,197 @@ impl Game {
 
     /// Return a `StateDiff` with the boilerplate for chance cards:
     /// - Sets `next_move` to `Roll`
-    /// - Updates `current_player` if needed
-    /// - Updates `seen_ccs` or `top_cc`
+    /// - Draws the card from the chance deck
+    ///
+    /// The turn is handed over once the card's effect has been applied to the state.
     fn new_state_from_cc(&self, card: ChanceCard, handle: usize) -> StateDiff {
         let mut state = StateDiff::new_with_parent(handle);
         state.next_move = MoveType::Roll;
-
-        // It's the next player's turn if the current player didn't roll doubles
-        if self.get_current_player(handle).doubles_rolled == 0 {
-            state.set_current_pindex(self.get_next_pindex(handle));
-        }
-
-        // Update the top_cc if needed
-        if self.diff_seen_ccs(handle).len() == TOTAL_CHANCE_CARDS {
-            state.set_top_cc(self.get_next_top_cc(handle));
-        } else {
-            let mut seen_ccs = self.diff_seen_ccs(handle).clone();
-            seen_ccs.push(card);
-            state.set_seen_ccs(seen_ccs);
-        }
-
+        self.record_cc(card, handle, &mut state);
         state
     }
 
-    /// Modify the state to be the next player's turn if the current player didn't roll doubles.
-    /// This only affects the state's next_move and current_pindex
-    fn advance_move(&self, handle: usize, state: &mut StateDiff) {
-        state.next_move = MoveType::Roll;
+    /// Record `card` as the latest chance card drawn in `state`, a child of the state at `handle`.
+    fn record_cc(&self, card: ChanceCard, handle: usize, state: &mut StateDiff) {
+        let deck = self
+            .diff_chance_deck(handle)
+            .drawn(card, self.config.reshuffle_chance_deck);
+        state.set_chance_deck(deck);
+    }
 
-        if self.get_current_player(handle).doubles_rolled == 0 {
-            state.set_current_pindex(self.get_next_pindex(handle));
+    /// Return the states that can be reached by the current player teleporting to a property
+    /// for `fee`, which they have to be able to pay. With `GameConfig::restrict_location_targets`,
+    /// they can't teleport to an opponent's property.
+    fn gen_teleport_children(&self, handle: usize, fee: i32) -> Vec<StateDiff> {
+        let curr_pindex = self.diff_current_pindex(handle);
+        let owned_props = self.diff_owned_properties(handle);
+
+        if self.get_current_player(handle).balance < fee {
+            return vec![];
         }
-    }
 
-    fn get_auction_winner_chances(&self, handle: usize) -> Vec<(usize, f64)> {
-        let possible_winners = self
-            .diff_players(handle)
+        let targets: Vec<u8> = self
+            .board
+            .prop_positions()
+            .filter(|pos| {
+                let opponent_owned = owned_props
+                    .get(pos)
+                    .is_some_and(|prop| prop.owner != curr_pindex);
+                !(self.config.restrict_location_targets && opponent_owned)
+            })
+            .collect();
+
+        // Only the first of each group of interchangeable properties
+        // whose color set is still unowned is worth offering
+        let by_color = self.board.props_by_color();
+        let unowned_set = |pos: u8| {
+            by_color[&self.board.property(pos).color]
+                .iter()
+                .all(|p| !owned_props.contains_key(p))
+        };
+        let redundant = |pos: u8| {
+            self.config.symmetry_reduction
+                && unowned_set(pos)
+                && targets
+                    .iter()
+                    .any(|&other| other < pos && self.board.interchangeable(other, pos))
+        };
+
+        targets
             .iter()
-            .enumerate()
-            .filter(|(_, p)| p.balance >= 20);
-        let total_balance = possible_winners
-            .clone()
-            .map(|(_, p)| p.balance)
-            .sum::<i32>() as f64;
-
-        possible_winners
-            .map(|(i, p)| (i, p.balance as f64 / total_balance))
+            .copied()
+            .filter(|&pos| !redundant(pos))
+            .map(|pos| {
+                let mut players = self.diff_players(handle).clone();
+                players[curr_pindex].balance -= fee;
+                players[curr_pindex].position = pos;
+
+                let mut new_state = StateDiff::new_with_parent(handle);
+                new_state.message = DiffMessage::Location(pos);
+                new_state.next_move = MoveType::Property;
+                new_state.branch_type = BranchType::Choice;
+                new_state.set_players(players);
+                new_state
+            })
             .collect()
     }
 
-    fn get_winning_bid_chances(&self, handle: usize, winner: usize) -> Vec<(i32, f64)> {
-        let balance = self.diff_players(handle)[winner].balance;
-        let balance_at_pos =
-            |pos: f64| ((balance - 20) as f64 * pos / 20.0).round() as i32 * 20 + 20;
-
-        if balance < 20 {
-            // Just in case...
-            panic!("get_winning_bid_chances() received players with <= $20");
-        }
-
-        // Based on a bell curve
-        [
-            (1. / 6., 0.0675),
-            (2. / 6., 0.2410),
-            (3. / 6., 0.3830),
-            (4. / 6., 0.2410),
-            (5. / 6., 0.0675),
-        ]
-        .iter()
-        .map(|(pos, chance)| (balance_at_pos(*pos), *chance))
-        .fold(vec![], |mut acc, (p, c)| {
-            if let Some(last) = acc.last_mut() {
-                if p == last.0 {
-                    last.1 += c;
-                    return acc;
-                }
+    /// Put `amount` (paid to the bank as a tax or penalty) on 'Free parking' instead,
+    /// if the game is played with the Free Parking jackpot house rule.
+    fn add_to_jackpot(&self, handle: usize, state: &mut StateDiff, amount: i32) {
+        if self.config.free_parking_jackpot {
+            state.set_jackpot(self.diff_jackpot(handle) + amount);
+        }
+    }
+
+    /// Give the jackpot to the current player if they land on 'Free parking' in `state`.
+    fn collect_jackpot(&self, handle: usize, state: &mut StateDiff) {
+        let pindex = self.diff_current_pindex(handle);
+        let free_parking = self.board.free_parking_position;
+        let jackpot = match state.get_diff(DiffID::Jackpot) {
+            Some(&FieldDiff::Jackpot(x)) => x,
+            Some(_) => unreachable!(),
+            None => self.diff_jackpot(handle),
+        };
+
+        if jackpot == 0 || self.diff_players(handle)[pindex].position == free_parking {
+            return;
+        }
+
+        let landed = match state.get_diff_mut(DiffID::Players) {
+            Some(FieldDiff::Players(players)) if players[pindex].position == free_parking => {
+                players[pindex].balance += jackpot;
+                true
             }
+            Some(FieldDiff::Players(_)) | None => false,
+            Some(_) => unreachable!(),
+        };
 
-            acc.push((p, c));
-            acc
-        })
+        if landed {
+            state.set_jackpot(0);
+        }
     }
 
+    /// Return whether the game is over, which is when only one player isn't bankrupt,
+    /// or when the current state is reached after the turn limit.
     fn is_terminal(&self, handle: usize) -> bool {
-        let bankrupt = self.diff_players(handle).iter().any(|p| p.balance < 0);
-        bankrupt && !matches!(self.nodes[handle].next_move, MoveType::SellProperty)
+        (handle == self.root_handle
+            && (self.turn_limit_reached() || self.repetition_limit_reached()))
+            || self
+                .diff_players(handle)
+                .iter()
+                .filter(|p| !p.bankrupt)
+                .count()
+                <= 1
     }
 
-    fn get_loser(&self, handle: usize) -> usize {
+    /// Return whether the game has been played for as many turns as its turn limit, if it has one.
+    fn turn_limit_reached(&self) -> bool {
+        self.config
+            .max_turns
+            .is_some_and(|max_turns| self.root_turn >= max_turns)
+    }
+
+    /// Return whether the game has been in the current state as many times as its
+    /// repetition limit, if it has one.
+    fn repetition_limit_reached(&self) -> bool {
+        self.config.max_repetitions.is_some_and(|max_repetitions| {
+            let hash = self.nodes[self.root_handle].hash;
+            self.state_counts.get(&hash).copied().unwrap_or(0) >= max_repetitions
+        })
+    }
+
+    /// Return the last player standing, or the player with the highest net worth
+    /// (the lowest index on a tie) if more than one player is left.
+    fn get_winner(&self, handle: usize) -> Result<usize, GameError> {
         if !self.is_terminal(handle) {
-            panic!("non-terminal state found while getting loser");
+            return Err(GameError::Internal(
+                "non-terminal state found while getting winner".to_string(),
+            ));
         }
 
-        let losers: Vec<usize> = self
+        let players = self.diff_players(handle);
+        let net_worths = self.get_net_worths(handle);
+
+        (0..players.len())
+            .filter(|&i| !players[i].bankrupt)
+            .fold(None, |best: Option<usize>, i| match best {
+                Some(b) if net_worths[b] >= net_worths[i] => Some(b),
+                _ => Some(i),
+            })
+            .ok_or_else(|| GameError::Internal("every player is bankrupt".to_string()))
+    }
+
+    /// Return each player's net worth, which is their balance plus the price
+    /// and current rent of every property they own.
+    fn get_net_worths(&self, handle: usize) -> Vec<i32> {
+        let mut net_worths: Vec<i32> = self
             .diff_players(handle)
             .iter()
-            .enumerate()
-            .filter(|(_, p)| p.balance < 0)
-            .map(|(i, _)| i)
+            .map(|p| p.balance)
             .collect();
-        if losers.len() > 1 {
-            panic!("more than 1 loser");
+
+        for (&pos, prop) in self.diff_owned_properties(handle) {
+            let property = self.board.property(pos);
+            net_worths[prop.owner] += property.price + property.rent(prop.rent_level);
         }
 
-        losers[0]
-    }
-
-    fn get_player_count(&self) -> usize {
-        self.diff_players(self.root_handle).len()
+        net_worths
     }
 
     /*********        STATE DIFF GETTERS        *********/
 
-    fn diff_field(&self, handle: usize, diff_id: DiffID) -> &FieldDiff {
-        // Alias for the state
-        let s = &self.nodes[handle];
+    /// Return the specified field of the state at `handle`, from the
+    /// state itself or the nearest ancestor that changed it.
+    fn diff_field(&self, mut handle: usize, diff_id: DiffID) -> &FieldDiff {
+        loop {
+            // Alias for the state
+            let s = &self.nodes[handle];
 
-        match s.get_diff_index(diff_id) {
-            Some(i) => &s.diffs[i],
-            None => self.diff_field(s.parent, diff_id),
+            match s.get_diff(diff_id) {
+                Some(diff) => return diff,
+                None => handle = s.parent,
+            }
         }
     }
 
@@ -431,18 +1987,10 @@ impl Game {
         }
     }
 
-    /// Return a vector of chance cards that have already been seen from the specified state.
-    fn diff_seen_ccs(&self, handle: usize) -> &Vec<ChanceCard> {
-        match self.diff_field(handle, DiffID::SeenCcs) {
-            FieldDiff::SeenCCs(x) => x,
-            _ => unreachable!(),
-        }
-    }
-
-    /// Return top_cc from the specified state.
-    fn diff_top_cc(&self, handle: usize) -> usize {
-        match self.diff_field(handle, DiffID::SeenCcsHead) {
-            FieldDiff::SeenCCsHead(x) => *x,
+    /// Return the chance deck at the specified state.
+    fn diff_chance_deck(&self, handle: usize) -> &ChanceDeck {
+        match self.diff_field(handle, DiffID::ChanceDeck) {
+            FieldDiff::ChanceDeck(x) => x,
             _ => unreachable!(),
         }
     }
@@ -455,21 +2003,72 @@ impl Game {
         }
     }
 
+    /// Return the money waiting on 'Free parking' at the specified state.
+    fn diff_jackpot(&self, handle: usize) -> i32 {
+        match self.diff_field(handle, DiffID::Jackpot) {
+            FieldDiff::Jackpot(x) => *x,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return every field of the state at `handle`.
+    fn resolve_state(&self, handle: usize) -> ResolvedState {
+        ResolvedState {
+            players: self.diff_players(handle).clone(),
+            current_pindex: self.diff_current_pindex(handle),
+            owned_properties: self.diff_owned_properties(handle).clone(),
+            chance_deck: self.diff_chance_deck(handle).clone(),
+            level_1_rent: self.diff_lvl_1_rent(handle),
+            jail_rounds: self.diff_jail_rounds(handle).clone(),
+            jackpot: self.diff_jackpot(handle),
+            next_move: self.nodes[handle].next_move.clone(),
+        }
+    }
+
     /*********        GENERAL STATE GENERATION        *********/
 
     /// Return child states that can be reached from the specified state.
     fn gen_children(&self, handle: usize) -> Vec<StateDiff> {
         let mut children = match self.nodes[handle].next_move {
             MoveType::Roll => self.gen_roll_children(handle),
+            MoveType::ChanceCard if self.config.rules == RuleSet::Classic => {
+                self.gen_classic_card_children(handle, false)
+            }
             MoveType::ChanceCard => self.gen_cc_children(handle),
+            MoveType::CommunityChest => self.gen_classic_card_children(handle, true),
+            MoveType::Tax => self.gen_tax_children(handle),
+            MoveType::Build => self.gen_build_children(handle),
             MoveType::ChoicefulCC(cc) => self.gen_choiceful_cc_children(handle, cc),
             MoveType::Property => self.gen_property_children(handle),
-            MoveType::SellProperty => self.gen_sell_prop_children(handle),
+            MoveType::SellProperty { creditor, ref then } => {
+                self.gen_sell_prop_children(handle, creditor, then)
+            }
             MoveType::Auction => self.gen_auction_children(handle),
             MoveType::Location => self.gen_location_children(handle),
+            MoveType::Trade => self.gen_trade_children(handle),
+            MoveType::TradeOffer(offer) => self.gen_trade_offer_children(handle, offer),
+            MoveType::Bid(auction) => self.gen_bid_children(handle, auction),
             MoveType::Undefined => unreachable!(),
         };
 
+        // Players can build houses and hotels at the start of their turn
+        if self.config.rules == RuleSet::Classic {
+            for child in &mut children {
+                if child.next_move.is_roll() && child.diff_exists(DiffID::CurrentPlayer) {
+                    child.next_move = MoveType::Build;
+                }
+            }
+        }
+
+        // Players can trade at the start of their turn
+        if self.config.trading {
+            for child in &mut children {
+                if child.next_move.is_roll() && child.diff_exists(DiffID::CurrentPlayer) {
+                    child.next_move = MoveType::Trade;
+                }
+            }
+        }
+
         // It's the end of this player's turn
         if self.nodes[handle].diff_exists(DiffID::CurrentPlayer) {
             let lvl_1_rent = self.diff_lvl_1_rent(handle);
@@ -480,37 +2079,51 @@ impl Game {
             }
         }
 
-        // Update all the children's JailRounds diff
+        // Whoever lands on 'Free parking' collects the jackpot
+        if self.config.free_parking_jackpot {
+            for child in &mut children {
+                self.collect_jackpot(handle, child);
+            }
+        }
+
+        // Anyone who ends up in debt has to settle it before the game continues
         for child in &mut children {
-            if !child.diff_exists(DiffID::CurrentPlayer) {
+            if child.next_move.is_sell_property() {
                 continue;
             }
 
-            match child.get_diff_index(DiffID::JailRounds) {
-                Some(i) => {
-                    // Update JailRounds diff
-                    let updated_jail_rounds = match &mut child.diffs[i] {
-                        FieldDiff::JailRounds(jr) => jr,
-                        _ => unreachable!(),
-                    };
-
-                    *updated_jail_rounds = updated_jail_rounds
-                        .iter()
-                        .map(|&jr| if jr > 0 { jr - 1 } else { 0 })
-                        .collect();
-                }
-                None => {
-                    // Set new JailRounds diff
-                    let new_diff: Vec<u8> = self
-                        .diff_jail_rounds(handle)
-                        .iter()
-                        .map(|&jr| if jr > 0 { jr - 1 } else { 0 })
-                        .collect();
-                    child.set_jail_rounds(new_diff);
+            let in_debt = match child.get_diff(DiffID::Players) {
+                Some(FieldDiff::Players(players)) => {
+                    players.iter().any(|p| p.balance < 0 && !p.bankrupt)
                 }
+                Some(_) => unreachable!(),
+                None => false,
+            };
+
+            if in_debt {
+                let then = std::mem::replace(&mut child.next_move, MoveType::Undefined);
+                child.next_move = MoveType::SellProperty {
+                    creditor: None,
+                    then: Box::new(then),
+                };
             }
         }
 
+        // Choices that lead to the same state (e.g. swapping properties that are
+        // alike in every way that matters) are the same choice, so only the first is kept
+        if children
+            .iter()
+            .all(|child| matches!(child.branch_type, BranchType::Choice))
+        {
+            let mut seen = HashSet::new();
+            children.retain(|child| seen.insert(self.child_hash(handle, child)));
+        }
+        // Chance outcomes that lead to the same state (e.g. drawing 'All to parking'
+        // after different rolls) are merged into one, with their probabilities summed
+        else {
+            self.merge_chance_children(handle, &mut children);
+        }
+
         children
     }
 
@@ -522,74 +2135,85 @@ impl Game {
 
         // Get the player out of jail if they're in jail
         if self.get_current_player(handle).in_jail {
-            let jail_rounds = self.diff_jail_rounds(handle)[i];
+            let jail_rounds = self.diff_jail_rounds(handle);
+            // On their last try, the player leaves jail whatever they roll
+            let last_try = jail_rounds[i] <= 1;
+
+            // The player's tries are used up once they're out of jail
+            let mut released_jail_rounds = jail_rounds.clone();
+            released_jail_rounds[i] = 0;
 
             // Loop through all possible dice results
             for roll in SIGNIFICANT_ROLLS.iter() {
-                if !(roll.is_double || jail_rounds == 0) {
+                if !(roll.is_double || last_try) {
                     continue;
                 }
 
                 let mut players = self.diff_players(handle).clone();
                 let mut new_state = StateDiff::new_with_parent(handle);
                 new_state.branch_type = BranchType::Chance(roll.probability);
+                new_state.set_jail_rounds(released_jail_rounds.clone());
 
-                if !roll.is_double && jail_rounds == 0 {
-                    // $100 penalty for not rolling doubles
-                    players[i].balance -= 100;
+                if !roll.is_double {
+                    // Fine for not rolling doubles
+                    players[i].balance -= self.config.jail_penalty;
+                    self.add_to_jackpot(handle, &mut new_state, self.config.jail_penalty);
                 }
 
                 // Update the current player's position
-                players[i].move_by(roll.sum);
+                players[i].move_by(roll.sum, &self.board, self.config.go_salary);
                 new_state.message = DiffMessage::Roll(players[i].position);
-                new_state.next_move = MoveType::when_landed_on(players[i].position);
+                new_state.next_move = MoveType::when_landed_on(players[i].position, &self.board);
                 new_state.set_players(players);
-
-                // Update the current_player if needed
-                if new_state.next_move.is_roll() {
-                    new_state.set_current_pindex(self.get_next_pindex(handle));
-                }
+                self.hand_over_turn(handle, &mut new_state);
 
                 children.push(new_state);
             }
 
-            // A single state for staying in jail
-            if jail_rounds > 0 {
+            // A single state for staying in jail, which uses up one of the player's tries
+            if !last_try {
+                let mut stay_jail_rounds = jail_rounds.clone();
+                stay_jail_rounds[i] -= 1;
+
                 let mut stay_in_jail = StateDiff::new_with_parent(handle);
                 stay_in_jail.branch_type = BranchType::Chance(*SINGLE_PROBABILITY);
                 stay_in_jail.next_move = MoveType::Roll;
                 stay_in_jail.message = DiffMessage::StayInJail;
-                stay_in_jail.set_current_pindex(self.get_next_pindex(handle));
+                stay_in_jail.set_jail_rounds(stay_jail_rounds);
+                self.hand_over_turn(handle, &mut stay_in_jail);
 
                 children.push(stay_in_jail);
             }
         }
         // Otherwise, play as normal
         else {
+            // The jail rounds of the current player if they get sent to jail
+            let mut advanced_jail_rounds = self.diff_jail_rounds(handle).clone();
+            advanced_jail_rounds[i] = self.config.jail_tries;
+
             // Loop through all possible dice results
             for roll in SIGNIFICANT_ROLLS.iter() {
                 // Update the current player's position
                 let mut players = self.diff_players(handle).clone();
-                players[i].move_by(roll.sum);
+                players[i].move_by(roll.sum, &self.board, self.config.go_salary);
 
                 let mut new_state = StateDiff::new_with_parent(handle);
                 new_state.branch_type = BranchType::Chance(roll.probability);
-                new_state.next_move = MoveType::when_landed_on(players[i].position);
+                new_state.next_move = MoveType::when_landed_on(players[i].position, &self.board);
 
-                let mut advanced_jail_rounds = self.diff_jail_rounds(handle).clone();
-                advanced_jail_rounds[i] = JAIL_TRIES * self.diff_players(handle).len() as u8;
-
-                if players[i].position == GO_TO_JAIL_POSITION {
-                    players[i].send_to_jail();
-                    new_state.set_jail_rounds(advanced_jail_rounds);
+                if players[i].position == self.board.go_to_jail_position {
+                    players[i].send_to_jail(&self.board);
+                    new_state.set_jail_rounds(advanced_jail_rounds.clone());
                     new_state.message = DiffMessage::RollToJail;
                 } else if roll.is_double {
                     players[i].doubles_rolled += 1;
 
                     // Go to jail after three consecutive doubles
                     if players[i].doubles_rolled == 3 {
-                        players[i].send_to_jail();
-                        new_state.set_jail_rounds(advanced_jail_rounds);
+                        players[i].send_to_jail(&self.board);
+                        // The player doesn't get to act on the tile they rolled to
+                        new_state.next_move = MoveType::Roll;
+                        new_state.set_jail_rounds(advanced_jail_rounds.clone());
                         new_state.message = DiffMessage::RollToJail;
                     } else {
                         new_state.message = DiffMessage::RollDoubles(players[i].position);
@@ -600,29 +2224,135 @@ impl Game {
                     new_state.message = DiffMessage::Roll(players[i].position);
                 }
 
-                // Update the current_player if needed
-                if new_state.next_move.is_roll() && players[i].doubles_rolled == 0 {
-                    new_state.set_current_pindex(self.get_next_pindex(handle));
-                }
-
                 new_state.set_players(players);
+                self.hand_over_turn(handle, &mut new_state);
                 children.push(new_state);
             }
         }
 
+        if self.config.rules == RuleSet::UltimateBanking {
+            self.predraw_all_to_parking(handle, &mut children);
+        }
+
         children
     }
 
+    /// Return the probability that the next chance card drawn after the state at `handle`
+    /// is 'All to parking'.
+    fn all_to_parking_probability(&self, handle: usize) -> f64 {
+        self.diff_chance_deck(handle)
+            .probability(ChanceCard::AllToParking)
+    }
+
+    /// Return whether 'All to parking' was already drawn (see `predraw_all_to_parking()`)
+    /// for the chance card that has to be drawn at the state at `handle`.
+    fn all_to_parking_predrawn(&self, handle: usize) -> bool {
+        matches!(
+            self.nodes[handle].message,
+            DiffMessage::Roll(_) | DiffMessage::RollDoubles(_)
+        )
+    }
+
+    /// Draw 'All to parking' for each of the roll `children` of the state at `handle` that
+    /// lands on a chance tile, splitting each of them into a child that draws it and one that
+    /// draws any other card. Everyone ends up on 'Free parking' whichever chance tile the
+    /// player rolled to, so the children that draw it can then be merged into one.
+    fn predraw_all_to_parking(&self, handle: usize, children: &mut Vec<StateDiff>) {
+        let probability = self.all_to_parking_probability(handle);
+        if probability == 0. {
+            return;
+        }
+
+        let curr_pindex = self.diff_current_pindex(handle);
+        let mut drawn = vec![];
+
+        for child in children.iter_mut() {
+            if !matches!(child.next_move, MoveType::ChanceCard) {
+                continue;
+            }
+
+            let mut players = match child.get_diff(DiffID::Players) {
+                Some(FieldDiff::Players(players)) => players.clone(),
+                _ => unreachable!(),
+            };
+
+            // The player has to settle their debt (e.g. from a jail fine) before drawing
+            if players[curr_pindex].balance < 0 {
+                continue;
+            }
+
+            let roll_probability = match child.branch_type {
+                BranchType::Chance(p) => p,
+                _ => unreachable!(),
+            };
+
+            let mut state = child.clone();
+            self.move_all_to_parking(&mut players);
+            state.set_players(players);
+            self.record_cc(ChanceCard::AllToParking, handle, &mut state);
+            state.message = DiffMessage::ChanceCard(ChanceCard::AllToParking);
+            state.branch_type = BranchType::Chance(roll_probability * probability);
+            state.next_move = MoveType::Roll;
+            self.hand_over_turn(handle, &mut state);
+
+            if probability < 1. {
+                child.branch_type = BranchType::Chance(roll_probability * (1. - probability));
+                drawn.push(state);
+            } else {
+                *child = state;
+            }
+        }
+
+        children.append(&mut drawn);
+    }
+
+    /// Merge the chance `children` of the state at `handle` that lead to the same state,
+    /// keeping the first of each and adding up their probabilities.
+    fn merge_chance_children(&self, handle: usize, children: &mut Vec<StateDiff>) {
+        let chance_sum = |children: &[StateDiff]| -> f64 {
+            children
+                .iter()
+                .filter_map(|child| match child.branch_type {
+                    BranchType::Chance(p) => Some(p),
+                    _ => None,
+                })
+                .sum()
+        };
+        let total = chance_sum(children);
+
+        let mut merged: Vec<StateDiff> = Vec::with_capacity(children.len());
+        let mut indices: HashMap<u64, usize> = HashMap::new();
+
+        for child in children.drain(..) {
+            let hash = self.child_hash(handle, &child);
+
+            match (indices.get(&hash), child.branch_type) {
+                (Some(&i), BranchType::Chance(p)) => {
+                    if let BranchType::Chance(ref mut merged_p) = merged[i].branch_type {
+                        *merged_p += p;
+                    }
+                }
+                _ => {
+                    indices.insert(hash, merged.len());
+                    merged.push(child);
+                }
+            }
+        }
+
+        debug_assert!(
+            (chance_sum(&merged) - total).abs() < validation::CHANCE_TOLERANCE,
+            "merging chance children changed their total probability"
+        );
+
+        *children = merged;
+    }
+
     /// Return child states that can be reached by picking a chance card from the specified state.
     fn gen_cc_children(&self, handle: usize) -> Vec<StateDiff> {
-        let mut children = vec![];
-        let seen_ccs = self.diff_seen_ccs(handle);
+        let deck = self.diff_chance_deck(handle);
 
         // We can deduce the exact chance card that we're going to get since we've seen them all
-        if seen_ccs.len() == TOTAL_CHANCE_CARDS {
-            // The chance card that the player will definitely get
-            let definite_cc = seen_ccs[self.diff_top_cc(handle)];
-
+        if let Some(definite_cc) = deck.next_card() {
             // Get the child diffs according to the choicefulness of the chance card
             if definite_cc.is_choiceless() {
                 // This is the only possibility since this is a choiceless chance card
@@ -632,19 +2362,15 @@ impl Game {
             return self.gen_choiceful_cc_children(handle, definite_cc);
         }
 
+        // 'All to parking' was drawn along with the roll, so it's been ruled out here
+        let excluded = self
+            .all_to_parking_predrawn(handle)
+            .then_some(ChanceCard::AllToParking);
+
         // We can't know the exact chance card that we're
         // going to get, so calculate all their probabilities
-        let unseen_cards = ChanceCard::unseen_counts(&seen_ccs);
-
-        for (card, count) in unseen_cards {
-            // Skip if the chance card has no chance of occurring
-            if count == 0 {
-                continue;
-            }
-
-            // Calculate the probability of encountering this chance card
-            let probability = count as f64 / (TOTAL_CHANCE_CARDS - seen_ccs.len()) as f64;
-
+        let mut children = vec![];
+        for (card, probability) in deck.draw_probabilities_except(excluded) {
             if card.is_choiceless() {
                 children.push(self.gen_choiceless_cc_child(card, handle, probability));
             } else {
@@ -661,33 +2387,12 @@ impl Game {
 
     /// Return child states that can be reached by landing on a location tile.
     fn gen_location_children(&self, handle: usize) -> Vec<StateDiff> {
-        let mut children = vec![];
-        let curr_pindex = self.diff_current_pindex(handle);
-        let balance = self.get_current_player(handle).balance;
-
-        if balance >= 100 {
-            for &pos in PROP_POSITIONS.iter() {
-                let mut players = self.diff_players(handle).clone();
-
-                // Pay $100
-                players[curr_pindex].balance -= 100;
-                // Move to a property
-                players[curr_pindex].position = pos;
-
-                // Add the new state to children
-                let mut new_state = StateDiff::new_with_parent(handle);
-                new_state.message = DiffMessage::Location(pos);
-                new_state.next_move = MoveType::Property;
-                new_state.branch_type = BranchType::Choice;
-                new_state.set_players(players);
-                children.push(new_state);
-            }
-        }
+        let mut children = self.gen_teleport_children(handle, self.config.location_fee);
 
         // There's also the option to do nothing
         let mut no_move = StateDiff::new_with_parent(handle);
         no_move.message = DiffMessage::NoLocation;
-        self.advance_move(handle, &mut no_move);
+        self.end_move(handle, &mut no_move);
         no_move.branch_type = BranchType::Choice;
         children.push(no_move);
 
@@ -709,37 +2414,45 @@ impl Game {
             // The current player owes rent to the owner of this property
             if prop.owner != curr_pindex {
                 let mut players = self.diff_players(handle).clone();
-                let new_rent_level = if self.diff_lvl_1_rent(handle) == 0 {
-                    prop.rent_level
+                let balance_due = if self.config.rules == RuleSet::Classic {
+                    self.classic_rent(handle, player_pos, prop)
+                } else if self.diff_lvl_1_rent(handle) == 0 {
+                    self.board.property(player_pos).rents[prop.rent_level - 1]
                 } else {
-                    1
+                    self.board.property(player_pos).rents[0]
                 };
-                let balance_due = PROPERTIES[&player_pos].rents[new_rent_level - 1];
 
                 // Pay the owner using the current player's money
                 players[curr_pindex].balance -= balance_due;
                 players[prop.owner].balance += balance_due;
-
-                // The player has to sell his own properties if he goes bankrupt
-                if players[curr_pindex].balance < 0 {
-                    new_state.next_move = MoveType::SellProperty;
-                }
+                let in_debt = players[curr_pindex].balance < 0;
 
                 new_state.set_players(players);
-                new_state.message = DiffMessage::LandOppProp;
+                new_state.message = DiffMessage::LandOppProp {
+                    pos: player_pos,
+                    owner: prop.owner,
+                    rent: balance_due,
+                };
+                self.end_move(handle, &mut new_state);
+
+                // The player has to sell their own properties if they can't afford the rent
+                if in_debt {
+                    let then = std::mem::replace(&mut new_state.next_move, MoveType::Undefined);
+                    new_state.next_move = MoveType::SellProperty {
+                        creditor: Some(prop.owner),
+                        then: Box::new(then),
+                    };
+                }
             } else {
-                new_state.message = DiffMessage::LandOwnProp;
+                new_state.message = DiffMessage::LandOwnProp(player_pos);
+                self.end_move(handle, &mut new_state);
             }
 
-            // Raise the rent level
-            let mut props = self.diff_owned_properties(handle).clone();
-            props.get_mut(&player_pos).unwrap().raise_rent();
-            new_state.set_owned_properties(props);
-
-            // Advance to the next turn if the move type hasn't already been defined
-            match new_state.next_move {
-                MoveType::Undefined => self.advance_move(handle, &mut new_state),
-                _ => (),
+            // Raise the rent level, which only happens in Monopoly: Ultimate Banking
+            if self.config.rules == RuleSet::UltimateBanking {
+                let mut props = self.diff_owned_properties(handle).clone();
+                props.get_mut(&player_pos).unwrap().raise_rent();
+                new_state.set_owned_properties(props);
             }
 
             return vec![new_state];
@@ -747,15 +2460,18 @@ impl Game {
 
         let curr_player_balance = self.diff_players(handle)[curr_pindex].balance;
         // Check if the player has enough money to buy the property
-        if curr_player_balance > PROPERTIES[&player_pos].price {
+        if curr_player_balance > self.board.property(player_pos).price {
             // The state where the player buys the property
             let mut buy_state = StateDiff::new_with_parent(handle);
-            buy_state.message = DiffMessage::BuyProp;
-            self.advance_move(handle, &mut buy_state);
+            buy_state.message = DiffMessage::BuyProp {
+                pos: player_pos,
+                price: self.board.property(player_pos).price,
+            };
+            self.end_move(handle, &mut buy_state);
             buy_state.branch_type = BranchType::Choice;
             // New players
             let mut buy_state_players = self.diff_players(handle).clone();
-            buy_state_players[curr_pindex].balance -= PROPERTIES[&player_pos].price;
+            buy_state_players[curr_pindex].balance -= self.board.property(player_pos).price;
             buy_state.set_players(buy_state_players);
             // New owned properties
             let mut buy_state_props = self.diff_owned_properties(handle).clone();
@@ -773,9 +2489,14 @@ impl Game {
 
         // The state where the player auctions the property
         let mut auction_state = StateDiff::new_with_parent(handle);
-        auction_state.message = DiffMessage::AuctionProp;
+        auction_state.message = DiffMessage::AuctionProp(player_pos);
         auction_state.branch_type = BranchType::Choice;
-        auction_state.next_move = MoveType::Auction;
+        auction_state.next_move = if self.config.real_auctions {
+            // The current player can still bid for the property they turned down
+            MoveType::Bid(AuctionProgress::new(player_pos, curr_pindex))
+        } else {
+            MoveType::Auction
+        };
         children.push(auction_state);
 
         children
@@ -786,122 +2507,374 @@ impl Game {
     fn gen_auction_children(&self, handle: usize) -> Vec<StateDiff> {
         let mut children = vec![];
 
+        // It's the current player who is on the property that is being auctioned,
+        // so we use their position instead of the position of the player who won the auction
+        let players = self.diff_players(handle);
+        let prop_pos = players[self.diff_current_pindex(handle)].position;
+        let property = self.board.property(prop_pos);
+
         // Loop through all the possible auction winners and winning bids
-        for (auction_winner, player_chance) in self.get_auction_winner_chances(handle) {
-            for (winning_bid, bid_chance) in self.get_winning_bid_chances(handle, auction_winner) {
+        for (auction_winner, player_chance) in self.auction_model.winner_chances(players) {
+            // The auction winner gets the property, whatever they bid for it
+            let mut props = self.diff_owned_properties(handle).clone();
+            props.insert(
+                prop_pos,
+                PropertyOwnership {
+                    owner: auction_winner,
+                    rent_level: 1,
+                },
+            );
+            let props = Arc::new(props);
+
+            let balance = players[auction_winner].balance;
+            for (winning_bid, bid_chance) in
+                self.auction_model.winning_bid_chances(balance, property)
+            {
                 let mut players = self.diff_players(handle).clone();
-                let mut props = self.diff_owned_properties(handle).clone();
                 let mut new_state = StateDiff::new_with_parent(handle);
                 new_state.message = DiffMessage::AfterAuction(auction_winner, winning_bid);
 
-                // It's the current player who is on the property that is being auctioned,
-                // so we use their position instead of the position of the player who won the auction
-                let prop_pos = players[self.diff_current_pindex(handle)].position;
-
-                // The auction winner pays the bid...
+                // The auction winner pays the bid
                 players[auction_winner].balance -= winning_bid;
-                // ...to get the property
-                props.insert(
-                    prop_pos,
-                    PropertyOwnership {
-                        owner: auction_winner,
-                        rent_level: 1,
-                    },
-                );
 
                 new_state.set_players(players);
-                new_state.set_owned_properties(props);
+                new_state.set_owned_properties(props.clone());
                 new_state.branch_type = BranchType::Chance(player_chance * bid_chance);
 
-                self.advance_move(handle, &mut new_state);
+                self.end_move(handle, &mut new_state);
                 children.push(new_state);
             }
         }
 
-        if children.len() == 0 {
+        if children.is_empty() {
             let mut state = StateDiff::new_with_parent(handle);
             state.branch_type = BranchType::Chance(1.);
-            self.advance_move(handle, &mut state);
+            self.end_move(handle, &mut state);
             children.push(state);
         }
 
         children
     }
 
-    fn gen_sell_prop_children(&self, handle: usize) -> Vec<StateDiff> {
+    /// Return child states that can be reached by the next bidder in a real auction
+    /// either dropping out or raising the highest bid by one of the bid increments.
+    fn gen_bid_children(&self, handle: usize, auction: AuctionProgress) -> Vec<StateDiff> {
+        let bidder = auction.bidder;
+        let balance = self.diff_players(handle)[bidder].balance;
+
+        // Dropping out comes first, so that agents that break ties by
+        // picking the first move don't bid without a reason to
+        let mut dropped = auction;
+        dropped.dropped |= 1 << bidder;
+        let mut children =
+            vec![self.gen_after_bid_state(handle, dropped, DiffMessage::PassBid(bidder))];
+
+        for increment in AuctionProgress::BID_INCREMENTS {
+            let amount = auction.high_bid + increment;
+            if amount > balance {
+                break;
+            }
+
+            let mut raised = auction;
+            raised.high_bid = amount;
+            raised.high_bidder = Some(bidder);
+            children.push(self.gen_after_bid_state(
+                handle,
+                raised,
+                DiffMessage::Bid {
+                    pos: auction.pos,
+                    bidder,
+                    amount,
+                },
+            ));
+        }
+
+        children
+    }
+
+    /// Return the state after a bid or a drop out left the auction as `auction`. The
+    /// auction moves on to the next player still in it, or ends if there isn't anyone
+    /// left to outbid the highest bidder, who then pays their bid for the property.
+    fn gen_after_bid_state(
+        &self,
+        handle: usize,
+        auction: AuctionProgress,
+        message: DiffMessage,
+    ) -> StateDiff {
+        let mut state = StateDiff::new_with_parent(handle);
+        state.message = message;
+        state.branch_type = BranchType::Choice;
+
+        let players = self.diff_players(handle);
+        let next_bidder = (1..players.len())
+            .map(|i| (auction.bidder + i) % players.len())
+            .find(|&i| {
+                !players[i].bankrupt && !auction.has_dropped(i) && Some(i) != auction.high_bidder
+            });
+
+        if let Some(bidder) = next_bidder {
+            state.next_move = MoveType::Bid(AuctionProgress { bidder, ..auction });
+            return state;
+        }
+
+        // The property stays with the bank if nobody bid for it
+        if let Some(winner) = auction.high_bidder {
+            let mut players = players.clone();
+            players[winner].balance -= auction.high_bid;
+            let mut props = self.diff_owned_properties(handle).clone();
+            props.insert(
+                auction.pos,
+                PropertyOwnership {
+                    owner: winner,
+                    rent_level: 1,
+                },
+            );
+
+            state.set_players(players);
+            state.set_owned_properties(props);
+        }
+
+        self.end_move(handle, &mut state);
+        state
+    }
+
+    /// Return child states that can be reached by a player selling properties to cover their
+    /// debt. If they can't cover it, they go bankrupt and their properties go to `creditor`.
+    fn gen_sell_prop_children(
+        &self,
+        handle: usize,
+        creditor: Option<usize>,
+        then: &MoveType,
+    ) -> Vec<StateDiff> {
         let mut children = vec![];
-        let curr_pindex = self.diff_current_pindex(handle);
-        let curr_balance = self.diff_players(handle)[curr_pindex].balance;
-        // The positions of all the properties the current player owns
+        let debtor = self
+            .get_debtor(handle)
+            .expect("no player in debt while selling properties");
+        let debtor_balance = self.diff_players(handle)[debtor].balance;
+        // The positions of all the properties the debtor owns
         let mut my_props = vec![];
 
         // Fill up my_props
         for (&pos, prop) in self.diff_owned_properties(handle) {
-            if prop.owner == curr_pindex {
-                my_props.push(pos);
+            if prop.owner == debtor {
+                my_props.push((pos, prop.rent_level));
             }
         }
+        my_props.sort_unstable();
+
+        for (comb, total_worth) in self.get_sale_combinations(&my_props, debtor_balance) {
+            let mut sell_prop = StateDiff::new_with_parent(handle);
+            sell_prop.branch_type = BranchType::Choice;
+            sell_prop.next_move = then.clone();
+            sell_prop.message =
+                DiffMessage::SellProps(comb.iter().map(|&i| my_props[i].0).collect());
+
+            // Sell all the properties in `comb` to the bank
+            let mut props = self.diff_owned_properties(handle).clone();
+            for i in comb {
+                props.remove(&my_props[i].0);
+            }
+            sell_prop.set_owned_properties(props);
+
+            // The player gets the money
+            let mut players = self.diff_players(handle).clone();
+            players[debtor].balance += total_worth;
+            sell_prop.set_players(players);
 
-        // If the current player doesn't have any properties to sell then it's game over
-        if my_props.len() == 0 {
-            let mut gameover = StateDiff::new_with_parent(handle);
-            gameover.branch_type = BranchType::Chance(1.);
-            self.advance_move(handle, &mut gameover);
-            return vec![gameover];
+            children.push(sell_prop);
         }
 
-        for k in 1..my_props.len() {
-            let mut stop_here = false;
+        if children.is_empty() {
+            vec![self.gen_bankruptcy_child(handle, debtor, creditor, then)]
+        } else {
+            children
+        }
+    }
 
-            // Go through all the possible combinations of selling k properties
-            for comb in get_combinations(my_props.len(), k) {
-                let total_worth: i32 = comb.iter().map(|&i| PROPERTIES[&my_props[i]].price).sum();
+    /// Return the combinations of `props` (as `(position, rent_level)`) that a player with
+    /// `balance` can sell to get out of debt, as the indexes of the properties in `props`
+    /// along with how much they sell for. These are the combinations of the fewest
+    /// properties that cover the debt, or with `GameConfig::pareto_sales`, only the ones
+    /// that no other combination beats on both the number of properties and the money
+    /// left over after the debt is paid. Combinations of more than `PARETO_EXTRA_SALES`
+    /// properties over the fewest aren't considered, so the ones that would leave even
+    /// less money over are missing when the player owns many properties.
+    fn get_sale_combinations(&self, props: &[(u8, usize)], balance: i32) -> Vec<(Vec<usize>, i32)> {
+        let mut combinations = vec![];
+        // The least money left over by any combination found so far
+        let mut least_overshoot = None;
+        // The size of the smallest combinations that cover the debt
+        let mut fewest = None;
+
+        for k in 1..=props.len() {
+            if fewest
+                .is_some_and(|fewest| !self.config.pareto_sales || k > fewest + PARETO_EXTRA_SALES)
+                || least_overshoot == Some(0)
+            {
+                break;
+            }
 
-                if curr_balance + total_worth < 0 {
-                    continue;
+            // The combinations of k properties that cover the debt
+            let covering = get_combinations(props.len(), k)
+                .into_iter()
+                .filter_map(|comb| {
+                    let worth: i32 = comb
+                        .iter()
+                        .map(|&i| self.sale_value(props[i].0, props[i].1))
+                        .sum();
+                    (balance + worth >= 0).then_some((comb, worth))
+                });
+
+            if !self.config.pareto_sales {
+                combinations.extend(covering);
+            } else if let Some(best) = covering.min_by_key(|(_, worth)| *worth) {
+                // Selling more properties only pays off if it leaves less money over
+                let overshoot = balance + best.1;
+                if least_overshoot.is_none_or(|least| overshoot < least) {
+                    least_overshoot = Some(overshoot);
+                    combinations.push(best);
                 }
+            }
 
-                stop_here = true;
-                let mut sell_prop = StateDiff::new_with_parent(handle);
-                sell_prop.branch_type = BranchType::Choice;
+            if fewest.is_none() && !combinations.is_empty() {
+                fewest = Some(k);
+            }
+        }
 
-                // Sell all the properties in `comb` to the bank
-                let mut props = self.diff_owned_properties(handle).clone();
-                for prop_i in comb {
-                    props.remove(&(prop_i as u8));
+        combinations
+    }
+
+    /// Return the state where `debtor` goes bankrupt and is eliminated from the game.
+    /// Their properties (and whatever money they had left) go to `creditor`, or
+    /// back to the bank if they owe the bank.
+    fn gen_bankruptcy_child(
+        &self,
+        handle: usize,
+        debtor: usize,
+        creditor: Option<usize>,
+        then: &MoveType,
+    ) -> StateDiff {
+        let mut players = self.diff_players(handle).clone();
+        let mut props = self.diff_owned_properties(handle).clone();
+
+        match creditor {
+            Some(c) => {
+                // The creditor has already been paid in full, so take back the shortfall
+                players[c].balance += players[debtor].balance;
+
+                for prop in props.values_mut() {
+                    if prop.owner == debtor {
+                        prop.owner = c;
+                    }
                 }
-                sell_prop.set_owned_properties(props);
+            }
+            None => props.retain(|_, prop| prop.owner != debtor),
+        }
 
-                // The player gets the money
-                let mut players = self.diff_players(handle).clone();
-                players[curr_pindex].balance += total_worth;
-                sell_prop.set_players(players);
+        players[debtor].balance = 0;
+        players[debtor].in_jail = false;
+        players[debtor].doubles_rolled = 0;
+        players[debtor].bankrupt = true;
+
+        let mut bankruptcy = StateDiff::new_with_parent(handle);
+        bankruptcy.branch_type = BranchType::Chance(1.);
+        bankruptcy.message = DiffMessage::Bankrupt(debtor);
+        bankruptcy.next_move = then.clone();
+
+        // Skip the rest of the debtor's turn if it's currently their turn
+        if self.diff_current_pindex(handle) == debtor {
+            bankruptcy.next_move = MoveType::Roll;
+            bankruptcy.set_current_pindex(TurnFlow::next_pindex(&players, debtor));
+        }
+
+        bankruptcy.set_players(players);
+        bankruptcy.set_owned_properties(props);
+        bankruptcy
+    }
 
-                self.advance_move(handle, &mut sell_prop);
-                children.push(sell_prop);
+    /// Return child states that can be reached by the current player proposing a trade.
+    fn gen_trade_children(&self, handle: usize) -> Vec<StateDiff> {
+        let curr_pindex = self.diff_current_pindex(handle);
+        let players = self.diff_players(handle);
+        let props = self.diff_owned_properties(handle);
+
+        // The player can always choose not to trade
+        let mut no_trade = StateDiff::new_with_parent(handle);
+        no_trade.branch_type = BranchType::Choice;
+        no_trade.message = DiffMessage::NoTrade;
+        no_trade.next_move = MoveType::Roll;
+        let mut children = vec![no_trade];
+
+        // Offer any of my properties for any of my opponents' properties
+        for (&give, my_prop) in props {
+            if my_prop.owner != curr_pindex {
+                continue;
             }
 
-            if stop_here {
-                break;
+            for (&take, opp_prop) in props {
+                if opp_prop.owner == curr_pindex {
+                    continue;
+                }
+
+                for &cash in TRADE_CASH_OFFERS.iter() {
+                    // Both sides must be able to afford the cash part of the trade
+                    if players[curr_pindex].balance < cash
+                        || players[opp_prop.owner].balance < -cash
+                    {
+                        continue;
+                    }
+
+                    let offer = TradeOffer {
+                        proposer: curr_pindex,
+                        receiver: opp_prop.owner,
+                        give,
+                        take,
+                        cash,
+                    };
+
+                    let mut new_state = StateDiff::new_with_parent(handle);
+                    new_state.branch_type = BranchType::Choice;
+                    new_state.message = DiffMessage::ProposeTrade(offer);
+                    new_state.next_move = MoveType::TradeOffer(offer);
+                    children.push(new_state);
+                }
             }
         }
 
-        if children.len() == 0 {
-            // This state doesn't need a `next_move` because it's a terminal state
-            let mut gameover = StateDiff::new_with_parent(handle);
-            self.advance_move(handle, &mut gameover);
-            gameover.branch_type = BranchType::Chance(1.);
-            vec![gameover]
-        } else {
-            children
-        }
+        children
+    }
+
+    /// Return child states that can be reached by the receiver of a trade offer accepting or declining it.
+    fn gen_trade_offer_children(&self, handle: usize, offer: TradeOffer) -> Vec<StateDiff> {
+        // Exchange the properties and cash
+        let mut players = self.diff_players(handle).clone();
+        players[offer.proposer].balance -= offer.cash;
+        players[offer.receiver].balance += offer.cash;
+
+        let mut props = self.diff_owned_properties(handle).clone();
+        props.get_mut(&offer.give).unwrap().owner = offer.receiver;
+        props.get_mut(&offer.take).unwrap().owner = offer.proposer;
+
+        let mut accept = StateDiff::new_with_parent(handle);
+        accept.branch_type = BranchType::Choice;
+        accept.message = DiffMessage::AcceptTrade;
+        accept.next_move = MoveType::Roll;
+        accept.set_players(players);
+        accept.set_owned_properties(props);
+
+        let mut decline = StateDiff::new_with_parent(handle);
+        decline.branch_type = BranchType::Choice;
+        decline.message = DiffMessage::DeclineTrade;
+        decline.next_move = MoveType::Roll;
+
+        vec![accept, decline]
     }
 
     /*********        CHOICEFUL CC STATE GENERATION        *********/
 
     /// Return child states that can be reached by getting a choiceful chance card.
     fn gen_choiceful_cc_children(&self, handle: usize, cc: ChanceCard) -> Vec<StateDiff> {
-        let children = match cc {
+        let mut children = match cc {
             ChanceCard::RentTo5 => self.gen_cc_rent_to_x(true, handle),
             ChanceCard::RentTo1 => self.gen_cc_rent_to_x(false, handle),
             ChanceCard::SetRentInc => self.gen_cc_set_rent_change(true, handle),
@@ -916,13 +2889,17 @@ impl Game {
             _ => panic!("choiceless cc passed to Game.gen_choiceful_cc_children()"),
         };
 
-        if children.len() > 0 {
-            children
-        } else {
+        if children.is_empty() {
             let mut no_change = self.new_state_from_cc(cc, handle);
             no_change.branch_type = BranchType::Chance(1.);
-            vec![no_change]
+            children.push(no_change);
         }
+
+        for child in &mut children {
+            self.hand_over_turn(handle, child);
+        }
+
+        children
     }
 
     fn gen_cc_rent_to_x(&self, max: bool, handle: usize) -> Vec<StateDiff> {
@@ -947,7 +2924,7 @@ impl Game {
 
             // Update the owned_properties
             let mut owned_props = self.diff_owned_properties(handle).clone();
-            owned_props.get_mut(&pos).unwrap().rent_level = target_rent;
+            owned_props.get_mut(pos).unwrap().rent_level = target_rent;
             child.set_owned_properties(owned_props);
 
             children.push(child);
@@ -966,19 +2943,19 @@ impl Game {
         let my_props = self.get_current_props(handle);
 
         // Loop through each color set
-        for (_, positions) in PROPS_BY_COLOR.iter() {
-            let mut owned_props = self.diff_owned_properties(handle).clone();
-            let mut has_effect = false;
-
+        for positions in self.board.props_by_color().values() {
             // The player has to own at least one of the properties in this colour set
             if my_props.is_disjoint(positions) {
                 continue;
             }
 
+            let mut owned_props = self.diff_owned_properties(handle).clone();
+            let mut has_effect = false;
+
             // Loop through all the properties in this color set
             for pos in positions {
                 // Check if a property exists at `pos`
-                if let Some(prop) = owned_props.get_mut(&pos) {
+                if let Some(prop) = owned_props.get_mut(pos) {
                     has_effect |= prop.change_rent(increase);
                 }
             }
@@ -1004,18 +2981,18 @@ impl Game {
         };
         let my_props = self.get_current_props(handle);
 
-        for positions in PROPS_BY_SIDE.iter() {
-            let mut owned_properties = self.diff_owned_properties(handle).clone();
-            let mut has_effect = false;
-
+        for positions in self.board.props_by_side().iter() {
             // The player has to own at least one of the properties on this side of the board
             if my_props.is_disjoint(positions) {
                 continue;
             }
 
+            let mut owned_properties = self.diff_owned_properties(handle).clone();
+            let mut has_effect = false;
+
             for pos in positions {
                 // Check if the property is owned
-                if let Some(prop) = owned_properties.get_mut(&pos) {
+                if let Some(prop) = owned_properties.get_mut(pos) {
                     has_effect |= prop.change_rent(increase);
                 }
             }
@@ -1046,10 +3023,10 @@ impl Game {
             let mut has_effect = false;
 
             // Raise this property's rent level
-            has_effect |= properties.get_mut(&pos).unwrap().raise_rent();
+            has_effect |= properties.get_mut(pos).unwrap().raise_rent();
 
             // Lower neighbours' rent levels (if they're owned)
-            for n_pos in PROPERTY_NEIGHBOURS[&pos] {
+            for n_pos in self.board.property_neighbours(*pos) {
                 if let Some(n_prop) = properties.get_mut(&n_pos) {
                     has_effect |= n_prop.lower_rent();
                 }
@@ -1072,8 +3049,8 @@ impl Game {
         let curr_pindex = self.diff_current_pindex(handle);
 
         for i in 0..self.diff_players(handle).len() {
-            // Skip the current player
-            if i == curr_pindex {
+            // Skip the current player and players who are out of the game
+            if i == curr_pindex || self.diff_players(handle)[i].bankrupt {
                 continue;
             }
 
@@ -1116,8 +3093,8 @@ impl Game {
 
                 // Swap properties
                 let mut props = parent_props.clone();
-                props.get_mut(&my_pos).unwrap().owner = opp_prop.owner;
-                props.get_mut(&opp_pos).unwrap().owner = my_prop.owner;
+                props.get_mut(my_pos).unwrap().owner = opp_prop.owner;
+                props.get_mut(opp_pos).unwrap().owner = my_prop.owner;
 
                 // Add the new state
                 let mut new_state = self.new_state_from_cc(ChanceCard::SwapProperty, handle);
@@ -1136,16 +3113,16 @@ impl Game {
         let curr_pindex = self.diff_current_pindex(handle);
 
         for i in 0..curr_players.len() {
-            // Skip the current player and players who are already in jail
-            if i == curr_pindex || curr_players[i].in_jail {
+            // Skip the current player, players who are already in jail, and players who are out of the game
+            if i == curr_pindex || curr_players[i].in_jail || curr_players[i].bankrupt {
                 continue;
             }
 
             // Send the opponent to jail
             let mut players = self.diff_players(handle).clone();
-            players[i].send_to_jail();
+            players[i].send_to_jail(&self.board);
             let mut jail_rounds = self.diff_jail_rounds(handle).clone();
-            jail_rounds[i] = JAIL_TRIES * self.diff_players(handle).len() as u8;
+            jail_rounds[i] = self.config.jail_tries;
 
             // Add the new state
             let mut new_state = self.new_state_from_cc(ChanceCard::OpponentToJail, handle);
@@ -1158,31 +3135,12 @@ impl Game {
         children
     }
 
+    /// Return child states that can be reached by getting the "go to any property"
+    /// chance card, which works like a location tile without the fee.
     fn gen_cc_go_to_any_property(&self, handle: usize) -> Vec<StateDiff> {
-        let mut children = vec![];
-        let curr_pindex = self.diff_current_pindex(handle);
-
-        for pos in PROP_POSITIONS.iter() {
-            // Move the player to any property
-            let mut players = self.diff_players(handle).clone();
-            players[curr_pindex].position = *pos;
-
-            // Create the new state
-            let mut new_state = StateDiff::new_with_parent(handle);
-            new_state.branch_type = BranchType::Choice;
-            new_state.set_players(players);
-            new_state.next_move = MoveType::Property;
-
-            // Update top_cc or seen_ccs
-            if self.diff_seen_ccs(handle).len() == TOTAL_CHANCE_CARDS {
-                new_state.set_top_cc(self.get_next_top_cc(handle));
-            } else {
-                let mut seen_ccs = self.diff_seen_ccs(handle).clone();
-                seen_ccs.push(ChanceCard::GoToAnyProperty);
-                new_state.set_seen_ccs(seen_ccs);
-            }
-
-            children.push(new_state);
+        let mut children = self.gen_teleport_children(handle, 0);
+        for child in &mut children {
+            self.record_cc(ChanceCard::GoToAnyProperty, handle, child);
         }
 
         children
@@ -1197,22 +3155,25 @@ impl Game {
         handle: usize,
         probability: f64,
     ) -> StateDiff {
-        match cc {
+        let mut state = match cc {
             ChanceCard::PropertyTax => self.gen_cc_property_tax(probability, handle),
             ChanceCard::Level1Rent => self.gen_cc_level_1_rent(probability, handle),
             ChanceCard::AllToParking => self.gen_cc_all_to_parking(probability, handle),
             _ => panic!("choiceful cc passed to Game.gen_choiceless_cc()"),
-        }
+        };
+
+        self.hand_over_turn(handle, &mut state);
+        state
     }
 
     fn gen_cc_property_tax(&self, probability: f64, handle: usize) -> StateDiff {
         let mut tax = 0;
         let i = self.diff_current_pindex(handle);
 
-        // Tax $50 per property owned
-        for (_, prop) in self.diff_owned_properties(handle) {
+        // Tax every property owned
+        for prop in self.diff_owned_properties(handle).values() {
             if prop.owner == i {
-                tax += 50;
+                tax += PROPERTY_TAX;
             }
         }
 
@@ -1225,6 +3186,7 @@ impl Game {
         let mut state = self.new_state_from_cc(ChanceCard::PropertyTax, handle);
         state.branch_type = BranchType::Chance(probability);
         state.set_players(updated_players);
+        self.add_to_jackpot(handle, &mut state, tax);
 
         state
     }
@@ -1241,13 +3203,7 @@ impl Game {
     fn gen_cc_all_to_parking(&self, probability: f64, handle: usize) -> StateDiff {
         // Clone players
         let mut updated_players = self.diff_players(handle).clone();
-
-        // Move every player who's not in jail to free parking
-        for player in &mut updated_players {
-            if !player.in_jail {
-                player.position = FREE_PARKING_POSITION;
-            }
-        }
+        self.move_all_to_parking(&mut updated_players);
 
         // Create a new state
         let mut state = self.new_state_from_cc(ChanceCard::AllToParking, handle);
@@ -1256,4 +3212,13 @@ impl Game {
 
         state
     }
+
+    /// Move every player who's not in jail to free parking.
+    fn move_all_to_parking(&self, players: &mut [Player]) {
+        for player in players {
+            if !player.in_jail {
+                player.position = self.board.free_parking_position;
+            }
+        }
+    }
 }
@@ -0,0 +1,103 @@
+use super::classic::ClassicCard;
+use super::globals::ChanceCard;
+use super::state_diff::DiffMessage;
+use super::Game;
+use std::time::Duration;
+
+/// Gets notified of what happens in a game as it's played, e.g. to update a UI or collect
+/// statistics. Subscribe one to a game with `Game::subscribe()`. Every callback is given
+/// the game as it is after the move that triggered it, and does nothing by default.
+pub trait GameObserver: Send {
+    /// Called after every move, including chance moves such as dice rolls,
+    /// before any of the other callbacks for the same move.
+    fn on_move(&mut self, _game: &Game, _message: &DiffMessage, _probability: Option<f64>) {}
+
+    /// Called when a player buys a property from the bank, either outright or at auction.
+    fn on_property_bought(&mut self, _game: &Game, _pindex: usize, _pos: u8, _price: i32) {}
+
+    /// Called when a player pays rent to the owner of the property at `pos`.
+    fn on_rent_paid(&mut self, _game: &Game, _payer: usize, _owner: usize, _pos: u8, _rent: i32) {}
+
+    /// Called when a player draws a chance card.
+    fn on_chance_card(&mut self, _game: &Game, _pindex: usize, _card: ChanceCard) {}
+
+    /// Called when a player draws a Chance or Community Chest card in classic Monopoly.
+    fn on_classic_card(&mut self, _game: &Game, _pindex: usize, _card: ClassicCard) {}
+
+    /// Called when a player is sent to jail.
+    fn on_player_jailed(&mut self, _game: &Game, _pindex: usize) {}
+
+    /// Called when a player goes bankrupt and is eliminated from the game.
+    fn on_bankruptcy(&mut self, _game: &Game, _pindex: usize) {}
+
+    /// Called when a player's agent runs out of time to make a decision (see
+    /// `Game::with_decision_timeout()`), before a random move is played for it.
+    fn on_decision_timeout(&mut self, _game: &Game, _pindex: usize, _timeout: Duration) {}
+}
+
+/// Something that happened in a move, which observers are notified of once the move is made.
+pub(super) enum GameEvent {
+    Move(DiffMessage, Option<f64>),
+    PropertyBought {
+        pindex: usize,
+        pos: u8,
+        price: i32,
+    },
+    RentPaid {
+        payer: usize,
+        owner: usize,
+        pos: u8,
+        rent: i32,
+    },
+    ChanceCard(usize, ChanceCard),
+    ClassicCard(usize, ClassicCard),
+    PlayerJailed(usize),
+    Bankruptcy(usize),
+    DecisionTimeout(usize, Duration),
+}
+
+/// The observers subscribed to a game. Cloning a game (e.g. to search it)
+/// doesn't clone its observers, since they're meant to follow a single game.
+#[derive(Default)]
+pub(super) struct Observers(pub Vec<Box<dyn GameObserver>>);
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Observers::default()
+    }
+}
+
+impl Observers {
+    /// Notify every observer of each of `events`, in order.
+    pub fn notify(&mut self, game: &Game, events: &[GameEvent]) {
+        for observer in &mut self.0 {
+            for event in events {
+                match *event {
+                    GameEvent::Move(ref message, probability) => {
+                        observer.on_move(game, message, probability)
+                    }
+                    GameEvent::PropertyBought { pindex, pos, price } => {
+                        observer.on_property_bought(game, pindex, pos, price)
+                    }
+                    GameEvent::RentPaid {
+                        payer,
+                        owner,
+                        pos,
+                        rent,
+                    } => observer.on_rent_paid(game, payer, owner, pos, rent),
+                    GameEvent::ChanceCard(pindex, card) => {
+                        observer.on_chance_card(game, pindex, card)
+                    }
+                    GameEvent::ClassicCard(pindex, card) => {
+                        observer.on_classic_card(game, pindex, card)
+                    }
+                    GameEvent::PlayerJailed(pindex) => observer.on_player_jailed(game, pindex),
+                    GameEvent::Bankruptcy(pindex) => observer.on_bankruptcy(game, pindex),
+                    GameEvent::DecisionTimeout(pindex, timeout) => {
+                        observer.on_decision_timeout(game, pindex, timeout)
+                    }
+                }
+            }
+        }
+    }
+}
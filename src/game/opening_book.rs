@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// The number of searches a book entry needs before it's trusted instead of searching.
+const MIN_SAMPLES: u32 = 8;
+
+/// The visit counts that searches gave the children of a single state.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookEntry {
+    /// The total number of visits each child received, over every recorded search.
+    pub visits: Vec<u32>,
+    /// The number of searches that were recorded for this state.
+    pub samples: u32,
+}
+
+impl BookEntry {
+    /// Return the index of the child with the most visits.
+    pub fn best_move(&self) -> usize {
+        self.visits
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, &visits)| visits)
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+/// The results of MCTS searches for the early moves of games, which are nearly identical
+/// from game to game. Once a state has been searched often enough, agents can play the
+/// move the book recommends instead of spending their budget searching it again.
+///
+/// States are identified by the moves that led to them from the start of the game,
+/// so a book only applies to games with the same number of players, rules and board
+/// as the games it was built from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpeningBook {
+    /// How many moves into a game states are recorded.
+    depth: usize,
+    /// The number of searches an entry needs before it's used instead of searching.
+    min_samples: u32,
+    /// The entries, keyed by the path of child indexes from the start of the game.
+    entries: HashMap<String, BookEntry>,
+}
+
+impl OpeningBook {
+    /// Return an empty book that records the first `depth` moves of games.
+    pub fn new(depth: usize) -> Self {
+        OpeningBook {
+            depth,
+            min_samples: MIN_SAMPLES,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Only use entries that have been searched at least `min_samples` times.
+    pub fn with_min_samples(mut self, min_samples: u32) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Load a book that was previously saved with `OpeningBook::save()`,
+    /// or return an empty book of `depth` moves if `path` doesn't exist.
+    pub fn load(path: impl AsRef<Path>, depth: usize) -> io::Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(OpeningBook::new(depth));
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Save the book to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Return the number of states in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return whether the book has no states.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return whether the state reached by the moves in `path` is within the book's depth.
+    pub fn covers(&self, path: &[usize]) -> bool {
+        path.len() < self.depth
+    }
+
+    /// Return the entry for the state reached by the moves in `path`, if it has
+    /// `children` children and has been searched at least `min_samples` times.
+    pub fn lookup(&self, path: &[usize], children: usize) -> Option<&BookEntry> {
+        if !self.covers(path) {
+            return None;
+        }
+
+        self.entries
+            .get(&path_key(path))
+            .filter(|entry| entry.visits.len() == children && entry.samples >= self.min_samples)
+    }
+
+    /// Add the visit counts that a search gave the children of
+    /// the state reached by the moves in `path` to the book.
+    pub fn record(&mut self, path: &[usize], visits: &[u32]) {
+        if !self.covers(path) {
+            return;
+        }
+
+        let entry = self.entries.entry(path_key(path)).or_default();
+        // The state had different children when it was recorded, so it can't be trusted
+        if entry.visits.len() != visits.len() {
+            *entry = BookEntry {
+                visits: vec![0; visits.len()],
+                samples: 0,
+            };
+        }
+
+        for (total, v) in entry.visits.iter_mut().zip(visits) {
+            *total += v;
+        }
+        entry.samples += 1;
+    }
+}
+
+/// Return the key of the state reached by the moves in `path`, e.g. "0/3/1".
+fn path_key(path: &[usize]) -> String {
+    path.iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
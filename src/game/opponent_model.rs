@@ -0,0 +1,97 @@
+use super::agent::HeuristicAgent;
+use super::globals::with_rng;
+use super::state_diff::{BranchType, DiffMessage};
+use super::transcript::TranscriptEntry;
+use super::Game;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// How MCTS expects the searching player's opponents to choose their moves, instead of
+/// searching their moves like its own (see `MctsAgent::with_opponent_model()`).
+#[derive(Debug, Clone)]
+pub enum OpponentModel {
+    /// Opponents play uniformly at random.
+    Random,
+    /// Opponents play like `HeuristicAgent`.
+    Heuristic,
+    /// Opponents make each kind of move as often as they've been seen making it so far.
+    Frequencies(MoveFrequencies),
+}
+
+impl OpponentModel {
+    /// Return the index of the child of the choice node at `handle`
+    /// that the acting opponent is expected to play.
+    pub(super) fn choose(&self, game: &Game, handle: usize) -> usize {
+        let children = &game.nodes[handle].children;
+
+        match self {
+            OpponentModel::Random => with_rng(|rng| rng.gen_range(0..children.len())),
+            OpponentModel::Heuristic => HeuristicAgent::choose(game, handle),
+            OpponentModel::Frequencies(frequencies) => {
+                let weights: Vec<f64> = children
+                    .iter()
+                    .map(|&child| frequencies.weight(&game.nodes[child].message))
+                    .collect();
+                let mut threshold = with_rng(|rng| rng.gen::<f64>()) * weights.iter().sum::<f64>();
+
+                for (i, weight) in weights.iter().enumerate() {
+                    threshold -= weight;
+                    if threshold < 0. {
+                        return i;
+                    }
+                }
+
+                children.len() - 1
+            }
+        }
+    }
+
+    /// Learn from the moves in `entries`, which the opponents made since the last decision.
+    pub(super) fn observe(&mut self, entries: &[TranscriptEntry]) {
+        if let OpponentModel::Frequencies(frequencies) = self {
+            for entry in entries {
+                if matches!(entry.branch_type, BranchType::Choice) {
+                    frequencies.observe(&entry.message);
+                }
+            }
+        }
+    }
+}
+
+/// The number of times that the opponents have been seen making each kind of move
+/// (e.g. buying a property or declining a trade), keyed by `DiffMessage::name()`.
+///
+/// ```
+/// use monopoly_math::{DiffMessage, MoveFrequencies};
+///
+/// let mut frequencies = MoveFrequencies::default();
+/// frequencies.observe(&DiffMessage::NoTrade);
+/// frequencies.observe(&DiffMessage::NoTrade);
+///
+/// assert_eq!(frequencies.count(&DiffMessage::NoTrade), 2);
+/// assert_eq!(frequencies.count(&DiffMessage::DeclineTrade), 0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MoveFrequencies {
+    counts: HashMap<&'static str, u32>,
+}
+
+impl MoveFrequencies {
+    /// Record that an opponent made the move described by `message`.
+    pub fn observe(&mut self, message: &DiffMessage) {
+        *self.counts.entry(message.name()).or_insert(0) += 1;
+    }
+
+    /// Return the number of times that an opponent was seen making the kind of move
+    /// described by `message`.
+    pub fn count(&self, message: &DiffMessage) -> u32 {
+        self.counts.get(message.name()).copied().unwrap_or(0)
+    }
+
+    /// Return how likely the kind of move described by `message` is to be chosen, relative
+    /// to the other moves. Every kind of move counts as seen once more than it was, so that
+    /// moves that haven't been seen yet can still be chosen.
+    fn weight(&self, message: &DiffMessage) -> f64 {
+        (self.count(message) + 1) as f64
+    }
+}
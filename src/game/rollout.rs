@@ -0,0 +1,228 @@
+use super::agent::{RolloutPolicy, MAX_ROLLOUT_MOVES};
+use super::state_diff::BranchType;
+use super::state_view::ResolvedState;
+use super::value_function::{NetWorthDifferential, ValueFunction};
+use super::{Board, Game};
+use std::iter;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+/// Scores the states at the end of a batch of rollouts all at once. Every `ValueFunction`
+/// scores batches one state at a time, but a model that is faster at evaluating many
+/// states together (e.g. a neural network on a GPU) can implement this directly.
+pub trait BatchValueFunction: Send + Sync {
+    /// Return the value of each of `states` for player `pindex`, in the same order.
+    fn evaluate_batch(&self, states: &[ResolvedState], board: &Board, pindex: usize) -> Vec<f64>;
+}
+
+impl<V: ValueFunction> BatchValueFunction for V {
+    fn evaluate_batch(&self, states: &[ResolvedState], board: &Board, pindex: usize) -> Vec<f64> {
+        states
+            .iter()
+            .map(|state| self.evaluate(&state.players, &state.owned_properties, board, pindex))
+            .collect()
+    }
+}
+
+/// The combined outcome of the rollouts played after one move.
+#[derive(Debug, Clone, Default)]
+pub struct RolloutOutcome {
+    /// The index of the move in `Game::legal_moves()`.
+    pub index: usize,
+    /// The number of rollouts played after the move.
+    pub rollouts: usize,
+    /// The mean value of the states that the rollouts ended in, for the player making the move.
+    pub mean_value: f64,
+    /// The number of rollouts that each player won.
+    pub wins: Vec<usize>,
+    /// The number of rollouts that were stopped before the game was over.
+    pub unfinished: usize,
+}
+
+/// The state at the end of a rollout.
+struct Leaf {
+    /// The position of the rollout's move in the list of moves that were rolled out.
+    move_i: usize,
+    /// The state that the rollout ended in.
+    state: ResolvedState,
+    /// The player left standing, or `None` if the rollout was stopped before the game was over.
+    winner: Option<usize>,
+}
+
+/// Plays many rollouts after several moves at once, spread across threads, and scores
+/// the states that they end in as a single batch. Playing out the moves and scoring
+/// the results are kept apart, so that the scoring can be handed to a batched model.
+pub struct BatchRollout {
+    /// The number of rollouts to play after each move.
+    rollouts: usize,
+    /// How moves are chosen during the rollouts.
+    policy: RolloutPolicy,
+    /// How the states that the rollouts end in are scored.
+    value_function: Box<dyn BatchValueFunction>,
+    /// The number of threads to play the rollouts on.
+    threads: usize,
+}
+
+impl BatchRollout {
+    /// Return a batch that plays `rollouts` random rollouts after each move on one thread,
+    /// and scores them with `NetWorthDifferential`.
+    pub fn new(rollouts: usize) -> BatchRollout {
+        BatchRollout {
+            rollouts,
+            policy: RolloutPolicy::Random,
+            value_function: Box::new(NetWorthDifferential),
+            threads: 1,
+        }
+    }
+
+    /// Choose the moves in the rollouts with `policy`.
+    pub fn with_policy(mut self, policy: RolloutPolicy) -> BatchRollout {
+        self.policy = policy;
+        self
+    }
+
+    /// Score the states that the rollouts end in with `value_function`.
+    pub fn with_value_function(
+        mut self,
+        value_function: Box<dyn BatchValueFunction>,
+    ) -> BatchRollout {
+        self.value_function = value_function;
+        self
+    }
+
+    /// Play the rollouts on `threads` threads, each with its own copy of the game.
+    /// There are no threads on the web, so this has no effect there.
+    pub fn with_threads(mut self, threads: usize) -> BatchRollout {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Play the rollouts after each of `moves` (indexes in `Game::legal_moves()`), and
+    /// return their outcomes for the acting player, in the same order. Panics if any
+    /// of `moves` isn't a legal move, or if the engine runs into a bug.
+    pub fn run(&self, game: &mut Game, moves: &[usize]) -> Vec<RolloutOutcome> {
+        let move_count = game.legal_moves().unwrap_or_else(|e| panic!("{}", e)).len();
+        if let Some(index) = moves.iter().find(|&&index| index >= move_count) {
+            panic!(
+                "move {} is not legal ({} moves available)",
+                index, move_count
+            );
+        }
+
+        // Every rollout to play, as the position of its move in `moves`
+        let jobs: Vec<usize> = (0..moves.len())
+            .flat_map(|move_i| iter::repeat_n(move_i, self.rollouts))
+            .collect();
+        let leaves = self.play(game, moves, &jobs);
+
+        let pindex = game.get_acting_pindex(game.root_handle);
+        let (states, ends): (Vec<ResolvedState>, Vec<_>) = leaves
+            .into_iter()
+            .map(|leaf| (leaf.state, (leaf.move_i, leaf.winner)))
+            .unzip();
+        let values = self
+            .value_function
+            .evaluate_batch(&states, &game.board, pindex);
+
+        let mut outcomes: Vec<RolloutOutcome> = moves
+            .iter()
+            .map(|&index| RolloutOutcome {
+                index,
+                wins: vec![0; game.diff_players(game.root_handle).len()],
+                ..RolloutOutcome::default()
+            })
+            .collect();
+        for ((move_i, winner), value) in ends.into_iter().zip(values) {
+            let outcome = &mut outcomes[move_i];
+            outcome.rollouts += 1;
+            outcome.mean_value += value;
+            match winner {
+                Some(winner) => outcome.wins[winner] += 1,
+                None => outcome.unfinished += 1,
+            }
+        }
+        for outcome in &mut outcomes {
+            outcome.mean_value /= outcome.rollouts.max(1) as f64;
+        }
+
+        outcomes
+    }
+
+    /// Play the rollouts in `jobs` across the threads, with the first share
+    /// on this thread and the rest on their own copy of the game.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn play(&self, game: &mut Game, moves: &[usize], jobs: &[usize]) -> Vec<Leaf> {
+        let share = jobs.len().div_ceil(self.threads).max(1);
+        let mut shares = jobs.chunks(share);
+        let first_share = shares.next().unwrap_or_default();
+
+        thread::scope(|s| {
+            let handles: Vec<_> = shares
+                .map(|share| {
+                    let mut game = game.clone();
+                    s.spawn(move || self.play_share(&mut game, moves, share))
+                })
+                .collect();
+
+            let mut leaves = self.play_share(game, moves, first_share);
+            leaves.extend(handles.into_iter().flat_map(|h| h.join().unwrap()));
+            leaves
+        })
+    }
+
+    /// Play every rollout in `jobs`. There are no threads on the web.
+    #[cfg(target_arch = "wasm32")]
+    fn play(&self, game: &mut Game, moves: &[usize], jobs: &[usize]) -> Vec<Leaf> {
+        self.play_share(game, moves, jobs)
+    }
+
+    /// Play a rollout after the move of each of `jobs` (positions in `moves`).
+    fn play_share(&self, game: &mut Game, moves: &[usize], jobs: &[usize]) -> Vec<Leaf> {
+        jobs.iter()
+            .map(|&move_i| {
+                let child = game.nodes[game.root_handle].children[moves[move_i]];
+                let handle = self.rollout(game, child);
+                Leaf {
+                    move_i,
+                    state: game.resolve_state(handle),
+                    winner: game.get_winner(handle).ok(),
+                }
+            })
+            .collect()
+    }
+
+    /// Play the game from the state at `handle` with the rollout policy until game-over,
+    /// or until the game has probably stalled, and return the handle of the final state.
+    fn rollout(&self, game: &mut Game, mut handle: usize) -> usize {
+        let mut moves = 0;
+        while !game.is_terminal(handle) && moves < MAX_ROLLOUT_MOVES {
+            moves += 1;
+            game.gen_children_save(handle);
+            let first_child = game.nodes[handle].children[0];
+
+            let child_index = match game.nodes[first_child].branch_type {
+                BranchType::Chance(_) => game.get_any_chance_child(handle),
+                BranchType::Choice => self.policy.choose(game, handle),
+                BranchType::Undefined => unreachable!(),
+            };
+            handle = game.nodes[handle].children[child_index];
+        }
+
+        handle
+    }
+}
+
+impl Game {
+    /// Play `n` random rollouts after each of `moves` (indexes in `Game::legal_moves()`)
+    /// on a thread per available core, and return their outcomes for the acting player.
+    /// Use `BatchRollout` directly to choose the rollout policy or to score the rollouts
+    /// with a batched model.
+    pub fn batch_rollout(&mut self, moves: &[usize], n: usize) -> Vec<RolloutOutcome> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        #[cfg(target_arch = "wasm32")]
+        let threads = 1;
+
+        BatchRollout::new(n).with_threads(threads).run(self, moves)
+    }
+}
@@ -0,0 +1,218 @@
+use super::chance_deck::ChanceDeck;
+use super::globals::{ChanceCard, GameConfig, Player};
+use super::state_diff::{FieldDiff, PropertyOwnership};
+use super::{Board, Game};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Builds a `Game` that starts from an arbitrary mid-game position, e.g. to test how
+/// an agent handles a specific tactical situation. Anything that isn't specified is
+/// the same as at the start of a normal game.
+///
+/// ```
+/// use monopoly_math::ScenarioBuilder;
+///
+/// // Player 0 is in jail with $200, and player 1 owns both brown properties at rent level 3
+/// let game = ScenarioBuilder::new(2)
+///     .with_balance(0, 200)
+///     .with_jailed(0, 2)
+///     .with_property(1, 1, 3)
+///     .with_property(3, 1, 3)
+///     .build();
+/// assert_eq!(game.current_state().players()[0].balance, 200);
+/// ```
+///
+/// The game's transcript only records the moves made after the scenario,
+/// so it can't be used to replay the game from the scenario's position.
+pub struct ScenarioBuilder {
+    /// The number of players in the game.
+    player_count: usize,
+    /// The optional rules that the game is played with.
+    config: GameConfig,
+    /// The board that the game is played on, or `None` for the board of `config.rules`.
+    board: Option<Board>,
+    /// Each player's balance, or `None` for `config.starting_balance`.
+    balances: Vec<Option<i32>>,
+    /// Each player's position around the board.
+    positions: Vec<u8>,
+    /// The number of times each player in jail can still try to roll doubles
+    /// before they have to pay to leave, or `None` if they aren't in jail.
+    jail_tries: Vec<Option<u8>>,
+    /// Whether each player has gone bankrupt.
+    bankrupt: Vec<bool>,
+    /// The owned properties, in the form `HashMap<property_position, ownership>`.
+    owned_properties: HashMap<u8, PropertyOwnership>,
+    /// The deck of chance cards, as far as the players know it.
+    chance_deck: ChanceDeck,
+    /// The index of the player whose turn it is.
+    current_pindex: usize,
+    /// The number of turns that have already been played.
+    turn: usize,
+    /// The money on 'Free parking'.
+    jackpot: i32,
+}
+
+impl ScenarioBuilder {
+    /// Return a builder for a game between `player_count` players with the default rules.
+    pub fn new(player_count: usize) -> Self {
+        ScenarioBuilder {
+            player_count,
+            config: GameConfig::default(),
+            board: None,
+            balances: vec![None; player_count],
+            positions: vec![0; player_count],
+            jail_tries: vec![None; player_count],
+            bankrupt: vec![false; player_count],
+            owned_properties: HashMap::new(),
+            chance_deck: ChanceDeck::new(),
+            current_pindex: 0,
+            turn: 0,
+            jackpot: 0,
+        }
+    }
+
+    /// Play with the optional rules in `config`.
+    pub fn with_config(mut self, config: GameConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Play on `board` instead of the board of the config's rule set.
+    pub fn with_board(mut self, board: Board) -> Self {
+        self.board = Some(board);
+        self
+    }
+
+    /// Give player `pindex` `balance` in cash.
+    pub fn with_balance(mut self, pindex: usize, balance: i32) -> Self {
+        self.balances[pindex] = Some(balance);
+        self
+    }
+
+    /// Put player `pindex` on the tile at `position`.
+    pub fn with_position(mut self, pindex: usize, position: u8) -> Self {
+        self.positions[pindex] = position;
+        self
+    }
+
+    /// Put player `pindex` in jail, where they can try to roll doubles `tries` more
+    /// times (at most one fewer than `GameConfig::jail_tries`) before they have to pay.
+    pub fn with_jailed(mut self, pindex: usize, tries: u8) -> Self {
+        self.jail_tries[pindex] = Some(tries);
+        self
+    }
+
+    /// Eliminate player `pindex` from the game.
+    pub fn with_bankrupt(mut self, pindex: usize) -> Self {
+        self.bankrupt[pindex] = true;
+        self
+    }
+
+    /// Give the property at `pos` to player `owner`, at `rent_level` (from 1 to 5).
+    pub fn with_property(mut self, pos: u8, owner: usize, rent_level: usize) -> Self {
+        self.owned_properties
+            .insert(pos, PropertyOwnership { owner, rent_level });
+        self
+    }
+
+    /// Mark `cards` as having been drawn, from least recent to most recent. Panics if
+    /// a card was drawn more times than it's in the deck.
+    pub fn with_seen_ccs(mut self, cards: Vec<ChanceCard>) -> Self {
+        self.chance_deck = ChanceDeck::with_seen(cards);
+        self
+    }
+
+    /// Make it player `pindex`'s turn to roll.
+    pub fn with_current_player(mut self, pindex: usize) -> Self {
+        self.current_pindex = pindex;
+        self
+    }
+
+    /// Start the game as if `turn` turns had already been played, which counts towards
+    /// the config's turn limit.
+    pub fn with_turn(mut self, turn: usize) -> Self {
+        self.turn = turn;
+        self
+    }
+
+    /// Put `jackpot` on 'Free parking', for games played with the jackpot house rule.
+    pub fn with_jackpot(mut self, jackpot: i32) -> Self {
+        self.jackpot = jackpot;
+        self
+    }
+
+    /// Return a game that starts from the scenario's position, with the current player
+    /// about to roll. Panics if the position is impossible, e.g. if a property is
+    /// owned by a player who doesn't exist, or a chance card was drawn too many times.
+    pub fn build(self) -> Game {
+        let board = match &self.board {
+            Some(board) => board.clone(),
+            None => self.config.rules.board(),
+        };
+        self.check(&board);
+
+        let players: Vec<Player> = (0..self.player_count)
+            .map(|i| {
+                let mut player =
+                    Player::new(self.balances[i].unwrap_or(self.config.starting_balance));
+                player.position = self.positions[i];
+                player.bankrupt = self.bankrupt[i];
+                if self.jail_tries[i].is_some() {
+                    player.send_to_jail(&board);
+                }
+                player
+            })
+            .collect();
+
+        // Jail rounds count the player's tries, including the last one that they pay on
+        let max_tries = self.config.jail_tries.saturating_sub(1);
+        let jail_rounds: Vec<u8> = self
+            .jail_tries
+            .iter()
+            .map(|tries| tries.map_or(0, |t| t.min(max_tries) + 1))
+            .collect();
+
+        let mut game = Game::new_with_board(self.player_count, board, self.config);
+        let root = &mut game.nodes[game.root_handle];
+        root.set_diff(FieldDiff::Players(players));
+        root.set_diff(FieldDiff::JailRounds(jail_rounds));
+        root.set_diff(FieldDiff::CurrentPlayer(self.current_pindex));
+        root.set_diff(FieldDiff::OwnedProperties(Arc::new(self.owned_properties)));
+        root.set_diff(FieldDiff::ChanceDeck(self.chance_deck));
+        root.set_diff(FieldDiff::Jackpot(self.jackpot));
+        game.root_turn = self.turn;
+        game.rehash();
+
+        game
+    }
+
+    /// Panic if the scenario's position can't happen on `board`.
+    fn check(&self, board: &Board) {
+        let player_count = self.player_count;
+
+        if self.current_pindex >= player_count || self.bankrupt[self.current_pindex] {
+            panic!("player {} can't take the current turn", self.current_pindex);
+        }
+
+        for (i, &pos) in self.positions.iter().enumerate() {
+            if pos >= board.size {
+                panic!("player {} is off the board at position {}", i, pos);
+            }
+        }
+
+        for (&pos, prop) in &self.owned_properties {
+            if !board.is_property(pos) {
+                panic!("there is no property at position {}", pos);
+            }
+            if prop.owner >= player_count || self.bankrupt[prop.owner] {
+                panic!(
+                    "the property at {} can't be owned by player {}",
+                    pos, prop.owner
+                );
+            }
+            if !(1..=5).contains(&prop.rent_level) {
+                panic!("rent level {} is out of range", prop.rent_level);
+            }
+        }
+    }
+}
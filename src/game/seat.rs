@@ -0,0 +1,93 @@
+use super::agent::{Agent, Decision};
+use super::error::catch_internal;
+use super::globals::{seed_rng, with_rng};
+use super::{Game, GameError};
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A player's agent, as the game loop keeps it. An agent with a decision timeout decides on
+/// its own thread, so if it runs out of time, the game can go on without it while it's
+/// still deciding, and it only gets its agent back once that decision is done. The agent
+/// is told to stop (see `Game::is_cancelled()`) as soon as it runs out of time, but an
+/// agent that doesn't check for that keeps its thread busy until it's done regardless.
+pub(super) struct Seat {
+    /// The agent, unless it's still making a decision that it ran out of time for.
+    agent: Option<Box<dyn Agent>>,
+    /// Gives the agent back, along with the decision it came to too late, once it's done.
+    pending: Option<Receiver<(Box<dyn Agent>, Decision)>>,
+}
+
+impl Seat {
+    /// Return a seat for `agent`.
+    pub fn new(agent: Box<dyn Agent>) -> Self {
+        Seat {
+            agent: Some(agent),
+            pending: None,
+        }
+    }
+
+    /// Ask the agent to decide what to do at the root of `game`. If there's a `timeout`, the
+    /// agent decides with a copy of `game` on its own thread, and `None` is returned if it
+    /// doesn't decide in time, or if it's still busy with a decision it ran out of time for.
+    /// There are no threads on the web, so timeouts aren't enforced there. An error is
+    /// returned if the agent (which plays for player `pindex`) panics while deciding.
+    pub fn decide(
+        &mut self,
+        game: &mut Game,
+        pindex: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Decision>, GameError> {
+        let panicked = GameError::AgentPanicked { pindex };
+
+        // Take the agent back if it has finished the decision it ran out of time for
+        if let Some(pending) = &self.pending {
+            match pending.try_recv() {
+                Ok((agent, _)) => {
+                    self.agent = Some(agent);
+                    self.pending = None;
+                }
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(panicked),
+            }
+        }
+
+        let mut agent = self.agent.take().unwrap();
+        let Some(timeout) = timeout.filter(|_| cfg!(not(target_arch = "wasm32"))) else {
+            let decision = catch_internal(|| agent.decide(game)).map_err(|_| panicked)?;
+            self.agent = Some(agent);
+            return Ok(Some(decision));
+        };
+
+        // The observers aren't cloned, so they don't hear about the agent's search
+        let mut game = game.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        game.cancelled = Some(cancelled.clone());
+        // The thread gets its own random number generator, which is seeded from this
+        // one so that seeded games are still reproducible
+        let seed = with_rng(|rng| rng.gen());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            seed_rng(seed);
+            let decision = agent.decide(&mut game);
+            // The game may have ended (and dropped the receiver) in the meantime
+            let _ = sender.send((agent, decision));
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok((agent, decision)) => {
+                self.agent = Some(agent);
+                Ok(Some(decision))
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                cancelled.store(true, Ordering::Relaxed);
+                self.pending = Some(receiver);
+                Ok(None)
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(panicked),
+        }
+    }
+}
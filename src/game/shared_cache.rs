@@ -0,0 +1,125 @@
+use super::opening_book::OpeningBook;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The number of independently locked parts that the transposition table is split into,
+/// so that threads looking up different states rarely wait for each other.
+const SHARDS: usize = 64;
+
+/// The most visits that a cached estimate counts as when it seeds a new search node,
+/// so that the search can still overrule estimates that were made with little effort.
+const MAX_SEED_VISITS: u32 = 16;
+
+/// The value that searches estimated for a state, for the player who was searching.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ValueEstimate {
+    /// The sum of the values of every visit to the state.
+    pub total_value: f64,
+    /// The number of visits to the state, over every recorded search.
+    pub visits: u32,
+}
+
+impl ValueEstimate {
+    /// Return the average value of a visit to the state.
+    pub fn mean_value(&self) -> f64 {
+        self.total_value / self.visits as f64
+    }
+
+    /// Return this estimate with at most `MAX_SEED_VISITS` visits, but the same average value.
+    pub(super) fn as_seed(&self) -> ValueEstimate {
+        let visits = self.visits.min(MAX_SEED_VISITS);
+        ValueEstimate {
+            total_value: self.mean_value() * visits as f64,
+            visits,
+        }
+    }
+}
+
+/// Knowledge about the game that MCTS agents build up and share across every game and
+/// thread they play on, so that each game doesn't have to rediscover it: an opening book
+/// and a transposition table of the values that searches found for states. States are
+/// looked up by their Zobrist hashes, so unlike the opening book, the transposition table
+/// helps wherever the same state is reached, even by different moves.
+///
+/// ```
+/// use monopoly_math::{OpeningBook, SharedCache, ValueEstimate};
+///
+/// let cache = SharedCache::new(OpeningBook::new(8));
+/// cache.record(0x1234, 0, 3., 4);
+/// cache.record(0x1234, 0, 1., 4);
+///
+/// let estimate = cache.estimate(0x1234, 0).unwrap();
+/// assert_eq!(estimate, ValueEstimate { total_value: 4., visits: 8 });
+/// assert_eq!(estimate.mean_value(), 0.5);
+/// // Values are kept separately for each searching player
+/// assert_eq!(cache.estimate(0x1234, 1), None);
+/// ```
+pub struct SharedCache {
+    /// The opening book that the agents consult and add their searches to.
+    opening_book: Arc<Mutex<OpeningBook>>,
+    /// The value estimates keyed by state hash and searching player, split into `SHARDS`
+    /// parts by state hash.
+    transpositions: Vec<Mutex<HashMap<(u64, usize), ValueEstimate>>>,
+}
+
+impl SharedCache {
+    /// Return a cache with `opening_book` and an empty transposition table.
+    pub fn new(opening_book: OpeningBook) -> Self {
+        SharedCache::with_shared_book(Arc::new(Mutex::new(opening_book)))
+    }
+
+    /// Return a cache with an empty transposition table, whose opening
+    /// book is `opening_book`, which can be shared with other agents.
+    pub fn with_shared_book(opening_book: Arc<Mutex<OpeningBook>>) -> Self {
+        SharedCache {
+            opening_book,
+            transpositions: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    /// Return the opening book of this cache.
+    pub fn opening_book(&self) -> &Arc<Mutex<OpeningBook>> {
+        &self.opening_book
+    }
+
+    /// Return the value that searches by player `pindex` estimated for the state with
+    /// Zobrist hash `hash`, if it's been recorded.
+    pub fn estimate(&self, hash: u64, pindex: usize) -> Option<ValueEstimate> {
+        self.shard(hash)
+            .lock()
+            .unwrap()
+            .get(&(hash, pindex))
+            .copied()
+    }
+
+    /// Add `visits` visits with a total value of `total_value` for player `pindex`
+    /// to the estimate of the state with Zobrist hash `hash`.
+    pub fn record(&self, hash: u64, pindex: usize, total_value: f64, visits: u32) {
+        if visits == 0 {
+            return;
+        }
+
+        let mut shard = self.shard(hash).lock().unwrap();
+        let estimate = shard.entry((hash, pindex)).or_default();
+        estimate.total_value += total_value;
+        estimate.visits += visits;
+    }
+
+    /// Return the number of value estimates in the transposition table.
+    pub fn len(&self) -> usize {
+        self.transpositions
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    /// Return whether the transposition table has no value estimates.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the part of the transposition table that the state with hash `hash` is in.
+    fn shard(&self, hash: u64) -> &Mutex<HashMap<(u64, usize), ValueEstimate>> {
+        &self.transpositions[hash as usize % SHARDS]
+    }
+}
@@ -1,10 +1,16 @@
+use super::board::Board;
+use super::chance_deck::ChanceDeck;
+use super::classic::ClassicCard;
 use super::globals::*;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 
 /*********        BRANCH TYPE        *********/
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 /// The type of branch that led to a game state.
 pub enum BranchType {
     /// A game state that was achieved by chance (e.g. by rolling the dice / getting a chance card).
@@ -17,7 +23,7 @@ pub enum BranchType {
 
 /*********        PROPERTY OWNERSHIP        *********/
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 /// Information about a property related to its ownership.
 pub struct PropertyOwnership {
     /// The index of the player who owns this property
@@ -58,45 +64,183 @@ impl PropertyOwnership {
     }
 }
 
+/*********        TRADE OFFER        *********/
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+/// A property trade proposed by one player to another.
+pub struct TradeOffer {
+    /// The index of the player proposing the trade.
+    pub proposer: usize,
+    /// The index of the player who has to accept or decline the trade.
+    pub receiver: usize,
+    /// The position of the property that the proposer gives up.
+    pub give: u8,
+    /// The position of the property that the proposer receives.
+    pub take: u8,
+    /// The cash that the proposer pays the receiver on top of the
+    /// properties. A negative amount is paid by the receiver instead.
+    pub cash: i32,
+}
+
+/*********        AUCTION PROGRESS        *********/
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+/// The state of an auction that's being played out with real bids.
+pub struct AuctionProgress {
+    /// The position of the property being auctioned.
+    pub pos: u8,
+    /// The highest bid so far, which is 0 if nobody has bid yet.
+    pub high_bid: i32,
+    /// The index of the player who made the highest bid, if anyone has bid yet.
+    pub high_bidder: Option<usize>,
+    /// The index of the player who has to bid or drop out next.
+    pub bidder: usize,
+    /// The players who have dropped out of the auction, as a bitmap of player indexes.
+    pub dropped: u32,
+}
+
+impl AuctionProgress {
+    /// The amounts that a bidder can raise the highest bid by.
+    pub const BID_INCREMENTS: [i32; 2] = [20, 100];
+
+    /// Return a new auction of the property at `pos`, with `first_bidder` bidding first.
+    pub fn new(pos: u8, first_bidder: usize) -> Self {
+        AuctionProgress {
+            pos,
+            high_bid: 0,
+            high_bidder: None,
+            bidder: first_bidder,
+            dropped: 0,
+        }
+    }
+
+    /// Return whether the player at `pindex` has dropped out of the auction.
+    pub fn has_dropped(&self, pindex: usize) -> bool {
+        self.dropped & (1 << pindex) != 0
+    }
+}
+
 /*********        MOVE TYPE        *********/
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MoveType {
     Undefined,
     Roll,
     Property,
-    SellProperty,
+    /// A player has a negative balance and has to sell properties to cover
+    /// their debt, or be eliminated from the game if they can't.
+    SellProperty {
+        /// The player owed the debt, or `None` if it's owed to the bank.
+        creditor: Option<usize>,
+        /// The move to be made once the debt has been settled.
+        then: Box<MoveType>,
+    },
     Auction,
     Location,
     ChanceCard,
     ChoicefulCC(ChanceCard),
+    /// The current player may propose a trade before rolling.
+    Trade,
+    /// A player has to accept or decline a proposed trade.
+    TradeOffer(TradeOffer),
+    /// A player has to raise the highest bid in a real auction, or drop out of it.
+    Bid(AuctionProgress),
+    /// The current player draws a Community Chest card (classic Monopoly only).
+    CommunityChest,
+    /// The current player pays the tax of the tile they're on.
+    Tax,
+    /// The current player may build houses and hotels before rolling (classic Monopoly only).
+    Build,
 }
 
 impl MoveType {
-    pub fn when_landed_on(tile: u8) -> MoveType {
-        if PROP_POSITIONS.contains(&tile) {
+    /// Return the name of the variant, without any of its fields (e.g. "SellProperty").
+    pub fn name(&self) -> &'static str {
+        match self {
+            MoveType::Undefined => "Undefined",
+            MoveType::Roll => "Roll",
+            MoveType::Property => "Property",
+            MoveType::SellProperty { .. } => "SellProperty",
+            MoveType::Auction => "Auction",
+            MoveType::Location => "Location",
+            MoveType::ChanceCard => "ChanceCard",
+            MoveType::ChoicefulCC(_) => "ChoicefulCC",
+            MoveType::Trade => "Trade",
+            MoveType::TradeOffer(_) => "TradeOffer",
+            MoveType::Bid(_) => "Bid",
+            MoveType::CommunityChest => "CommunityChest",
+            MoveType::Tax => "Tax",
+            MoveType::Build => "Build",
+        }
+    }
+
+    pub fn when_landed_on(tile: u8, board: &Board) -> MoveType {
+        if board.is_property(tile) {
             MoveType::Property
-        } else if CC_POSITIONS.contains(&tile) {
+        } else if board.cc_positions.contains(&tile) {
             MoveType::ChanceCard
-        } else if LOC_POSITIONS.contains(&tile) {
+        } else if board.loc_positions.contains(&tile) {
             MoveType::Location
+        } else if board.community_chest_positions.contains(&tile) {
+            MoveType::CommunityChest
+        } else if board.tax_positions.contains_key(&tile) {
+            MoveType::Tax
         } else {
             MoveType::Roll
         }
     }
 
     pub fn is_roll(&self) -> bool {
-        match self {
-            MoveType::Roll => true,
-            _ => false,
-        }
+        matches!(self, MoveType::Roll)
+    }
+
+    pub fn is_sell_property(&self) -> bool {
+        matches!(self, MoveType::SellProperty { .. })
+    }
+}
+
+/*********        DIFF ID        *********/
+
+/// Identifies one of the fields of a game state. The value of each variant is the
+/// bit that marks the field as present in `StateDiff::present_diffs`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiffID {
+    Jackpot,
+    Level1Rent,
+    ChanceDeck,
+    OwnedProperties,
+    CurrentPlayer,
+    Players,
+    JailRounds,
+}
+
+impl DiffID {
+    /// Return every field, from the lowest bit to the highest.
+    pub fn all() -> [DiffID; 7] {
+        [
+            DiffID::Jackpot,
+            DiffID::Level1Rent,
+            DiffID::ChanceDeck,
+            DiffID::OwnedProperties,
+            DiffID::CurrentPlayer,
+            DiffID::Players,
+            DiffID::JailRounds,
+        ]
+    }
+
+    /// Return the bitmask of this field in `StateDiff::present_diffs`.
+    pub fn bit(self) -> u8 {
+        1 << self as u8
     }
 }
 
 /*********        FIELD DIFF        *********/
 
-/// A field or property of a game state. There are 8 different fields (8 variants of this enum).
-#[derive(Debug, Clone)]
+/// A field or property of a game state. There are 7 different fields (7 variants of this enum).
+/// The owned properties are reference-counted, since a hashmap is expensive to clone. Cloning
+/// a diff (e.g. into the transcript, or when the root state is filled in) doesn't copy them,
+/// and sibling states with the same owned properties can share them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FieldDiff {
     /// The players playing the game.
     Players(Vec<Player>),
@@ -104,20 +248,89 @@ pub enum FieldDiff {
     CurrentPlayer(usize),
     /// A hashmap of properties owned by the players, with the
     /// keys being the position of a property around the board.
-    OwnedProperties(HashMap<u8, PropertyOwnership>),
-    /// The chance cards that have been used, ordered from least recent to most recent.
-    SeenCCs(Vec<ChanceCard>),
-    /// The starting index of `SeenCCs`.
-    SeenCCsHead(usize),
+    OwnedProperties(Arc<HashMap<u8, PropertyOwnership>>),
+    /// The deck of chance cards, as far as the players know it.
+    ChanceDeck(ChanceDeck),
     /// The number of rounds to go before the effect of the chance card
     /// "all players pay level 1 rent for the next two rounds" wears off.
     Level1Rent(u8),
+    /// The number of tries that each player in jail has left to roll doubles,
+    /// including the last one, after which they leave jail whatever they roll.
     JailRounds(Vec<u8>),
+    /// The money waiting on 'Free parking' for the next player to land there.
+    Jackpot(i32),
+}
+
+impl FieldDiff {
+    /// Return the field that this diff changes.
+    pub fn id(&self) -> DiffID {
+        match self {
+            FieldDiff::Players(_) => DiffID::Players,
+            FieldDiff::CurrentPlayer(_) => DiffID::CurrentPlayer,
+            FieldDiff::OwnedProperties(_) => DiffID::OwnedProperties,
+            FieldDiff::ChanceDeck(_) => DiffID::ChanceDeck,
+            FieldDiff::Level1Rent(_) => DiffID::Level1Rent,
+            FieldDiff::JailRounds(_) => DiffID::JailRounds,
+            FieldDiff::Jackpot(_) => DiffID::Jackpot,
+        }
+    }
+}
+
+impl std::fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldDiff::Players(players) => {
+                let players: Vec<String> = players
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| {
+                        if f.alternate() {
+                            format!("p{} {:#}", i, p)
+                        } else {
+                            format!("p{} {}", i, p)
+                        }
+                    })
+                    .collect();
+                write!(f, "players: {}", players.join(", "))
+            }
+            FieldDiff::CurrentPlayer(i) => write!(f, "current player: {}", i),
+            FieldDiff::OwnedProperties(props) => {
+                let mut positions: Vec<&u8> = props.keys().collect();
+                positions.sort();
+
+                let props: Vec<String> = positions
+                    .into_iter()
+                    .map(|pos| {
+                        format!(
+                            "{} p{} lvl {}",
+                            pos, props[pos].owner, props[pos].rent_level
+                        )
+                    })
+                    .collect();
+                write!(f, "owned properties: {}", props.join(", "))
+            }
+            FieldDiff::ChanceDeck(deck) => match deck.next_card() {
+                Some(card) => write!(f, "next chance card: {:?}", card),
+                None => write!(f, "seen chance cards: {}", deck.seen().len()),
+            },
+            FieldDiff::Level1Rent(rounds) => write!(f, "level 1 rent turns: {}", rounds),
+            FieldDiff::JailRounds(rounds) => write!(f, "jail rounds: {:?}", rounds),
+            FieldDiff::Jackpot(jackpot) => write!(f, "free parking jackpot: ${}", jackpot),
+        }
+    }
 }
 
 /*********        STATE DIFF        *********/
 
-#[derive(Debug, Clone)]
+/// The diff fields of a state. Most states only change a couple of fields,
+/// so these are stored inline to avoid a heap allocation for every state.
+pub type Diffs = SmallVec<[FieldDiff; 2]>;
+
+/// The handles of a state's children. Many choices are between two options
+/// (e.g. buying or auctioning a property), so two children are stored inline.
+pub type Children = SmallVec<[usize; 2]>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateDiff {
     pub present_diffs: u8,
     /// Changes to the game state since the previous (parent) state.
@@ -127,11 +340,20 @@ pub struct StateDiff {
     /// 1. `FieldDiff::Players`
     /// 2. `FieldDiff::CurrentPlayer`
     /// 3. `FieldDiff::OwnedProperties`
-    /// 4. `FieldDiff::SeenCCs`
-    /// 5. `FieldDiff::SeenCCsHead`
-    pub diffs: Vec<FieldDiff>,
+    /// 4. `FieldDiff::ChanceDeck`
+    /// 5. `FieldDiff::Level1Rent`
+    /// 6. `FieldDiff::Jackpot`
+    pub diffs: Diffs,
+    /// The number of states between this one and the nearest ancestor (or itself)
+    /// that stores every field, which bounds how far a field lookup has to walk.
+    #[serde(default)]
+    pub chain_length: usize,
+    /// The Zobrist hash of the whole state (including `next_move`), which is updated
+    /// from the parent's hash as the state is appended to the game tree.
+    #[serde(skip)]
+    pub hash: u64,
     pub parent: usize,
-    pub children: Vec<usize>,
+    pub children: Children,
     pub branch_type: BranchType,
     /// The type of move to be made after a state.
     /// This is not in `diffs` as it changes every move.
@@ -146,61 +368,61 @@ impl StateDiff {
     /// Return a new `StateDiff` without any diff fields.
     pub fn new_with_parent(parent: usize) -> Self {
         StateDiff {
-            diffs: vec![],
+            diffs: Diffs::new(),
             present_diffs: 0,
+            chain_length: 0,
+            hash: 0,
             parent,
-            children: vec![],
+            children: Children::new(),
             branch_type: BranchType::Undefined,
             next_move: MoveType::Undefined,
             message: DiffMessage::None,
         }
     }
 
-    /// Return a new `StateDiff` initialised to the root state of a game.
-    pub fn new_root(player_count: usize) -> Self {
-        Self {
-            diffs: vec![
+    /// Return a new `StateDiff` initialised to the root state of a game,
+    /// where every player starts with `starting_balance` in cash.
+    pub fn new_root(player_count: usize, starting_balance: i32) -> Self {
+        let mut root = Self {
+            diffs: smallvec::smallvec![
                 FieldDiff::JailRounds(vec![0; player_count]),
-                FieldDiff::Players(vec![Player::new(); player_count]),
+                FieldDiff::Players(vec![Player::new(starting_balance); player_count]),
                 FieldDiff::CurrentPlayer(0),
-                FieldDiff::OwnedProperties(HashMap::new()),
-                FieldDiff::SeenCCs(vec![]),
-                FieldDiff::SeenCCsHead(0),
+                FieldDiff::OwnedProperties(Arc::default()),
+                FieldDiff::ChanceDeck(ChanceDeck::new()),
                 FieldDiff::Level1Rent(0),
+                FieldDiff::Jackpot(0),
             ],
-            present_diffs: 0b11111110,
+            present_diffs: 0b1111111,
+            chain_length: 0,
+            hash: 0,
             parent: 0,
-            children: vec![],
+            children: Children::new(),
             branch_type: BranchType::Undefined,
             next_move: MoveType::Roll,
             message: DiffMessage::None,
-        }
+        };
+
+        root.hash = root.full_hash();
+        root
     }
 
     /*********        HELPERS        *********/
 
     /// Return whether the specified diff field is being tracked.
     pub fn diff_exists(&self, diff_id: DiffID) -> bool {
-        (self.present_diffs >> diff_id as u8) & 1 == 1
+        self.present_diffs & diff_id.bit() != 0
     }
 
     /// Return the index of the specified diff in `self.diffs` if it were to exist.
+    /// Diffs are stored from the highest bit to the lowest, so this
+    /// is the number of present diffs with a higher bit.
     pub fn get_supposed_diff_index(&self, diff_id: DiffID) -> usize {
-        let relevant_bits = self.present_diffs >> diff_id as u8;
-
-        let high_bit_sum = (relevant_bits >> 1 & 1)
-            + (relevant_bits >> 2 & 1)
-            + (relevant_bits >> 3 & 1)
-            + (relevant_bits >> 4 & 1)
-            + (relevant_bits >> 5 & 1)
-            + (relevant_bits >> 6 & 1)
-            + (relevant_bits >> 7 & 1);
-
-        high_bit_sum.into()
+        (self.present_diffs >> diff_id as u8 >> 1).count_ones() as usize
     }
 
     /// Return the index of the specified diff in `self.diffs`,
-    ///  or `None` if the state doesn't track it.
+    /// or `None` if the state doesn't track it.
     pub fn get_diff_index(&self, diff_id: DiffID) -> Option<usize> {
         if !self.diff_exists(diff_id) {
             return None;
@@ -209,20 +431,27 @@ impl StateDiff {
         Some(self.get_supposed_diff_index(diff_id))
     }
 
-    /// Insert the specified diff, or update it if it  
-    /// already exists. Return a mutable reference to the diff.
-    pub fn set_diff(&mut self, diff_id: DiffID, diff: FieldDiff) {
-        // Get the new index of the diff field
+    /// Return the specified diff, or `None` if the state doesn't track it.
+    pub fn get_diff(&self, diff_id: DiffID) -> Option<&FieldDiff> {
+        self.get_diff_index(diff_id).map(|i| &self.diffs[i])
+    }
+
+    /// Return the specified diff mutably, or `None` if the state doesn't track it.
+    pub fn get_diff_mut(&mut self, diff_id: DiffID) -> Option<&mut FieldDiff> {
+        let i = self.get_diff_index(diff_id)?;
+        Some(&mut self.diffs[i])
+    }
+
+    /// Insert the diff, or replace it if the state already tracks that field.
+    pub fn set_diff(&mut self, diff: FieldDiff) {
+        let diff_id = diff.id();
         let diff_index = self.get_supposed_diff_index(diff_id);
 
         if self.diff_exists(diff_id) {
-            // Set the diff
             self.diffs[diff_index] = diff;
         } else {
-            // Insert the diff
             self.diffs.insert(diff_index, diff);
-            // Amend the diff presence flag
-            self.present_diffs |= 1 << diff_id as u8;
+            self.present_diffs |= diff_id.bit();
         }
     }
 
@@ -230,53 +459,123 @@ impl StateDiff {
 
     /// Set a `players` vector as the state's own diff.
     pub fn set_players(&mut self, players: Vec<Player>) {
-        self.set_diff(DiffID::Players, FieldDiff::Players(players));
+        self.set_diff(FieldDiff::Players(players));
     }
 
     pub fn set_current_pindex(&mut self, curr_player: usize) {
-        self.set_diff(DiffID::CurrentPlayer, FieldDiff::CurrentPlayer(curr_player));
+        self.set_diff(FieldDiff::CurrentPlayer(curr_player));
     }
 
-    pub fn set_owned_properties(&mut self, owned_properties: HashMap<u8, PropertyOwnership>) {
-        self.set_diff(
-            DiffID::OwnedProperties,
-            FieldDiff::OwnedProperties(owned_properties),
-        );
+    pub fn set_owned_properties(
+        &mut self,
+        owned_properties: impl Into<Arc<HashMap<u8, PropertyOwnership>>>,
+    ) {
+        self.set_diff(FieldDiff::OwnedProperties(owned_properties.into()));
     }
 
-    /// Set a `seen_ccs` vector as the state's own diff.
-    pub fn set_seen_ccs(&mut self, seen_ccs: Vec<ChanceCard>) {
-        self.set_diff(DiffID::SeenCcs, FieldDiff::SeenCCs(seen_ccs));
-    }
-
-    pub fn set_top_cc(&mut self, seen_ccs_head: usize) {
-        self.set_diff(DiffID::SeenCcsHead, FieldDiff::SeenCCsHead(seen_ccs_head));
+    pub fn set_chance_deck(&mut self, deck: ChanceDeck) {
+        self.set_diff(FieldDiff::ChanceDeck(deck));
     }
 
     pub fn set_level_1_rent(&mut self, rent: u8) {
-        self.set_diff(DiffID::Level1Rent, FieldDiff::Level1Rent(rent));
+        self.set_diff(FieldDiff::Level1Rent(rent));
     }
 
     pub fn set_jail_rounds(&mut self, jail_rounds: Vec<u8>) {
-        self.set_diff(DiffID::JailRounds, FieldDiff::JailRounds(jail_rounds));
+        self.set_diff(FieldDiff::JailRounds(jail_rounds));
+    }
+
+    pub fn set_jackpot(&mut self, jackpot: i32) {
+        self.set_diff(FieldDiff::Jackpot(jackpot));
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A description of the move that led to a state.
 pub enum DiffMessage {
     None,
     Roll(u8),
     RollDoubles(u8),
     RollToJail,
     StayInJail,
-    LandOwnProp,
-    LandOppProp,
-    BuyProp,
-    AuctionProp,
+    /// The current player landed on their own property at this position.
+    LandOwnProp(u8),
+    /// The current player landed on an opponent's property and paid them rent.
+    LandOppProp {
+        pos: u8,
+        owner: usize,
+        rent: i32,
+    },
+    /// The current player bought the property at `pos` for `price`.
+    BuyProp {
+        pos: u8,
+        price: i32,
+    },
+    /// The current player put the property at this position up for auction.
+    AuctionProp(u8),
+    /// A player in debt sold the properties at these positions to the bank.
+    SellProps(Vec<u8>),
     AfterAuction(usize, i32),
+    /// A player bid `amount` for the property at `pos` in a real auction.
+    Bid {
+        pos: u8,
+        bidder: usize,
+        amount: i32,
+    },
+    /// A player dropped out of a real auction.
+    PassBid(usize),
     Location(u8),
     NoLocation,
     ChanceCard(ChanceCard),
+    Bankrupt(usize),
+    /// This player resigned from the game.
+    Resign(usize),
+    /// The current player drew a card from the classic Chance or Community Chest deck.
+    ClassicCard(ClassicCard),
+    /// The current player paid this much tax to the bank.
+    PayTax(i32),
+    /// The current player built a house (or a hotel) on the property at this position.
+    Build(u8),
+    /// The current player finished building.
+    NoBuild,
+    NoTrade,
+    ProposeTrade(TradeOffer),
+    AcceptTrade,
+    DeclineTrade,
+}
+
+impl DiffMessage {
+    /// Return the name of the variant, without any of its fields (e.g. "LandOppProp").
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffMessage::None => "None",
+            DiffMessage::Roll(_) => "Roll",
+            DiffMessage::RollDoubles(_) => "RollDoubles",
+            DiffMessage::RollToJail => "RollToJail",
+            DiffMessage::StayInJail => "StayInJail",
+            DiffMessage::LandOwnProp(_) => "LandOwnProp",
+            DiffMessage::LandOppProp { .. } => "LandOppProp",
+            DiffMessage::BuyProp { .. } => "BuyProp",
+            DiffMessage::AuctionProp(_) => "AuctionProp",
+            DiffMessage::SellProps(_) => "SellProps",
+            DiffMessage::AfterAuction(_, _) => "AfterAuction",
+            DiffMessage::Bid { .. } => "Bid",
+            DiffMessage::PassBid(_) => "PassBid",
+            DiffMessage::Location(_) => "Location",
+            DiffMessage::NoLocation => "NoLocation",
+            DiffMessage::ChanceCard(_) => "ChanceCard",
+            DiffMessage::Bankrupt(_) => "Bankrupt",
+            DiffMessage::Resign(_) => "Resign",
+            DiffMessage::ClassicCard(_) => "ClassicCard",
+            DiffMessage::PayTax(_) => "PayTax",
+            DiffMessage::Build(_) => "Build",
+            DiffMessage::NoBuild => "NoBuild",
+            DiffMessage::NoTrade => "NoTrade",
+            DiffMessage::ProposeTrade(_) => "ProposeTrade",
+            DiffMessage::AcceptTrade => "AcceptTrade",
+            DiffMessage::DeclineTrade => "DeclineTrade",
+        }
+    }
 }
 
 impl std::fmt::Display for DiffMessage {
@@ -287,18 +586,138 @@ impl std::fmt::Display for DiffMessage {
             DiffMessage::RollDoubles(p) => format!("roll to {} (doubles)", p),
             DiffMessage::RollToJail => "roll to jail".to_string(),
             DiffMessage::StayInJail => "stay in jail".to_string(),
-            DiffMessage::LandOwnProp => "raise rent".to_string(),
-            DiffMessage::LandOppProp => "pay and raise rent".to_string(),
-            DiffMessage::BuyProp => "buy property".to_string(),
-            DiffMessage::AuctionProp => "auction property".to_string(),
+            DiffMessage::LandOwnProp(pos) => format!("raise rent of {}", pos),
+            DiffMessage::LandOppProp { pos, owner, rent } => format!(
+                "pay ${} to player {} and raise rent of {}",
+                rent, owner, pos
+            ),
+            DiffMessage::BuyProp { pos, price } => format!("buy {} for ${}", pos, price),
+            DiffMessage::AuctionProp(pos) => format!("auction {}", pos),
+            DiffMessage::SellProps(positions) => format!("sell {:?}", positions),
             DiffMessage::AfterAuction(i, m) => {
                 format!("auction to {} for ${}", i, m)
             }
+            DiffMessage::Bid {
+                pos,
+                bidder,
+                amount,
+            } => format!("player {} bids ${} for {}", bidder, amount, pos),
+            DiffMessage::PassBid(i) => format!("player {} drops out of the auction", i),
             DiffMessage::Location(l) => format!("teleport to {}", l),
             DiffMessage::NoLocation => "don't teleport".to_string(),
             DiffMessage::ChanceCard(cc) => format!("get chance card '{:#?}'", cc),
+            DiffMessage::Bankrupt(i) => format!("player {} goes bankrupt", i),
+            DiffMessage::Resign(i) => format!("player {} resigns", i),
+            DiffMessage::ClassicCard(card) => format!("get card '{:?}'", card),
+            DiffMessage::PayTax(tax) => format!("pay ${} tax", tax),
+            DiffMessage::Build(pos) => format!("build on {}", pos),
+            DiffMessage::NoBuild => "don't build".to_string(),
+            DiffMessage::NoTrade => "don't trade".to_string(),
+            DiffMessage::ProposeTrade(t) => format!(
+                "offer player {} property {} for {} with ${} cash",
+                t.receiver, t.give, t.take, t.cash
+            ),
+            DiffMessage::AcceptTrade => "accept trade".to_string(),
+            DiffMessage::DeclineTrade => "decline trade".to_string(),
         };
 
         write!(f, "{}", msg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a diff of the field `id`.
+    fn diff_of(id: DiffID) -> FieldDiff {
+        match id {
+            DiffID::Jackpot => FieldDiff::Jackpot(0),
+            DiffID::Level1Rent => FieldDiff::Level1Rent(0),
+            DiffID::ChanceDeck => FieldDiff::ChanceDeck(ChanceDeck::new()),
+            DiffID::OwnedProperties => FieldDiff::OwnedProperties(Arc::default()),
+            DiffID::CurrentPlayer => FieldDiff::CurrentPlayer(0),
+            DiffID::Players => FieldDiff::Players(vec![]),
+            DiffID::JailRounds => FieldDiff::JailRounds(vec![]),
+        }
+    }
+
+    /// Return the fields of the diffs in `state`, in the order they're stored in.
+    fn stored_ids(state: &StateDiff) -> Vec<DiffID> {
+        state.diffs.iter().map(FieldDiff::id).collect()
+    }
+
+    #[test]
+    fn diffs_are_stored_from_the_highest_bit_down_in_any_insertion_order() {
+        let mut highest_first = DiffID::all();
+        highest_first.reverse();
+
+        let orders = [
+            DiffID::all(),
+            highest_first,
+            [
+                DiffID::CurrentPlayer,
+                DiffID::Jackpot,
+                DiffID::JailRounds,
+                DiffID::ChanceDeck,
+                DiffID::Players,
+                DiffID::Level1Rent,
+                DiffID::OwnedProperties,
+            ],
+        ];
+
+        for order in orders {
+            let mut state = StateDiff::new_with_parent(0);
+            for id in order {
+                state.set_diff(diff_of(id));
+            }
+
+            assert_eq!(stored_ids(&state), highest_first);
+            assert_eq!(state.present_diffs.count_ones(), 7);
+        }
+    }
+
+    #[test]
+    fn setting_a_present_diff_replaces_it() {
+        let mut state = StateDiff::new_with_parent(0);
+        state.set_players(vec![]);
+        state.set_level_1_rent(4);
+        state.set_jail_rounds(vec![]);
+        state.set_level_1_rent(2);
+
+        assert_eq!(
+            stored_ids(&state),
+            [DiffID::JailRounds, DiffID::Players, DiffID::Level1Rent]
+        );
+        assert!(matches!(
+            state.get_diff(DiffID::Level1Rent),
+            Some(FieldDiff::Level1Rent(2))
+        ));
+    }
+
+    #[test]
+    fn supposed_index_counts_the_present_diffs_with_higher_bits() {
+        let mut state = StateDiff::new_with_parent(0);
+        for id in DiffID::all() {
+            assert_eq!(state.get_supposed_diff_index(id), 0);
+        }
+
+        state.set_players(vec![]);
+        state.set_chance_deck(ChanceDeck::new());
+        let expected = [
+            (DiffID::JailRounds, 0),
+            (DiffID::Players, 0),
+            (DiffID::CurrentPlayer, 1),
+            (DiffID::OwnedProperties, 1),
+            (DiffID::ChanceDeck, 1),
+            (DiffID::Level1Rent, 2),
+            (DiffID::Jackpot, 2),
+        ];
+        for (id, index) in expected {
+            assert_eq!(state.get_supposed_diff_index(id), index, "{:?}", id);
+        }
+
+        assert_eq!(state.get_diff_index(DiffID::ChanceDeck), Some(1));
+        assert_eq!(state.get_diff_index(DiffID::Jackpot), None);
+    }
+}
@@ -0,0 +1,243 @@
+use super::chance_deck::ChanceDeck;
+use super::globals::{ChanceCard, Player, PROPERTY_TAX};
+use super::state_diff::{BranchType, DiffMessage, MoveType, PropertyOwnership};
+use super::{Board, Game};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A read-only view of a single state in the game tree. This lets agents
+/// that are implemented outside of this crate inspect the game without
+/// depending on how the states are stored.
+#[derive(Clone, Copy)]
+pub struct StateView<'a> {
+    game: &'a Game,
+    handle: usize,
+}
+
+impl<'a> StateView<'a> {
+    pub(super) fn new(game: &'a Game, handle: usize) -> Self {
+        StateView { game, handle }
+    }
+
+    /// Return the handle of this state in the game tree (e.g. for `Game::analyze()`),
+    /// which stays valid until the game moves past the state.
+    pub fn handle(&self) -> usize {
+        self.handle
+    }
+
+    /// Return every player in the game, including bankrupt ones.
+    pub fn players(&self) -> &'a [Player] {
+        self.game.diff_players(self.handle)
+    }
+
+    /// Return the index of the player whose turn it is.
+    pub fn current_pindex(&self) -> usize {
+        self.game.diff_current_pindex(self.handle)
+    }
+
+    /// Return the index of the player who makes the next move. This is usually
+    /// the current player, except when someone else has to settle a debt
+    /// or respond to a trade offer.
+    pub fn acting_pindex(&self) -> usize {
+        self.game.get_acting_pindex(self.handle)
+    }
+
+    /// Return the owned properties, in the form `HashMap<property_position, ownership>`.
+    pub fn owned_properties(&self) -> &'a HashMap<u8, PropertyOwnership> {
+        self.game.diff_owned_properties(self.handle)
+    }
+
+    /// Return the number of tries that each player in jail has left to roll doubles,
+    /// including the last one, after which they leave jail whatever they roll.
+    pub fn jail_rounds(&self) -> &'a [u8] {
+        self.game.diff_jail_rounds(self.handle)
+    }
+
+    /// Return a description of the move that led to this state.
+    pub fn message(&self) -> &'a DiffMessage {
+        &self.game.nodes[self.handle].message
+    }
+
+    /// Return the type of move to be made after this state.
+    pub fn next_move(&self) -> &'a MoveType {
+        &self.game.nodes[self.handle].next_move
+    }
+
+    /// Return the probability of reaching this state from its parent,
+    /// or `None` if it was reached by making a choice.
+    pub fn probability(&self) -> Option<f64> {
+        match self.game.nodes[self.handle].branch_type {
+            BranchType::Chance(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// Return whether the game is over at this state.
+    pub fn is_terminal(&self) -> bool {
+        self.game.is_terminal(self.handle)
+    }
+
+    /// Return the board that the game is played on.
+    pub fn board(&self) -> &'a Board {
+        &self.game.board
+    }
+
+    /// Return every field of this state, as a snapshot that doesn't borrow the game.
+    pub fn resolve(&self) -> ResolvedState {
+        self.game.resolve_state(self.handle)
+    }
+
+    /// Return the Zobrist hash of this state, which is the same as
+    /// `self.resolve().zobrist_hash()` but doesn't have to resolve every field.
+    pub fn hash(&self) -> u64 {
+        self.game.nodes[self.handle].hash
+    }
+}
+
+/// A move that can be made from the current game state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveSummary {
+    /// The index to pass to `Game::apply_move()` to make this move.
+    pub index: usize,
+    /// The index of the player making this move.
+    pub acting_pindex: usize,
+    /// A description of the move.
+    pub message: DiffMessage,
+    /// The probability of the move if it's down to chance (e.g. a dice roll),
+    /// or `None` if it's a choice.
+    pub probability: Option<f64>,
+}
+
+/// Every field of a game state, resolved from the chain of diffs that it's stored as.
+/// Unlike `StateView`, this doesn't borrow the game, so it can be kept around or
+/// serialized. Unlike `Observation`, it has every field of the state, but nothing
+/// derived from them (e.g. the moves that can be made).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedState {
+    /// Every player in the game, including bankrupt ones.
+    pub players: Vec<Player>,
+    /// The index of the player whose turn it is.
+    pub current_pindex: usize,
+    /// The owned properties, in the form `HashMap<property_position, ownership>`.
+    pub owned_properties: HashMap<u8, PropertyOwnership>,
+    /// The deck of chance cards, as far as the players know it.
+    pub chance_deck: ChanceDeck,
+    /// The number of rounds left until players stop paying level 1 rent.
+    pub level_1_rent: u8,
+    /// The number of tries that each player in jail has left to roll doubles.
+    pub jail_rounds: Vec<u8>,
+    /// The money waiting on 'Free parking'.
+    pub jackpot: i32,
+    /// The type of move to be made next.
+    pub next_move: MoveType,
+}
+
+impl ResolvedState {
+    /// Return how much every player's balance would change by if the current player drew
+    /// `card`, or `None` if that depends on a choice (e.g. who else gets the 'Bonus').
+    /// 'All to parking' hands the current player any jackpot on 'Free parking'.
+    pub fn card_impact(&self, card: ChanceCard) -> Option<Vec<i32>> {
+        let mut impact = vec![0; self.players.len()];
+
+        match card {
+            ChanceCard::PropertyTax => {
+                let owned = self
+                    .owned_properties
+                    .values()
+                    .filter(|p| p.owner == self.current_pindex)
+                    .count();
+                impact[self.current_pindex] -= PROPERTY_TAX * owned as i32;
+            }
+            // There's only ever a jackpot under the Free Parking jackpot house rule
+            ChanceCard::AllToParking if !self.players[self.current_pindex].in_jail => {
+                impact[self.current_pindex] += self.jackpot;
+            }
+            ChanceCard::Level1Rent | ChanceCard::AllToParking => {}
+            _ => return None,
+        }
+
+        Some(impact)
+    }
+
+    /// Return how much every player's balance is expected to change by when the current
+    /// player draws the next chance card, given the cards that are left in the deck. Only
+    /// the choiceless cards are counted (see `ResolvedState::card_impact()`), so this is
+    /// exact up to the choices that the other cards leave the players to make.
+    ///
+    /// ```
+    /// use monopoly_math::{ScenarioBuilder, PROPERTY_TAX, TOTAL_CHANCE_CARDS};
+    ///
+    /// let game = ScenarioBuilder::new(2)
+    ///     .with_property(1, 0, 1)
+    ///     .with_property(3, 0, 1)
+    ///     .build();
+    /// let impact = game.resolved_state().expected_card_impact();
+    ///
+    /// // 'Property tax' is one card in the deck, and charges for both properties
+    /// let expected = -2. * PROPERTY_TAX as f64 / TOTAL_CHANCE_CARDS as f64;
+    /// assert!((impact[0] - expected).abs() < 1e-9);
+    /// assert_eq!(impact[1], 0.);
+    /// ```
+    pub fn expected_card_impact(&self) -> Vec<f64> {
+        self.expected_card_impact_except(None)
+    }
+
+    /// Return `ResolvedState::expected_card_impact()`, given that the
+    /// next card isn't `excluded` (e.g. because it was already dealt with).
+    pub(super) fn expected_card_impact_except(&self, excluded: Option<ChanceCard>) -> Vec<f64> {
+        let mut expected = vec![0.; self.players.len()];
+
+        for (card, probability) in self.chance_deck.draw_probabilities_except(excluded) {
+            if let Some(impact) = self.card_impact(card) {
+                for (e, i) in expected.iter_mut().zip(impact) {
+                    *e += probability * i as f64;
+                }
+            }
+        }
+
+        expected
+    }
+}
+
+/// A snapshot of a game state that doesn't borrow the game, so that it can
+/// be kept around or sent elsewhere (e.g. over a network).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Observation {
+    /// The number of turns that have been played so far.
+    pub turn: usize,
+    /// Every player in the game, including bankrupt ones.
+    pub players: Vec<Player>,
+    /// The index of the player whose turn it is.
+    pub current_pindex: usize,
+    /// The index of the player who makes the next move.
+    pub acting_pindex: usize,
+    /// The owned properties, in the form `HashMap<property_position, ownership>`.
+    pub owned_properties: HashMap<u8, PropertyOwnership>,
+    /// The number of tries that each player in jail has left to roll doubles.
+    pub jail_rounds: Vec<u8>,
+    /// The type of move to be made next.
+    pub next_move: MoveType,
+    /// Whether the game is over.
+    pub is_terminal: bool,
+    /// The moves that can be made from this state, which are either all
+    /// choices or all down to chance. This is empty if the game is over.
+    pub moves: Vec<MoveSummary>,
+}
+
+impl Observation {
+    pub(super) fn new(state: StateView, turn: usize) -> Self {
+        let resolved = state.game.resolve_state(state.handle);
+
+        Observation {
+            turn,
+            players: resolved.players,
+            current_pindex: resolved.current_pindex,
+            acting_pindex: state.acting_pindex(),
+            owned_properties: resolved.owned_properties,
+            jail_rounds: resolved.jail_rounds,
+            next_move: resolved.next_move,
+            is_terminal: state.is_terminal(),
+            moves: state.game.get_move_summaries(state.handle),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use super::agent::{Agent, HeuristicAgent};
+use super::logger::LogLevel;
+use super::state_view::Observation;
+use super::Game;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A message that the engine writes to the external process, as one line of JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineRequest {
+    /// Choose one of `observation.moves`, and reply with an `AgentReply` with the same `id`.
+    Choose { id: u64, observation: Observation },
+    /// The game is over and the process should exit. No reply is expected.
+    Quit,
+}
+
+/// A message that the external process writes back to the engine, as one line of JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentReply {
+    /// The `id` of the request being answered.
+    pub id: u64,
+    /// The `index` of the chosen move.
+    #[serde(rename = "move")]
+    pub index: usize,
+}
+
+/// An agent that asks another process for its moves, so that agents can be written in
+/// other languages. The engine writes an `EngineRequest` to the process's stdin for
+/// every move, and the process writes an `AgentReply` to its stdout, e.g.
+///
+/// ```text
+/// > {"type":"choose","id":0,"observation":{"turn":0,...,"moves":[...]}}
+/// < {"id":0,"move":1}
+/// ```
+///
+/// If the process doesn't reply within the timeout (1 second by default), or replies
+/// with a move that doesn't exist, the fallback agent (`HeuristicAgent` by default)
+/// makes the move instead. Late replies are ignored. Once the process exits, the
+/// fallback agent makes every move.
+pub struct SubprocessAgent {
+    /// The external process.
+    child: Child,
+    /// The process's stdin, or `None` once it can't be written to.
+    stdin: Option<ChildStdin>,
+    /// The lines written to the process's stdout, read on a separate thread.
+    replies: Receiver<String>,
+    /// The `id` of the next request.
+    next_id: u64,
+    /// How long to wait for each reply.
+    timeout: Duration,
+    /// The agent that moves when the process doesn't.
+    fallback: Box<dyn Agent>,
+}
+
+impl SubprocessAgent {
+    /// Spawn `command` and return an agent that plays through it.
+    /// The command's stdin and stdout are replaced with pipes to the agent.
+    pub fn spawn(mut command: Command) -> io::Result<SubprocessAgent> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read replies on another thread so that waiting for them can time out
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(SubprocessAgent {
+            child,
+            stdin,
+            replies,
+            next_id: 0,
+            timeout: Duration::from_secs(1),
+            fallback: Box::new(HeuristicAgent),
+        })
+    }
+
+    /// Wait at most `timeout` for each reply before the fallback agent moves instead.
+    pub fn with_timeout(mut self, timeout: Duration) -> SubprocessAgent {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Let `fallback` move whenever the process doesn't.
+    pub fn with_fallback(mut self, fallback: Box<dyn Agent>) -> SubprocessAgent {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Write `request` to the process as a line of JSON.
+    fn send(&mut self, request: &EngineRequest) -> Result<(), String> {
+        let stdin = self.stdin.as_mut().ok_or("the process has exited")?;
+        let line = serde_json::to_string(request).unwrap();
+
+        if let Err(e) = writeln!(stdin, "{}", line).and_then(|_| stdin.flush()) {
+            self.stdin = None;
+            return Err(format!("failed to write to the process: {}", e));
+        }
+        Ok(())
+    }
+
+    /// Ask the process to choose one of `observation`'s moves, and return its index.
+    fn request(&mut self, observation: Observation) -> Result<usize, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let move_count = observation.moves.len();
+        self.send(&EngineRequest::Choose { id, observation })?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = match self.replies.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("no reply within {:?}", self.timeout))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.stdin = None;
+                    return Err("the process has exited".to_owned());
+                }
+            };
+
+            let reply: AgentReply = serde_json::from_str(&line)
+                .map_err(|e| format!("invalid reply '{}': {}", line, e))?;
+            // Skip the late replies to requests that already timed out
+            if reply.id != id {
+                continue;
+            }
+            if reply.index >= move_count {
+                return Err(format!("move {} doesn't exist", reply.index));
+            }
+            return Ok(reply.index);
+        }
+    }
+}
+
+impl Agent for SubprocessAgent {
+    fn make_choice(&mut self, game: &mut Game) -> usize {
+        // Once the process has exited, there's no point in asking it for moves
+        if self.stdin.is_none() {
+            return self.fallback.make_choice(game);
+        }
+
+        game.gen_children_save(game.root_handle);
+        match self.request(game.current_observation()) {
+            Ok(index) => index,
+            Err(e) => {
+                game.logger().log(
+                    LogLevel::Summary,
+                    format_args!("Subprocess agent: {}, so the fallback agent moved", e),
+                );
+                self.fallback.make_choice(game)
+            }
+        }
+    }
+}
+
+impl Drop for SubprocessAgent {
+    fn drop(&mut self) {
+        // Give the process a chance to exit by itself before it's killed
+        if self.send(&EngineRequest::Quit).is_ok() {
+            self.stdin = None;
+            let deadline = Instant::now() + self.timeout;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = self.child.try_wait() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
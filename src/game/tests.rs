@@ -0,0 +1,501 @@
+use super::*;
+
+#[test]
+fn advancing_past_a_deep_chain_frees_it() {
+    let mut game = Game::new(2);
+    game.gen_children_save(game.root_handle);
+    assert!(game.nodes[game.root_handle].children.len() > 1);
+
+    // Hang a chain that's far deeper than the call stack could recurse through
+    // off the child that won't be chosen
+    let mut handle = game.nodes[game.root_handle].children[1];
+    for _ in 0..1_000_000 {
+        let child = game.nodes.insert(StateDiff::new_with_parent(handle));
+        game.nodes[handle].children.push(child);
+        handle = child;
+    }
+    let slots = game.nodes.slot_count();
+
+    game.advance_root_node(0);
+    assert_eq!(game.node_count(), 1);
+
+    // The freed slots are reused by the new root's children
+    game.gen_children_save(game.root_handle);
+    assert_eq!(game.nodes.slot_count(), slots);
+}
+
+/// Return whether reaching the node at `handle` panics because it has been freed.
+fn is_stale(game: &Game, handle: usize) -> bool {
+    let nodes = std::panic::AssertUnwindSafe(&game.nodes);
+    std::panic::catch_unwind(|| nodes[handle].hash).is_err()
+}
+
+#[test]
+fn stale_handles_are_caught_after_freeing_a_subtree() {
+    let mut game = Game::new(2);
+    game.gen_children_save(game.root_handle);
+    let stale = game.nodes[game.root_handle].children[1];
+    game.gen_children_save(stale);
+    let stale_child = game.nodes[stale].children[0];
+
+    game.advance_root_node(0);
+    assert!(is_stale(&game, stale));
+    assert!(is_stale(&game, stale_child));
+
+    // New nodes that reuse the freed slots get new handles, which the old ones don't reach
+    game.gen_children_save(game.root_handle);
+    let children = game.nodes[game.root_handle].children.clone();
+    assert!(!children.contains(&stale) && !children.contains(&stale_child));
+    assert!(children.iter().all(|&child| !is_stale(&game, child)));
+    assert!(is_stale(&game, stale));
+}
+
+#[test]
+fn card_impact_counts_the_jackpot() {
+    let config = GameConfig {
+        free_parking_jackpot: true,
+        ..GameConfig::default()
+    };
+    let mut game = ScenarioBuilder::new(2)
+        .with_config(config)
+        .with_jackpot(100)
+        .build();
+    let root = game.root_handle;
+    game.nodes[root].next_move = MoveType::ChanceCard;
+
+    // 'All to parking' hands the jackpot to the player who drew it
+    let impact = game.resolved_state().card_impact(ChanceCard::AllToParking);
+    assert_eq!(impact, Some(vec![100, 0]));
+
+    game.gen_children_save(root);
+    let errors = game.validate_children(root);
+    assert!(
+        !errors
+            .iter()
+            .any(|e| matches!(e.kind, ValidationErrorKind::CardImpact { .. })),
+        "{:?}",
+        errors
+    );
+}
+
+#[test]
+fn positions_below_the_current_state_can_be_analyzed() {
+    let mut game = Game::new(2);
+    let child = game.child_states()[0].handle();
+    let (root_hash, node_count) = (game.current_state().hash(), game.node_count());
+
+    let budget = Budget::Iterations(50);
+    let analyses = game.analyze(child, budget).unwrap();
+    assert!(!analyses.is_empty());
+    assert!(analyses.iter().all(|a| a.visits > 0));
+
+    // The game itself is left where it was
+    assert_eq!(game.current_state().hash(), root_hash);
+    assert_eq!(game.node_count(), node_count);
+}
+
+/// Return the positions of the first `n` properties on the board of `config`.
+fn first_properties(config: GameConfig, n: usize) -> Vec<u8> {
+    let mut positions: Vec<u8> = config.rules.board().properties.keys().copied().collect();
+    positions.sort_unstable();
+    positions.truncate(n);
+    positions
+}
+
+/// Return a game where player 0 owns the properties at `props` and has to sell
+/// some of them to cover their `balance`, which they owe player 1.
+fn game_in_debt(config: GameConfig, balance: i32, props: &[u8]) -> Game {
+    let mut scenario = ScenarioBuilder::new(2)
+        .with_config(config)
+        .with_balance(0, balance);
+    for &pos in props {
+        scenario = scenario.with_property(pos, 0, 1);
+    }
+
+    let mut game = scenario.build();
+    let root = game.root_handle;
+    game.nodes[root].next_move = MoveType::SellProperty {
+        creditor: Some(1),
+        then: Box::new(MoveType::Roll),
+    };
+    game
+}
+
+/// Return the properties that player 0 sells in each child of the root of `game`,
+/// along with their balance afterwards.
+fn forced_sales(game: &mut Game) -> Vec<(Vec<u8>, i32)> {
+    let before = game.diff_owned_properties(game.root_handle).clone();
+    game.child_states()
+        .iter()
+        .map(|state| {
+            let after = state.owned_properties();
+            let mut sold: Vec<u8> = before
+                .keys()
+                .copied()
+                .filter(|pos| !after.contains_key(pos))
+                .collect();
+            sold.sort_unstable();
+            (sold, state.players()[0].balance)
+        })
+        .collect()
+}
+
+#[test]
+fn forced_sales_remove_only_the_sold_properties() {
+    let config = GameConfig::default();
+    let board = config.rules.board();
+    let props = first_properties(config, 3);
+    let mut game = game_in_debt(config, -1, &props);
+
+    // Any one property covers the debt, so each of them is offered on its own
+    let sales = forced_sales(&mut game);
+    assert_eq!(sales.len(), props.len());
+    for (pos, (sold, balance)) in zip(&props, sales) {
+        assert_eq!(sold, [*pos]);
+        assert_eq!(balance, board.property(*pos).price - 1);
+    }
+
+    for state in game.child_states() {
+        assert_eq!(state.owned_properties().len(), props.len() - 1);
+    }
+}
+
+#[test]
+fn forced_sales_sell_every_property_if_they_have_to() {
+    let config = GameConfig::default();
+    let board = config.rules.board();
+    let props = first_properties(config, 3);
+    let worth: i32 = props.iter().map(|&pos| board.property(pos).price).sum();
+
+    let mut game = game_in_debt(config, -worth, &props);
+    assert_eq!(forced_sales(&mut game), [(props.clone(), 0)]);
+
+    // Owing any more than that makes the player go bankrupt instead
+    let mut game = game_in_debt(config, -worth - 1, &props);
+    let children = game.child_states();
+    assert_eq!(children.len(), 1);
+    assert!(matches!(children[0].message(), DiffMessage::Bankrupt(0)));
+    assert!(children[0].players()[0].bankrupt);
+}
+
+#[test]
+fn pareto_sales_leave_out_dominated_combinations() {
+    let config = GameConfig {
+        pareto_sales: true,
+        ..GameConfig::default()
+    };
+    let board = config.rules.board();
+    let (cheap, also_cheap, pricey) = (1, 3, 10);
+    assert_eq!(board.property(cheap).price, 60);
+    assert_eq!(board.property(also_cheap).price, 60);
+    assert_eq!(board.property(pricey).price, 140);
+
+    // Selling the two cheap properties leaves less over than selling the pricey one, so
+    // both are offered, but selling the pricey one with anything else is beaten by both
+    let mut game = game_in_debt(config, -115, &[cheap, also_cheap, pricey]);
+    let sales = forced_sales(&mut game);
+    assert_eq!(sales, [(vec![pricey], 25), (vec![cheap, also_cheap], 5)]);
+    assert!(!sales.iter().any(|(sold, _)| sold == &[cheap, pricey]));
+}
+
+#[test]
+fn forced_sales_pay_the_resale_percent() {
+    let config = GameConfig {
+        resale_percent: 50,
+        ..GameConfig::default()
+    };
+    let board = config.rules.board();
+    let props = first_properties(config, 2);
+    let prices: Vec<i32> = props.iter().map(|&pos| board.property(pos).price).collect();
+
+    // Either property sells for half its price
+    let mut game = game_in_debt(config, -1, &props);
+    let sales = forced_sales(&mut game);
+    assert_eq!(sales.len(), 2);
+    for ((_, balance), price) in zip(sales, &prices) {
+        assert_eq!(balance, price / 2 - 1);
+    }
+
+    // A debt that either property would cover at full price takes both at half price
+    let debt = prices[0].min(prices[1]);
+    let mut game = game_in_debt(config, -debt, &props);
+    assert_eq!(
+        forced_sales(&mut game),
+        [(props.clone(), prices[0] / 2 + prices[1] / 2 - debt)]
+    );
+}
+
+/// Return the positions that player 0 (with `balance`) can teleport to from a location
+/// tile, in a game with `config` where they own the first property and player 1 owns
+/// the second, along with whether they can choose not to teleport.
+fn location_targets(config: GameConfig, balance: i32) -> (Vec<u8>, bool) {
+    let props = first_properties(config, 2);
+    let mut game = ScenarioBuilder::new(2)
+        .with_config(config)
+        .with_balance(0, balance)
+        .with_property(props[0], 0, 1)
+        .with_property(props[1], 1, 1)
+        .build();
+    let root = game.root_handle;
+    game.nodes[root].next_move = MoveType::Location;
+
+    let mut targets = vec![];
+    let mut can_stay = false;
+    for state in game.child_states() {
+        match *state.message() {
+            DiffMessage::Location(pos) => {
+                assert_eq!(state.players()[0].position, pos);
+                assert_eq!(state.players()[0].balance, balance - config.location_fee);
+                targets.push(pos);
+            }
+            DiffMessage::NoLocation => can_stay = true,
+            ref message => panic!("unexpected move {:?}", message),
+        }
+    }
+
+    targets.sort_unstable();
+    (targets, can_stay)
+}
+
+#[test]
+fn teleporting_is_skipped_if_the_fee_is_unaffordable() {
+    let config = GameConfig::default();
+    assert!(config.location_fee > 0);
+
+    let (targets, can_stay) = location_targets(config, config.location_fee - 1);
+    assert!(targets.is_empty());
+    assert!(can_stay);
+
+    let (targets, can_stay) = location_targets(config, config.location_fee);
+    assert_eq!(targets.len(), config.rules.board().properties.len());
+    assert!(can_stay);
+}
+
+#[test]
+fn restricted_teleports_leave_out_opponents_properties() {
+    let config = GameConfig::default();
+    let props = first_properties(config, 2);
+    let (everywhere, _) = location_targets(config, 1000);
+    assert!(everywhere.contains(&props[0]));
+    assert!(everywhere.contains(&props[1]));
+
+    let config = GameConfig {
+        restrict_location_targets: true,
+        ..config
+    };
+    let (restricted, can_stay) = location_targets(config, 1000);
+    assert!(can_stay);
+    assert!(restricted.contains(&props[0]));
+    assert!(!restricted.contains(&props[1]));
+    assert_eq!(restricted.len(), everywhere.len() - 1);
+}
+
+/// Return the chances that player 0 (who's in jail with `jail_rounds` tries left, under
+/// `jail_tries`) stays in jail, and that they pay to leave it, after their next roll.
+fn jail_roll_chances(jail_tries: u8, jail_rounds: u8) -> (f64, f64) {
+    let config = GameConfig {
+        jail_tries,
+        ..GameConfig::default()
+    };
+    let mut game = ScenarioBuilder::new(2)
+        .with_config(config)
+        .with_jailed(0, jail_rounds - 1)
+        .build();
+    assert_eq!(game.diff_jail_rounds(game.root_handle)[0], jail_rounds);
+
+    let balance = game.diff_players(game.root_handle)[0].balance;
+    let (mut stay, mut pay, mut total) = (0., 0., 0.);
+    for state in game.child_states() {
+        let p = state.probability().unwrap();
+        let player = &state.players()[0];
+        total += p;
+        if player.in_jail {
+            stay += p;
+        } else if player.balance == balance - config.jail_penalty {
+            pay += p;
+        }
+    }
+
+    assert!((total - 1.).abs() < 1e-9);
+    (stay, pay)
+}
+
+#[test]
+fn jail_escape_chances_follow_jail_tries() {
+    for jail_tries in 1..=4 {
+        // The player has `jail_tries` rolls to get doubles, and only pays if they don't
+        let mut in_jail = 1.;
+        let mut paid = 0.;
+        for jail_rounds in (1..=jail_tries).rev() {
+            let (stay, pay) = jail_roll_chances(jail_tries, jail_rounds);
+            if jail_rounds > 1 {
+                assert!((stay - 5. / 6.).abs() < 1e-9, "stay = {}", stay);
+                assert_eq!(pay, 0.);
+            } else {
+                assert_eq!(stay, 0.);
+            }
+
+            paid += in_jail * pay;
+            in_jail *= stay;
+        }
+
+        let expected = (5_f64 / 6.).powi(jail_tries as i32);
+        assert!(
+            (paid - expected).abs() < 1e-9,
+            "with {} tries, paid {} instead of {}",
+            jail_tries,
+            paid,
+            expected
+        );
+    }
+}
+
+#[test]
+fn equivalent_chance_children_are_merged() {
+    let game = Game::new(2);
+    let root = game.root_handle;
+    let players = game.diff_players(root);
+
+    // A chance child of the root where player 0 ends up at `position`
+    let child = |position: u8, probability: f64, message: DiffMessage| {
+        let mut players = players.clone();
+        players[0].position = position;
+
+        let mut state = StateDiff::new_with_parent(root);
+        state.branch_type = BranchType::Chance(probability);
+        state.next_move = MoveType::Roll;
+        state.message = message;
+        state.set_players(players);
+        state
+    };
+
+    // Getting to the same state in different ways (here, with different messages)
+    // is the same outcome
+    let mut children = vec![
+        child(4, 0.25, DiffMessage::Roll(4)),
+        child(6, 0.5, DiffMessage::Roll(6)),
+        child(4, 0.125, DiffMessage::NoLocation),
+        child(4, 0.125, DiffMessage::Roll(4)),
+    ];
+    game.merge_chance_children(root, &mut children);
+
+    let chances: Vec<(u8, f64)> = children
+        .iter()
+        .map(|c| match (c.get_diff(DiffID::Players), c.branch_type) {
+            (Some(FieldDiff::Players(players)), BranchType::Chance(p)) => (players[0].position, p),
+            _ => panic!("the children changed"),
+        })
+        .collect();
+    assert_eq!(chances, [(4, 0.5), (6, 0.5)]);
+    assert!(matches!(children[0].message, DiffMessage::Roll(4)));
+}
+
+#[test]
+fn generated_chance_children_are_distinct_and_add_up() {
+    let mut game = Game::new(3);
+    for _ in 0..200 {
+        if game.is_terminal(game.root_handle) {
+            break;
+        }
+
+        let root = game.root_handle;
+        game.gen_children_save(root);
+        let children = game.nodes[root].children.clone();
+        if let BranchType::Chance(_) = game.nodes[children[0]].branch_type {
+            let hashes: HashSet<u64> = children.iter().map(|&c| game.nodes[c].hash).collect();
+            assert_eq!(hashes.len(), children.len());
+
+            let sum: f64 = children
+                .iter()
+                .map(|&c| match game.nodes[c].branch_type {
+                    BranchType::Chance(p) => p,
+                    _ => panic!("chance and choice children are mixed"),
+                })
+                .sum();
+            assert!((sum - 1.).abs() < 1e-9, "the chances add up to {}", sum);
+        }
+
+        let index = with_rng(|rng| rng.gen_range(0..children.len()));
+        game.advance_root_node(index);
+    }
+}
+
+#[test]
+fn undefined_chance_moves_are_errors() {
+    let mut game = Game::new(2);
+    let root = game.root_handle;
+    let mut state = StateDiff::new_with_parent(root);
+    state.next_move = MoveType::Roll;
+    game.append_state(state);
+
+    assert!(matches!(game.legal_moves(), Err(GameError::Internal(_))));
+    assert!(matches!(game.apply_move(0), Err(GameError::Internal(_))));
+}
+
+#[test]
+fn games_without_a_winner_are_errors() {
+    let mut game = Game::new(2);
+    let root = game.root_handle;
+    if let Some(FieldDiff::Players(players)) = game.nodes[root].get_diff_mut(DiffID::Players) {
+        for player in players {
+            player.bankrupt = true;
+        }
+    }
+
+    assert!(matches!(game.result(), Err(GameError::Internal(_))));
+}
+
+#[test]
+fn broken_transcripts_are_errors() {
+    let mut game = Game::new(2);
+    for _ in 0..10 {
+        let moves = game.legal_moves().unwrap();
+        game.apply_move(moves.len() - 1).unwrap();
+    }
+    let transcript = game.transcript().clone();
+    assert!(Game::replay(&transcript).is_ok());
+
+    // A player who wasn't in the game
+    let mut extra_player = transcript.clone();
+    let entry = extra_player
+        .entries
+        .iter_mut()
+        .find(|e| e.diffs.iter().any(|d| d.id() == DiffID::Players))
+        .unwrap();
+    for diff in &mut entry.diffs {
+        if let FieldDiff::Players(players) = diff {
+            players.push(Player::default());
+        }
+    }
+    assert!(matches!(
+        Game::replay(&extra_player),
+        Err(GameError::InvalidTranscript { .. })
+    ));
+
+    // Fields that are recorded but not marked as present
+    let mut unmarked = transcript.clone();
+    let index = unmarked
+        .entries
+        .iter()
+        .position(|e| !e.diffs.is_empty())
+        .unwrap();
+    unmarked.entries[index].present_diffs = 0;
+    assert_eq!(
+        Game::replay(&unmarked).err(),
+        Some(GameError::InvalidTranscript { index })
+    );
+}
+
+#[test]
+fn undoing_a_game_without_recorded_hashes_stops_early() {
+    let mut game = Game::new(2);
+    for _ in 0..5 {
+        let moves = game.legal_moves().unwrap();
+        game.apply_move(moves.len() - 1).unwrap();
+    }
+
+    // Saves made before the hashes were recorded don't have them
+    let hashes = game.root_hashes.len();
+    game.root_hashes.drain(..hashes - 2);
+    assert_eq!(game.undo(100), 2);
+}
@@ -0,0 +1,76 @@
+use super::state_diff::{BranchType, DiffMessage, FieldDiff, MoveType};
+use super::{Board, GameConfig};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// A single move made over the course of a game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// The index of the chosen state among the children of the previous state.
+    pub child_index: usize,
+    /// Whether the move was a choice or chance, and the probability of the chance.
+    pub branch_type: BranchType,
+    /// A description of the move.
+    pub message: DiffMessage,
+    /// Which fields of the game state the move changed, as a bitmap of `DiffID`s.
+    pub present_diffs: u8,
+    /// The changes to the game state that the move made.
+    pub diffs: Vec<FieldDiff>,
+    /// The type of move to be made after this one.
+    pub next_move: MoveType,
+}
+
+/// A record of every move made in a game, which is enough to reconstruct the game with
+/// `Game::replay()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    /// The number of players in the game.
+    pub player_count: usize,
+    /// The optional rules that the game was played with.
+    pub config: GameConfig,
+    /// The board that the game was played on.
+    pub board: Board,
+    /// Every move made in the game, in order.
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Return an empty transcript of a game.
+    pub fn new(player_count: usize, config: GameConfig, board: Board) -> Self {
+        Transcript {
+            player_count,
+            config,
+            board,
+            entries: vec![],
+        }
+    }
+
+    /// Save the transcript to a JSON replay file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Load a transcript that was previously saved with `Transcript::save()`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            match entry.branch_type {
+                BranchType::Chance(p) => writeln!(f, "{:>5}: {} (p = {:.3})", i, entry.message, p)?,
+                _ => writeln!(f, "{:>5}: {}", i, entry.message)?,
+            }
+        }
+
+        Ok(())
+    }
+}
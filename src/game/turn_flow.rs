@@ -0,0 +1,224 @@
+use super::globals::Player;
+use super::state_diff::{DiffID, FieldDiff, MoveType, StateDiff};
+use super::Game;
+
+/// Decides whose turn it is once the current player has finished acting on the tile
+/// they landed on (e.g. buying a property or drawing a chance card), so that every
+/// kind of move hands over the turn by the same rules.
+pub(super) struct TurnFlow;
+
+impl TurnFlow {
+    /// Return whether `player` gets to roll again at the end of their move, which is the case
+    /// if their last roll was doubles, unless it sent them to jail or they went bankrupt.
+    pub fn rolls_again(player: &Player) -> bool {
+        player.doubles_rolled > 0 && !player.in_jail && !player.bankrupt
+    }
+
+    /// Return the index of the first player after `pindex` who hasn't gone bankrupt.
+    pub fn next_pindex(players: &[Player], pindex: usize) -> usize {
+        (1..=players.len())
+            .map(|offset| (pindex + offset) % players.len())
+            .find(|&i| !players[i].bankrupt)
+            .unwrap_or(pindex)
+    }
+}
+
+impl Game {
+    /// End the current player's move in `state`, a child of the state at `handle`: the
+    /// next move is a roll, which is made by the current player again if they rolled
+    /// doubles, and by the next player otherwise. This goes by the players as they are in
+    /// `state`, so a move that sends the current player to jail also ends their turn.
+    pub(super) fn end_move(&self, handle: usize, state: &mut StateDiff) {
+        state.next_move = MoveType::Roll;
+        self.hand_over_turn(handle, state);
+    }
+
+    /// Make it the next player's turn in `state`, a child of the state at `handle`, if its
+    /// next move is a roll that the current player doesn't get to make again.
+    pub(super) fn hand_over_turn(&self, handle: usize, state: &mut StateDiff) {
+        if !state.next_move.is_roll() {
+            return;
+        }
+
+        let players = match state.get_diff(DiffID::Players) {
+            Some(FieldDiff::Players(players)) => players,
+            Some(_) => unreachable!(),
+            None => self.diff_players(handle),
+        };
+        let curr_pindex = self.diff_current_pindex(handle);
+
+        if !TurnFlow::rolls_again(&players[curr_pindex]) {
+            let next_pindex = TurnFlow::next_pindex(players, curr_pindex);
+            state.set_current_pindex(next_pindex);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ScenarioBuilder;
+
+    /// Return a player who has rolled `doubles` doubles in a row.
+    fn player(doubles: u8, in_jail: bool, bankrupt: bool) -> Player {
+        Player {
+            doubles_rolled: doubles,
+            in_jail,
+            bankrupt,
+            ..Player::default()
+        }
+    }
+
+    /// Return a game between 3 players where player 0, who has just rolled `doubles`
+    /// doubles in a row, is about to make `next_move` on the first property.
+    fn game_after_roll(doubles: u8, next_move: MoveType) -> Game {
+        let mut game = ScenarioBuilder::new(3).with_position(0, 1).build();
+        let root = &mut game.nodes[game.root_handle];
+        if let Some(FieldDiff::Players(players)) = root.get_diff_mut(DiffID::Players) {
+            players[0].doubles_rolled = doubles;
+        }
+        root.next_move = next_move;
+
+        game
+    }
+
+    /// Return the player who rolls next in every child of the root of `game` that ends
+    /// the current player's move.
+    fn next_rollers(game: &mut Game) -> Vec<usize> {
+        game.gen_children_save(game.root_handle);
+        game.nodes[game.root_handle]
+            .children
+            .clone()
+            .into_iter()
+            .filter(|&c| game.nodes[c].next_move.is_roll())
+            .map(|c| game.diff_current_pindex(c))
+            .collect()
+    }
+
+    #[test]
+    fn only_free_solvent_players_with_doubles_roll_again() {
+        for doubles in 0..3 {
+            for in_jail in [false, true] {
+                for bankrupt in [false, true] {
+                    assert_eq!(
+                        TurnFlow::rolls_again(&player(doubles, in_jail, bankrupt)),
+                        doubles > 0 && !in_jail && !bankrupt,
+                        "doubles = {}, in jail = {}, bankrupt = {}",
+                        doubles,
+                        in_jail,
+                        bankrupt
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn next_player_skips_bankrupt_players_and_wraps_around() {
+        let players = |bankrupt: &[bool]| -> Vec<Player> {
+            bankrupt.iter().map(|&b| player(0, false, b)).collect()
+        };
+
+        assert_eq!(TurnFlow::next_pindex(&players(&[false; 3]), 0), 1);
+        assert_eq!(TurnFlow::next_pindex(&players(&[false; 3]), 2), 0);
+
+        let some_bankrupt = players(&[false, true, false, true]);
+        assert_eq!(TurnFlow::next_pindex(&some_bankrupt, 0), 2);
+        assert_eq!(TurnFlow::next_pindex(&some_bankrupt, 2), 0);
+
+        // Players in jail still take their turn
+        let mut one_jailed = players(&[false; 3]);
+        one_jailed[1].in_jail = true;
+        assert_eq!(TurnFlow::next_pindex(&one_jailed, 0), 1);
+
+        // The last player standing keeps the turn
+        assert_eq!(TurnFlow::next_pindex(&players(&[true, false, true]), 1), 1);
+    }
+
+    #[test]
+    fn doubles_keep_the_turn_unless_the_move_jails_or_bankrupts_the_player() {
+        let game = game_after_roll(1, MoveType::Property);
+        let root = game.root_handle;
+        let rolls_next = |players: Option<Vec<Player>>| {
+            let mut state = StateDiff::new_with_parent(root);
+            if let Some(players) = players {
+                state.set_players(players);
+            }
+            game.end_move(root, &mut state);
+
+            assert!(state.next_move.is_roll());
+            match state.get_diff(DiffID::CurrentPlayer) {
+                Some(&FieldDiff::CurrentPlayer(pindex)) => pindex,
+                _ => game.diff_current_pindex(root),
+            }
+        };
+
+        // A move that doesn't change the players (e.g. a chance card that only
+        // changes rent levels) goes by the players before it
+        assert_eq!(rolls_next(None), 0);
+
+        let players = game.diff_players(root).clone();
+        let mut paid = players.clone();
+        paid[0].balance -= 100;
+        assert_eq!(rolls_next(Some(paid)), 0);
+
+        let mut jailed = players.clone();
+        jailed[0].in_jail = true;
+        assert_eq!(rolls_next(Some(jailed)), 1);
+
+        let mut bankrupt = players.clone();
+        bankrupt[0].bankrupt = true;
+        bankrupt[1].bankrupt = true;
+        assert_eq!(rolls_next(Some(bankrupt)), 2);
+    }
+
+    #[test]
+    fn moves_that_arent_rolls_keep_the_turn() {
+        let game = game_after_roll(0, MoveType::Property);
+        let mut state = StateDiff::new_with_parent(game.root_handle);
+        state.next_move = MoveType::Auction;
+        game.hand_over_turn(game.root_handle, &mut state);
+
+        assert!(state.get_diff(DiffID::CurrentPlayer).is_none());
+    }
+
+    #[test]
+    fn purchases_hand_over_the_turn_unless_doubles_were_rolled() {
+        let rollers = next_rollers(&mut game_after_roll(0, MoveType::Property));
+        assert!(!rollers.is_empty());
+        assert!(rollers.iter().all(|&p| p == 1), "{:?}", rollers);
+
+        let rollers = next_rollers(&mut game_after_roll(1, MoveType::Property));
+        assert!(!rollers.is_empty());
+        assert!(rollers.iter().all(|&p| p == 0), "{:?}", rollers);
+    }
+
+    #[test]
+    fn chance_cards_hand_over_the_turn_unless_doubles_were_rolled() {
+        let rollers = next_rollers(&mut game_after_roll(0, MoveType::ChanceCard));
+        assert!(!rollers.is_empty());
+        assert!(rollers.iter().all(|&p| p == 1), "{:?}", rollers);
+
+        let rollers = next_rollers(&mut game_after_roll(2, MoveType::ChanceCard));
+        assert!(!rollers.is_empty());
+        assert!(rollers.iter().all(|&p| p == 0), "{:?}", rollers);
+    }
+
+    #[test]
+    fn third_doubles_send_the_player_to_jail_and_end_their_turn() {
+        let mut game = game_after_roll(2, MoveType::Roll);
+        game.gen_children_save(game.root_handle);
+
+        let jailed: Vec<usize> = game.nodes[game.root_handle]
+            .children
+            .iter()
+            .copied()
+            .filter(|&c| game.diff_players(c)[0].in_jail)
+            .collect();
+        assert!(!jailed.is_empty());
+        for c in jailed {
+            assert!(game.nodes[c].next_move.is_roll());
+            assert_eq!(game.diff_current_pindex(c), 1);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use super::classic::HOTEL_LEVEL;
+use super::globals::*;
+use super::state_diff::{BranchType, MoveType};
+use super::Game;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::zip;
+
+/// How far the chances of a state's children can be from adding up to 1.
+pub(super) const CHANCE_TOLERANCE: f64 = 1e-6;
+
+/// A problem found with the children generated for a state, which
+/// points to a bug in the rules rather than a mistake by an agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationError {
+    /// The move that was being made when the children were generated.
+    pub next_move: MoveType,
+    /// What was wrong with the children.
+    pub kind: ValidationErrorKind,
+}
+
+/// The ways that the children of a state can be invalid. `child` is the index
+/// of the offending child among its siblings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ValidationErrorKind {
+    /// A state that isn't terminal has no children.
+    NoChildren,
+    /// Some children are reached by chance and others by choice.
+    MixedBranchTypes,
+    /// The chances of the chance children don't add up to 1.
+    ChanceSum(f64),
+    /// A child's branch type was never set.
+    UndefinedBranchType { child: usize },
+    /// A child's next move was never set.
+    UndefinedNextMove { child: usize },
+    /// A terminal child still has a debt to be settled, which can never happen.
+    TerminalWithDebt { child: usize },
+    /// A player is in debt, but the next move isn't to settle it.
+    UnsettledDebt { child: usize, pindex: usize },
+    /// A player is off the board.
+    OffBoard { child: usize, pindex: usize },
+    /// A property is owned by a player who went bankrupt.
+    BankruptOwner { child: usize, pos: u8 },
+    /// A property has a rent level that the rules don't allow.
+    RentLevel {
+        child: usize,
+        pos: u8,
+        rent_level: usize,
+    },
+    /// It's the turn of a player who went bankrupt, although the game isn't over.
+    BankruptCurrentPlayer { child: usize },
+    /// The chance card children change the current player's balance by a different
+    /// amount on average than the cards left in the deck should.
+    CardImpact { expected: f64, actual: f64 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid children for {:?}: ", self.next_move)?;
+
+        match &self.kind {
+            ValidationErrorKind::NoChildren => write!(f, "no children"),
+            ValidationErrorKind::MixedBranchTypes => write!(f, "mixed chance and choice children"),
+            ValidationErrorKind::ChanceSum(sum) => write!(f, "chances add up to {}", sum),
+            ValidationErrorKind::UndefinedBranchType { child } => {
+                write!(f, "child {} has an undefined branch type", child)
+            }
+            ValidationErrorKind::UndefinedNextMove { child } => {
+                write!(f, "child {} has an undefined next move", child)
+            }
+            ValidationErrorKind::TerminalWithDebt { child } => {
+                write!(f, "child {} is terminal with a debt to settle", child)
+            }
+            ValidationErrorKind::UnsettledDebt { child, pindex } => {
+                write!(f, "player {} is in debt in child {}", pindex, child)
+            }
+            ValidationErrorKind::OffBoard { child, pindex } => {
+                write!(f, "player {} is off the board in child {}", pindex, child)
+            }
+            ValidationErrorKind::BankruptOwner { child, pos } => {
+                write!(
+                    f,
+                    "{} is owned by a bankrupt player in child {}",
+                    pos, child
+                )
+            }
+            ValidationErrorKind::RentLevel {
+                child,
+                pos,
+                rent_level,
+            } => write!(
+                f,
+                "{} has rent level {} in child {}",
+                pos, rent_level, child
+            ),
+            ValidationErrorKind::BankruptCurrentPlayer { child } => {
+                write!(f, "it's a bankrupt player's turn in child {}", child)
+            }
+            ValidationErrorKind::CardImpact { expected, actual } => write!(
+                f,
+                "cards change the balance by {:.2} on average instead of {:.2}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Game {
+    /*********        VALIDATION        *********/
+
+    /// Return every problem with the (already generated) children of the state at `handle`.
+    pub(super) fn validate_children(&self, handle: usize) -> Vec<ValidationError> {
+        let children = &self.nodes[handle].children;
+        let mut kinds = vec![];
+
+        if children.is_empty() {
+            kinds.push(ValidationErrorKind::NoChildren);
+        }
+
+        // Children have to be either all chance or all choice, and chances have to add up to 1
+        let chances: Vec<f64> = children
+            .iter()
+            .filter_map(|&c| match self.nodes[c].branch_type {
+                BranchType::Chance(p) => Some(p),
+                _ => None,
+            })
+            .collect();
+        if !chances.is_empty() {
+            let sum: f64 = chances.iter().sum();
+            if chances.len() != children.len() {
+                kinds.push(ValidationErrorKind::MixedBranchTypes);
+            } else if (sum - 1.).abs() > CHANCE_TOLERANCE {
+                kinds.push(ValidationErrorKind::ChanceSum(sum));
+            }
+        }
+
+        // On average, the cards have to change the current player's balance by as much as
+        // the cards left in the deck would ('All to parking' may have been drawn already)
+        if matches!(self.nodes[handle].next_move, MoveType::ChanceCard)
+            && self.config.rules == RuleSet::UltimateBanking
+            && !chances.is_empty()
+            && chances.len() == children.len()
+        {
+            let pindex = self.diff_current_pindex(handle);
+            let balance = self.diff_players(handle)[pindex].balance;
+            let excluded = self
+                .all_to_parking_predrawn(handle)
+                .then_some(ChanceCard::AllToParking);
+
+            let expected = self
+                .resolve_state(handle)
+                .expected_card_impact_except(excluded)[pindex];
+            let actual: f64 = zip(children, &chances)
+                .map(|(&c, p)| p * (self.diff_players(c)[pindex].balance - balance) as f64)
+                .sum();
+            if (actual - expected).abs() > CHANCE_TOLERANCE {
+                kinds.push(ValidationErrorKind::CardImpact { expected, actual });
+            }
+        }
+
+        let max_rent_level = match self.config.rules {
+            RuleSet::UltimateBanking => 5,
+            RuleSet::Classic => HOTEL_LEVEL,
+        };
+
+        for (child, &c) in children.iter().enumerate() {
+            let state = &self.nodes[c];
+            let players = self.diff_players(c);
+            let terminal = self.is_terminal(c);
+
+            if matches!(state.branch_type, BranchType::Undefined) {
+                kinds.push(ValidationErrorKind::UndefinedBranchType { child });
+            }
+            if matches!(state.next_move, MoveType::Undefined) {
+                kinds.push(ValidationErrorKind::UndefinedNextMove { child });
+            }
+            if terminal && state.next_move.is_sell_property() {
+                kinds.push(ValidationErrorKind::TerminalWithDebt { child });
+            }
+            if !terminal && players[self.diff_current_pindex(c)].bankrupt {
+                kinds.push(ValidationErrorKind::BankruptCurrentPlayer { child });
+            }
+
+            for (pindex, player) in players.iter().enumerate() {
+                if player.balance < 0 && !player.bankrupt && !state.next_move.is_sell_property() {
+                    kinds.push(ValidationErrorKind::UnsettledDebt { child, pindex });
+                }
+                if player.position >= self.board.size {
+                    kinds.push(ValidationErrorKind::OffBoard { child, pindex });
+                }
+            }
+
+            for (&pos, prop) in self.diff_owned_properties(c) {
+                if players[prop.owner].bankrupt {
+                    kinds.push(ValidationErrorKind::BankruptOwner { child, pos });
+                }
+                if !(1..=max_rent_level).contains(&prop.rent_level) {
+                    kinds.push(ValidationErrorKind::RentLevel {
+                        child,
+                        pos,
+                        rent_level: prop.rent_level,
+                    });
+                }
+            }
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| ValidationError {
+                next_move: self.nodes[handle].next_move.clone(),
+                kind,
+            })
+            .collect()
+    }
+}
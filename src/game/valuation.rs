@@ -0,0 +1,71 @@
+use super::globals::{Color, GameConfig};
+use super::Board;
+
+/// How much the tiles of a board are worth in the long run: how often a player lands on
+/// each of them, and how much rent a property or color set can be expected to collect
+/// from every turn of an opponent.
+///
+/// ```
+/// use monopoly_math::{Board, Color, GameConfig, Valuation};
+///
+/// let board = Board::default();
+/// let valuation = Valuation::new(&board, &GameConfig::default());
+///
+/// // Rolling doubles means rolling again, so there's more than one landing per turn
+/// assert!(valuation.landing_frequencies().iter().sum::<f64>() > 1.);
+/// // 'All to parking' sends players to 'Free parking' on top of the rolls that land there
+/// assert!(valuation.relative_frequency(board.free_parking_position) > 1.);
+/// assert!(valuation.color_set_yield(&board, Color::Blue, 1) > 0.);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Valuation {
+    /// The expected number of times that a player lands on each tile per turn, by position.
+    landing_frequencies: Vec<f64>,
+}
+
+impl Valuation {
+    /// Return the valuation of `board` when it's played with `config`.
+    pub fn new(board: &Board, config: &GameConfig) -> Self {
+        Valuation {
+            landing_frequencies: board.landing_frequencies(config),
+        }
+    }
+
+    /// Return the expected number of times that a player lands on each tile per turn, by position.
+    pub fn landing_frequencies(&self) -> &[f64] {
+        &self.landing_frequencies
+    }
+
+    /// Return the expected number of times that a player lands on the tile at `pos` per turn.
+    pub fn landing_frequency(&self, pos: u8) -> f64 {
+        self.landing_frequencies[pos as usize]
+    }
+
+    /// Return how much more often than the average tile the tile at `pos` is landed on.
+    pub fn relative_frequency(&self, pos: u8) -> f64 {
+        let mean =
+            self.landing_frequencies.iter().sum::<f64>() / self.landing_frequencies.len() as f64;
+        self.landing_frequency(pos) / mean
+    }
+
+    /// Return the rent that the property at `pos` is expected to
+    /// collect per turn of an opponent at `rent_level`.
+    pub fn rent_yield(&self, board: &Board, pos: u8, rent_level: usize) -> f64 {
+        self.landing_frequency(pos) * board.property(pos).rent(rent_level) as f64
+    }
+
+    /// Return the rent that all of the properties in the `color` set are
+    /// expected to collect per turn of an opponent at `rent_level`.
+    pub fn color_set_yield(&self, board: &Board, color: Color, rent_level: usize) -> f64 {
+        board
+            .props_by_color()
+            .get(&color)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .map(|&pos| self.rent_yield(board, pos, rent_level))
+                    .sum()
+            })
+            .unwrap_or(0.)
+    }
+}
@@ -0,0 +1,290 @@
+use super::globals::{Player, SIGNIFICANT_ROLLS};
+use super::state_diff::PropertyOwnership;
+use super::valuation::Valuation;
+use super::Board;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::zip;
+
+/// Evaluates how good a game state is for a player. This is used by the
+/// MCTS agent to score the end of a rollout, and by the expectimax agent
+/// to score the states at its search horizon.
+pub trait ValueFunction: Send + Sync {
+    /// Return the value of a game state with `players` and `props` for player `pindex`.
+    fn evaluate(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> f64;
+}
+
+/// Return the net worth (cash plus property prices) of every player.
+pub(super) fn net_worths(
+    players: &[Player],
+    props: &HashMap<u8, PropertyOwnership>,
+    board: &Board,
+) -> Vec<f64> {
+    let mut net_worths: Vec<f64> = players.iter().map(|p| p.balance as f64).collect();
+
+    for (pos, prop) in props {
+        net_worths[prop.owner] += board.property(*pos).price as f64;
+    }
+
+    net_worths
+}
+
+/// A player's balance multiplied by the total price of their
+/// properties, relative to the mean of everyone's scores.
+pub struct BalanceTimesPropertyWorth;
+
+impl ValueFunction for BalanceTimesPropertyWorth {
+    fn evaluate(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> f64 {
+        // Tabulate everyone's property worths
+        let mut total_prop_worths = vec![0.; players.len()];
+        for (pos, prop) in props {
+            total_prop_worths[prop.owner] += board.property(*pos).price as f64;
+        }
+
+        let scores: Vec<f64> = players
+            .iter()
+            .zip(total_prop_worths)
+            .map(|(p, prop_worth)| p.balance as f64 * prop_worth)
+            .collect();
+        let mean_score: f64 = scores.iter().sum::<f64>() / scores.len() as f64;
+
+        // The value of the game state is calculated as a player's distance from the mean score
+        scores[pindex] - mean_score
+    }
+}
+
+/// A player's net worth (cash plus property prices) relative to the mean net worth.
+pub struct NetWorthDifferential;
+
+impl ValueFunction for NetWorthDifferential {
+    fn evaluate(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> f64 {
+        let net_worths = net_worths(players, props, board);
+        let mean_net_worth = net_worths.iter().sum::<f64>() / net_worths.len() as f64;
+
+        net_worths[pindex] - mean_net_worth
+    }
+}
+
+/// 1 if the player has the greatest net worth (which, at the end of the
+/// game, means they've won), or -1 otherwise.
+pub struct WinLoss;
+
+impl ValueFunction for WinLoss {
+    fn evaluate(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> f64 {
+        let net_worths = net_worths(players, props, board);
+
+        if net_worths.iter().all(|&w| w <= net_worths[pindex]) {
+            1.
+        } else {
+            -1.
+        }
+    }
+}
+
+/// The player's rank by net worth, scaled from -1 (poorest) to 1 (richest).
+pub struct Rank;
+
+impl ValueFunction for Rank {
+    fn evaluate(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> f64 {
+        let net_worths = net_worths(players, props, board);
+        let poorer = net_worths
+            .iter()
+            .filter(|&&w| w < net_worths[pindex])
+            .count();
+
+        2. * poorer as f64 / (players.len() - 1).max(1) as f64 - 1.
+    }
+}
+
+/// The number of factors that `FactoredEvaluation` breaks the value of a state down into.
+pub const NUM_FACTORS: usize = 6;
+
+/// The names of the factors of `FactoredEvaluation`, in the order they're stored in.
+pub const FACTOR_NAMES: [&str; NUM_FACTORS] = [
+    "cash",
+    "property count",
+    "color sets",
+    "rent potential",
+    "position",
+    "jail status",
+];
+
+/// How much each factor of `FactoredEvaluation` contributed to the value of a state.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FactorBreakdown {
+    /// The weighted contribution of each factor, in the order of `FACTOR_NAMES`.
+    pub contributions: [f64; NUM_FACTORS],
+}
+
+impl FactorBreakdown {
+    /// Return the value of the state, which is the sum of the contributions.
+    pub fn total(&self) -> f64 {
+        self.contributions.iter().sum()
+    }
+}
+
+impl fmt::Display for FactorBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = zip(FACTOR_NAMES, self.contributions)
+            .map(|(name, contribution)| format!("{} {:+.1}", name, contribution + 0.))
+            .collect();
+
+        write!(f, "{:.1} ({})", self.total(), parts.join(", "))
+    }
+}
+
+/// A static evaluation made up of named factors, so that the reasons why a state is
+/// good or bad for a player can be shown. Each factor is measured for every player,
+/// and the player's score for it is their distance from the mean, times its weight:
+///
+/// - cash: the player's balance.
+/// - property count: the number of properties they own.
+/// - color sets: the number of color sets they own completely.
+/// - rent potential: the total current rent of their properties. With a `Valuation`, each
+///   property's rent is weighted by how much more often than the average tile it's landed on.
+/// - position: minus the rent they expect to pay on their next roll.
+/// - jail status: 1 if they're in jail.
+pub struct FactoredEvaluation {
+    /// The weight of each factor, in the order of `FACTOR_NAMES`.
+    pub weights: [f64; NUM_FACTORS],
+    /// How often the properties are landed on, if that should be taken into account.
+    pub valuation: Option<Valuation>,
+}
+
+impl Default for FactoredEvaluation {
+    fn default() -> Self {
+        FactoredEvaluation {
+            weights: [1., 50., 300., 1., 1., -50.],
+            valuation: None,
+        }
+    }
+}
+
+impl FactoredEvaluation {
+    /// Weight the rent potential of every property by how often it's landed on according to
+    /// `valuation`, which should be the valuation of the board that's being evaluated.
+    pub fn with_valuation(mut self, valuation: Valuation) -> Self {
+        self.valuation = Some(valuation);
+        self
+    }
+
+    /// Return how much each factor contributes to the value
+    /// of a game state with `players` and `props` for `pindex`.
+    pub fn breakdown(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> FactorBreakdown {
+        let factors: Vec<[f64; NUM_FACTORS]> = (0..players.len())
+            .map(|i| factors(players, props, board, self.valuation.as_ref(), i))
+            .collect();
+
+        let mut contributions = [0.; NUM_FACTORS];
+        for (f, contribution) in contributions.iter_mut().enumerate() {
+            let mean = factors.iter().map(|x| x[f]).sum::<f64>() / players.len() as f64;
+            *contribution = self.weights[f] * (factors[pindex][f] - mean);
+        }
+
+        FactorBreakdown { contributions }
+    }
+}
+
+impl ValueFunction for FactoredEvaluation {
+    fn evaluate(
+        &self,
+        players: &[Player],
+        props: &HashMap<u8, PropertyOwnership>,
+        board: &Board,
+        pindex: usize,
+    ) -> f64 {
+        self.breakdown(players, props, board, pindex).total()
+    }
+}
+
+/// Return the unweighted factors of `FactoredEvaluation` for `pindex`.
+fn factors(
+    players: &[Player],
+    props: &HashMap<u8, PropertyOwnership>,
+    board: &Board,
+    valuation: Option<&Valuation>,
+    pindex: usize,
+) -> [f64; NUM_FACTORS] {
+    let player = &players[pindex];
+    let owned: Vec<(u8, &PropertyOwnership)> = props
+        .iter()
+        .filter(|(_, p)| p.owner == pindex)
+        .map(|(&pos, p)| (pos, p))
+        .collect();
+
+    let color_sets = board
+        .props_by_color()
+        .values()
+        .filter(|positions| {
+            positions
+                .iter()
+                .all(|pos| props.get(pos).map(|p| p.owner) == Some(pindex))
+        })
+        .count();
+
+    let rent_potential: f64 = owned
+        .iter()
+        .map(|(pos, p)| {
+            let frequency = valuation.map_or(1., |v| v.relative_frequency(*pos));
+            board.property(*pos).rent(p.rent_level) as f64 * frequency
+        })
+        .sum();
+
+    // The rent of the opponents' properties that the next roll could land on
+    let expected_rent: f64 = SIGNIFICANT_ROLLS
+        .iter()
+        .filter_map(|roll| {
+            let pos = (player.position + roll.sum) % board.size;
+            props
+                .get(&pos)
+                .filter(|p| p.owner != pindex)
+                .map(|p| roll.probability * board.property(pos).rent(p.rent_level) as f64)
+        })
+        .sum();
+
+    [
+        player.balance as f64,
+        owned.len() as f64,
+        color_sets as f64,
+        rent_potential,
+        -expected_rent,
+        player.in_jail as u8 as f64,
+    ]
+}
@@ -0,0 +1,118 @@
+use super::chance_deck::ChanceDeck;
+use super::encoding::encode_move_type;
+use super::globals::Player;
+use super::state_diff::{FieldDiff, MoveType, StateDiff};
+use super::state_view::ResolvedState;
+
+/// The tags that keep the keys of different kinds of features apart.
+const PLAYER: u64 = 1;
+const JAIL_ROUNDS: u64 = 2;
+const CURRENT_PLAYER: u64 = 3;
+const PROPERTY: u64 = 4;
+const SEEN_CC: u64 = 5;
+const TOP_CC: u64 = 6;
+const LEVEL_1_RENT: u64 = 7;
+const JACKPOT: u64 = 8;
+const NEXT_MOVE: u64 = 9;
+
+/// Scramble `x` with the finalizer of SplitMix64. This is a bijection, so distinct
+/// features always get distinct keys.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Return the Zobrist key of a feature of a state, e.g. the card at some index of the
+/// seen chance cards. `value` has to fit in 48 bits. Rather than being looked up in a
+/// table of random numbers, the keys are derived from the features themselves, so that
+/// they're the same in every process.
+fn key(tag: u64, index: usize, value: u64) -> u64 {
+    mix(tag << 56 ^ (index as u64) << 48 ^ value)
+}
+
+/// Return the key of the player at `pindex`.
+fn player_key(pindex: usize, player: &Player) -> u64 {
+    let flags =
+        player.in_jail as u64 | (player.bankrupt as u64) << 1 | (player.doubles_rolled as u64) << 2;
+    let value = player.position as u64 | (player.balance as u32 as u64) << 8 | flags << 40;
+    key(PLAYER, pindex, value)
+}
+
+/// Return the hash of the chance deck of a state.
+pub(super) fn deck_hash(deck: &ChanceDeck) -> u64 {
+    deck.seen()
+        .iter()
+        .enumerate()
+        .fold(key(TOP_CC, 0, deck.top() as u64), |h, (i, &card)| {
+            h ^ key(SEEN_CC, i, card as u64)
+        })
+}
+
+/// Return the hash of a single field of a state, which is the XOR of the keys of its features.
+pub(super) fn field_hash(diff: &FieldDiff) -> u64 {
+    match diff {
+        FieldDiff::Players(players) => players
+            .iter()
+            .enumerate()
+            .fold(0, |h, (i, p)| h ^ player_key(i, p)),
+        FieldDiff::JailRounds(rounds) => rounds
+            .iter()
+            .enumerate()
+            .fold(0, |h, (i, &r)| h ^ key(JAIL_ROUNDS, i, r as u64)),
+        FieldDiff::CurrentPlayer(pindex) => key(CURRENT_PLAYER, 0, *pindex as u64),
+        FieldDiff::OwnedProperties(props) => props.iter().fold(0, |h, (&pos, prop)| {
+            h ^ key(
+                PROPERTY,
+                pos as usize,
+                (prop.owner as u64) << 8 | prop.rent_level as u64,
+            )
+        }),
+        FieldDiff::ChanceDeck(deck) => deck_hash(deck),
+        FieldDiff::Level1Rent(rounds) => key(LEVEL_1_RENT, 0, *rounds as u64),
+        FieldDiff::Jackpot(jackpot) => key(JACKPOT, 0, *jackpot as u32 as u64),
+    }
+}
+
+/// Return the hash of the type of move to be made after a state.
+pub(super) fn move_hash(next_move: &MoveType) -> u64 {
+    let mut bytes = vec![];
+    encode_move_type(&mut bytes, next_move);
+    bytes.chunks(4).enumerate().fold(0, |h, (i, chunk)| {
+        let value = chunk.iter().fold(0, |v, &b| v << 8 | b as u64);
+        h ^ key(NEXT_MOVE, i, value | (chunk.len() as u64) << 32)
+    })
+}
+
+impl StateDiff {
+    /// Return the Zobrist hash of this state from scratch. This only makes
+    /// sense for a state that stores every field (e.g. the root state).
+    pub(super) fn full_hash(&self) -> u64 {
+        self.diffs
+            .iter()
+            .fold(move_hash(&self.next_move), |h, diff| h ^ field_hash(diff))
+    }
+}
+
+impl ResolvedState {
+    /// Return the Zobrist hash of this state. This is the same as the hash that
+    /// `StateView::hash()` keeps up to date incrementally as moves are generated, so
+    /// the two can be mixed in the same transposition table. Like any hash, distinct
+    /// states can (very rarely) collide, so use `encode()` where that matters.
+    pub fn zobrist_hash(&self) -> u64 {
+        let fields = [
+            FieldDiff::Players(self.players.clone()),
+            FieldDiff::JailRounds(self.jail_rounds.clone()),
+            FieldDiff::CurrentPlayer(self.current_pindex),
+            FieldDiff::OwnedProperties(self.owned_properties.clone().into()),
+            FieldDiff::ChanceDeck(self.chance_deck.clone()),
+            FieldDiff::Level1Rent(self.level_1_rent),
+            FieldDiff::Jackpot(self.jackpot),
+        ];
+
+        fields
+            .iter()
+            .fold(move_hash(&self.next_move), |h, diff| h ^ field_hash(diff))
+    }
+}
@@ -0,0 +1,348 @@
+//! A gRPC service for starting games, stepping through them, analyzing their positions
+//! and running tournaments, so that simulations can be orchestrated from other services
+//! and languages. The service is described in `proto/monopoly.proto`.
+
+use crate::tournament::{Contestant, Tournament};
+use crate::{
+    Budget, Game, GameConfig, GameError, HeuristicAgent, MctsAgent, MoveSummary, RandomAgent,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tonic::{Request, Response, Status};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/monopoly.Monopoly.rs"));
+}
+use generated::monopoly_server::{Monopoly, MonopolyServer};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StartGameRequest {
+    #[prost(uint32, tag = "1")]
+    pub players: u32,
+    #[prost(bool, tag = "2")]
+    pub classic: bool,
+    #[prost(bool, tag = "3")]
+    pub trading: bool,
+    #[prost(uint32, tag = "4")]
+    pub max_turns: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StepRequest {
+    #[prost(uint64, tag = "1")]
+    pub game_id: u64,
+    #[prost(uint32, tag = "2")]
+    pub move_index: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Move {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub acting_player: u32,
+    #[prost(string, tag = "3")]
+    pub description: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GameState {
+    #[prost(uint64, tag = "1")]
+    pub game_id: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub moves: Vec<Move>,
+    #[prost(bool, tag = "3")]
+    pub is_over: bool,
+    #[prost(uint32, tag = "4")]
+    pub winner: u32,
+    #[prost(uint32, tag = "5")]
+    pub turn: u32,
+    #[prost(string, tag = "6")]
+    pub observation_json: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AnalyzeRequest {
+    #[prost(uint64, tag = "1")]
+    pub game_id: u64,
+    #[prost(uint32, tag = "2")]
+    pub iterations: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MoveRating {
+    #[prost(message, optional, tag = "1")]
+    pub r#move: Option<Move>,
+    #[prost(uint32, tag = "2")]
+    pub visits: u32,
+    #[prost(double, tag = "3")]
+    pub value: f64,
+    #[prost(string, repeated, tag = "4")]
+    pub principal_variation: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AnalyzeResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub ratings: Vec<MoveRating>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TournamentRequest {
+    #[prost(uint32, tag = "1")]
+    pub games: u32,
+    #[prost(uint32, tag = "2")]
+    pub iterations: u32,
+    #[prost(string, tag = "3")]
+    pub opponent: String,
+    #[prost(uint32, tag = "4")]
+    pub threads: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Record {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint32, tag = "2")]
+    pub wins: u32,
+    #[prost(uint32, tag = "3")]
+    pub losses: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TournamentResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub records: Vec<Record>,
+    #[prost(double, tag = "2")]
+    pub mean_turns: f64,
+}
+
+impl From<MoveSummary> for Move {
+    fn from(summary: MoveSummary) -> Self {
+        Move {
+            index: summary.index as u32,
+            acting_player: summary.acting_pindex as u32,
+            description: summary.message.to_string(),
+        }
+    }
+}
+
+/// Use `value` if it was given, or `default` if it was left as 0.
+fn or_default(value: u32, default: u32) -> u32 {
+    if value == 0 {
+        default
+    } else {
+        value
+    }
+}
+
+/// The games being played through the service, in the form `HashMap<game_id, game>`.
+type Games = HashMap<u64, Arc<Mutex<Game>>>;
+
+/// Serves the `Monopoly` gRPC service. Searches and tournaments run on tokio's
+/// blocking threads, so that they don't hold up other requests.
+#[derive(Default)]
+pub struct MonopolyService {
+    /// The games started with `StartGame`.
+    games: Mutex<Games>,
+    /// The ID of the next game to be started.
+    next_id: Mutex<u64>,
+}
+
+impl MonopolyService {
+    /// Return the game with ID `game_id`.
+    #[allow(clippy::result_large_err)]
+    fn game(&self, game_id: u64) -> Result<Arc<Mutex<Game>>, Status> {
+        self.games
+            .lock()
+            .unwrap()
+            .get(&game_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("there is no game {}", game_id)))
+    }
+
+    /// Return the state of `game`, which is waiting for the next choice.
+    #[allow(clippy::result_large_err)]
+    fn state(game_id: u64, game: &mut Game) -> Result<GameState, Status> {
+        // Listing the legal moves resolves any chance moves leading up to them
+        let moves = game
+            .legal_moves()
+            .map_err(status)?
+            .into_iter()
+            .map(Move::from)
+            .collect();
+        let observation = game.current_observation();
+        let result = game.result().map_err(status)?;
+
+        Ok(GameState {
+            game_id,
+            moves,
+            is_over: result.is_some(),
+            winner: result.map_or(0, |r| r.winner as u32),
+            turn: observation.turn as u32,
+            observation_json: serde_json::to_string(&observation).unwrap(),
+        })
+    }
+}
+
+/// Return the status that `error` is reported with, which blames the
+/// request unless the error is down to a bug in the engine.
+fn status(error: GameError) -> Status {
+    match error {
+        GameError::Internal(_) | GameError::AgentPanicked { .. } => {
+            Status::internal(error.to_string())
+        }
+        _ => Status::invalid_argument(error.to_string()),
+    }
+}
+
+/// Run `f` on a blocking thread, so that it doesn't hold up the service.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<Response<T>, Status> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map(Response::new)
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl Monopoly for MonopolyService {
+    async fn start_game(
+        &self,
+        request: Request<StartGameRequest>,
+    ) -> Result<Response<GameState>, Status> {
+        let request = request.into_inner();
+        let players = or_default(request.players, 2) as usize;
+        if players < 2 {
+            return Err(Status::invalid_argument("a game needs at least 2 players"));
+        }
+
+        let defaults = if request.classic {
+            GameConfig::classic()
+        } else {
+            GameConfig::default()
+        };
+        let config = GameConfig {
+            trading: request.trading,
+            max_turns: (request.max_turns > 0).then_some(request.max_turns as usize),
+            ..defaults
+        };
+
+        let mut game = Game::with_config(players, config);
+
+        let game_id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id - 1
+        };
+        let state = MonopolyService::state(game_id, &mut game)?;
+        self.games
+            .lock()
+            .unwrap()
+            .insert(game_id, Arc::new(Mutex::new(game)));
+
+        Ok(Response::new(state))
+    }
+
+    async fn step(&self, request: Request<StepRequest>) -> Result<Response<GameState>, Status> {
+        let request = request.into_inner();
+        let game = self.game(request.game_id)?;
+        let mut game = game.lock().unwrap();
+
+        game.apply_move(request.move_index as usize)
+            .map_err(status)?;
+        Ok(Response::new(MonopolyService::state(
+            request.game_id,
+            &mut game,
+        )?))
+    }
+
+    async fn analyze(
+        &self,
+        request: Request<AnalyzeRequest>,
+    ) -> Result<Response<AnalyzeResponse>, Status> {
+        let request = request.into_inner();
+        let game = self.game(request.game_id)?;
+        let budget = Budget::Iterations(or_default(request.iterations, 2000));
+
+        let analyses = blocking(move || {
+            let mut game = game.lock().unwrap();
+            let root = game.current_state().handle();
+            game.analyze(root, budget)
+        })
+        .await?
+        .into_inner()
+        .map_err(status)?;
+        let ratings = analyses
+            .into_iter()
+            .map(|analysis| MoveRating {
+                r#move: Some(Move::from(analysis.summary)),
+                visits: analysis.visits,
+                value: analysis.value,
+                principal_variation: analysis
+                    .principal_variation
+                    .iter()
+                    .map(|message| message.to_string())
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Response::new(AnalyzeResponse { ratings }))
+    }
+
+    async fn run_tournament(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<TournamentResponse>, Status> {
+        let request = request.into_inner();
+        let budget = Budget::Iterations(or_default(request.iterations, 2000));
+        let opponent = match request.opponent.as_str() {
+            "" | "random" => Contestant::new("random", |_| Box::new(RandomAgent)),
+            "heuristic" => Contestant::new("heuristic", |_| Box::new(HeuristicAgent)),
+            name => {
+                return Err(Status::invalid_argument(format!(
+                    "'{}' is not an opponent",
+                    name
+                )))
+            }
+        };
+        let lineup = vec![
+            Contestant::new("mcts", move |seat| {
+                Box::new(MctsAgent::new(2000, 2., seat).with_budget(budget))
+            }),
+            opponent,
+        ];
+
+        let games = or_default(request.games, 100) as usize;
+        let threads = or_default(request.threads, 4) as usize;
+        blocking(move || {
+            let config = GameConfig::default();
+            let board = config.rules.board();
+            let summary = Tournament::new(lineup, games, threads, config, board).run();
+
+            TournamentResponse {
+                records: summary
+                    .records
+                    .iter()
+                    .map(|record| Record {
+                        name: record.name.clone(),
+                        wins: record.wins as u32,
+                        losses: record.losses as u32,
+                    })
+                    .collect(),
+                mean_turns: summary.mean_turns().0,
+            }
+        })
+        .await
+    }
+}
+
+/// Serve the `Monopoly` service at `addr` until the server fails.
+pub async fn serve(addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(MonopolyServer::new(MonopolyService::default()))
+        .serve(addr)
+        .await
+}
@@ -0,0 +1,60 @@
+//! A simulation of Monopoly: Ultimate Banking, along with agents that play it.
+//!
+//! A game is played by giving [`Game::play()`] one [`Agent`] per player. Agents
+//! that are implemented outside of this crate can inspect the game through
+//! [`Game::current_state()`] and [`Game::child_states()`], and choose a move by
+//! returning the index of one of the child states.
+//!
+//! ```no_run
+//! use monopoly_math::{Agent, Game, MctsAgent, RandomAgent};
+//!
+//! let agents: Vec<Box<dyn Agent>> = vec![
+//!     Box::new(MctsAgent::new(1000, 2., 0)),
+//!     Box::new(RandomAgent),
+//! ];
+//! let result = Game::play(agents).expect("the game failed");
+//! println!("Player {} won after {} turns", result.winner, result.turns);
+//! ```
+//!
+//! The engine also compiles to `wasm32-unknown-unknown`, where threads, the file
+//! system and the terminal aren't available. There, games are played through
+//! `WasmGame` instead, and agents search on a single thread.
+//!
+//! With the `tui` feature, games against the AI can also be played on
+//! a board drawn in the terminal, with `tui::Tui`. With the `grpc` feature, games
+//! can be played, analyzed and simulated from other languages through `grpc::serve()`.
+//! With the `storage` feature, tournament results can be accumulated in a SQLite
+//! database with `storage::Database`.
+
+mod game;
+pub use game::*;
+
+#[cfg(all(feature = "grpc", not(target_arch = "wasm32")))]
+pub mod grpc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod prometheus;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rating;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repl;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod report;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod selfplay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod simulator;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod statespace;
+#[cfg(all(feature = "storage", not(target_arch = "wasm32")))]
+pub mod storage;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sweep;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tournament;
+#[cfg(all(feature = "tui", not(target_arch = "wasm32")))]
+pub mod tui;
+
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+#[cfg(target_arch = "wasm32")]
+pub use wasm::WasmGame;
@@ -1,14 +1,865 @@
-use std::thread;
+use monopoly_math::{
+    Agent, Board, Budget, CrashReport, ExpectimaxAgent, Game, GameConfig, GameError, GameResult,
+    HeuristicAgent, HumanAgent, IsmctsAgent, LogLevel, Logger, MctsAgent, Metrics, MoveFrequencies,
+    OpeningBook, OpponentModel, OutputFormat, RandomAgent, RolloutPolicy, SharedCache,
+    SubprocessAgent, Transcript,
+};
+use monopoly_math::{AggressiveBids, AuctionModel, BellCurveBids, UniformBids};
+use monopoly_math::{
+    BalanceTimesPropertyWorth, FactoredEvaluation, NetWorthDifferential, Rank, ValueFunction,
+    WinLoss,
+};
 
-mod game;
-use game::{Agent, Game};
+use monopoly_math::prometheus;
+use monopoly_math::rating::Ratings;
+use monopoly_math::repl::Repl;
+use monopoly_math::report;
+use monopoly_math::selfplay::SelfPlay;
+use monopoly_math::statespace::StateSpaceReport;
+use monopoly_math::sweep::Sweep;
+use monopoly_math::tournament::{Contestant, ProgressReporter, Tournament};
+#[cfg(feature = "tui")]
+use monopoly_math::tui::{Seat, Tui};
+#[cfg(feature = "storage")]
+use monopoly_math::{storage::Database, tournament::TournamentSummary};
+
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let log_level = log_level(&args);
+    let format = output_format(&args);
+    let game_logger = Logger::new(log_level.unwrap_or_default()).with_format(format);
+    // Tournaments and sweeps play many games, so they're silent unless asked otherwise
+    let batch_logger = Logger::new(log_level.unwrap_or(LogLevel::Silent)).with_format(format);
+
+    let defaults = if args.iter().any(|arg| arg == "--classic") {
+        GameConfig::classic()
+    } else {
+        GameConfig::default()
+    };
+    let config = GameConfig {
+        trading: args.iter().any(|arg| arg == "--trading"),
+        real_auctions: args.iter().any(|arg| arg == "--real-auctions"),
+        free_parking_jackpot: args.iter().any(|arg| arg == "--free-parking-jackpot"),
+        pareto_sales: args.iter().any(|arg| arg == "--pareto-sales"),
+        reshuffle_chance_deck: args.iter().any(|arg| arg == "--reshuffle-chance-deck"),
+        symmetry_reduction: args.iter().any(|arg| arg == "--symmetry-reduction"),
+        restrict_location_targets: args.iter().any(|arg| arg == "--restrict-location-targets"),
+        starting_balance: parsed_arg(&args, "--starting-balance")
+            .unwrap_or(defaults.starting_balance),
+        go_salary: parsed_arg(&args, "--go-salary").unwrap_or(defaults.go_salary),
+        jail_penalty: parsed_arg(&args, "--jail-penalty").unwrap_or(defaults.jail_penalty),
+        jail_tries: parsed_arg(&args, "--jail-tries").unwrap_or(defaults.jail_tries),
+        location_fee: parsed_arg(&args, "--location-fee").unwrap_or(defaults.location_fee),
+        resale_percent: parsed_arg(&args, "--resale-percent").unwrap_or(defaults.resale_percent),
+        max_turns: parsed_arg(&args, "--max-turns"),
+        max_repetitions: parsed_arg(&args, "--max-repetitions"),
+        ..defaults
+    };
+
+    // Serve games, analyses and tournaments over gRPC
+    #[cfg(feature = "grpc")]
+    if let Some(i) = args.iter().position(|arg| arg == "--grpc") {
+        let addr = args
+            .get(i + 1)
+            .and_then(|addr| addr.parse().ok())
+            .expect("--grpc requires an address, like 127.0.0.1:50051");
+        let runtime = tokio::runtime::Runtime::new().expect("failed to start the async runtime");
+        runtime
+            .block_on(monopoly_math::grpc::serve(addr))
+            .expect("the gRPC server failed");
+        return;
+    }
+
+    // Play against the AI in the terminal
+    if args.iter().any(|arg| arg == "--human") {
+        let mut game = Game::with_config(2, config)
+            .with_logger(game_logger)
+            .with_auction_model(auction_model(&args));
+        let result = game.play_on(vec![
+            Box::new(HumanAgent::new()),
+            Box::new(human_opponent(&args)),
+        ]);
+        save_game(&game, result, &args);
+        return;
+    }
+
+    // Play between several people sharing the terminal
+    if let Some(players) = parsed_arg::<usize>(&args, "--hot-seat") {
+        #[cfg(feature = "tui")]
+        if args.iter().any(|arg| arg == "--tui") {
+            let seats = (0..players).map(|_| Seat::Human).collect();
+            let game = Game::with_config(players, config).with_auction_model(auction_model(&args));
+            let result = Tui::new(game, seats)
+                .run()
+                .expect("failed to draw the game in the terminal");
+            if let Some(result) = result {
+                println!("Player {} won after {} turns", result.winner, result.turns);
+            }
+            return;
+        }
+
+        let mut game = Game::with_config(players, config)
+            .with_logger(game_logger)
+            .with_auction_model(auction_model(&args));
+        let result = game.play_on(
+            (0..players)
+                .map(|_| Box::new(HumanAgent::hot_seat()) as Box<dyn Agent>)
+                .collect(),
+        );
+        save_game(&game, result, &args);
+        return;
+    }
+
+    // Play against the AI on a board drawn in the terminal
+    #[cfg(feature = "tui")]
+    if args.iter().any(|arg| arg == "--tui") {
+        let seats = vec![Seat::Human, Seat::Ai(Box::new(human_opponent(&args)))];
+        let game = Game::with_config(2, config).with_auction_model(auction_model(&args));
+        let result = Tui::new(game, seats)
+            .run()
+            .expect("failed to draw the game in the terminal");
+        if let Some(result) = result {
+            println!("Player {} won after {} turns", result.winner, result.turns);
+        }
+        return;
+    }
+
+    // Print how the static evaluation and the AI's search rate every move at the position of a saved game
+    if let Some(i) = args.iter().position(|arg| arg == "--analyze") {
+        let path = args.get(i + 1).expect("--analyze requires a file path");
+        let mut game = Game::load(path).expect("failed to load saved game");
+        let evaluation = FactoredEvaluation::default().with_valuation(game.valuation().clone());
+        let explanations = game
+            .explain_moves(&evaluation)
+            .unwrap_or_else(|e| panic!("{}", e));
+        for (summary, breakdown) in explanations {
+            println!("[{}] {}: {}", summary.index, summary.message, breakdown);
+        }
+        let root = game.current_state().handle();
+        let analyses = game
+            .analyze(root, ai_budget(&args))
+            .unwrap_or_else(|e| panic!("{}", e));
+        for analysis in analyses {
+            println!("{}", analysis);
+        }
+        return;
+    }
+
+    // Resume a game that was saved by a human player
+    if let Some(i) = args.iter().position(|arg| arg == "--load") {
+        let path = args.get(i + 1).expect("--load requires a file path");
+        let game = Game::load(path).expect("failed to load saved game");
+        let mut game = game.with_logger(game_logger);
+        let result = game.play_on(vec![
+            Box::new(HumanAgent::new()),
+            Box::new(human_opponent(&args)),
+        ]);
+        save_game(&game, result, &args);
+        return;
+    }
+
+    // Replay a game from a transcript, and then carry on playing it
+    if let Some(i) = args.iter().position(|arg| arg == "--replay") {
+        let path = args.get(i + 1).expect("--replay requires a file path");
+        let transcript = Transcript::load(path).expect("failed to load transcript");
+        print!("{}", transcript);
+
+        let game = Game::replay(&transcript).unwrap_or_else(|e| panic!("{}", e));
+        let mut game = game.with_logger(game_logger);
+        let result = game.play_on(vec![
+            Box::new(HumanAgent::new()),
+            Box::new(human_opponent(&args)),
+        ]);
+        save_game(&game, result, &args);
+        return;
+    }
+
+    // Explore a new game (or a saved one) one state at a time
+    if let Some(i) = args.iter().position(|arg| arg == "--repl") {
+        let game = match args.get(i + 1).filter(|arg| !arg.starts_with("--")) {
+            Some(path) => Game::load(path).expect("failed to load saved game"),
+            None => Game::with_config(2, config),
+        };
+        Repl::new(game)
+            .run(std::io::stdin().lock(), std::io::stdout())
+            .expect("failed to read commands");
+        return;
+    }
+
+    // Reconstruct the game from a crash report (or a transcript), and optionally step through it
+    if let Some(i) = args.iter().position(|arg| arg == "--repro") {
+        let path = args.get(i + 1).expect("--repro requires a file path");
+        let report = CrashReport::load(path).ok();
+        let transcript = match &report {
+            Some(report) => report.transcript.clone(),
+            None => Transcript::load(path).expect("failed to load crash report or transcript"),
+        };
+        print!("{}", transcript);
+
+        let game = Game::replay(&transcript).unwrap_or_else(|e| panic!("{}", e));
+        if let Some(report) = &report {
+            println!("The game failed with: {}", report.error);
+            if let Some(seed) = report.seed {
+                println!("It was seeded with {}", seed);
+            }
+            // The replayed state should be the one that the game crashed in
+            match &report.state {
+                Some(state) if state.zobrist_hash() != game.resolved_state().zobrist_hash() => {
+                    println!("The replayed state differs from the state that the game crashed in")
+                }
+                Some(_) => {
+                    println!("The replayed state matches the state that the game crashed in")
+                }
+                None => println!("The state that the game crashed in couldn't be resolved"),
+            }
+        }
+
+        if args.iter().any(|arg| arg == "--debug") {
+            Repl::new(game)
+                .run(std::io::stdin().lock(), std::io::stdout())
+                .expect("failed to read commands");
+        }
+        return;
+    }
+
+    // The number of games to simulate
+    let games = match args.iter().position(|arg| arg == "--games") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|n| n.parse().ok())
+            .expect("--games requires a number"),
+        None => 100,
+    };
+
+    // The board to play on, which can be loaded from a TOML or JSON file
+    let board = match args.iter().position(|arg| arg == "--board") {
+        Some(i) => {
+            let path = args.get(i + 1).expect("--board requires a file path");
+            Board::load(path).expect("failed to load board")
+        }
+        None => config.rules.board(),
+    };
+
+    // Print how often each tile of the board is landed on instead of running a tournament
+    if args.iter().any(|arg| arg == "--landing-distribution") {
+        for (pos, p) in board.landing_distribution(&config).iter().enumerate() {
+            println!(
+                "{:>2} {:<3} {:.2}%",
+                pos,
+                board.tile_label(pos as u8),
+                p * 100.
+            );
+        }
+        return;
+    }
+
+    let budget = ai_budget(&args);
+    let rollout_policy = ai_rollout_policy(&args);
+    let value_function = ai_value_function(&args);
+
+    // Generate training data from games between MCTS agents instead of running a tournament
+    if let Some(i) = args.iter().position(|arg| arg == "--selfplay") {
+        let dir = args.get(i + 1).expect("--selfplay requires a directory");
+        let new_agent = move |seat| {
+            MctsAgent::new(2000, 2., seat)
+                .with_budget(budget)
+                .with_rollout_policy(rollout_policy)
+                .with_value_function(value_function())
+        };
+
+        // 4 threads for multi-threading
+        let mut selfplay = SelfPlay::new(new_agent, 2, games, 4, dir).with_rules(config, board);
+        if let Some(threshold) = parsed_arg(&args, "--adjudicate") {
+            selfplay = selfplay.with_adjudication(threshold);
+        }
+        let played = selfplay.run().expect("failed to write self-play data");
+        println!("Played {} self-play games", played);
+        return;
+    }
+
+    // Play a round-robin between combinations of MCTS hyperparameters instead of a tournament
+    if let Some(i) = args.iter().position(|arg| arg == "--sweep") {
+        let path = args.get(i + 1).expect("--sweep requires a CSV file path");
+        let rollout_policies = list_arg(&args, "--sweep-rollouts")
+            .unwrap_or_else(|| vec!["random".to_string()])
+            .iter()
+            .map(|p| match p.as_str() {
+                "random" => RolloutPolicy::Random,
+                epsilon => RolloutPolicy::EpsilonGreedy {
+                    epsilon: epsilon
+                        .parse()
+                        .expect("--sweep-rollouts requires 'random' or epsilons"),
+                },
+            })
+            .collect();
+        // 4 threads for multi-threading
+        let mut sweep = Sweep::new(
+            parsed_list_arg(&args, "--sweep-time-limits").unwrap_or_else(|| vec![2000]),
+            parsed_list_arg(&args, "--sweep-temperatures").unwrap_or_else(|| vec![2.]),
+            rollout_policies,
+            games,
+            4,
+        )
+        .with_rules(config, board)
+        .with_logger(batch_logger);
+        if let Some((interval, report)) = progress(&args) {
+            sweep = sweep.with_progress(interval, report);
+        }
+        let results = sweep.run();
+        print!("{}", results);
+        results.to_csv(path).expect("failed to save sweep results");
+
+        if let Some(i) = args.iter().position(|arg| arg == "--ratings") {
+            let path = args.get(i + 1).expect("--ratings requires a file path");
+            let mut ratings = Ratings::load(path).expect("failed to load ratings");
+            for summary in &results.summaries {
+                ratings.record_tournament(summary);
+            }
+            ratings.save(path).expect("failed to save ratings");
+            print!("\n{}", ratings);
+        }
+        return;
+    }
+
+    // The opponent of the MCTS agent, which is an expectimax agent if a search depth is
+    // given, an external program with `--subprocess "<command>"`, a rule-based agent
+    // with `--heuristic`, or otherwise random
+    let subprocess = args.iter().position(|arg| arg == "--subprocess").map(|i| {
+        args.get(i + 1)
+            .expect("--subprocess requires a command")
+            .clone()
+    });
+    let opponent = match args.iter().position(|arg| arg == "--expectimax") {
+        Some(i) => {
+            let depth: u32 = args
+                .get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .expect("--expectimax requires a search depth");
+            Contestant::new("expectimax", move |seat| {
+                Box::new(ExpectimaxAgent::new(depth, seat))
+            })
+            .with_params(&format!("depth={}", depth))
+        }
+        None if subprocess.is_some() => {
+            let command_line = subprocess.unwrap();
+            let params = format!("command={}", command_line);
+            Contestant::new("subprocess", move |_| {
+                let mut words = command_line.split_whitespace();
+                let mut command = Command::new(words.next().expect("the command is empty"));
+                command.args(words);
+                Box::new(SubprocessAgent::spawn(command).expect("failed to start the subprocess"))
+            })
+            .with_params(&params)
+        }
+        None if args.iter().any(|arg| arg == "--heuristic") => {
+            Contestant::new("heuristic", |_| Box::new(HeuristicAgent))
+        }
+        None => Contestant::new("random", |_| Box::new(RandomAgent)),
+    };
+
+    // The name of the MCTS contestant, so that different settings can be told apart in ratings
+    let ai_name = match args.iter().position(|arg| arg == "--ai-name") {
+        Some(i) => args.get(i + 1).expect("--ai-name requires a name").as_str(),
+        None => "mcts",
+    };
+
+    // The opening book that the MCTS agents share, which covers
+    // the first `--book-depth` moves of each game (8 by default)
+    let book_path = args
+        .iter()
+        .position(|arg| arg == "--opening-book")
+        .map(|i| {
+            args.get(i + 1)
+                .expect("--opening-book requires a file path")
+                .clone()
+        });
+    let book = book_path.as_ref().map(|path| {
+        let depth = parsed_arg(&args, "--book-depth").unwrap_or(8);
+        let book = OpeningBook::load(path, depth).expect("failed to load opening book");
+        Arc::new(Mutex::new(book))
+    });
+    let agent_book = book.clone();
+
+    // Share value estimates (and the opening book, which is kept in memory if
+    // `--opening-book` isn't given) between every game with `--shared-cache`
+    let shared_cache = args.iter().any(|arg| arg == "--shared-cache").then(|| {
+        let book = agent_book.clone().unwrap_or_else(|| {
+            let depth = parsed_arg(&args, "--book-depth").unwrap_or(8);
+            Arc::new(Mutex::new(OpeningBook::new(depth)))
+        });
+        Arc::new(SharedCache::with_shared_book(book))
+    });
+    let agent_cache = shared_cache.clone();
+
+    // Solve endgames with at most `--endgame-nodes` nodes exactly instead of searching them
+    let endgame_nodes: Option<usize> = parsed_arg(&args, "--endgame-nodes");
+
+    // Hide the drawn chance cards from the search with `--determinize`
+    let determinize = args.iter().any(|arg| arg == "--determinize");
+    let opponent_model = ai_opponent_model(&args);
+
+    // Search information sets instead of the game tree with `--ismcts`
+    let ismcts = args.iter().any(|arg| arg == "--ismcts");
+
+    // Resign once the estimated chance of winning drops below `--resign`
+    let resign_threshold: Option<f64> = parsed_arg(&args, "--resign");
+
+    // Serve the tournament's progress to Prometheus at `--metrics <address>`
+    let metrics = args.iter().position(|arg| arg == "--metrics").map(|i| {
+        let addr = args
+            .get(i + 1)
+            .and_then(|addr| addr.parse().ok())
+            .expect("--metrics requires an address, like 127.0.0.1:9100");
+        let metrics = Arc::new(Metrics::new());
+        prometheus::serve(addr, metrics.clone()).expect("failed to serve metrics");
+        metrics
+    });
+    let agent_metrics = metrics.clone();
+
+    // Everything that the MCTS agents are set up with, which sets apart runs with different
+    // settings when a tournament is resumed
+    let value_function_name = args
+        .iter()
+        .position(|arg| arg == "--value-function")
+        .and_then(|i| args.get(i + 1))
+        .map_or("balance-product", String::as_str);
+    let ai_params = format!(
+        "ismcts={} budget={:?} rollouts={:?} value_function={} opponent_model={:?} \
+        determinize={} endgame_nodes={:?} resign={:?} opening_book={:?} shared_cache={}",
+        ismcts,
+        budget,
+        rollout_policy,
+        value_function_name,
+        opponent_model,
+        determinize,
+        endgame_nodes,
+        resign_threshold,
+        book_path,
+        shared_cache.is_some()
+    );
+
+    let lineup = vec![
+        Contestant::new(ai_name, move |seat| -> Box<dyn Agent> {
+            if ismcts {
+                let mut agent = IsmctsAgent::new(2000, 1., seat)
+                    .with_budget(budget)
+                    .with_rollout_policy(rollout_policy)
+                    .with_value_function(value_function());
+                if let Some(metrics) = &agent_metrics {
+                    agent = agent.with_metrics(metrics.clone());
+                }
+                return Box::new(agent);
+            }
+
+            let mut agent = MctsAgent::new(2000, 2., seat)
+                .with_budget(budget)
+                .with_rollout_policy(rollout_policy)
+                .with_value_function(value_function());
+            if let Some(cache) = &agent_cache {
+                agent = agent.with_shared_cache(cache.clone());
+            } else if let Some(book) = &agent_book {
+                agent = agent.with_opening_book(book.clone());
+            }
+            if let Some(node_limit) = endgame_nodes {
+                agent = agent.with_endgame_solver(node_limit);
+            }
+            if determinize {
+                agent = agent.with_determinization();
+            }
+            if let Some(model) = &opponent_model {
+                agent = agent.with_opponent_model(model.clone());
+            }
+            if let Some(threshold) = resign_threshold {
+                agent = agent.with_resignation(threshold);
+            }
+            if let Some(metrics) = &agent_metrics {
+                agent = agent.with_metrics(metrics.clone());
+            }
+            Box::new(agent)
+        })
+        .with_params(&ai_params),
+        opponent,
+    ];
+
+    // Play randomly for any agent that takes longer than `--decision-timeout` milliseconds
+    let lineup = match parsed_arg(&args, "--decision-timeout") {
+        Some(timeout) => lineup
+            .into_iter()
+            .map(|c| c.with_decision_timeout(Duration::from_millis(timeout)))
+            .collect(),
+        None => lineup,
+    };
+
+    // Stop starting new games on Ctrl-C, so that the games played so far are still saved
+    let interrupted = interrupt_flag();
+
     // 4 threads for multi-threading
-    for _ in 0..4 {
-        thread::spawn(|| loop {
-            // Continuously run the simulations
-            Game::play(vec![Agent::new_ai(2000, 2., 0), Agent::new_random()]);
+    let mut tournament = Tournament::new(lineup, games, 4, config, board)
+        .with_logger(batch_logger)
+        .with_auction_model(auction_model(&args))
+        .with_stop_flag(interrupted.clone());
+    if args.iter().any(|arg| arg == "--validate") {
+        tournament = tournament.with_validation();
+    }
+    if let Some(threshold) = parsed_arg(&args, "--adjudicate") {
+        tournament = tournament.with_adjudication(threshold);
+    }
+    // Play every game again with the seats swapped, to cancel out any first-move advantage
+    if args.iter().any(|arg| arg == "--swap-seats") {
+        tournament = tournament.with_swapped_seats();
+    }
+    if let Some(seed) = parsed_arg(&args, "--seed") {
+        tournament = tournament.with_seed(seed);
+    }
+    // Save enough to reproduce any game that runs into a bug
+    let crash_dir = match args.iter().position(|arg| arg == "--crash-dir") {
+        Some(i) => args
+            .get(i + 1)
+            .expect("--crash-dir requires a directory")
+            .clone(),
+        None => "crashes".to_string(),
+    };
+    tournament = tournament.with_crash_dir(&crash_dir);
+    if let Some((interval, report)) = progress(&args) {
+        tournament = tournament.with_progress(interval, report);
+    }
+    if let Some(metrics) = metrics {
+        tournament = tournament.with_metrics(metrics);
+    }
+    let summary = match args.iter().position(|arg| arg == "--database") {
+        // Store every game as it finishes, resuming the tournament if it was run before
+        #[cfg(feature = "storage")]
+        Some(i) => {
+            let path = args.get(i + 1).expect("--database requires a file path");
+            run_stored(tournament, config, path)
+        }
+        _ => tournament.run(),
+    };
+    if interrupted.load(Ordering::Relaxed) {
+        eprintln!(
+            "Interrupted after {} of {} games",
+            summary.results.len(),
+            games
+        );
+    }
+    println!("{}", summary);
+
+    // Report any problems found with the game trees, one line per kind of problem
+    let mut problems: Vec<String> = summary
+        .results
+        .iter()
+        .flat_map(|r| &r.validation_errors)
+        .map(|e| e.to_string())
+        .collect();
+    problems.sort();
+    problems.dedup();
+    for problem in problems {
+        eprintln!("{}", problem);
+    }
+    for (game_index, error) in &summary.errors {
+        eprintln!("game {} failed: {}", game_index, error);
+    }
+    if !summary.errors.is_empty() {
+        eprintln!(
+            "Crash reports of any games that ran into bugs are in {}",
+            crash_dir
+        );
+    }
+
+    // The games are already stored in the database (including those from earlier runs)
+    if !cfg!(feature = "storage") || !args.iter().any(|arg| arg == "--database") {
+        for result in &summary.results {
+            save_stats(result, &args);
+        }
+    }
+
+    // Report the shape of the game tree that the games passed through
+    if let Some(i) = args.iter().position(|arg| arg == "--statespace") {
+        let path = args
+            .get(i + 1)
+            .expect("--statespace requires a CSV file path");
+        let report = StateSpaceReport::new(&summary.results);
+        println!("\n{}", report);
+        report
+            .to_csv(path)
+            .expect("failed to save state space report");
+    }
+
+    if let (Some(path), Some(book)) = (book_path, book) {
+        let book = book.lock().unwrap();
+        book.save(path).expect("failed to save opening book");
+        eprintln!("Opening book has {} states", book.len());
+    }
+    if let Some(cache) = &shared_cache {
+        eprintln!("Shared cache has {} value estimates", cache.len());
+    }
+
+    // Accumulate the agents' ratings over every run that uses the same ratings file
+    if let Some(i) = args.iter().position(|arg| arg == "--ratings") {
+        let path = args.get(i + 1).expect("--ratings requires a file path");
+        let mut ratings = Ratings::load(path).expect("failed to load ratings");
+        ratings.record_tournament(&summary);
+        ratings.save(path).expect("failed to save ratings");
+        print!("\n{}", ratings);
+    }
+}
+
+/// Run `tournament` (played with `config`), storing every game in the database at `path`
+/// as it finishes. If the tournament was run before, only the games that weren't stored are played.
+#[cfg(feature = "storage")]
+fn run_stored(tournament: Tournament, config: GameConfig, path: &str) -> TournamentSummary {
+    let mut database = Database::open(path).expect("failed to open database");
+    let id = database
+        .tournament(&tournament, &config)
+        .expect("failed to save the tournament to the database");
+    let completed = database
+        .completed_games(id)
+        .expect("failed to load games from the database");
+    if !completed.is_empty() {
+        eprintln!("Resuming after {} stored games", completed.len());
+    }
+
+    let names = tournament.names();
+    let tournament = tournament.with_completed_games(completed);
+    tournament.run_with(|game_index, seating, result| {
+        if let Err(e) = database.record_game(id, game_index, seating, result, &names) {
+            eprintln!("failed to save game {} to the database: {}", game_index, e);
+        }
+    })
+}
+
+/// Return a flag that's set when the process is interrupted (e.g. with Ctrl-C).
+/// If it's interrupted again after that, it exits straight away.
+fn interrupt_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+
+    for &signal in signal_hook::consts::TERM_SIGNALS {
+        // The exit has to be registered first, so that it only happens once the flag is set
+        signal_hook::flag::register_conditional_shutdown(signal, 130, flag.clone())
+            .expect("failed to handle interrupts");
+        signal_hook::flag::register(signal, flag.clone()).expect("failed to handle interrupts");
+    }
+
+    flag
+}
+
+/// Return how often to print the progress of tournaments to stderr if `--progress [seconds]`
+/// is given (every 10 seconds by default), along with the function that prints it.
+fn progress(args: &[String]) -> Option<(Duration, Arc<ProgressReporter>)> {
+    if !args.iter().any(|arg| arg == "--progress") {
+        return None;
+    }
+
+    let interval = Duration::from_secs(parsed_arg(args, "--progress").unwrap_or(10));
+    Some((interval, Arc::new(|progress| eprintln!("{}", progress))))
+}
+
+/// Return the comma-separated values given after `flag`, if it was given.
+fn list_arg(args: &[String], flag: &str) -> Option<Vec<String>> {
+    args.iter().position(|arg| arg == flag).map(|i| {
+        args.get(i + 1)
+            .unwrap_or_else(|| panic!("{} requires a comma-separated list", flag))
+            .split(',')
+            .map(|v| v.to_string())
+            .collect()
+    })
+}
+
+/// Save the statistics of a game that was played to the end (and its report, if `--report`
+/// was given), or report why it wasn't.
+fn save_game(game: &Game, result: Result<GameResult, GameError>, args: &[String]) {
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("the game failed: {}", e);
+            return;
+        }
+    };
+    save_stats(&result, args);
+
+    // Look back over the game in a browser
+    if let Some(i) = args.iter().position(|arg| arg == "--report") {
+        let path = args.get(i + 1).expect("--report requires a file path");
+        if let Err(e) = report::save(game.transcript(), &result, path) {
+            eprintln!("failed to save the game report: {}", e);
+        }
+    }
+}
+
+/// Save the gameplay statistics of a finished game to a new directory in the
+/// directory given after `--stats-dir`, if it was given.
+fn save_stats(result: &GameResult, args: &[String]) {
+    let Some(i) = args.iter().position(|arg| arg == "--stats-dir") else {
+        return;
+    };
+
+    let dir = args.get(i + 1).expect("--stats-dir requires a directory");
+    if let Err(e) = result.stats.save_to_csv(dir, result.winner) {
+        eprintln!("failed to save gameplay stats: {}", e);
+    }
+}
+
+/// Return the number given after `flag`, if it was given.
+fn parsed_arg<T: FromStr>(args: &[String], flag: &str) -> Option<T> {
+    args.iter().position(|arg| arg == flag).map(|i| {
+        args.get(i + 1)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| panic!("{} requires a number", flag))
+    })
+}
+
+/// Return the comma-separated numbers given after `flag`, if it was given.
+fn parsed_list_arg<T: FromStr>(args: &[String], flag: &str) -> Option<Vec<T>> {
+    list_arg(args, flag).map(|values| {
+        values
+            .iter()
+            .map(|v| {
+                v.parse()
+                    .unwrap_or_else(|_| panic!("{} requires numbers", flag))
+            })
+            .collect()
+    })
+}
+
+/// Return the level given with `--log-level <silent|summary|move|search>`, if any.
+fn log_level(args: &[String]) -> Option<LogLevel> {
+    args.iter().position(|arg| arg == "--log-level").map(|i| {
+        args.get(i + 1)
+            .expect("--log-level requires a level")
+            .parse()
+            .unwrap_or_else(|e| panic!("{}", e))
+    })
+}
+
+/// Return the model that auctions are resolved with, chosen
+/// with `--auction-model <bell-curve|uniform|aggressive>`.
+fn auction_model(args: &[String]) -> Arc<dyn AuctionModel> {
+    let name = match args.iter().position(|arg| arg == "--auction-model") {
+        Some(i) => args
+            .get(i + 1)
+            .expect("--auction-model requires a name")
+            .as_str(),
+        None => "bell-curve",
+    };
+
+    match name {
+        "bell-curve" => Arc::new(BellCurveBids),
+        "uniform" => Arc::new(UniformBids),
+        "aggressive" => Arc::new(AggressiveBids),
+        _ => panic!("unknown auction model '{}'", name),
+    }
+}
+
+/// Return the format given with `--output <ansi|plain|json>`, or ANSI-highlighted text.
+fn output_format(args: &[String]) -> OutputFormat {
+    match args.iter().position(|arg| arg == "--output") {
+        Some(i) => args
+            .get(i + 1)
+            .expect("--output requires a format")
+            .parse()
+            .unwrap_or_else(|e| panic!("{}", e)),
+        None => OutputFormat::default(),
+    }
+}
+
+/// Return the AI that plays against a human, which searches with `--ai-threads`
+/// threads and logs its search statistics if `--search-stats` is given.
+fn human_opponent(args: &[String]) -> MctsAgent {
+    let threads = match args.iter().position(|arg| arg == "--ai-threads") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|n| n.parse().ok())
+            .expect("--ai-threads requires a number"),
+        None => 1,
+    };
+    let log_level = if args.iter().any(|arg| arg == "--search-stats") {
+        LogLevel::Search
+    } else {
+        log_level(args).unwrap_or_default()
+    };
+
+    let agent = MctsAgent::new(2000, 2., 1)
+        .with_budget(ai_budget(args))
+        .with_rollout_policy(ai_rollout_policy(args))
+        .with_value_function(ai_value_function(args)())
+        .with_threads(threads)
+        .with_logger(if args.iter().any(|arg| arg == "--tui") {
+            // Anything logged would be drawn over the board
+            Logger::silent()
+        } else {
+            Logger::new(log_level).with_format(output_format(args))
         });
+
+    match ai_opponent_model(args) {
+        Some(model) => agent.with_opponent_model(model),
+        None => agent,
+    }
+}
+
+/// Return the search budget of the MCTS AI, which is `--ai-iterations`
+/// iterations per decision if given, or otherwise 2 seconds.
+fn ai_budget(args: &[String]) -> Budget {
+    match args.iter().position(|arg| arg == "--ai-iterations") {
+        Some(i) => Budget::Iterations(
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .expect("--ai-iterations requires a number"),
+        ),
+        None => Budget::Time(Duration::from_millis(2000)),
+    }
+}
+
+/// Return the rollout policy of the MCTS AI, which is epsilon-greedy
+/// if `--rollout-epsilon` is given, or otherwise random.
+fn ai_rollout_policy(args: &[String]) -> RolloutPolicy {
+    match args.iter().position(|arg| arg == "--rollout-epsilon") {
+        Some(i) => RolloutPolicy::EpsilonGreedy {
+            epsilon: args
+                .get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .expect("--rollout-epsilon requires a number"),
+        },
+        None => RolloutPolicy::Random,
+    }
+}
+
+/// Return how the MCTS AI expects its opponents to play, chosen with
+/// `--opponent-model <random|heuristic|frequencies>`, or `None` to search their moves.
+fn ai_opponent_model(args: &[String]) -> Option<OpponentModel> {
+    let i = args.iter().position(|arg| arg == "--opponent-model")?;
+    let name = args.get(i + 1).expect("--opponent-model requires a name");
+
+    Some(match name.as_str() {
+        "random" => OpponentModel::Random,
+        "heuristic" => OpponentModel::Heuristic,
+        "frequencies" => OpponentModel::Frequencies(MoveFrequencies::default()),
+        _ => panic!("unknown opponent model '{}'", name),
+    })
+}
+
+/// Return a constructor for the function that the MCTS AI scores its rollouts
+/// with, chosen with `--value-function <balance-product|net-worth|win-loss|rank>`.
+fn ai_value_function(args: &[String]) -> fn() -> Box<dyn ValueFunction> {
+    let name = match args.iter().position(|arg| arg == "--value-function") {
+        Some(i) => args
+            .get(i + 1)
+            .expect("--value-function requires a name")
+            .as_str(),
+        None => "balance-product",
+    };
+
+    match name {
+        "balance-product" => || Box::new(BalanceTimesPropertyWorth),
+        "net-worth" => || Box::new(NetWorthDifferential),
+        "win-loss" => || Box::new(WinLoss),
+        "rank" => || Box::new(Rank),
+        "factored" => || Box::new(FactoredEvaluation::default()),
+        _ => panic!("unknown value function '{}'", name),
     }
 }
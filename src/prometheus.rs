@@ -0,0 +1,128 @@
+//! An HTTP endpoint that serves `Metrics` in the Prometheus text format, so that long
+//! simulations can be monitored (and graphed or alerted on) with standard tooling.
+//! Only `GET /metrics` is answered, one request per connection, which is all that a
+//! Prometheus scraper needs.
+
+use crate::game::{Metrics, MetricsSnapshot};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a scraper has to send its request (or read the response) before its
+/// connection is dropped, which is as long as it can hold up the scrapes after it.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Serve `metrics` at `http://<addr>/metrics` on a background thread,
+/// which keeps serving until the process exits.
+pub fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let start_time = Instant::now();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let body = render(&metrics.snapshot(), start_time.elapsed().as_secs_f64());
+            // Connections are answered one at a time, and the timeouts keep a slow
+            // or idle scraper from holding up the ones after it for long
+            if let Err(e) = respond(stream, &body) {
+                eprintln!("failed to answer a metrics scrape: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Answer the request on `stream` with `body` if it's for `/metrics`, or with a 404 otherwise.
+fn respond(mut stream: TcpStream, body: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let (status, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", body),
+        _ => ("404 Not Found", "not found\n"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Return `snapshot` in the Prometheus text format, along with the average rates of games
+/// and rollouts over the `elapsed` seconds that the metrics have been collected for.
+fn render(snapshot: &MetricsSnapshot, elapsed: f64) -> String {
+    let per_second = |total: u64| total as f64 / elapsed.max(f64::EPSILON);
+    let metrics: [(&str, &str, &str, f64); 9] = [
+        (
+            "games_total",
+            "counter",
+            "Games that have finished.",
+            snapshot.games as f64,
+        ),
+        (
+            "turns_total",
+            "counter",
+            "Turns played over every finished game.",
+            snapshot.turns as f64,
+        ),
+        (
+            "searches_total",
+            "counter",
+            "Decisions that were searched.",
+            snapshot.searches as f64,
+        ),
+        (
+            "rollouts_total",
+            "counter",
+            "Rollouts performed by every search.",
+            snapshot.rollouts as f64,
+        ),
+        (
+            "nodes_expanded_total",
+            "counter",
+            "MCTS nodes created by every search.",
+            snapshot.nodes_expanded as f64,
+        ),
+        (
+            "game_tree_nodes",
+            "gauge",
+            "Game tree nodes stored by the games in progress.",
+            snapshot.game_nodes as f64,
+        ),
+        (
+            "game_tree_bytes",
+            "gauge",
+            "Estimated memory used by the game trees of the games in progress.",
+            snapshot.game_memory as f64,
+        ),
+        (
+            "games_per_second",
+            "gauge",
+            "Games finished per second since the metrics started.",
+            per_second(snapshot.games),
+        ),
+        (
+            "rollouts_per_second",
+            "gauge",
+            "Rollouts performed per second since the metrics started.",
+            per_second(snapshot.rollouts),
+        ),
+    ];
+
+    let mut text = String::new();
+    for &(name, kind, help, value) in &metrics {
+        // Writing to a string can't fail
+        writeln!(text, "# HELP monopoly_{} {}", name, help).unwrap();
+        writeln!(text, "# TYPE monopoly_{} {}", name, kind).unwrap();
+        writeln!(text, "monopoly_{} {}", name, value).unwrap();
+    }
+
+    text
+}
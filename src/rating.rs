@@ -0,0 +1,129 @@
+use crate::tournament::TournamentSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// The rating that every agent configuration starts with.
+const INITIAL_ELO: f64 = 1500.;
+
+/// How much a single game can change a rating.
+const K_FACTOR: f64 = 32.;
+
+/// The rating of a single agent configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    /// The Elo rating of the agent.
+    pub elo: f64,
+    /// The number of games that the rating is based on.
+    pub games: usize,
+}
+
+/// Elo ratings of agent configurations (identified by their contestant names),
+/// which can be saved between runs so that they accumulate over many tournaments.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ratings {
+    ratings: HashMap<String, Rating>,
+}
+
+impl Ratings {
+    /// Return an empty set of ratings.
+    pub fn new() -> Self {
+        Ratings::default()
+    }
+
+    /// Load ratings that were previously saved with `Ratings::save()`,
+    /// or return an empty set of ratings if `path` doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Ratings::new());
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Save the ratings to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Return the rating of the agent called `name`, if it has played any games.
+    pub fn get(&self, name: &str) -> Option<&Rating> {
+        self.ratings.get(name)
+    }
+
+    /// Update the ratings with a game between the agents in `ranking`, which
+    /// is ordered from first place to last. Multiplayer games are rated as
+    /// a win for every agent against each agent ranked below them.
+    pub fn record_game(&mut self, ranking: &[&str]) {
+        let elos: Vec<f64> = ranking
+            .iter()
+            .map(|name| self.ratings.get(*name).map_or(INITIAL_ELO, |r| r.elo))
+            .collect();
+
+        // Split the K-factor between every pairing so that a game
+        // with more players doesn't move the ratings further
+        let k = K_FACTOR / (ranking.len() - 1).max(1) as f64;
+
+        for (i, name) in ranking.iter().enumerate() {
+            let change: f64 = (0..ranking.len())
+                .filter(|&j| j != i)
+                .map(|j| {
+                    let expected = 1. / (1. + 10f64.powf((elos[j] - elos[i]) / 400.));
+                    let actual = if i < j { 1. } else { 0. };
+                    k * (actual - expected)
+                })
+                .sum();
+
+            let rating = self.ratings.entry(name.to_string()).or_insert(Rating {
+                elo: INITIAL_ELO,
+                games: 0,
+            });
+            rating.elo += change;
+            rating.games += 1;
+        }
+    }
+
+    /// Update the ratings with every game played in a tournament.
+    pub fn record_tournament(&mut self, summary: &TournamentSummary) {
+        for (result, seating) in std::iter::zip(&summary.results, &summary.seatings) {
+            // The winner comes first, followed by the players who went bankrupt last
+            let ranking: Vec<&str> = std::iter::once(&result.winner)
+                .chain(result.losers.iter().rev())
+                .map(|&seat| summary.records[seating.contestants[seat]].name.as_str())
+                .collect();
+
+            self.record_game(&ranking);
+        }
+    }
+}
+
+impl fmt::Display for Ratings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut leaderboard: Vec<(&String, &Rating)> = self.ratings.iter().collect();
+        leaderboard.sort_by(|a, b| b.1.elo.partial_cmp(&a.1.elo).unwrap());
+
+        writeln!(
+            f,
+            "{:>4} {:<24} {:>6} {:>6}",
+            "rank", "agent", "elo", "games"
+        )?;
+        for (i, (name, rating)) in leaderboard.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>4} {:<24} {:>6.0} {:>6}",
+                i + 1,
+                name,
+                rating.elo,
+                rating.games
+            )?;
+        }
+
+        Ok(())
+    }
+}
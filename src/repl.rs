@@ -0,0 +1,153 @@
+//! A command line for exploring the engine, by stepping through a game one state at a time
+//! to see what the move generators produce in any position (e.g. to follow how a game from
+//! a `CrashReport` got into a state that it shouldn't be in). Every state is reached by
+//! choosing one of the children of the current state, including chance outcomes (such as
+//! a particular dice roll), so the game only goes where it's told to.
+//!
+//! The commands are:
+//!
+//! - `show`: print the board and players at the current state
+//! - `children`: list the states that can be reached from the current state
+//! - `apply <i>`: move to the state at index `i` of `children`
+//! - `undo [n]`: go back `n` states (1 by default)
+//! - `analyze <ms>`: make any chance moves up to the next choice, then search it for
+//!   `ms` milliseconds and rate every move
+//! - `save <file>`: save the game, so that it can be loaded again with `Game::load()`
+//! - `help`: list the commands
+//! - `quit`: stop (as does the end of the input)
+
+use crate::game::{render_board, Budget, Game};
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The commands, as printed by `help`.
+const HELP: &str = "\
+show         print the board and players at the current state
+children     list the states that can be reached from the current state
+apply <i>    move to the state at index i of the children
+undo [n]     go back n states (1 by default)
+analyze <ms> make any chance moves up to the next choice, then search it for ms milliseconds
+save <file>  save the game to a JSON file
+help         list the commands
+quit         stop
+";
+
+/// A command line for stepping through a game and exploring its states.
+pub struct Repl {
+    game: Game,
+}
+
+impl Repl {
+    /// Return a command line that starts at the current state of `game`.
+    pub fn new(game: Game) -> Self {
+        Repl { game }
+    }
+
+    /// Read commands from `input` until it ends or `quit` is entered,
+    /// writing a prompt before each one and its output after it.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let command = line.trim();
+            if command == "quit" {
+                break;
+            }
+
+            // Bugs in the engine are what this is for, so they shouldn't end the session.
+            // The game may not be left in a consistent state by a panic, though.
+            let text = panic::catch_unwind(AssertUnwindSafe(|| self.execute(command)))
+                .unwrap_or_else(|_| "The engine panicked (see the message above)\n".to_string());
+            write!(output, "{}> ", text)?;
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the output of `command`, which is a line of input.
+    fn execute(&mut self, command: &str) -> String {
+        let mut words = command.split_whitespace();
+        let result = match (words.next(), words.next()) {
+            (None, _) => Ok(String::new()),
+            (Some("show"), None) => Ok(self.show()),
+            (Some("children"), None) => Ok(self.children()),
+            (Some("apply"), Some(index)) => parse(index).and_then(|index| {
+                self.game.step(index).map_err(|e| e.to_string())?;
+                Ok(self.show())
+            }),
+            (Some("undo"), n) => parse(n.unwrap_or("1")).map(|n| {
+                let undone = self.game.undo(n);
+                format!("Went back {} states\n{}", undone, self.show())
+            }),
+            (Some("analyze"), Some(ms)) => parse(ms).and_then(|ms| self.analyze(ms)),
+            (Some("save"), Some(path)) => match self.game.save(path) {
+                Ok(()) => Ok(format!("Saved the game to {}\n", path)),
+                Err(e) => Err(format!("Failed to save the game: {}", e)),
+            },
+            (Some("help"), None) => Ok(HELP.to_string()),
+            _ => Err(format!(
+                "Unknown command '{}' (enter 'help' for a list)",
+                command
+            )),
+        };
+
+        result.unwrap_or_else(|e| format!("{}\n", e))
+    }
+
+    /// Return the board and players at the current state, along with
+    /// how the game got there and what happens next.
+    fn show(&self) -> String {
+        let state = self.game.current_state();
+        format!(
+            "Turn {}, after move {}: {}\n{}Next: {:?}\n",
+            self.game.turn(),
+            self.game.transcript().entries.len(),
+            state.message(),
+            render_board(state),
+            state.next_move()
+        )
+    }
+
+    /// Return a line for each child of the current state.
+    fn children(&mut self) -> String {
+        let children = self.game.child_states();
+        if children.is_empty() {
+            return "The game is over\n".to_string();
+        }
+
+        let mut text = String::new();
+        for (i, child) in children.iter().enumerate() {
+            text += &match child.probability() {
+                Some(p) => format!("[{}] {} (p = {:.3})\n", i, child.message(), p),
+                None => format!("[{}] {}\n", i, child.message()),
+            };
+        }
+
+        text
+    }
+
+    /// Return how an MCTS search of `ms` milliseconds rates every move at the next choice.
+    fn analyze(&mut self, ms: u64) -> Result<String, String> {
+        let root = self.game.current_state().handle();
+        let analyses = self
+            .game
+            .analyze(root, Budget::Time(Duration::from_millis(ms)))
+            .map_err(|e| e.to_string())?;
+        if analyses.is_empty() {
+            return Ok("The game is over\n".to_string());
+        }
+
+        Ok(analyses.iter().map(|a| format!("{}\n", a)).collect())
+    }
+}
+
+/// Parse the argument `word` of a command.
+fn parse<T: FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("'{}' isn't a number", word))
+}
@@ -0,0 +1,296 @@
+//! A self-contained HTML report of a finished game, which can be opened in a browser to
+//! look back over how the game went: a summary of the outcome, a chart of the players'
+//! balances over time, when each property was acquired, how long the agents took to
+//! decide, and every turn's moves along with the board at the end of it. The report is
+//! put together from the game's transcript (which is replayed for the board snapshots
+//! and balances) and the statistics in its result.
+
+use crate::game::{
+    render_board, Board, BranchType, Game, GameError, GameResult, MoveType, Transcript,
+};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The width and height of the balance chart, in pixels.
+const CHART_SIZE: (f64, f64) = (800., 300.);
+/// The space around the plot area of the chart, for its axis labels.
+const CHART_MARGIN: f64 = 40.;
+/// The line color of each player in the chart (in order), repeating for larger games.
+const PLAYER_COLORS: [&str; 6] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+];
+
+/// The styles of the report.
+const STYLE: &str = "
+body { font-family: sans-serif; max-width: 960px; margin: 2em auto; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: right; }
+th { background: #f0f0f0; }
+pre { background: #f8f8f8; padding: 0.5em; overflow-x: auto; }
+details { margin: 0.2em 0; }
+summary { cursor: pointer; }
+";
+
+/// What happened in a single turn of the game.
+struct Turn {
+    /// A description of each move made in the turn.
+    moves: Vec<String>,
+    /// The board and players at the end of the turn.
+    board: String,
+    /// The balance of each player at the end of the turn.
+    balances: Vec<i32>,
+}
+
+/// Write the report of the game with `transcript` and `result` to an HTML file at `path`.
+pub fn save(
+    transcript: &Transcript,
+    result: &GameResult,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let html = render(transcript, result).map_err(io::Error::other)?;
+    fs::write(path, html)
+}
+
+/// Return the report of the game with `transcript` and `result` as an HTML page,
+/// or an error if the transcript can't be replayed.
+pub fn render(transcript: &Transcript, result: &GameResult) -> Result<String, GameError> {
+    let turns = replay_turns(transcript)?;
+    let mut html = String::new();
+
+    // Writing to a string can't fail
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Monopoly game report</title>\n<style>{}</style>\n</head>\n<body>",
+        STYLE
+    )
+    .unwrap();
+
+    html += &summary(result);
+    html += "<h2>Balances</h2>\n";
+    html += &balance_chart(transcript, &turns);
+    html += &acquisitions(result, &transcript.board);
+    html += &decisions(result);
+
+    html += "<h2>Turns</h2>\n";
+    for (i, turn) in turns.iter().enumerate() {
+        writeln!(
+            html,
+            "<details>\n<summary>Turn {} ({} moves)</summary>\n<ol>",
+            i,
+            turn.moves.len()
+        )
+        .unwrap();
+        for message in &turn.moves {
+            writeln!(html, "<li>{}</li>", escape(message)).unwrap();
+        }
+        writeln!(
+            html,
+            "</ol>\n<pre>{}</pre>\n</details>",
+            escape(&turn.board)
+        )
+        .unwrap();
+    }
+
+    html += "</body>\n</html>\n";
+    Ok(html)
+}
+
+/// Replay the game with `transcript`, and return what happened in every turn.
+fn replay_turns(transcript: &Transcript) -> Result<Vec<Turn>, GameError> {
+    let mut turns = vec![];
+    let mut moves = vec![];
+
+    let game = Game::replay_with(transcript, |game| {
+        let entry = &transcript.entries[game.transcript().entries.len() - 1];
+        moves.push(match entry.branch_type {
+            BranchType::Chance(p) => format!("{} (p = {:.3})", entry.message, p),
+            _ => entry.message.to_string(),
+        });
+
+        // A turn ends once the next move is the next player's roll
+        if matches!(entry.next_move, MoveType::Roll) {
+            turns.push(snapshot(game, std::mem::take(&mut moves)));
+        }
+    })?;
+
+    // The last turn may have ended the game before the next roll
+    if !moves.is_empty() {
+        turns.push(snapshot(&game, moves));
+    }
+
+    Ok(turns)
+}
+
+/// Return the turn made up of `moves`, which left `game` in its current state.
+fn snapshot(game: &Game, moves: Vec<String>) -> Turn {
+    Turn {
+        moves,
+        board: render_board(game.current_state()),
+        balances: game.players().iter().map(|p| p.balance).collect(),
+    }
+}
+
+/// Return the outcome of the game as an HTML section.
+fn summary(result: &GameResult) -> String {
+    let mut ending = String::new();
+    if result.turn_limit_reached {
+        ending += ", when the turn limit was reached";
+    }
+    if result.repetition_limit_reached {
+        ending += ", when the same state came up too many times";
+    }
+    if result.adjudicated {
+        ending += ", after a player was made to resign";
+    }
+
+    let mut html = format!(
+        "<h1>Game report</h1>\n<p>Player {} won after {} turns{}.</p>\n\
+        <table>\n<tr><th>Player</th><th>Final balance</th><th>Properties acquired</th></tr>\n",
+        result.winner, result.turns, ending
+    );
+    let acquired = result.stats.acquired_properties();
+    for (pindex, balance) in result.final_balances.iter().enumerate() {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>${}</td><td>{}</td></tr>",
+            pindex,
+            balance,
+            acquired.get(pindex).map_or(0, Vec::len)
+        )
+        .unwrap();
+    }
+
+    html += "</table>\n";
+    html
+}
+
+/// Return an SVG line chart of each player's balance at the end of every turn.
+fn balance_chart(transcript: &Transcript, turns: &[Turn]) -> String {
+    let (width, height) = CHART_SIZE;
+    let starting_balance = transcript.config.starting_balance;
+    let balances = || turns.iter().flat_map(|t| &t.balances).copied();
+    // The balances are plotted from 0 (or the lowest debt) up, even if no one ever went broke
+    let max_balance = balances().chain([starting_balance, 1]).max().unwrap_or(1) as f64;
+    let min_balance = balances().chain([0]).min().unwrap_or(0) as f64;
+
+    // The turns run along the x-axis, starting from before the first turn
+    let x = |turn: usize| {
+        CHART_MARGIN + turn as f64 / turns.len().max(1) as f64 * (width - 2. * CHART_MARGIN)
+    };
+    let y = |balance: i32| {
+        height
+            - CHART_MARGIN
+            - (balance as f64 - min_balance) / (max_balance - min_balance)
+                * (height - 2. * CHART_MARGIN)
+    };
+
+    let mut svg = format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">\n\
+        <line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#888\"/>\n\
+        <line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\" stroke=\"#888\"/>\n\
+        <text x=\"2\" y=\"{m}\" font-size=\"12\">${max}</text>\n\
+        <text x=\"2\" y=\"{b}\" font-size=\"12\">${min}</text>\n\
+        <text x=\"{r}\" y=\"{l}\" font-size=\"12\" text-anchor=\"end\">turn {turns}</text>\n",
+        w = width,
+        h = height,
+        l = height - CHART_MARGIN + 15.,
+        m = CHART_MARGIN,
+        b = height - CHART_MARGIN,
+        r = width - CHART_MARGIN,
+        max = max_balance,
+        min = min_balance,
+        turns = turns.len(),
+    );
+
+    for pindex in 0..transcript.player_count {
+        let points: Vec<String> = std::iter::once(starting_balance)
+            .chain(turns.iter().map(|t| t.balances[pindex]))
+            .enumerate()
+            .map(|(turn, balance)| format!("{:.1},{:.1}", x(turn), y(balance)))
+            .collect();
+        let color = PLAYER_COLORS[pindex % PLAYER_COLORS.len()];
+        writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>\n\
+            <text x=\"{}\" y=\"{}\" font-size=\"12\" fill=\"{}\">player {}</text>",
+            color,
+            points.join(" "),
+            CHART_MARGIN + 10. + 70. * pindex as f64,
+            CHART_MARGIN - 10.,
+            color,
+            pindex
+        )
+        .unwrap();
+    }
+
+    svg += "</svg>\n";
+    svg
+}
+
+/// Return a table of every property acquisition as an HTML section.
+fn acquisitions(result: &GameResult, board: &Board) -> String {
+    let mut html = "<h2>Property acquisitions</h2>\n<table>\n\
+        <tr><th>Turn</th><th>Player</th><th>Property</th><th>Price</th></tr>\n"
+        .to_string();
+    for &(round, pindex, pos) in result.stats.acquisitions() {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:02} {}</td><td>${}</td></tr>",
+            round,
+            pindex,
+            pos,
+            escape(board.tile_label(pos)),
+            board.property(pos).price
+        )
+        .unwrap();
+    }
+
+    html += "</table>\n";
+    html
+}
+
+/// Return a summary of the decisions made by each player's agent as an HTML section.
+fn decisions(result: &GameResult) -> String {
+    let players = result.final_balances.len();
+    // The number of decisions, total and longest milliseconds, and total nodes of each player
+    let mut totals = vec![(0, 0., 0_f64, 0); players];
+    for &(_, pindex, milliseconds, nodes) in result.stats.decisions() {
+        if let Some(total) = totals.get_mut(pindex) {
+            total.0 += 1;
+            total.1 += milliseconds;
+            total.2 = total.2.max(milliseconds);
+            total.3 += nodes;
+        }
+    }
+
+    let mut html = "<h2>Decisions</h2>\n<table>\n<tr><th>Player</th><th>Decisions</th>\
+        <th>Mean time</th><th>Longest time</th><th>Mean nodes created</th></tr>\n"
+        .to_string();
+    for (pindex, &(count, total_ms, max_ms, total_nodes)) in totals.iter().enumerate() {
+        let count_f = (count as f64).max(1.);
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:.1} ms</td><td>{:.1} ms</td><td>{:.0}</td></tr>",
+            pindex,
+            count,
+            total_ms / count_f,
+            max_ms,
+            total_nodes as f64 / count_f
+        )
+        .unwrap();
+    }
+
+    html += "</table>\n";
+    html
+}
+
+/// Return `text` with the characters that are special in HTML escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
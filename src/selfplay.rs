@@ -0,0 +1,243 @@
+use crate::game::{
+    Agent, Board, Decision, Game, GameConfig, GameError, Logger, MctsAgent, Observation,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// A single training example, recorded at a decision made during self-play.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingSample {
+    /// The index of the game that the sample was recorded in.
+    pub game: usize,
+    /// The state that the decision was made at.
+    pub observation: Observation,
+    /// The fraction of the search's visits that went to each move in `observation.moves`.
+    pub policy: Vec<f64>,
+    /// 1 if the player who made the decision went on to win the game, or -1 otherwise.
+    pub outcome: f64,
+}
+
+/// Plays games between MCTS agents and writes training samples for value/policy models
+/// to JSONL files. Every thread writes to its own shard of the output, and the games
+/// already in the output are skipped, so an interrupted run can simply be restarted.
+pub struct SelfPlay {
+    /// Creates the agent for every seat in every game, given the seat it will play in.
+    new_agent: Box<dyn Fn(usize) -> MctsAgent + Send + Sync>,
+    /// The number of players in every game.
+    player_count: usize,
+    /// The number of games to play.
+    games: usize,
+    /// The number of games to play concurrently, which is also the number of output shards.
+    threads: usize,
+    /// How long each thread waits between games, to limit the load on the machine.
+    pause: Duration,
+    /// The directory to write the shards to.
+    output_dir: PathBuf,
+    /// The optional rules that every game is played with.
+    config: GameConfig,
+    /// The board that every game is played on.
+    board: Board,
+    /// The estimated chance of winning below which players resign in every game, if any.
+    adjudication_threshold: Option<f64>,
+}
+
+impl SelfPlay {
+    /// Return a new self-play run of `games` games between agents created by `new_agent`.
+    pub fn new(
+        new_agent: impl Fn(usize) -> MctsAgent + Send + Sync + 'static,
+        player_count: usize,
+        games: usize,
+        threads: usize,
+        output_dir: impl AsRef<Path>,
+    ) -> Self {
+        SelfPlay {
+            new_agent: Box::new(new_agent),
+            player_count,
+            games,
+            threads,
+            pause: Duration::ZERO,
+            output_dir: output_dir.as_ref().to_path_buf(),
+            config: GameConfig::default(),
+            board: Board::default(),
+            adjudication_threshold: None,
+        }
+    }
+
+    /// Wait for `pause` between games on every thread.
+    pub fn with_pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Play every game with `config` on `board`.
+    pub fn with_rules(mut self, config: GameConfig, board: Board) -> Self {
+        self.config = config;
+        self.board = board;
+        self
+    }
+
+    /// End every game early by making players resign once their estimated chance of
+    /// winning drops below `threshold` (see `Game::with_adjudication()`). Lost games
+    /// are rarely worth learning much from, and playing them out is slow.
+    pub fn with_adjudication(mut self, threshold: f64) -> Self {
+        self.adjudication_threshold = Some(threshold);
+        self
+    }
+
+    /// Play all the games that aren't in the output yet, and return the number played.
+    pub fn run(&self) -> io::Result<usize> {
+        fs::create_dir_all(&self.output_dir)?;
+        let threads = self.threads.max(1);
+
+        thread::scope(|s| {
+            let handles: Vec<_> = (0..threads)
+                .map(|shard| s.spawn(move || self.run_shard(shard, threads)))
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        })
+    }
+
+    /// Play the games belonging to `shard` (every `shard_count`-th game),
+    /// appending their samples to the shard's file.
+    fn run_shard(&self, shard: usize, shard_count: usize) -> io::Result<usize> {
+        let path = self.output_dir.join(format!("shard-{}.jsonl", shard));
+        let finished = recover_shard(&path)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut played = 0;
+
+        for game_index in (shard..self.games).step_by(shard_count) {
+            if finished.contains(&game_index) {
+                continue;
+            }
+
+            // Write the whole game at once so that the file only ever has finished games
+            let mut lines = String::new();
+            // A game that goes wrong stops the shard, rather than leaving a gap in the data
+            for sample in self.play_game(game_index).map_err(io::Error::other)? {
+                lines += &serde_json::to_string(&sample)?;
+                lines.push('\n');
+            }
+            file.write_all(lines.as_bytes())?;
+            file.flush()?;
+            played += 1;
+
+            thread::sleep(self.pause);
+        }
+
+        Ok(played)
+    }
+
+    /// Play a single game and return a sample for every decision made in it.
+    fn play_game(&self, game_index: usize) -> Result<Vec<TrainingSample>, GameError> {
+        let mut agents: Vec<MctsAgent> = (0..self.player_count)
+            .map(|seat| (self.new_agent)(seat))
+            .collect();
+        let mut game = Game::new_with_board(self.player_count, self.board.clone(), self.config)
+            .with_logger(Logger::silent());
+        if let Some(threshold) = self.adjudication_threshold {
+            game = game.with_adjudication(threshold);
+        }
+        let mut samples = vec![];
+
+        while !game.legal_moves()?.is_empty() {
+            let observation = game.current_observation();
+            let agent = &mut agents[observation.acting_pindex];
+            let choice = match agent.decide(&mut game) {
+                Decision::Move(choice) => choice,
+                // There's no search to learn from if the agent resigned
+                Decision::Resign => {
+                    game.resign(observation.acting_pindex)?;
+                    continue;
+                }
+            };
+
+            let visits: Vec<f64> = agent
+                .search_stats()
+                .children
+                .iter()
+                .map(|&(n, _)| n as f64)
+                .collect();
+            let total_visits: f64 = visits.iter().sum();
+
+            samples.push(TrainingSample {
+                game: game_index,
+                policy: visits.iter().map(|n| n / total_visits).collect(),
+                observation,
+                outcome: 0.,
+            });
+            game.apply_move(choice)?;
+        }
+
+        // Fill in the outcomes now that the winner is known
+        let winner = game
+            .result()?
+            .ok_or_else(|| GameError::Internal("game ended without a result".to_string()))?
+            .winner;
+        for sample in &mut samples {
+            sample.outcome = if sample.observation.acting_pindex == winner {
+                1.
+            } else {
+                -1.
+            };
+        }
+
+        Ok(samples)
+    }
+}
+
+/// Return the indexes of the games already written to the shard at `path`, after
+/// removing any partially written game left behind by an interrupted run.
+fn recover_shard(path: &Path) -> io::Result<HashSet<usize>> {
+    let mut finished = HashSet::new();
+    if !path.exists() {
+        return Ok(finished);
+    }
+
+    let file_len = fs::metadata(path)?.len();
+    // The game whose samples are currently being read, and the offset of its first sample
+    let mut current_game = None;
+    let mut game_start = 0;
+    let mut offset = 0;
+    let mut interrupted = false;
+
+    for line in BufReader::new(File::open(path)?).split(b'\n') {
+        let line = line?;
+        let line_start = offset;
+        offset += line.len() as u64 + 1;
+
+        // A line without a trailing newline was cut off, even if it happens to be valid JSON
+        let sample = match serde_json::from_slice::<TrainingSample>(&line) {
+            Ok(sample) if offset <= file_len => sample,
+            _ => {
+                interrupted = true;
+                break;
+            }
+        };
+
+        if current_game != Some(sample.game) {
+            finished.extend(current_game);
+            current_game = Some(sample.game);
+            game_start = line_start;
+        }
+    }
+
+    // The last game was only written in full if the file wasn't cut off
+    let valid_len = if interrupted {
+        game_start
+    } else {
+        finished.extend(current_game);
+        file_len
+    };
+
+    OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_len(valid_len)?;
+    Ok(finished)
+}
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+/// Plays many games across a pool of worker threads. Each worker takes the next game
+/// that hasn't been started as soon as it's free, so that threads stay busy even when
+/// some games take much longer than others, and the results are streamed back over a
+/// channel as the games finish.
+pub struct Simulator {
+    /// The number of games to play.
+    games: usize,
+    /// The number of worker threads.
+    threads: usize,
+    /// Once set, workers finish the games they're playing but don't start new ones.
+    stop: Arc<AtomicBool>,
+}
+
+impl Simulator {
+    /// Return a new simulator that plays `games` games on `threads` threads.
+    pub fn new(games: usize, threads: usize) -> Self {
+        Simulator {
+            games,
+            threads: threads.max(1),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Stop starting new games once `stop` is set, e.g. by a Ctrl-C handler.
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Return the flag that stops the simulation when it's set.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Play every game with `play_game`, which is given the index of the game, and pass
+    /// each result to `on_result` on the calling thread as soon as it's finished. Every
+    /// worker is joined before this returns the number of games that were played, which
+    /// is fewer than requested if the simulation was stopped.
+    pub fn run<T: Send>(
+        &self,
+        play_game: impl Fn(usize) -> T + Sync,
+        mut on_result: impl FnMut(usize, T),
+    ) -> usize {
+        let next_game = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let mut played = 0;
+
+        thread::scope(|s| {
+            for _ in 0..self.threads.min(self.games) {
+                let sender = sender.clone();
+                let (next_game, play_game) = (&next_game, &play_game);
+
+                s.spawn(move || {
+                    // Keep taking games until all of them have been started
+                    while !self.stop.load(Ordering::Relaxed) {
+                        let game_index = next_game.fetch_add(1, Ordering::Relaxed);
+                        if game_index >= self.games {
+                            break;
+                        }
+
+                        let result = play_game(game_index);
+                        if sender.send((game_index, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            // The channel closes once every worker has dropped its sender
+            drop(sender);
+            for (game_index, result) in receiver {
+                on_result(game_index, result);
+                played += 1;
+            }
+        });
+
+        played
+    }
+}
@@ -0,0 +1,124 @@
+use crate::game::GameResult;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A summary of a set of samples, such as the number of children of every state.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Distribution {
+    /// The number of samples.
+    pub samples: usize,
+    /// The mean of the samples.
+    pub mean: f64,
+    /// The smallest sample.
+    pub min: usize,
+    /// The median sample.
+    pub median: usize,
+    /// The 90th percentile of the samples.
+    pub p90: usize,
+    /// The largest sample.
+    pub max: usize,
+}
+
+impl Distribution {
+    /// Summarise `samples`, which don't need to be sorted.
+    pub fn new(mut samples: Vec<usize>) -> Self {
+        if samples.is_empty() {
+            return Distribution::default();
+        }
+
+        samples.sort_unstable();
+        let n = samples.len();
+
+        Distribution {
+            samples: n,
+            mean: samples.iter().sum::<usize>() as f64 / n as f64,
+            min: samples[0],
+            median: samples[n / 2],
+            p90: samples[(n * 9 / 10).min(n - 1)],
+            max: samples[n - 1],
+        }
+    }
+}
+
+/// The shape of the game tree that a set of games passed through: how many moves were
+/// available at each type of move, and how many moves deep the games went. This helps
+/// to pick MCTS parameters (e.g. a temperature that explores wide Auction nodes enough)
+/// and to plan how much memory a search of a given depth needs.
+pub struct StateSpaceReport {
+    /// The number of children of the states of each move type (e.g. "Roll").
+    pub branching: BTreeMap<String, Distribution>,
+    /// The number of moves (including chance events) from the start to the end of each game.
+    pub plies: Distribution,
+    /// The mean number of moves (including chance events) per turn.
+    pub plies_per_turn: f64,
+}
+
+impl StateSpaceReport {
+    /// Tabulate the states passed through by every game in `results`.
+    pub fn new(results: &[GameResult]) -> Self {
+        let mut children: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for result in results {
+            for (_, move_type, n) in result.stats.branching() {
+                children.entry(move_type.clone()).or_default().push(*n);
+            }
+        }
+
+        let plies: Vec<usize> = results.iter().map(|r| r.stats.branching().len()).collect();
+        let turns: usize = results.iter().map(|r| r.turns.max(1)).sum();
+
+        StateSpaceReport {
+            branching: children
+                .into_iter()
+                .map(|(move_type, n)| (move_type, Distribution::new(n)))
+                .collect(),
+            plies_per_turn: plies.iter().sum::<usize>() as f64 / turns.max(1) as f64,
+            plies: Distribution::new(plies),
+        }
+    }
+
+    /// Save the branching factor of each move type, followed by the game depth, as CSV.
+    pub fn to_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut csv = "move type,states,mean,min,median,p90,max".to_owned();
+
+        let rows = self
+            .branching
+            .iter()
+            .map(|(move_type, d)| (move_type.as_str(), d))
+            .chain([("game depth", &self.plies)]);
+        for (name, d) in rows {
+            csv.push_str(&format!(
+                "\n{},{},{},{},{},{},{}",
+                name, d.samples, d.mean, d.min, d.median, d.p90, d.max
+            ));
+        }
+
+        fs::write(path, csv)
+    }
+}
+
+impl fmt::Display for StateSpaceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>9} {:>8} {:>5} {:>7} {:>5} {:>5}",
+            "move type", "states", "mean", "min", "median", "p90", "max"
+        )?;
+
+        for (move_type, d) in &self.branching {
+            writeln!(
+                f,
+                "{:<16} {:>9} {:>8.1} {:>5} {:>7} {:>5} {:>5}",
+                move_type, d.samples, d.mean, d.min, d.median, d.p90, d.max
+            )?;
+        }
+
+        write!(
+            f,
+            "game depth: {:.1} moves on average (median {}, p90 {}, max {}), {:.1} moves per turn",
+            self.plies.mean, self.plies.median, self.plies.p90, self.plies.max, self.plies_per_turn
+        )
+    }
+}
@@ -0,0 +1,218 @@
+//! A SQLite database that the results of tournaments accumulate in, so that experiments
+//! spanning many runs (and days) can be queried in one place instead of across the CSV
+//! files of every game. Every game is stored with the contestant in each of its seats,
+//! the decisions made by the agents and the money that changed hands, while the rest of
+//! its result and statistics are kept as JSON.
+//!
+//! Games are stored as soon as they finish, so a tournament that's interrupted can be
+//! resumed by running it again with the same lineup and settings (see `Tournament::key()`),
+//! which only plays the games that aren't in the database yet.
+
+use crate::game::{GameConfig, GameResult};
+use crate::tournament::{Seating, Tournament};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use rusqlite::Result;
+
+/// The tables of the database, which are created if they don't exist yet.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tournaments (
+    id INTEGER PRIMARY KEY,
+    -- The lineup and settings of the tournament, from `Tournament::key()`
+    key TEXT NOT NULL UNIQUE,
+    -- Seconds since the Unix epoch
+    started INTEGER NOT NULL,
+    -- The `GameConfig` as JSON
+    config TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS games (
+    id INTEGER PRIMARY KEY,
+    tournament INTEGER NOT NULL REFERENCES tournaments(id),
+    -- The index of the game in its tournament
+    game_index INTEGER NOT NULL,
+    pairing INTEGER NOT NULL,
+    winner INTEGER NOT NULL,
+    turns INTEGER NOT NULL,
+    turn_limit_reached INTEGER NOT NULL,
+    repetition_limit_reached INTEGER NOT NULL,
+    adjudicated INTEGER NOT NULL,
+    -- The whole `GameResult` (including its statistics) as JSON
+    result TEXT NOT NULL,
+    UNIQUE (tournament, game_index)
+);
+CREATE TABLE IF NOT EXISTS seats (
+    game INTEGER NOT NULL REFERENCES games(id),
+    seat INTEGER NOT NULL,
+    contestant TEXT NOT NULL,
+    -- 1 for the winner, followed by the other players from the last to be eliminated
+    place INTEGER NOT NULL,
+    final_balance INTEGER,
+    PRIMARY KEY (game, seat)
+);
+CREATE TABLE IF NOT EXISTS decisions (
+    game INTEGER NOT NULL REFERENCES games(id),
+    round INTEGER NOT NULL,
+    seat INTEGER NOT NULL,
+    milliseconds REAL NOT NULL,
+    nodes INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transfers (
+    game INTEGER NOT NULL REFERENCES games(id),
+    round INTEGER NOT NULL,
+    payer TEXT NOT NULL,
+    payee TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    reason TEXT NOT NULL
+);
+";
+
+/// A SQLite database of tournament results.
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Open the database at `path`, creating it (and its tables) if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Database { connection })
+    }
+
+    /// Return the ID of `tournament` in the database, storing it first if this is the
+    /// first time it's been run. It's identified by its lineup and settings, so running the
+    /// same tournament again (e.g. after it was interrupted) returns the same ID.
+    pub fn tournament(&self, tournament: &Tournament, config: &GameConfig) -> Result<i64> {
+        let key = tournament.key();
+        let existing = self
+            .connection
+            .query_row(
+                "SELECT id FROM tournaments WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(id) = existing {
+            return Ok(id);
+        }
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        // Serializing a config of plain fields can't fail
+        let config = serde_json::to_string(config).unwrap();
+
+        self.connection.execute(
+            "INSERT INTO tournaments (key, started, config) VALUES (?1, ?2, ?3)",
+            params![key, started, config],
+        )?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// Return the games of the tournament with the ID `tournament` that were
+    /// already stored, along with their indexes in the tournament.
+    pub fn completed_games(&self, tournament: i64) -> Result<Vec<(usize, GameResult)>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT game_index, result FROM games WHERE tournament = ?1")?;
+        let rows = statement.query_map(params![tournament], |row| {
+            Ok((row.get::<_, usize>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut games = vec![];
+        for row in rows {
+            let (game_index, json) = row?;
+            let result = serde_json::from_str(&json).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+            })?;
+            games.push((game_index, result));
+        }
+
+        Ok(games)
+    }
+
+    /// Store the game at `game_index` of the tournament with the ID `tournament`, which was
+    /// played by the contestants called `names` (in lineup order) sat according to `seating`.
+    pub fn record_game(
+        &mut self,
+        tournament: i64,
+        game_index: usize,
+        seating: &Seating,
+        result: &GameResult,
+        names: &[String],
+    ) -> Result<()> {
+        // Either the whole game is stored or none of it is
+        let tx = self.connection.transaction()?;
+        // Serializing plain fields can't fail
+        let json = serde_json::to_string(result).unwrap();
+
+        tx.execute(
+            "INSERT INTO games (tournament, game_index, pairing, winner, turns,
+                turn_limit_reached, repetition_limit_reached, adjudicated, result)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                tournament,
+                game_index,
+                seating.pairing,
+                result.winner,
+                result.turns,
+                result.turn_limit_reached,
+                result.repetition_limit_reached,
+                result.adjudicated,
+                json,
+            ],
+        )?;
+        let game = tx.last_insert_rowid();
+
+        // The winner comes first, followed by the players who went bankrupt last
+        let ranking = std::iter::once(&result.winner).chain(result.losers.iter().rev());
+        for (place, &seat) in ranking.enumerate() {
+            tx.execute(
+                "INSERT INTO seats (game, seat, contestant, place, final_balance)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    game,
+                    seat,
+                    names[seating.contestants[seat]],
+                    place + 1,
+                    result.final_balances.get(seat),
+                ],
+            )?;
+        }
+
+        {
+            let mut insert_decision = tx.prepare_cached(
+                "INSERT INTO decisions (game, round, seat, milliseconds, nodes)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for &(round, seat, milliseconds, nodes) in result.stats.decisions() {
+                insert_decision.execute(params![game, round, seat, milliseconds, nodes])?;
+            }
+
+            let mut insert_transfer = tx.prepare_cached(
+                "INSERT INTO transfers (game, round, payer, payee, amount, reason)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for t in result.stats.ledger() {
+                insert_transfer.execute(params![
+                    game,
+                    t.round,
+                    t.payer.to_string(),
+                    t.payee.to_string(),
+                    t.amount,
+                    t.reason,
+                ])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    /// Return the number of games stored in the database.
+    pub fn games(&self) -> Result<usize> {
+        self.connection
+            .query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+    }
+}
@@ -0,0 +1,238 @@
+use crate::game::{Board, GameConfig, Logger, MctsAgent, RolloutPolicy};
+use crate::tournament::{Contestant, ProgressReporter, Tournament, TournamentSummary};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A single combination of MCTS hyperparameters.
+#[derive(Debug, Clone, Copy)]
+pub struct MctsSettings {
+    /// The time limit for each decision, in milliseconds.
+    pub time_limit: u64,
+    /// Value of `C` constant in UCB1 formula.
+    pub temperature: f64,
+    /// How moves are chosen during rollouts.
+    pub rollout_policy: RolloutPolicy,
+}
+
+impl fmt::Display for MctsSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "t={} c={} ", self.time_limit, self.temperature)?;
+
+        match self.rollout_policy {
+            RolloutPolicy::Random => write!(f, "random"),
+            RolloutPolicy::EpsilonGreedy { epsilon } => write!(f, "eps={}", epsilon),
+        }
+    }
+}
+
+/// Plays a round-robin between every combination of the given MCTS hyperparameters.
+pub struct Sweep {
+    /// The time limits to try, in milliseconds.
+    time_limits: Vec<u64>,
+    /// The UCB1 temperatures to try.
+    temperatures: Vec<f64>,
+    /// The rollout policies to try.
+    rollout_policies: Vec<RolloutPolicy>,
+    /// The number of games to play between every pair of settings.
+    games_per_matchup: usize,
+    /// The number of games to play concurrently.
+    threads: usize,
+    /// The optional rules that every game is played with.
+    config: GameConfig,
+    /// The board that every game is played on.
+    board: Board,
+    /// Where every game is logged, which is silent by default.
+    logger: Logger,
+    /// How often the progress of each matchup is reported, and what it's reported to.
+    progress: Option<(Duration, Arc<ProgressReporter>)>,
+}
+
+impl Sweep {
+    /// Return a new sweep over every combination of `time_limits`, `temperatures`
+    /// and `rollout_policies`, with `games_per_matchup` games between every pair.
+    pub fn new(
+        time_limits: Vec<u64>,
+        temperatures: Vec<f64>,
+        rollout_policies: Vec<RolloutPolicy>,
+        games_per_matchup: usize,
+        threads: usize,
+    ) -> Self {
+        Sweep {
+            time_limits,
+            temperatures,
+            rollout_policies,
+            games_per_matchup,
+            threads,
+            config: GameConfig::default(),
+            board: Board::default(),
+            logger: Logger::silent(),
+            progress: None,
+        }
+    }
+
+    /// Play every game with `config` on `board`.
+    pub fn with_rules(mut self, config: GameConfig, board: Board) -> Self {
+        self.config = config;
+        self.board = board;
+        self
+    }
+
+    /// Log every game with `logger`.
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Report the progress of each matchup to `report`, at most once every `interval`.
+    pub fn with_progress(mut self, interval: Duration, report: Arc<ProgressReporter>) -> Self {
+        self.progress = Some((interval, report));
+        self
+    }
+
+    /// Return every combination of hyperparameters in the sweep.
+    pub fn settings(&self) -> Vec<MctsSettings> {
+        let mut settings = vec![];
+        for &time_limit in &self.time_limits {
+            for &temperature in &self.temperatures {
+                for &rollout_policy in &self.rollout_policies {
+                    settings.push(MctsSettings {
+                        time_limit,
+                        temperature,
+                        rollout_policy,
+                    });
+                }
+            }
+        }
+
+        settings
+    }
+
+    /// Play the round-robin and return the results. Each pair of settings
+    /// plays half of its games in each seat, to cancel out any first-move advantage.
+    pub fn run(&self) -> SweepResults {
+        let settings = self.settings();
+        let n = settings.len();
+        let mut results = SweepResults {
+            names: settings.iter().map(|s| s.to_string()).collect(),
+            wins: vec![vec![0; n]; n],
+            games: vec![vec![0; n]; n],
+            summaries: vec![],
+        };
+
+        for a in 0..n {
+            for b in 0..n {
+                if a == b {
+                    continue;
+                }
+
+                let lineup = vec![
+                    contestant(&results.names[a], settings[a]),
+                    contestant(&results.names[b], settings[b]),
+                ];
+                let mut tournament = Tournament::new(
+                    lineup,
+                    self.games_per_matchup / 2,
+                    self.threads,
+                    self.config,
+                    self.board.clone(),
+                )
+                .with_logger(self.logger);
+                if let Some((interval, report)) = &self.progress {
+                    tournament = tournament.with_progress(*interval, report.clone());
+                }
+                let summary = tournament.run();
+
+                results.wins[a][b] += summary.records[0].wins;
+                results.wins[b][a] += summary.records[1].wins;
+                results.games[a][b] += summary.turns.len();
+                results.games[b][a] += summary.turns.len();
+                results.summaries.push(summary);
+            }
+        }
+
+        results
+    }
+}
+
+/// Return a contestant that plays with an MCTS agent using `settings`.
+fn contestant(name: &str, settings: MctsSettings) -> Contestant {
+    Contestant::new(name, move |seat| {
+        Box::new(
+            MctsAgent::new(settings.time_limit, settings.temperature, seat)
+                .with_rollout_policy(settings.rollout_policy),
+        )
+    })
+}
+
+/// The results of a sweep.
+pub struct SweepResults {
+    /// The name of every combination of settings.
+    pub names: Vec<String>,
+    /// `wins[a][b]` is the number of games that settings `a` won against settings `b`.
+    pub wins: Vec<Vec<usize>>,
+    /// `games[a][b]` is the number of games played between settings `a` and `b`.
+    pub games: Vec<Vec<usize>>,
+    /// The summary of every matchup, which can be used to update `Ratings`.
+    pub summaries: Vec<TournamentSummary>,
+}
+
+impl SweepResults {
+    /// Return the fraction of its games that settings `a` won against settings `b`.
+    pub fn win_rate(&self, a: usize, b: usize) -> f64 {
+        self.wins[a][b] as f64 / self.games[a][b] as f64
+    }
+
+    /// Return the fraction of all its games that settings `a` won.
+    pub fn overall_win_rate(&self, a: usize) -> f64 {
+        self.wins[a].iter().sum::<usize>() as f64 / self.games[a].iter().sum::<usize>() as f64
+    }
+
+    /// Save the matrix of win rates to a CSV file.
+    pub fn to_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut csv = format!("settings,{}\n", self.names.join(","));
+
+        for (a, name) in self.names.iter().enumerate() {
+            let rates: Vec<String> = (0..self.names.len())
+                .map(|b| {
+                    if a == b {
+                        String::new()
+                    } else {
+                        self.win_rate(a, b).to_string()
+                    }
+                })
+                .collect();
+            csv += &format!("{},{}\n", name, rates.join(","));
+        }
+
+        fs::write(path, csv)
+    }
+}
+
+impl fmt::Display for SweepResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Each row is the win rate of one setting against every other one (in the columns)
+        write!(f, "{:<24}", "")?;
+        for i in 0..self.names.len() {
+            write!(f, " {:>6}", format!("[{}]", i))?;
+        }
+        writeln!(f, " {:>7}", "overall")?;
+
+        for (a, name) in self.names.iter().enumerate() {
+            write!(f, "{:<24}", format!("[{}] {}", a, name))?;
+            for b in 0..self.names.len() {
+                if a == b {
+                    write!(f, " {:>6}", "-")?;
+                } else {
+                    write!(f, " {:>5.1}%", self.win_rate(a, b) * 100.)?;
+                }
+            }
+            writeln!(f, " {:>6.1}%", self.overall_win_rate(a) * 100.)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,596 @@
+use crate::game::{
+    Agent, AuctionModel, BellCurveBids, Board, Game, GameConfig, GameError, GameResult, Logger,
+    Metrics,
+};
+use crate::simulator::Simulator;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::zip;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The z-score of a 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// A named agent configuration taking part in a tournament.
+pub struct Contestant {
+    /// The name shown for this contestant in the summary table.
+    pub name: String,
+    /// Creates a fresh agent for every game, given the seat (player index) it will play in.
+    new_agent: Box<dyn Fn(usize) -> Box<dyn Agent> + Send + Sync>,
+    /// The longest the contestant's agents can take to make a decision, if there's a limit.
+    decision_timeout: Option<Duration>,
+    /// A description of how the contestant's agents are set up (see `Contestant::with_params()`).
+    params: String,
+}
+
+impl Contestant {
+    /// Return a new contestant that plays with agents created by `new_agent`.
+    pub fn new(
+        name: &str,
+        new_agent: impl Fn(usize) -> Box<dyn Agent> + Send + Sync + 'static,
+    ) -> Self {
+        Contestant {
+            name: name.to_string(),
+            new_agent: Box::new(new_agent),
+            decision_timeout: None,
+            params: String::new(),
+        }
+    }
+
+    /// Describe how the contestant's agents are set up (e.g. their search parameters), so
+    /// that a tournament between differently set up agents of the same name isn't mistaken
+    /// for this one when it's resumed (see `Tournament::key()`).
+    pub fn with_params(mut self, params: &str) -> Self {
+        self.params = params.to_string();
+        self
+    }
+
+    /// Play a random move for the contestant whenever its agent takes longer than
+    /// `timeout` to make a decision (see `Game::with_decision_timeout()`).
+    pub fn with_decision_timeout(mut self, timeout: Duration) -> Self {
+        self.decision_timeout = Some(timeout);
+        self
+    }
+}
+
+/// A callback that is given a tournament's progress while it runs.
+pub type ProgressReporter = dyn Fn(&Progress) + Send + Sync;
+
+/// Runs many games between a fixed lineup of agents and aggregates the results.
+pub struct Tournament {
+    /// The agents playing in every game, in seating order (unless the seats are swapped).
+    lineup: Vec<Contestant>,
+    /// The number of games to play.
+    games: usize,
+    /// The number of games to play concurrently.
+    threads: usize,
+    /// The optional rules that every game is played with.
+    config: GameConfig,
+    /// The board that every game is played on.
+    board: Board,
+    /// Where every game is logged, which is silent by default.
+    logger: Logger,
+    /// How auctions are resolved in every game.
+    auction_model: Arc<dyn AuctionModel>,
+    /// Whether every game is played with validation.
+    validating: bool,
+    /// The estimated chance of winning below which players resign in every game, if any.
+    adjudication_threshold: Option<f64>,
+    /// Once set, no more games are started, and the games played so far are summarized.
+    stop: Arc<AtomicBool>,
+    /// How often progress is reported, and what it's reported to.
+    progress: Option<(Duration, Arc<ProgressReporter>)>,
+    /// Whether every pairing is played once with the lineup in each rotation of the seats.
+    swap_seats: bool,
+    /// The seed of the dice and agents' random choices in the first pairing, if any.
+    seed: Option<u64>,
+    /// The games that were already played, with their indexes.
+    completed: Vec<(usize, GameResult)>,
+    /// Where every finished game is added up, if anywhere.
+    metrics: Option<Arc<Metrics>>,
+    /// Where a `CrashReport` is saved for every game that runs into a bug, if anywhere.
+    crash_dir: Option<PathBuf>,
+}
+
+impl Tournament {
+    /// Return a new tournament of `games` games between the contestants in `lineup`.
+    pub fn new(
+        lineup: Vec<Contestant>,
+        games: usize,
+        threads: usize,
+        config: GameConfig,
+        board: Board,
+    ) -> Self {
+        Tournament {
+            lineup,
+            games,
+            threads,
+            config,
+            board,
+            logger: Logger::silent(),
+            auction_model: Arc::new(BellCurveBids),
+            validating: false,
+            adjudication_threshold: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            progress: None,
+            swap_seats: false,
+            seed: None,
+            completed: vec![],
+            metrics: None,
+            crash_dir: None,
+        }
+    }
+
+    /// Resolve auctions in every game with `auction_model`.
+    pub fn with_auction_model(mut self, auction_model: Arc<dyn AuctionModel>) -> Self {
+        self.auction_model = auction_model;
+        self
+    }
+
+    /// Play every game with validation, so that the results include any problems found.
+    pub fn with_validation(mut self) -> Self {
+        self.validating = true;
+        self
+    }
+
+    /// End every game early by making players resign once their estimated chance of
+    /// winning drops below `threshold` (see `Game::with_adjudication()`).
+    pub fn with_adjudication(mut self, threshold: f64) -> Self {
+        self.adjudication_threshold = Some(threshold);
+        self
+    }
+
+    /// Stop starting new games once `stop` is set, e.g. by a Ctrl-C handler.
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Log every game with `logger`.
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Give the tournament's progress to `report` whenever a game finishes, as long
+    /// as at least `interval` has passed since the last report.
+    pub fn with_progress(mut self, interval: Duration, report: Arc<ProgressReporter>) -> Self {
+        self.progress = Some((interval, report));
+        self
+    }
+
+    /// Play every pairing of games once with each rotation of the lineup around the seats
+    /// (i.e. twice with the seats swapped in a two-player game), so that any advantage of
+    /// going first cancels out. The number of games is rounded up to a whole number of pairings.
+    pub fn with_swapped_seats(mut self) -> Self {
+        let rotations = self.lineup.len().max(1);
+        self.games = self.games.div_ceil(rotations) * rotations;
+        self.swap_seats = true;
+        self
+    }
+
+    /// Seed the dice and agents' random choices of every game, so that the tournament can be
+    /// repeated. Every game of a pairing (see `Tournament::with_swapped_seats()`) gets the same
+    /// seed, so that each rotation of the lineup is dealt the same rolls as far as possible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Add every game to `metrics` as it finishes.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Save a `CrashReport` to a new file in `dir` for every game that runs into a bug
+    /// (see `Game::with_crash_dir()`), so that it can be reproduced after the tournament.
+    pub fn with_crash_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.crash_dir = Some(dir.into());
+        self
+    }
+
+    /// Count the games at the given indexes as already played (e.g. by a run of the tournament
+    /// that was interrupted), so that only the rest of the games are played.
+    pub fn with_completed_games(mut self, games: Vec<(usize, GameResult)>) -> Self {
+        self.completed = games;
+        self
+    }
+
+    /// Return a description of the tournament's lineup and settings, which is the same
+    /// for every run of the same tournament, so that it can be resumed later.
+    pub fn key(&self) -> String {
+        let contestants: Vec<String> = self
+            .lineup
+            .iter()
+            .map(|c| format!("{}({}; timeout={:?})", c.name, c.params, c.decision_timeout))
+            .collect();
+
+        // Serializing a config and board of plain fields can't fail
+        format!(
+            "lineup={} games={} swap_seats={} seed={:?} auction_model={} config={} board={}",
+            contestants.join(","),
+            self.games,
+            self.swap_seats,
+            self.seed,
+            self.auction_model.name(),
+            serde_json::to_string(&self.config).unwrap(),
+            serde_json::to_string(&self.board).unwrap()
+        )
+    }
+
+    /// Return the names of the contestants, in lineup order.
+    pub fn names(&self) -> Vec<String> {
+        self.lineup.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// Play all the games of the tournament and return the aggregated results.
+    /// If the tournament is stopped, only the games that were finished are included,
+    /// and games that ended with an error are reported apart from the rest.
+    pub fn run(&self) -> TournamentSummary {
+        self.run_with(|_, _, _| {})
+    }
+
+    /// Play all the games of the tournament like `Tournament::run()`, passing the index,
+    /// seating and result of each game to `on_game` on the calling thread as it finishes.
+    pub fn run_with(
+        &self,
+        mut on_game: impl FnMut(usize, &Seating, &GameResult),
+    ) -> TournamentSummary {
+        let start_time = Instant::now();
+        let mut last_report = start_time;
+        let mut results = vec![];
+        let mut seatings = vec![];
+        let mut errors = vec![];
+
+        let mut played = vec![false; self.games];
+        for (game_index, result) in &self.completed {
+            if let Some(p @ false) = played.get_mut(*game_index) {
+                *p = true;
+                results.push(result.clone());
+                seatings.push(self.seating(*game_index));
+            }
+        }
+        let remaining: Vec<usize> = (0..self.games).filter(|&g| !played[g]).collect();
+
+        Simulator::new(remaining.len(), self.threads)
+            .with_stop_flag(self.stop.clone())
+            .run(
+                |i| self.play_game(&self.seating(remaining[i])),
+                |i, result| {
+                    let result = match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            errors.push((remaining[i], e));
+                            return;
+                        }
+                    };
+                    let seating = self.seating(remaining[i]);
+                    on_game(remaining[i], &seating, &result);
+                    if let Some(metrics) = &self.metrics {
+                        metrics.record_game(&result);
+                    }
+                    results.push(result);
+                    seatings.push(seating);
+
+                    if let Some((interval, report)) = &self.progress {
+                        if last_report.elapsed() >= *interval {
+                            last_report = Instant::now();
+                            report(&Progress {
+                                games_played: results.len(),
+                                games: self.games,
+                                elapsed: start_time.elapsed(),
+                                records: TournamentSummary::new(&self.lineup, &results, &seatings)
+                                    .records,
+                            });
+                        }
+                    }
+                },
+            );
+
+        let mut summary = TournamentSummary::new(&self.lineup, &results, &seatings);
+        summary.errors = errors;
+        summary
+    }
+
+    /// Return where the contestants sit in the game at `game_index`.
+    fn seating(&self, game_index: usize) -> Seating {
+        let n = self.lineup.len();
+        if !self.swap_seats {
+            return Seating {
+                pairing: game_index,
+                contestants: (0..n).collect(),
+            };
+        }
+
+        // Every game of a pairing rotates the lineup one seat further
+        let rotation = game_index % n;
+        Seating {
+            pairing: game_index / n,
+            contestants: (0..n).map(|seat| (seat + rotation) % n).collect(),
+        }
+    }
+
+    /// Play a single game between fresh agents of the lineup, sat according to `seating`.
+    fn play_game(&self, seating: &Seating) -> Result<GameResult, GameError> {
+        let mut game =
+            Game::new_with_board(seating.contestants.len(), self.board.clone(), self.config)
+                .with_logger(self.logger)
+                .with_auction_model(self.auction_model.clone());
+        // The game is seeded before the agents are made, in case they make random choices
+        if let Some(seed) = self.seed {
+            game = game.with_seed(seed.wrapping_add(seating.pairing as u64));
+        }
+        if let Some(dir) = &self.crash_dir {
+            game = game.with_crash_dir(dir);
+        }
+        if self.validating {
+            game = game.with_validation();
+        }
+        if let Some(threshold) = self.adjudication_threshold {
+            game = game.with_adjudication(threshold);
+        }
+        for (seat, &c) in seating.contestants.iter().enumerate() {
+            if let Some(timeout) = self.lineup[c].decision_timeout {
+                game = game.with_decision_timeout(seat, timeout);
+            }
+        }
+
+        let agents: Vec<Box<dyn Agent>> = seating
+            .contestants
+            .iter()
+            .enumerate()
+            .map(|(seat, &c)| (self.lineup[c].new_agent)(seat))
+            .collect();
+        let game_id = game.id();
+        let result = game.resume(agents);
+        if let Some(metrics) = &self.metrics {
+            metrics.end_game(game_id);
+        }
+
+        result
+    }
+}
+
+/// Where the contestants sat in a game of a tournament.
+#[derive(Clone)]
+pub struct Seating {
+    /// The index of the pairing that the game belongs to. Without swapped
+    /// seats, every game is a pairing of its own.
+    pub pairing: usize,
+    /// The index (in the lineup) of the contestant sitting in each seat.
+    pub contestants: Vec<usize>,
+}
+
+/// The win/loss record of a single contestant.
+pub struct Record {
+    /// The name of the contestant.
+    pub name: String,
+    /// The number of games that the contestant won.
+    pub wins: usize,
+    /// The number of games that the contestant lost.
+    pub losses: usize,
+}
+
+impl Record {
+    /// Return the fraction of games won.
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / (self.wins + self.losses) as f64
+    }
+
+    /// Return the 95% Wilson score interval of the win rate.
+    pub fn win_rate_interval(&self) -> (f64, f64) {
+        let n = (self.wins + self.losses) as f64;
+        let p = self.win_rate();
+        let z2 = Z_95 * Z_95;
+
+        let centre = (p + z2 / (2. * n)) / (1. + z2 / n);
+        let margin = Z_95 / (1. + z2 / n) * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt();
+
+        (centre - margin, centre + margin)
+    }
+}
+
+/// How far a tournament has got.
+pub struct Progress {
+    /// The number of games that have finished.
+    pub games_played: usize,
+    /// The number of games in the tournament.
+    pub games: usize,
+    /// How long the tournament has been running.
+    pub elapsed: Duration,
+    /// The record of each contestant so far, in lineup order.
+    pub records: Vec<Record>,
+}
+
+impl Progress {
+    /// Return an estimate of how long is left until every game has finished,
+    /// assuming that the remaining games take as long as the finished ones.
+    pub fn eta(&self) -> Duration {
+        let remaining = self.games.saturating_sub(self.games_played) as u32;
+        self.elapsed / self.games_played.max(1) as u32 * remaining
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} games, {} elapsed, {} left",
+            self.games_played,
+            self.games,
+            format_duration(self.elapsed),
+            format_duration(self.eta())
+        )?;
+
+        for r in &self.records {
+            write!(f, ", {} {:.1}%", r.name, r.win_rate() * 100.)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Format `duration` in hours, minutes and seconds (e.g. "1h 02m 03s").
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
+
+/// The aggregated results of a tournament.
+pub struct TournamentSummary {
+    /// The record of each contestant, in lineup order.
+    pub records: Vec<Record>,
+    /// The number of turns that each game lasted.
+    pub turns: Vec<usize>,
+    /// The result of every game, in the order they finished.
+    pub results: Vec<GameResult>,
+    /// Where the contestants sat in each of the `results`.
+    pub seatings: Vec<Seating>,
+    /// The games that ended with an error instead of a result, with their indexes.
+    /// These aren't counted in the records.
+    pub errors: Vec<(usize, GameError)>,
+}
+
+impl TournamentSummary {
+    /// Tabulate the results of the games played by `lineup`, which were sat according to `seatings`.
+    fn new(lineup: &[Contestant], results: &[GameResult], seatings: &[Seating]) -> Self {
+        let records = lineup
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let games = || zip(results, seatings);
+                Record {
+                    name: c.name.clone(),
+                    wins: games()
+                        .filter(|(r, s)| s.contestants[r.winner] == i)
+                        .count(),
+                    losses: games()
+                        .filter(|(r, s)| r.losers.iter().any(|&seat| s.contestants[seat] == i))
+                        .count(),
+                }
+            })
+            .collect();
+
+        TournamentSummary {
+            records,
+            turns: results.iter().map(|r| r.turns).collect(),
+            results: results.to_vec(),
+            seatings: seatings.to_vec(),
+            errors: vec![],
+        }
+    }
+
+    /// Return the number of wins of each contestant (in lineup order) in every pairing
+    /// that was played in full, i.e. in which every contestant took every seat once.
+    pub fn pairing_wins(&self) -> Vec<Vec<usize>> {
+        let n = self.records.len();
+        let mut pairings: BTreeMap<usize, (usize, Vec<usize>)> = BTreeMap::new();
+
+        for (result, seating) in zip(&self.results, &self.seatings) {
+            let (games, wins) = pairings
+                .entry(seating.pairing)
+                .or_insert_with(|| (0, vec![0; n]));
+            *games += 1;
+            wins[seating.contestants[result.winner]] += 1;
+        }
+
+        pairings
+            .into_values()
+            .filter(|(games, _)| *games == n)
+            .map(|(_, wins)| wins)
+            .collect()
+    }
+
+    /// Return whether each pairing was played with the lineup in every rotation of the seats.
+    fn seats_swapped(&self) -> bool {
+        self.seatings
+            .iter()
+            .any(|s| s.contestants.first().is_some_and(|&c| c != 0))
+    }
+
+    /// Return the mean number of turns per game and the half-width of its 95% confidence interval.
+    pub fn mean_turns(&self) -> (f64, f64) {
+        let n = self.turns.len() as f64;
+        let mean = self.turns.iter().sum::<usize>() as f64 / n;
+        let variance = self
+            .turns
+            .iter()
+            .map(|&t| (t as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.);
+
+        (mean, Z_95 * (variance / n).sqrt())
+    }
+
+    /// Return the number of games that ended because a state was repeated too many times.
+    pub fn repetition_draws(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| r.repetition_limit_reached)
+            .count()
+    }
+}
+
+impl fmt::Display for TournamentSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>6} {:>6} {:>8} {:>17}",
+            "agent", "wins", "losses", "win %", "95% CI"
+        )?;
+
+        for r in &self.records {
+            let (low, high) = r.win_rate_interval();
+            writeln!(
+                f,
+                "{:<16} {:>6} {:>6} {:>7.1}% {:>7.1}% - {:>5.1}%",
+                r.name,
+                r.wins,
+                r.losses,
+                r.win_rate() * 100.,
+                low * 100.,
+                high * 100.
+            )?;
+        }
+
+        let (mean, margin) = self.mean_turns();
+        write!(
+            f,
+            "{} games, {:.1} ± {:.1} turns per game",
+            self.turns.len(),
+            mean,
+            margin
+        )?;
+
+        if let n @ 1.. = self.repetition_draws() {
+            write!(f, ", {} ended by repetition", n)?;
+        }
+        if !self.errors.is_empty() {
+            write!(f, ", {} failed with errors", self.errors.len())?;
+        }
+
+        // How many pairings each contestant won from every seat
+        if self.seats_swapped() {
+            let pairings = self.pairing_wins();
+            write!(f, "\n{} seat-swapped pairings", pairings.len())?;
+            for (i, r) in self.records.iter().enumerate() {
+                let swept = pairings
+                    .iter()
+                    .filter(|wins| wins[i] == self.records.len())
+                    .count();
+                write!(f, ", {} won every seat in {}", r.name, swept)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{Agent, Board, Color, Game, GameResult, Logger, MoveSummary, StateView};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Color as TermColor, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+/// The width of a tile on the screen, in characters.
+const TILE_WIDTH: u16 = 7;
+/// The height of a tile on the screen, in lines.
+const TILE_HEIGHT: u16 = 3;
+/// The maximum number of legal moves listed below the board at once.
+const MOVES_SHOWN: usize = 8;
+/// The colors of the players' tokens, by player index.
+const PLAYER_COLORS: [TermColor; 6] = [
+    TermColor::Cyan,
+    TermColor::Yellow,
+    TermColor::Green,
+    TermColor::Magenta,
+    TermColor::Red,
+    TermColor::Blue,
+];
+
+/// Who plays in a seat of a game shown in the terminal.
+pub enum Seat {
+    /// A person who picks moves with the keyboard.
+    Human,
+    /// An agent that moves by itself, either automatically or one move at a time.
+    Ai(Box<dyn Agent>),
+}
+
+/// Plays a game in the terminal, redrawing the board with every player's token,
+/// the owner and rent level of every property, and the most recent moves after
+/// every move. AI moves are made automatically, but auto-play can be paused to
+/// step through them one at a time.
+///
+/// Agents shouldn't log to the terminal while it's in use, so they
+/// should be given `Logger::silent()` if they log anything.
+pub struct Tui {
+    game: Game,
+    /// Who plays in each seat, by player index.
+    seats: Vec<Seat>,
+    /// Whether AI moves are made automatically, rather than with the step key.
+    auto_play: bool,
+    /// How long to wait between automatic AI moves.
+    delay: Duration,
+    /// The index of the legal move that a human player has highlighted.
+    selected: usize,
+}
+
+impl Tui {
+    /// Return a new terminal view of `game`, played by `seats`.
+    pub fn new(game: Game, seats: Vec<Seat>) -> Self {
+        Tui {
+            // Anything logged would be drawn over the board
+            game: game.with_logger(Logger::silent()),
+            seats,
+            auto_play: true,
+            delay: Duration::from_millis(500),
+            selected: 0,
+        }
+    }
+
+    /// Wait for `delay` between automatic AI moves.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Start with auto-play paused, so that AI moves are only made with the step key.
+    pub fn paused(mut self) -> Self {
+        self.auto_play = false;
+        self
+    }
+
+    /// Play the game until it ends, and return the result,
+    /// or `None` if the game was quit before it ended.
+    pub fn run(mut self) -> io::Result<Option<GameResult>> {
+        let _terminal = TerminalGuard::enter()?;
+        let mut out = io::stdout();
+        let mut last_move = Instant::now();
+
+        loop {
+            let moves = self.game.legal_moves().map_err(io::Error::other)?;
+            if moves.is_empty() {
+                self.draw(&mut out, &moves, "Game over, press any key to exit")?;
+                wait_for_key()?;
+                return self.game.result().map_err(io::Error::other);
+            }
+
+            let pindex = self.game.current_state().acting_pindex();
+            let human = matches!(self.seats[pindex], Seat::Human);
+            let status = if human {
+                format!("Player {}, choose a move", pindex)
+            } else if self.auto_play {
+                format!("Player {} (AI) is moving", pindex)
+            } else {
+                format!("Player {} (AI) to move, paused", pindex)
+            };
+            self.draw(&mut out, &moves, &status)?;
+
+            // AI moves are made once the delay is up, unless a key is pressed first
+            if !human && self.auto_play {
+                let remaining = self.delay.saturating_sub(last_move.elapsed());
+                if !event::poll(remaining)? {
+                    self.ai_move(&mut out, &moves, pindex)?;
+                    last_move = Instant::now();
+                    continue;
+                }
+            }
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key.code,
+                _ => continue,
+            };
+
+            match key {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                KeyCode::Char('p') => {
+                    self.auto_play = !self.auto_play;
+                    last_move = Instant::now();
+                }
+                KeyCode::Char(' ') | KeyCode::Char('s') if !human => {
+                    self.ai_move(&mut out, &moves, pindex)?;
+                    last_move = Instant::now();
+                }
+                KeyCode::Up | KeyCode::Char('k') if human => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                KeyCode::Down | KeyCode::Char('j') if human => {
+                    self.selected = (self.selected + 1).min(moves.len() - 1);
+                }
+                KeyCode::Enter if human => {
+                    self.game
+                        .apply_move(self.selected)
+                        .map_err(io::Error::other)?;
+                    self.selected = 0;
+                    last_move = Instant::now();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Get the AI in `pindex`'s seat to make its move.
+    fn ai_move(
+        &mut self,
+        out: &mut Stdout,
+        moves: &[MoveSummary],
+        pindex: usize,
+    ) -> io::Result<()> {
+        self.draw(
+            out,
+            moves,
+            &format!("Player {} (AI) is thinking...", pindex),
+        )?;
+
+        if let Seat::Ai(agent) = &mut self.seats[pindex] {
+            let choice = agent.make_choice(&mut self.game);
+            self.game.apply_move(choice).map_err(io::Error::other)?;
+        }
+
+        Ok(())
+    }
+
+    /// Redraw the whole screen, with `status` and the legal moves below the board.
+    fn draw(&self, out: &mut Stdout, moves: &[MoveSummary], status: &str) -> io::Result<()> {
+        let state = self.game.current_state();
+        let board = state.board();
+        let side = (board.size as u16).div_ceil(4);
+
+        queue!(out, Clear(ClearType::All))?;
+
+        for pos in 0..board.size {
+            let (col, row) = tile_cell(pos as u16, side);
+            draw_tile(out, state, pos, col * TILE_WIDTH, row * TILE_HEIGHT)?;
+        }
+
+        // The players and the most recent moves go in the middle of the board
+        let inner_width = (side.saturating_sub(1) * TILE_WIDTH) as usize;
+        let inner_height = side.saturating_sub(1) * TILE_HEIGHT;
+        let mut lines = vec![];
+        for (i, player) in state.players().iter().enumerate() {
+            let marker = if i == state.acting_pindex() { ">" } else { " " };
+            lines.push((
+                Some(player_color(i)),
+                format!("{} p{} {:#}", marker, i, player),
+            ));
+        }
+        lines.push((None, String::new()));
+
+        let entries = &self.game.transcript().entries;
+        let log_length = (inner_height as usize).saturating_sub(lines.len());
+        for (i, entry) in entries
+            .iter()
+            .enumerate()
+            .skip(entries.len().saturating_sub(log_length))
+        {
+            lines.push((None, format!("{:>4}: {}", i, entry.message)));
+        }
+
+        for (y, (color, line)) in (TILE_HEIGHT..).zip(lines.iter().take(inner_height as usize)) {
+            queue!(out, MoveTo(TILE_WIDTH + 1, y))?;
+            print_colored(out, &fit(line, inner_width.saturating_sub(2)), *color)?;
+        }
+
+        // The status, legal moves and controls go below the board
+        let mut y = (side + 1) * TILE_HEIGHT + 1;
+        queue!(out, MoveTo(0, y), Print(status))?;
+        y += 1;
+
+        let human = !moves.is_empty() && matches!(self.seats[state.acting_pindex()], Seat::Human);
+        if human {
+            let first = self.selected.saturating_sub(MOVES_SHOWN - 1);
+            for (i, m) in moves.iter().enumerate().skip(first).take(MOVES_SHOWN) {
+                let marker = if i == self.selected { ">" } else { " " };
+                queue!(
+                    out,
+                    MoveTo(0, y),
+                    Print(format!("{} [{}] {}", marker, i, m.message))
+                )?;
+                y += 1;
+            }
+        }
+
+        let controls = if human {
+            "[up/down] select  [enter] play move  [p] pause/resume AI  [q] quit"
+        } else {
+            "[space] step  [p] pause/resume  [q] quit"
+        };
+        queue!(out, MoveTo(0, y + 1), Print(controls))?;
+
+        out.flush()
+    }
+}
+
+/// Return the column and row of the tile at `pos` on the screen, going
+/// anticlockwise around a square of `side` tiles per edge from the bottom right.
+fn tile_cell(pos: u16, side: u16) -> (u16, u16) {
+    match pos / side {
+        0 => (side - pos, side),
+        1 => (0, side - (pos - side)),
+        2 => (pos - 2 * side, 0),
+        _ => (side, pos - 3 * side),
+    }
+}
+
+/// Draw the tile at `pos` with its top left corner at `(x, y)`. The first line is
+/// the tile's position and kind, the second is the owner and rent level (or price)
+/// of a property, and the third has the tokens of the players standing on it.
+fn draw_tile(out: &mut Stdout, state: StateView, pos: u8, x: u16, y: u16) -> io::Result<()> {
+    let board = state.board();
+    let width = TILE_WIDTH as usize - 1;
+
+    let (label, color) = tile_label(board, pos);
+    queue!(out, MoveTo(x, y))?;
+    print_colored(out, &fit(&format!("{:02} {}", pos, label), width), color)?;
+
+    if let Some(property) = board.properties.get(&pos) {
+        queue!(out, MoveTo(x, y + 1))?;
+        match state.owned_properties().get(&pos) {
+            Some(ownership) => print_colored(
+                out,
+                &fit(
+                    &format!("p{} L{}", ownership.owner, ownership.rent_level),
+                    width,
+                ),
+                Some(player_color(ownership.owner)),
+            )?,
+            None => print_colored(
+                out,
+                &fit(&format!("${}", property.price), width),
+                Some(TermColor::DarkGrey),
+            )?,
+        }
+    }
+
+    queue!(out, MoveTo(x, y + 2))?;
+    for (i, player) in state.players().iter().enumerate() {
+        if !player.bankrupt && player.position == pos {
+            print_colored(out, &i.to_string(), Some(player_color(i)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Return a short name for the tile at `pos`, and the color of its property set if it has one.
+fn tile_label(board: &Board, pos: u8) -> (&'static str, Option<TermColor>) {
+    let color = board
+        .properties
+        .get(&pos)
+        .map(|property| match property.color {
+            Color::Brown => TermColor::AnsiValue(94),
+            Color::LightBlue => TermColor::Cyan,
+            Color::Pink => TermColor::Magenta,
+            Color::Orange => TermColor::AnsiValue(208),
+            Color::Red => TermColor::Red,
+            Color::Yellow => TermColor::Yellow,
+            Color::Green => TermColor::Green,
+            Color::Blue => TermColor::Blue,
+            Color::Railroad => TermColor::White,
+            Color::Utility => TermColor::Grey,
+        });
+
+    (board.tile_label(pos), color)
+}
+
+/// Return the color of player `pindex`'s token.
+fn player_color(pindex: usize) -> TermColor {
+    PLAYER_COLORS[pindex % PLAYER_COLORS.len()]
+}
+
+/// Print `text` in `color`, or in the default color if there isn't one.
+fn print_colored(out: &mut Stdout, text: &str, color: Option<TermColor>) -> io::Result<()> {
+    match color {
+        Some(color) => queue!(out, SetForegroundColor(color), Print(text), ResetColor),
+        None => queue!(out, Print(text)),
+    }
+}
+
+/// Return `text`, cut off at `width` characters.
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Block until a key is pressed.
+fn wait_for_key() -> io::Result<()> {
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Puts the terminal into raw mode on an alternate screen for as long as it's alive,
+/// and restores the terminal when it's dropped, even if the game panics.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
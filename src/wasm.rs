@@ -0,0 +1,71 @@
+use crate::{Agent, Budget, Game, MctsAgent};
+use wasm_bindgen::prelude::*;
+
+/// A game that can be played from JavaScript, one move at a time. Game states and
+/// moves are passed to JavaScript as JSON strings. Build it for the web with
+/// `wasm-pack build --target web`.
+#[wasm_bindgen]
+pub struct WasmGame {
+    game: Game,
+    /// An AI for every seat, which is only used when `aiMove()` is called for that seat.
+    agents: Vec<MctsAgent>,
+}
+
+#[wasm_bindgen]
+impl WasmGame {
+    /// Return a new game, with AI players that search for `ai_iterations` iterations per move.
+    #[wasm_bindgen(constructor)]
+    pub fn new(player_count: usize, ai_iterations: u32) -> WasmGame {
+        WasmGame {
+            game: Game::new(player_count),
+            agents: (0..player_count)
+                .map(|i| MctsAgent::new(0, 2., i).with_budget(Budget::Iterations(ai_iterations)))
+                .collect(),
+        }
+    }
+
+    /// Return the moves that can be made from the current state, as a JSON array,
+    /// or throw if the engine runs into a bug.
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&mut self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.game.legal_moves()?).unwrap())
+    }
+
+    /// Make the move at `index` in `legalMoves()`, or throw if it isn't legal.
+    #[wasm_bindgen(js_name = applyMove)]
+    pub fn apply_move(&mut self, index: usize) -> Result<(), JsError> {
+        Ok(self.game.apply_move(index)?)
+    }
+
+    /// Get the AI to make the next move, and return the index of the move it made.
+    #[wasm_bindgen(js_name = aiMove)]
+    pub fn ai_move(&mut self) -> Result<usize, JsError> {
+        self.game.legal_moves()?;
+
+        let acting_pindex = self.game.current_observation().acting_pindex;
+        let index = self.agents[acting_pindex].make_choice(&mut self.game);
+        self.game.apply_move(index)?;
+        Ok(index)
+    }
+
+    /// Return a snapshot of the current game state, as a JSON object.
+    pub fn observation(&self) -> String {
+        serde_json::to_string(&self.game.current_observation()).unwrap()
+    }
+
+    /// Return every field of the current game state, as a JSON object.
+    pub fn resolved_state(&self) -> String {
+        serde_json::to_string(&self.game.resolved_state()).unwrap()
+    }
+
+    /// Return the index of the winner, or `undefined` if the game isn't over yet.
+    pub fn winner(&self) -> Result<Option<usize>, JsError> {
+        Ok(self.game.result()?.map(|r| r.winner))
+    }
+
+    /// Return a record of every move made so far, as JSON that can be
+    /// loaded with `Transcript::load()` and replayed with `--replay`.
+    pub fn transcript(&self) -> String {
+        serde_json::to_string(self.game.transcript()).unwrap()
+    }
+}